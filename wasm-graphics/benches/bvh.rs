@@ -0,0 +1,90 @@
+// BVH build and traversal on the built-in scenes and the bundled STLs.
+//     cargo bench --bench bvh
+// To compare two commits, save a baseline on the first and compare against it on the second:
+//     cargo bench --bench bvh -- --save-baseline before
+//     cargo bench --bench bvh -- --baseline before
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use wasm_graphics::{graphics::ray_tracing::{bvh::{AABoundingBox, BVHBuildOptions, BVHSplitMethod, FlattenedBVH}, hittable::Hittable, rt::{HitRecord, Ray}}, utils::{math::Vec3, rng::SampleRng}};
+
+const RAY_COUNT: usize = 4096;
+const RAY_SEED: u64 = 42;
+
+/// Rays from around the geometry toward random points inside its bounds, the same ones every run
+fn make_rays(bounds: &AABoundingBox) -> Vec<Ray> {
+    let center = (bounds.min + bounds.max) * 0.5;
+    let radius = (bounds.max - bounds.min).len() * 0.5;
+    let mut rng = SampleRng::new(RAY_SEED);
+    return (0..RAY_COUNT).map(|_| {
+        let origin = center + rng.random_on_unit_sphere() * (2.0 * radius);
+        let target = Vec3::new(
+            bounds.min.x + rng.random_float() * (bounds.max.x - bounds.min.x),
+            bounds.min.y + rng.random_float() * (bounds.max.y - bounds.min.y),
+            bounds.min.z + rng.random_float() * (bounds.max.z - bounds.min.z),
+        );
+        return Ray::new(origin, (target - origin).normalized());
+    }).collect();
+}
+
+fn build_options() -> [(&'static str, BVHBuildOptions); 2] {
+    return [
+        ("sah", BVHBuildOptions::default()),
+        ("median", BVHBuildOptions { split_method: BVHSplitMethod::Median, ..BVHBuildOptions::default() }),
+    ];
+}
+
+fn bench_build(c: &mut Criterion, sets: &[(String, Vec<Box<dyn Hittable>>)]) {
+    let mut group = c.benchmark_group("bvh_build");
+    group.sample_size(20);
+    for (name, hittables) in sets {
+        group.throughput(Throughput::Elements(hittables.len() as u64));
+        for (method, options) in build_options() {
+            group.bench_with_input(BenchmarkId::new(method, name), hittables, |b, hittables| {
+                b.iter_batched(
+                    || common::clone_hittables(hittables),
+                    |hittables| FlattenedBVH::new_with_options(hittables, &options),
+                    BatchSize::LargeInput,
+                );
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_hit(c: &mut Criterion, sets: &[(String, Vec<Box<dyn Hittable>>)]) {
+    let mut group = c.benchmark_group("bvh_hit");
+    group.throughput(Throughput::Elements(RAY_COUNT as u64));
+    for (name, hittables) in sets {
+        for (method, options) in build_options() {
+            let bvh = FlattenedBVH::new_with_options(common::clone_hittables(hittables), &options);
+            let Some(bounds) = bvh.get_bounding_box() else {
+                continue;
+            };
+            let rays = make_rays(bounds);
+            group.bench_function(BenchmarkId::new(method, name), |b| {
+                b.iter(|| {
+                    let mut hit_count = 0;
+                    for ray in rays.iter() {
+                        let mut hit_record = HitRecord::default();
+                        if bvh.hit(ray, 0.001, f32::INFINITY, &mut hit_record) {
+                            hit_count += 1;
+                        }
+                    }
+                    return hit_count;
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_bvh(c: &mut Criterion) {
+    let sets = common::all_hittable_sets();
+    bench_build(c, &sets);
+    bench_hit(c, &sets);
+}
+
+criterion_group!(benches, bench_bvh);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+// Scene loading shared by the benches. Everything is read from the repo, so a bench measures
+// the same work on every commit. Not every bench uses every helper.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use wasm_graphics::{graphics::{game::Game, mesh::{Mesh, PhongProperties}, ray_tracing::{hittable::Hittable, material::Lambertian}, scene_file::builtin_scene, scene_object::SceneObject}, utils::{log::{set_min_log_level, LogLevel}, math::Vec3}};
+
+/// Built-in scenes that need no assets
+pub const BUILTIN_SCENES: [&str; 3] = ["spheres", "cornell", "quads"];
+/// The STL files bundled with the web app, in web/public/static/
+pub const BUNDLED_STLS: [&str; 4] = ["suzanne.stl", "skull.stl", "abstract_sculpture.stl", "angel.stl"];
+
+/// Where the scenes' asset paths are relative to
+pub fn asset_root() -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../web/public");
+}
+
+/// A headless game with the built-in scene loaded, at the given resolution
+pub fn load_game(scene: &str, width: usize, height: usize) -> Game {
+    set_min_log_level(LogLevel::Warn);
+    let description = builtin_scene(scene).unwrap_or_else(|| panic!("no built-in scene named {}", scene));
+    let assets = description.read_assets(&asset_root()).expect("scene assets failed to load");
+
+    let mut game = Game::new();
+    game.set_resolution(width, height);
+    game.load_scene_description(&description, &assets).expect("scene failed to load");
+    return game;
+}
+
+/// Every hittable of the built-in scene, placed in the world
+pub fn scene_hittables(scene: &str) -> Vec<Box<dyn Hittable>> {
+    let game = load_game(scene, 1, 1);
+    let scene_objects = game.scene_objects.read().unwrap();
+    return scene_objects.iter().flat_map(|scene_obj| scene_obj.get_world_hittables()).collect();
+}
+
+/// The triangles of a bundled STL file
+pub fn stl_hittables(file_name: &str) -> Vec<Box<dyn Hittable>> {
+    set_min_log_level(LogLevel::Warn);
+    let path = asset_root().join("static").join(file_name);
+    let bytes = std::fs::read(&path).unwrap_or_else(|e| panic!("couldn't read {}: {}", path.display(), e));
+    let mesh = Mesh::try_new_from_stl_bytes(&bytes, Vec3::new(0.8, 0.8, 0.8), PhongProperties::default()).expect("STL failed to parse");
+    return SceneObject::new_from_mesh(mesh, Box::new(Lambertian {}), false).get_world_hittables();
+}
+
+/// Every benchmarked geometry, named for the reports
+pub fn all_hittable_sets() -> Vec<(String, Vec<Box<dyn Hittable>>)> {
+    let mut sets = Vec::new();
+    for scene in BUILTIN_SCENES {
+        sets.push((scene.to_string(), scene_hittables(scene)));
+    }
+    for file_name in BUNDLED_STLS {
+        sets.push((file_name.to_string(), stl_hittables(file_name)));
+    }
+    return sets;
+}
+
+pub fn clone_hittables(hittables: &[Box<dyn Hittable>]) -> Vec<Box<dyn Hittable>> {
+    return hittables.iter().map(|hittable| hittable.clone_box()).collect();
+}
@@ -0,0 +1,53 @@
+// Material::scatter_mis throughput, with a quad light to sample toward.
+//     cargo bench --bench materials
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use wasm_graphics::{graphics::{ray_tracing::{hittable::Hittable, material::{ClearCoat, Dielectric, Lambertian, Material, Metal}, rt::{HitRecord, Ray}}, scene_object::SceneObject}, utils::{math::Vec3, rng::SampleRng}};
+
+const SCATTER_COUNT: usize = 4096;
+const SCATTER_SEED: u64 = 7;
+
+/// Rays hitting points of a unit sphere around the origin from outside, the same ones every run.
+/// On a unit sphere a point is also its normal.
+fn make_hits(rng: &mut SampleRng) -> Vec<(Ray, Vec3)> {
+    return (0..SCATTER_COUNT).map(|_| {
+        let point = rng.random_on_unit_sphere();
+        let origin = point * 3.0 + rng.random_on_unit_sphere();
+        return (Ray::new(origin, (point - origin).normalized()), point);
+    }).collect();
+}
+
+fn bench_scatter_mis(c: &mut Criterion) {
+    let lights: Vec<Box<dyn Hittable>> = SceneObject::new_rectangle_light(
+        Vec3::new(-0.5, -0.5, 4.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 1.0), 0.1, 16,
+    ).get_world_hittables();
+    let materials: [(&str, Box<dyn Material>); 4] = [
+        ("lambertian", Box::new(Lambertian {})),
+        ("metal", Box::new(Metal::new(0.2))),
+        ("dielectric", Box::new(Dielectric::new(1.5))),
+        ("clear_coat", Box::new(ClearCoat::new(Box::new(Lambertian {}), 1.5))),
+    ];
+    let hits = make_hits(&mut SampleRng::new(SCATTER_SEED));
+
+    let mut group = c.benchmark_group("scatter_mis");
+    group.throughput(Throughput::Elements(SCATTER_COUNT as u64));
+    for (name, material) in materials.iter() {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let mut rng = SampleRng::new(SCATTER_SEED);
+                let mut scattered = 0;
+                for (ray, point) in hits.iter() {
+                    let mut hit_record = HitRecord { t: 1.0, pos: *point, surface_color: Vec3::new(0.8, 0.8, 0.8), material: Some(material.as_ref()), ..HitRecord::default() };
+                    hit_record.set_face_normal(ray, *point);
+                    let (success, ..) = material.scatter_mis(ray, &hit_record, &lights, &mut rng);
+                    scattered += success as usize;
+                }
+                return scattered;
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scatter_mis);
+criterion_main!(benches);
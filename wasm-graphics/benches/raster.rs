@@ -0,0 +1,27 @@
+// Game::render_frame on the fantasy book GLB, in edit mode and with lighting, at the
+// resolution the web app uses.
+//     cargo bench --bench raster
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const WIDTH: usize = 500;
+const HEIGHT: usize = 500;
+
+fn bench_render_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_frame");
+    group.sample_size(30);
+
+    let mut game = common::load_game("fantasy_book", WIDTH, HEIGHT);
+    game.enter_edit_mode();
+    group.bench_function("fantasy_book/no_lighting", |b| b.iter(|| game.render_frame()));
+
+    game.exit_edit_mode();
+    group.bench_function("fantasy_book/lighting", |b| b.iter(|| game.render_frame()));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_render_frame);
+criterion_main!(benches);
@@ -0,0 +1,393 @@
+// Offline renderer, writes a single frame to disk.
+// Example:
+// cargo run --release --bin render -- --scene cornell --width 800 --height 800 --spp 500 --out cornell.png
+// cargo run --release --bin render -- --scene mirror_box --asset-root ../web/public --out mirror_box.exr
+
+use std::{path::{Path, PathBuf}, process::exit};
+
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use wasm_graphics::{graphics::{game::Game, ray_tracing::{bvh::{BVHBuildOptions, BVHSplitMethod}, bvh_heatmap::{BVHHeatmap, HeatmapMetric}}, scene_file::{builtin_scene, builtin_scene_names, SceneDescription}, texture::TextureFilter}, utils::{math::{degrees_to_radians, Vec3}, utils::get_time}};
+
+const USAGE: &str = "\
+Usage: render --scene <name or scene.json> [options]
+
+Scenes:
+    a scene file (see wasm-graphics/scenes/), or the name of a built-in one:
+    spheres, quads, simple_light, cornell, cornell_plus_plus, fantasy_book, magic_bridge,
+    gandalf_bust, roza_bust, dragon, mirror_box, suzanne_monkey
+
+Options:
+    --out <path>                 output image, .png (gamma corrected) or .exr (linear), default render.png
+    --asset-root <dir>           where mesh asset paths in the scene are relative to, defaults to the
+                                 scene file's directory (or the current directory for built-in scenes)
+    --save-scene <path>          also write the scene that was rendered, camera overrides included,
+                                 to this scene file
+    --export <path>              also export the scene as glTF, .glb (binary) or .gltf (text)
+    --mode <rt|raster|raster_lit|bvh_nodes|bvh_hittables>
+                                 renderer to use, default rt. bvh_nodes and bvh_hittables color each
+                                 pixel by the BVH nodes / hittable tests its primary ray took, and
+                                 print the averages
+    --heatmap-max <n>            the count drawn fully red in a BVH heatmap, default the frame's max
+    --texture-filter <nearest|bilinear>
+                                 how mesh textures are sampled, default bilinear
+    --width <px>                 default 500
+    --height <px>                default 500
+    --spp <n>                    ray tracing samples per pixel to stop at, default 100
+    --time-budget <seconds>      stop ray tracing after this long, even if --spp isn't reached
+    --max-depth <n>              ray_max_depth (max bounces)
+    --bvh <sah|median>           how the BVH is split, default sah
+    --bvh-leaf-size <n>          max hittables per BVH leaf, default 4
+    --seed <n>                   ray tracing seed, the same seed renders the same image, default 0
+    --camera-pos <x,y,z>         overrides the scene's camera position
+    --camera-yaw <degrees>       rotation around the z (up) axis
+    --camera-pitch <degrees>     rotation up/down, in [-90, 90]
+    --fov <degrees>              horizontal field of view
+    --defocus-angle <degrees>    depth of field strength, 0 disables it
+    --focus-dist <dist>          distance to the plane in focus
+    --quiet                      only log warnings and errors
+    --help                       print this message
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenderMode {
+    RayTracing,
+    Raster,
+    RasterLit,
+    BVHHeatmap(HeatmapMetric),
+}
+
+#[derive(Debug)]
+struct RenderArgs {
+    scene: String,
+    asset_root: Option<String>,
+    save_scene: Option<String>,
+    export: Option<String>,
+    out: String,
+    mode: RenderMode,
+    width: usize,
+    height: usize,
+    spp: usize,
+    time_budget: Option<f64>,
+    max_depth: Option<usize>,
+    seed: u64,
+    bvh_split: BVHSplitMethod,
+    bvh_leaf_size: usize,
+    heatmap_max: Option<usize>,
+    texture_filter: TextureFilter,
+    camera_pos: Option<Vec3>,
+    camera_yaw: Option<f32>,
+    camera_pitch: Option<f32>,
+    fov: Option<f32>,
+    defocus_angle: Option<f32>,
+    focus_dist: Option<f32>,
+    quiet: bool,
+}
+
+impl Default for RenderArgs {
+    fn default() -> Self {
+        return RenderArgs {
+            scene: String::new(),
+            asset_root: None,
+            save_scene: None,
+            export: None,
+            out: String::from("render.png"),
+            mode: RenderMode::RayTracing,
+            width: 500,
+            height: 500,
+            spp: 100,
+            time_budget: None,
+            max_depth: None,
+            seed: 0,
+            bvh_split: BVHBuildOptions::default().split_method,
+            bvh_leaf_size: BVHBuildOptions::default().max_leaf_size,
+            heatmap_max: None,
+            texture_filter: TextureFilter::default(),
+            camera_pos: None,
+            camera_yaw: None,
+            camera_pitch: None,
+            fov: None,
+            defocus_angle: None,
+            focus_dist: None,
+            quiet: false,
+        };
+    }
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            exit(1);
+        }
+    };
+
+    if args.quiet {
+        wasm_graphics::utils::log::set_min_log_level(wasm_graphics::utils::log::LogLevel::Warn);
+    }
+
+    let mut game = Game::new();
+    game.set_resolution(args.width, args.height);
+
+    if let Err(e) = load_scene(&mut game, &args) {
+        eprintln!("Failed to load scene: {}", e);
+        exit(1);
+    }
+    apply_overrides(&mut game, &args);
+
+    if let Some(save_path) = &args.save_scene {
+        if let Err(e) = game.to_scene_description().save_to_file(Path::new(save_path)) {
+            eprintln!("Failed to save scene: {}", e);
+            exit(1);
+        }
+    }
+    if let Some(export_path) = &args.export {
+        if let Err(e) = export_scene(&game, export_path) {
+            eprintln!("Failed to export {}: {}", export_path, e);
+            exit(1);
+        }
+    }
+
+    let start_time = get_time();
+    match args.mode {
+        RenderMode::RayTracing => {
+            if game.scene_objects.read().unwrap().iter().all(|obj| obj.hittables.is_empty()) {
+                eprintln!("Scene has nothing to ray trace");
+                exit(1);
+            }
+            game.enter_ray_tracing_mode();
+            println!("BVH: {}", game.bvh.get_stats());
+            // the whole render counts as one frame
+            game.begin_frame_stats();
+            while game.ray_samples_accumulated < args.spp {
+                // one pass at a time so exactly --spp samples are taken
+                game.render_ray_tracing_pass();
+
+                let elapsed_secs = (get_time() - start_time) / 1000.0;
+                if args.time_budget.is_some_and(|budget| elapsed_secs > budget) {
+                    eprintln!("Time budget reached at {} samples per pixel", game.ray_samples_accumulated);
+                    break;
+                }
+            }
+            game.end_frame_stats();
+        },
+        RenderMode::Raster => {
+            game.enter_edit_mode();
+            game.game_loop();
+        },
+        RenderMode::RasterLit => {
+            game.exit_edit_mode();
+            game.game_loop();
+        },
+        RenderMode::BVHHeatmap(metric) => {
+            if game.scene_objects.read().unwrap().iter().all(|obj| obj.hittables.is_empty()) {
+                eprintln!("Scene has nothing to ray trace");
+                exit(1);
+            }
+            game.enter_ray_tracing_mode();
+            println!("BVH: {}", game.bvh.get_stats());
+            game.set_bvh_heatmap(Some(BVHHeatmap { metric, max_count: args.heatmap_max }));
+            game.render_bvh_heatmap();
+            if let Some(summary) = &game.bvh_heatmap_summary {
+                println!("Heatmap: {}", summary);
+            }
+        },
+    }
+    let elapsed_secs = (get_time() - start_time) / 1000.0;
+    if matches!(args.mode, RenderMode::RayTracing | RenderMode::Raster | RenderMode::RasterLit) {
+        println!("Stats: {}", game.frame_stats);
+    }
+
+    if let Err(e) = save_image(&game, &args.out) {
+        eprintln!("Failed to write {}: {}", args.out, e);
+        exit(1);
+    }
+    let samples = match args.mode {
+        RenderMode::RayTracing => format!(", {} spp", game.ray_samples_accumulated),
+        _ => String::new(),
+    };
+    println!("Wrote {} ({}x{}{}, {:.2}s)", args.out, args.width, args.height, samples, elapsed_secs);
+}
+
+// ARGUMENT PARSING
+fn parse_args(raw_args: Vec<String>) -> Result<RenderArgs, String> {
+    let mut args = RenderArgs::default();
+    let mut iter = raw_args.into_iter();
+
+    while let Some(flag) = iter.next() {
+        if flag == "--help" || flag == "-h" {
+            println!("{}", USAGE);
+            exit(0);
+        }
+        if flag == "--quiet" {
+            args.quiet = true;
+            continue;
+        }
+
+        let value = iter.next().ok_or(format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--scene" => args.scene = value,
+            "--asset-root" => args.asset_root = Some(value),
+            "--save-scene" => args.save_scene = Some(value),
+            "--export" => args.export = Some(value),
+            "--out" => args.out = value,
+            "--mode" => {
+                args.mode = match value.as_str() {
+                    "rt" => RenderMode::RayTracing,
+                    "raster" => RenderMode::Raster,
+                    "raster_lit" => RenderMode::RasterLit,
+                    "bvh_nodes" => RenderMode::BVHHeatmap(HeatmapMetric::NodesVisited),
+                    "bvh_hittables" => RenderMode::BVHHeatmap(HeatmapMetric::HittablesTested),
+                    _ => return Err(format!("Unknown mode '{}'", value)),
+                };
+            },
+            "--width" => args.width = parse_num(&flag, &value)?,
+            "--height" => args.height = parse_num(&flag, &value)?,
+            "--spp" => args.spp = parse_num(&flag, &value)?,
+            "--time-budget" => args.time_budget = Some(parse_num(&flag, &value)?),
+            "--max-depth" => args.max_depth = Some(parse_num(&flag, &value)?),
+            "--seed" => args.seed = parse_num(&flag, &value)?,
+            "--bvh" => {
+                args.bvh_split = match value.as_str() {
+                    "sah" => BVHBuildOptions::default().split_method,
+                    "median" => BVHSplitMethod::Median,
+                    _ => return Err(format!("Unknown BVH split method '{}'", value)),
+                };
+            },
+            "--bvh-leaf-size" => args.bvh_leaf_size = parse_num(&flag, &value)?,
+            "--heatmap-max" => args.heatmap_max = Some(parse_num(&flag, &value)?),
+            "--texture-filter" => {
+                args.texture_filter = match value.as_str() {
+                    "nearest" => TextureFilter::Nearest,
+                    "bilinear" => TextureFilter::Bilinear,
+                    _ => return Err(format!("Unknown texture filter '{}'", value)),
+                };
+            },
+            "--camera-pos" => args.camera_pos = Some(parse_vec3(&flag, &value)?),
+            "--camera-yaw" => args.camera_yaw = Some(parse_num(&flag, &value)?),
+            "--camera-pitch" => args.camera_pitch = Some(parse_num(&flag, &value)?),
+            "--fov" => args.fov = Some(parse_num(&flag, &value)?),
+            "--defocus-angle" => args.defocus_angle = Some(parse_num(&flag, &value)?),
+            "--focus-dist" => args.focus_dist = Some(parse_num(&flag, &value)?),
+            _ => return Err(format!("Unknown flag '{}'", flag)),
+        }
+    }
+
+    if args.scene.is_empty() {
+        return Err(String::from("--scene is required"));
+    }
+    if args.width == 0 || args.height == 0 {
+        return Err(String::from("--width and --height must be at least 1"));
+    }
+    if args.heatmap_max == Some(0) {
+        return Err(String::from("--heatmap-max must be at least 1"));
+    }
+    if args.max_depth == Some(0) {
+        return Err(String::from("--max-depth must be at least 1"));
+    }
+    return Ok(args);
+}
+
+fn parse_num<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    return value.parse::<T>().map_err(|_| format!("Invalid value '{}' for {}", value, flag));
+}
+
+fn parse_vec3(flag: &str, value: &str) -> Result<Vec3, String> {
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() != 3 {
+        return Err(format!("{} expects x,y,z, got '{}'", flag, value));
+    }
+    return Ok(Vec3::new(
+        parse_num(flag, parts[0].trim())?,
+        parse_num(flag, parts[1].trim())?,
+        parse_num(flag, parts[2].trim())?,
+    ));
+}
+
+// SCENE SETUP
+fn load_scene(game: &mut Game, args: &RenderArgs) -> Result<(), String> {
+    let (scene, default_asset_root) = match builtin_scene(&args.scene) {
+        Some(scene) => (scene, PathBuf::from(".")),
+        None => {
+            let scene_path = Path::new(&args.scene);
+            if !scene_path.exists() {
+                return Err(format!(
+                    "'{}' isn't a scene file or a built-in scene ({})",
+                    args.scene, builtin_scene_names().join(", ")
+                ));
+            }
+            let scene = SceneDescription::load_from_file(scene_path)?;
+            (scene, scene_path.parent().unwrap_or(Path::new(".")).to_path_buf())
+        }
+    };
+
+    let asset_root = args.asset_root.as_ref().map(PathBuf::from).unwrap_or(default_asset_root);
+    let assets = scene.read_assets(&asset_root)?;
+    game.load_scene_description(&scene, &assets)?;
+    return Ok(());
+}
+
+fn export_scene(game: &Game, path: &str) -> Result<(), String> {
+    let is_gltf = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gltf"));
+
+    let bytes = if is_gltf {
+        game.export_scene_gltf()?.into_bytes()
+    } else {
+        game.export_scene_glb()?
+    };
+    return std::fs::write(path, bytes).map_err(|e| e.to_string());
+}
+
+fn apply_overrides(game: &mut Game, args: &RenderArgs) {
+    if let Some(max_depth) = args.max_depth {
+        game.ray_max_depth = max_depth;
+    }
+    game.rt_seed = args.seed;
+    game.set_texture_filter(args.texture_filter);
+    game.bvh_build_options = BVHBuildOptions {
+        split_method: args.bvh_split,
+        max_leaf_size: args.bvh_leaf_size,
+    };
+    if let Some(pos) = args.camera_pos {
+        game.camera.pos = pos;
+    }
+    if let Some(yaw) = args.camera_yaw {
+        game.camera.set_theta_z(degrees_to_radians(yaw));
+    }
+    if let Some(pitch) = args.camera_pitch {
+        game.camera.set_theta_y(degrees_to_radians(pitch));
+    }
+    if let Some(fov) = args.fov {
+        game.set_fov(degrees_to_radians(fov));
+    }
+    if let Some(defocus_angle) = args.defocus_angle {
+        game.set_defocus_angle(degrees_to_radians(defocus_angle));
+    }
+    if let Some(focus_dist) = args.focus_dist {
+        game.set_focal_dist(focus_dist);
+    }
+}
+
+// OUTPUT
+fn save_image(game: &Game, out: &str) -> Result<(), String> {
+    let width = game.pixel_buf.width as u32;
+    let height = game.pixel_buf.height as u32;
+
+    let is_exr = Path::new(out)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+
+    let image = if is_exr {
+        let buf = ImageBuffer::<Rgb<f32>, Vec<f32>>::from_raw(width, height, game.pixel_buf.get_buf_as_f32())
+            .ok_or("Pixel buffer has the wrong size")?;
+        DynamicImage::ImageRgb32F(buf)
+    } else {
+        let buf = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, game.pixel_buf.get_gamma_corrected_buf_as_u8())
+            .ok_or("Pixel buffer has the wrong size")?;
+        DynamicImage::ImageRgba8(buf)
+    };
+
+    return image.save(out).map_err(|e| e.to_string());
+}
@@ -1,7 +1,9 @@
-use std::sync::Mutex;
+use std::{collections::HashSet, sync::Mutex};
 
 use crate::{console_log, utils::{math::Vec3, utils::{color_to_u8, gamma_correct_color}}};
 
+use super::object_id::ObjectId;
+
 
 pub struct PixelBuf {
     pub width: usize,
@@ -72,6 +74,20 @@ impl PixelBuf {
         return buf;
     }
 
+    /// Linear (not gamma corrected) RGB floats, row by row. Used for HDR output.
+    pub fn get_buf_as_f32(&self) -> Vec<f32> {
+        let mut buf = Vec::with_capacity(self.height * self.width * 3);
+        for row in self.pixel_rows.iter() {
+            let pixels = row.lock().unwrap();
+            for p in pixels.iter() {
+                buf.push(p.x);
+                buf.push(p.y);
+                buf.push(p.z);
+            }
+        }
+        return buf;
+    }
+
     pub fn get_gamma_corrected_buf_as_u8(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(self.height * self.width * 4);
         for row in self.pixel_rows.iter() {
@@ -87,6 +103,82 @@ impl PixelBuf {
         }
         return buf;
     }
+
+    /// Draws a line between two points in pixel coordinates over whatever is in the buffer,
+    /// thickness pixels wide. The parts off the screen are skipped.
+    pub fn draw_line(&self, from: (f32, f32), to: (f32, f32), color: Vec3, thickness: usize) {
+        let Some((t0, t1)) = self.clip_line(from, to) else {
+            return;
+        };
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let (x0, y0) = (from.0 + t0 * dx, from.1 + t0 * dy);
+        let steps = ((t1 - t0) * dx.abs().max(dy.abs())).ceil().max(1.0) as usize;
+        let (step_x, step_y) = ((t1 - t0) * dx / steps as f32, (t1 - t0) * dy / steps as f32);
+        let half_thickness = thickness.saturating_sub(1) / 2;
+        for i in 0..=steps {
+            let x = (x0 + step_x * i as f32).round() as usize;
+            let y = (y0 + step_y * i as f32).round() as usize;
+            for brush_y in y.saturating_sub(half_thickness)..(y.saturating_sub(half_thickness) + thickness.max(1)).min(self.height) {
+                let mut pixel_row = self.pixel_rows[brush_y].lock().unwrap();
+                for brush_x in x.saturating_sub(half_thickness)..(x.saturating_sub(half_thickness) + thickness.max(1)).min(self.width) {
+                    pixel_row[brush_x] = color;
+                }
+            }
+        }
+    }
+
+    /// Draws a one pixel wide line like draw_line, but from and to are (x, y, depth) and only the
+    /// pixels not behind what's in zbuf are drawn. Lines lying on a surface show up on it.
+    /// Nothing is written to zbuf.
+    pub fn draw_line_depth_tested(&self, from: Vec3, to: Vec3, color: Vec3, zbuf: &ZBuffer) {
+        // how far behind a surface (relative to its depth) a line still shows on it
+        const DEPTH_TOLERANCE: f32 = 0.002;
+        if from.z <= 0.0 || to.z <= 0.0 {
+            return;
+        }
+        let Some((t0, t1)) = self.clip_line((from.x, from.y), (to.x, to.y)) else {
+            return;
+        };
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        let steps = ((t1 - t0) * dx.abs().max(dy.abs())).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let t = t0 + (t1 - t0) * i as f32 / steps as f32;
+            let x = (from.x + t * dx).round() as usize;
+            let y = (from.y + t * dy).round() as usize;
+            // depth isn't linear on the screen, its inverse is
+            let depth = 1.0 / ((1.0 / from.z) * (1.0 - t) + (1.0 / to.z) * t);
+            let zbuf_row = zbuf.get_row_guard(y).lock().unwrap();
+            if depth * (1.0 - DEPTH_TOLERANCE) < zbuf_row[x] {
+                self.pixel_rows[y].lock().unwrap()[x] = color;
+            }
+        }
+    }
+
+    /// The part of the line between two pixel coordinates that's on the screen, as the fractions
+    /// of the way from one to the other where it starts and ends. None if it's all off the screen.
+    fn clip_line(&self, from: (f32, f32), to: (f32, f32)) -> Option<(f32, f32)> {
+        if self.width == 0 || self.height == 0 || !(from.0.is_finite() && from.1.is_finite() && to.0.is_finite() && to.1.is_finite()) {
+            return None;
+        }
+        // clipped first, so lines running far off the screen don't take forever
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+        for (p, q) in [(-dx, from.0), (dx, self.width as f32 - 1.0 - from.0), (-dy, from.1), (dy, self.height as f32 - 1.0 - from.1)] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+        return Some((t0, t1));
+    }
 }
 
 
@@ -129,4 +221,55 @@ impl ZBuffer {
     // pub fn get_buf(&self) -> &Vec<f32> {
     //     return &self.zbuf;
     // }
-}
\ No newline at end of file
+}
+
+
+/// Which object each pixel shows, filled in alongside the ZBuffer while rasterizing in edit mode
+pub struct ObjectIdBuffer {
+    pub width: usize,
+    pub height: usize,
+    id_rows: Vec<Mutex<Vec<Option<ObjectId>>>>,
+}
+
+impl ObjectIdBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let id_rows = (0..height)
+            .map(|_| Mutex::new(vec![None; width]))
+            .collect();
+        return ObjectIdBuffer {
+            width,
+            height,
+            id_rows,
+        }
+    }
+    pub fn get_row_guard(&self, y: usize) -> &Mutex<Vec<Option<ObjectId>>> {
+        &self.id_rows[y]
+    }
+    pub fn get_id(&self, x: usize, y: usize) -> Option<ObjectId> {
+        return *self.id_rows.get(y)?.lock().unwrap().get(x)?;
+    }
+    pub fn clear(&mut self) {
+        for row in self.id_rows.iter_mut() {
+            row.get_mut().unwrap().fill(None);
+        }
+    }
+    /// Every object showing in the rectangle between the two corners (inclusive), in the order they're first found
+    pub fn get_ids_in_rect(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> Vec<ObjectId> {
+        let mut ids = Vec::new();
+        let mut seen = HashSet::new();
+        if self.width == 0 || self.height == 0 {
+            return ids;
+        }
+        let (x_min, x_max) = (x0.min(x1).min(self.width - 1), x0.max(x1).min(self.width - 1));
+        let (y_min, y_max) = (y0.min(y1).min(self.height - 1), y0.max(y1).min(self.height - 1));
+        for y in y_min..=y_max {
+            let row = self.id_rows[y].lock().unwrap();
+            for id in row[x_min..=x_max].iter().flatten() {
+                if seen.insert(*id) {
+                    ids.push(*id);
+                }
+            }
+        }
+        return ids;
+    }
+}
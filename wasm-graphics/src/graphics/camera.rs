@@ -107,8 +107,8 @@ impl Camera {
         self.vertex_world_to_camera_space(v2);
         self.vertex_world_to_camera_space(v3);
     }
-    pub fn vertices_world_to_camera_space(&self, vertices: &Vec<Vec3>) -> Vec<Vec3> {
-        let mut transformed_vertices = vertices.clone();
+    pub fn vertices_world_to_camera_space(&self, vertices: &[Vec3]) -> Vec<Vec3> {
+        let mut transformed_vertices = vertices.to_vec();
         for v in transformed_vertices.iter_mut() {
             self.vertex_world_to_camera_space(v);
         }
@@ -0,0 +1,290 @@
+use crate::{console_error, console_log, utils::math::{degrees_to_radians, Quat, Transform, Vec3}};
+
+use super::{game::{Game, GameStatus}, object_id::ObjectId, scene_object::SceneObject};
+
+// Copies of the selection kept apart from the scene, for duplicating, copy/paste and the array
+// tool. Nothing in here points into the scene (the objects are instances with no id or node,
+// node transforms are stored in world space), so a copy can be pasted into another scene.
+// A copied group keeps its shape: every node under it is copied along with its objects.
+// The copies share geometry with the originals, like instances do.
+
+#[derive(Clone)]
+pub struct ClipboardNode {
+    /// Name of the scene graph node, None for an object that wasn't in the graph
+    pub name: Option<String>,
+    pub world_transform: Transform,
+    /// Index into Clipboard::nodes, None for the copied roots
+    pub parent: Option<usize>,
+    /// Scene graph node the root was under when it was copied, only meaningful in the same scene
+    pub source_parent: Option<usize>,
+    pub object: Option<SceneObject>,
+}
+
+/// Parents always come before their children
+#[derive(Clone, Default)]
+pub struct Clipboard {
+    pub nodes: Vec<ClipboardNode>,
+}
+
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        return Clipboard { nodes: Vec::new() };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.nodes.is_empty();
+    }
+
+    /// True for a copied group: one root that isn't an object
+    pub fn is_group(&self) -> bool {
+        let mut roots = self.nodes.iter().filter(|node| node.parent.is_none());
+        return matches!((roots.next(), roots.next()), (Some(root), None) if root.object.is_none());
+    }
+
+    /// Center and radius of a sphere around every copied object
+    pub fn get_bounds(&self) -> Option<(Vec3, f32)> {
+        let objects: Vec<&SceneObject> = self.nodes.iter().filter_map(|node| node.object.as_ref()).collect();
+        if objects.is_empty() {
+            return None;
+        }
+        let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vec3::new(f32::MIN, f32::MIN, f32::MIN);
+        for obj in objects.iter() {
+            let (center, radius) = (obj.get_center(), obj.get_radius());
+            min = min.min_elementwise(center - Vec3::new(radius, radius, radius));
+            max = max.max_elementwise(center + Vec3::new(radius, radius, radius));
+        }
+        let center = (min + max) * 0.5;
+        let radius = objects.iter().fold(0.0_f32, |radius, obj| radius.max((obj.get_center() - center).len() + obj.get_radius()));
+        return Some((center, radius));
+    }
+}
+
+impl Game {
+    /// Copies the selected group, or the selected objects, for pasting later. The copy
+    /// is kept when another scene is loaded.
+    pub fn copy_selection(&mut self) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::copy_selection() called but not in EditMode, got GameStatus: {:?}", self.status);
+            return;
+        }
+        let clipboard = self.get_selection_clipboard();
+        if clipboard.is_empty() {
+            console_error!("Game::copy_selection() called but nothing is selected");
+            return;
+        }
+        console_log!("Copied {} nodes", clipboard.nodes.len());
+        self.clipboard = clipboard;
+    }
+
+    /// Adds the copied objects where the user is looking and selects them
+    pub fn paste(&mut self) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::paste() called but not in EditMode, got GameStatus: {:?}", self.status);
+            return;
+        }
+        let Some((center, radius)) = self.clipboard.get_bounds() else {
+            console_error!("Game::paste() called but nothing was copied");
+            return;
+        };
+        let destination = if let Some((_, looking_at_pos, _)) = *self.looking_at.read().unwrap() {
+            looking_at_pos
+        } else {
+            let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * radius);
+            self.camera.vertex_screen_to_world_space(&mut looking_at_pos);
+            looking_at_pos
+        };
+
+        let clipboard = self.clipboard.clone();
+        let graph_before = self.scene_graph.clone();
+        let (ids, roots) = self.add_clipboard(&clipboard, Transform::new(destination - center, Quat::identity(), Vec3::ones()), false);
+        console_log!("Pasted {} objects", ids.len());
+        self.record_added_objects(ids.clone(), graph_before);
+        self.select_pasted(&clipboard, ids, roots);
+    }
+
+    /// Copies the selection in place, moved by offset, and selects the copies.
+    /// Copies end up in the same group as what they were copied from.
+    pub fn duplicate_selection(&mut self, offset: Vec3) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::duplicate_selection() called but not in EditMode, got GameStatus: {:?}", self.status);
+            return;
+        }
+        let clipboard = self.get_selection_clipboard();
+        if clipboard.is_empty() {
+            console_error!("Game::duplicate_selection() called but nothing is selected");
+            return;
+        }
+        let graph_before = self.scene_graph.clone();
+        let (ids, roots) = self.add_clipboard(&clipboard, Transform::new(offset, Quat::identity(), Vec3::ones()), true);
+        console_log!("Duplicated {} objects", ids.len());
+        self.record_added_objects(ids.clone(), graph_before);
+        self.select_pasted(&clipboard, ids, roots);
+    }
+
+    /// Duplicates the selection with the default offset: one selection width to the
+    /// right of the camera, so the copy is next to the original rather than inside it
+    pub fn duplicate_selection_beside(&mut self) {
+        let bounds = match self.selected_group {
+            Some(group) => self.get_group_bounds(group),
+            None => self.get_selection_bounds(),
+        };
+        let Some((_, radius)) = bounds else {
+            console_error!("Game::duplicate_selection_beside() called but nothing is selected");
+            return;
+        };
+        let mut right = Vec3::cross(*self.camera.get_looking_dir(), Vec3::new(0.0, 0.0, 1.0));
+        if right.near_zero() { // looking straight up or down
+            right = Vec3::new(1.0, 0.0, 0.0);
+        }
+        self.duplicate_selection(right.normalized() * 2.0 * radius);
+    }
+
+    /// Adds count copies of the selection in a row, the i-th one moved by i * offset.
+    /// Everything (originals and copies) is selected afterwards.
+    pub fn array_selection_linear(&mut self, count: usize, offset: Vec3) {
+        let placements = (1..=count)
+            .map(|i| Transform::new(offset * i as f32, Quat::identity(), Vec3::ones()))
+            .collect();
+        self.array_selection("array_selection_linear", placements);
+    }
+
+    /// Adds count copies of the selection around a circle, rotated about axis through the
+    /// middle of the selection plus pivot_offset. The originals and the copies are spread
+    /// evenly over a whole turn, or over total_degrees when it's below 360.
+    pub fn array_selection_circular(&mut self, count: usize, axis: Vec3, pivot_offset: Vec3, total_degrees: f32) {
+        let bounds = match self.selected_group {
+            Some(group) => self.get_group_bounds(group),
+            None => self.get_selection_bounds(),
+        };
+        let Some((center, _)) = bounds else {
+            console_error!("Game::array_selection_circular() called but nothing is selected");
+            return;
+        };
+        if axis.near_zero() {
+            console_error!("Game::array_selection_circular() called with a zero axis");
+            return;
+        }
+        let step_degrees = if total_degrees.abs() < 360.0 {
+            total_degrees / count.max(1) as f32
+        } else {
+            360.0 / (count + 1) as f32
+        };
+        let pivot = center + pivot_offset;
+        let placements = (1..=count)
+            .map(|i| {
+                let mut placement = Transform::identity();
+                placement.rotate_around(pivot, Quat::from_axis_angle(axis.normalized(), degrees_to_radians(step_degrees * i as f32)));
+                placement
+            })
+            .collect();
+        self.array_selection("array_selection_circular", placements);
+    }
+
+    /// Adds a copy of the selection for each placement, recorded as one edit
+    fn array_selection(&mut self, caller: &str, placements: Vec<Transform>) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::{}() called but not in EditMode, got GameStatus: {:?}", caller, self.status);
+            return;
+        }
+        let clipboard = self.get_selection_clipboard();
+        if clipboard.is_empty() {
+            console_error!("Game::{}() called but nothing is selected", caller);
+            return;
+        }
+        if placements.is_empty() {
+            return;
+        }
+        let mut selection = self.get_selection_as_objects();
+        let graph_before = self.scene_graph.clone();
+        let mut ids = Vec::new();
+        for placement in placements {
+            let (copy_ids, _) = self.add_clipboard(&clipboard, placement, true);
+            ids.extend(copy_ids);
+        }
+        console_log!("Added an array of {} objects", ids.len());
+        self.record_added_objects(ids.clone(), graph_before);
+        selection.extend(ids);
+        self.select_objects(selection);
+    }
+
+    /// Copies what's selected: the selected group's whole subtree, or each selected object
+    fn get_selection_clipboard(&self) -> Clipboard {
+        let mut clipboard = Clipboard::new();
+        let scene_objects = self.scene_objects.read().unwrap();
+        if let Some(group) = self.selected_group {
+            let mut copied_nodes: Vec<usize> = Vec::new();
+            for (node, world_transform) in self.scene_graph.get_subtree_world_transforms(group) {
+                let scene_node = self.scene_graph.get(node).unwrap();
+                let parent = scene_node.parent.and_then(|parent| copied_nodes.iter().position(|&copied| copied == parent));
+                let object = scene_objects.iter().find(|obj| obj.node == Some(node)).map(|obj| obj.new_instance());
+                clipboard.nodes.push(ClipboardNode {
+                    name: Some(scene_node.name.clone()),
+                    world_transform,
+                    parent,
+                    source_parent: if parent.is_none() { scene_node.parent } else { None },
+                    object,
+                });
+                copied_nodes.push(node);
+            }
+            return clipboard;
+        }
+        for &id in self.selected_objects.iter() {
+            let Some(index) = self.get_object_index(id) else {
+                continue;
+            };
+            let scene_obj = &scene_objects[index];
+            let scene_node = scene_obj.node.and_then(|node| self.scene_graph.get(node));
+            clipboard.nodes.push(ClipboardNode {
+                name: scene_node.map(|node| node.name.clone()),
+                world_transform: scene_obj.transform,
+                parent: None,
+                source_parent: scene_node.and_then(|node| node.parent),
+                object: Some(scene_obj.new_instance()),
+            });
+        }
+        return clipboard;
+    }
+
+    /// Adds a copy of everything in the clipboard, moved in the world by placement. Roots go under
+    /// the node they were copied from if in_source_parents is set and it still exists, else at the
+    /// top level. Returns the new objects and the new root nodes (None for roots not in the graph).
+    fn add_clipboard(&mut self, clipboard: &Clipboard, placement: Transform, in_source_parents: bool) -> (Vec<ObjectId>, Vec<Option<usize>>) {
+        let mut ids = Vec::new();
+        let mut roots = Vec::new();
+        let mut new_nodes: Vec<Option<usize>> = Vec::with_capacity(clipboard.nodes.len());
+        for clipboard_node in clipboard.nodes.iter() {
+            let world_transform = Transform::from_mat4(&(placement.to_mat4() * clipboard_node.world_transform.to_mat4())).unwrap_or(clipboard_node.world_transform);
+            let new_node = clipboard_node.name.as_ref().map(|name| {
+                let parent = match clipboard_node.parent {
+                    Some(parent) => new_nodes[parent],
+                    None if in_source_parents => clipboard_node.source_parent.filter(|&parent| self.scene_graph.contains(parent)),
+                    None => None,
+                };
+                let node = self.scene_graph.add_node(name, Transform::identity(), parent);
+                self.scene_graph.set_world_transform(node, world_transform);
+                node
+            });
+            if let Some(object) = &clipboard_node.object {
+                let mut new_obj = object.new_instance();
+                new_obj.set_transform(world_transform);
+                new_obj.node = new_node;
+                ids.push(self.add_scene_object(new_obj));
+            }
+            if clipboard_node.parent.is_none() {
+                roots.push(new_node);
+            }
+            new_nodes.push(new_node);
+        }
+        return (ids, roots);
+    }
+
+    /// Selects what was just added from the clipboard: the new group if a group was copied
+    fn select_pasted(&mut self, clipboard: &Clipboard, ids: Vec<ObjectId>, roots: Vec<Option<usize>>) {
+        if let (true, [Some(group)]) = (clipboard.is_group(), roots.as_slice()) {
+            self.select_group(*group);
+        } else {
+            self.select_objects(ids);
+        }
+    }
+}
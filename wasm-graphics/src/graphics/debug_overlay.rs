@@ -0,0 +1,180 @@
+use crate::utils::math::Vec3;
+
+use super::{game::{Game, GameStatus}, ray_tracing::bvh::AABoundingBox, scene_object::SceneObject};
+
+// Debug views of the geometry, drawn over the rasterized frame in either raster mode: the edges
+// of every triangle, each face's normal, each object's bounding sphere, and the boxes of the
+// ray tracing BVH. Meant for telling apart a bad import, an inverted winding (the normals point
+// inward, the faces get culled) and a bad BVH, which all look alike in the shaded image.
+//
+// Edges and normals are hidden behind what's in front of them, the spheres and boxes are drawn
+// over everything since the objects they wrap would otherwise hide them.
+
+const WIREFRAME_COLOR: Vec3 = Vec3 { x: 0.85, y: 0.85, z: 0.85 };
+const NORMAL_COLOR: Vec3 = Vec3 { x: 0.0, y: 0.9, z: 0.9 };
+const BOUNDING_SPHERE_COLOR: Vec3 = Vec3 { x: 1.0, y: 0.6, z: 0.0 };
+/// BVH boxes are colored by depth, going around these
+const BVH_DEPTH_COLORS: [Vec3; 6] = [
+    Vec3 { x: 1.0, y: 0.2, z: 0.2 },
+    Vec3 { x: 1.0, y: 0.8, z: 0.1 },
+    Vec3 { x: 0.3, y: 1.0, z: 0.3 },
+    Vec3 { x: 0.2, y: 0.9, z: 1.0 },
+    Vec3 { x: 0.4, y: 0.4, z: 1.0 },
+    Vec3 { x: 1.0, y: 0.3, z: 1.0 },
+];
+/// Normals are drawn this many times the average edge length of their triangle...
+const NORMAL_LENGTH: f32 = 0.5;
+/// ...but no longer on the screen than this fraction of half the screen width
+const NORMAL_MAX_SCREEN_SIZE: f32 = 0.1;
+const SPHERE_SEGMENTS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugOverlays {
+    pub wireframe: bool,
+    pub normals: bool,
+    pub bounding_spheres: bool,
+    pub bvh_boxes: bool,
+    /// Only BVH nodes this deep or deeper are drawn, the top level's root is 0
+    pub bvh_min_depth: usize,
+    /// Only BVH nodes this deep or shallower are drawn, None for no limit
+    pub bvh_max_depth: Option<usize>,
+    /// Draws the overlays for the selected objects only
+    pub only_selected: bool,
+}
+
+impl DebugOverlays {
+    pub fn new() -> DebugOverlays {
+        return DebugOverlays {
+            wireframe: false,
+            normals: false,
+            bounding_spheres: false,
+            bvh_boxes: false,
+            bvh_min_depth: 0,
+            bvh_max_depth: None,
+            only_selected: false,
+        };
+    }
+
+    pub fn is_any_shown(&self) -> bool {
+        return self.wireframe || self.normals || self.bounding_spheres || self.bvh_boxes;
+    }
+
+    fn is_depth_shown(&self, depth: usize) -> bool {
+        return depth >= self.bvh_min_depth && self.bvh_max_depth.is_none_or(|max_depth| depth <= max_depth);
+    }
+}
+
+impl Default for DebugOverlays {
+    fn default() -> Self {
+        return DebugOverlays::new();
+    }
+}
+
+/// The 12 edges of a box, as pairs of corners
+fn get_box_edges(bounding_box: &AABoundingBox) -> [(Vec3, Vec3); 12] {
+    let (min, max) = (bounding_box.min, bounding_box.max);
+    let corner = |i: usize| Vec3::new(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z },
+    );
+    // corners that differ in one bit share an edge
+    let pairs = [(0, 1), (2, 3), (4, 5), (6, 7), (0, 2), (1, 3), (4, 6), (5, 7), (0, 4), (1, 5), (2, 6), (3, 7)];
+    return pairs.map(|(a, b)| (corner(a), corner(b)));
+}
+
+impl Game {
+    pub fn set_debug_overlays(&mut self, overlays: DebugOverlays) {
+        self.debug_overlays = overlays;
+    }
+
+    /// Brings the BVH up to date before its boxes are drawn, edits only mark what they changed
+    pub fn prepare_debug_overlays(&mut self) {
+        let object_count = self.scene_objects.read().unwrap().len();
+        if self.debug_overlays.bvh_boxes && !self.bvh.is_up_to_date(object_count) {
+            self.update_bvh();
+        }
+    }
+
+    /// Draws the overlays that are on over the frame
+    pub fn draw_debug_overlays(&self) {
+        let overlays = self.debug_overlays;
+        if !overlays.is_any_shown() || !matches!(self.status, GameStatus::RasterizingNoLighting | GameStatus::RasterizingWithLighting) {
+            return;
+        }
+        let scene_objects = self.scene_objects.read().unwrap();
+        let is_shown = |scene_obj: &SceneObject| !overlays.only_selected || self.is_object_selected(scene_obj);
+
+        for scene_obj in scene_objects.iter().filter(|scene_obj| is_shown(scene_obj)) {
+            if overlays.wireframe || overlays.normals {
+                let vertices = scene_obj.get_world_vertices();
+                let normals = scene_obj.get_world_normals();
+                for (i, triangle) in scene_obj.mesh.indices.chunks_exact(3).enumerate() {
+                    let (v1, v2, v3) = (vertices[triangle[0]], vertices[triangle[1]], vertices[triangle[2]]);
+                    if overlays.wireframe {
+                        self.draw_world_line(v1, v2, WIREFRAME_COLOR);
+                        self.draw_world_line(v2, v3, WIREFRAME_COLOR);
+                        self.draw_world_line(v3, v1, WIREFRAME_COLOR);
+                    }
+                    if overlays.normals {
+                        let Some(&normal) = normals.get(i) else {
+                            continue;
+                        };
+                        let centroid = (v1 + v2 + v3) * (1.0 / 3.0);
+                        let mut centroid_in_camera_space = centroid;
+                        self.camera.vertex_world_to_camera_space(&mut centroid_in_camera_space);
+                        let max_length = NORMAL_MAX_SCREEN_SIZE * centroid_in_camera_space.x.abs() * self.camera.max_plane_coord;
+                        let length = (NORMAL_LENGTH * ((v2 - v1).len() + (v3 - v2).len() + (v1 - v3).len()) / 3.0).min(max_length);
+                        self.draw_world_line(centroid, centroid + normal * length, NORMAL_COLOR);
+                    }
+                }
+            }
+            if overlays.bounding_spheres {
+                self.draw_bounding_sphere(scene_obj.get_center(), scene_obj.get_radius());
+            }
+        }
+
+        if overlays.bvh_boxes {
+            for node_box in self.bvh.get_node_boxes() {
+                if !overlays.is_depth_shown(node_box.depth) {
+                    continue;
+                }
+                let color = BVH_DEPTH_COLORS[node_box.depth % BVH_DEPTH_COLORS.len()];
+                match node_box.object_index.and_then(|index| scene_objects.get(index)) {
+                    // an object's nodes are in its own space, the box turns with it
+                    Some(scene_obj) => {
+                        if !is_shown(scene_obj) {
+                            continue;
+                        }
+                        for (a, b) in get_box_edges(&node_box.bounding_box) {
+                            self.draw_world_line_on_top(scene_obj.transform.transform_point(a), scene_obj.transform.transform_point(b), color);
+                        }
+                    },
+                    None => {
+                        if overlays.only_selected {
+                            continue;
+                        }
+                        for (a, b) in get_box_edges(&node_box.bounding_box) {
+                            self.draw_world_line_on_top(a, b, color);
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Three circles around the sphere, one in each world plane
+    fn draw_bounding_sphere(&self, center: Vec3, radius: f32) {
+        let axes = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+        for i in 0..3 {
+            let (a, b) = (axes[(i + 1) % 3] * radius, axes[(i + 2) % 3] * radius);
+            let point = |segment: usize| {
+                let angle = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+                return center + a * angle.cos() + b * angle.sin();
+            };
+            for segment in 0..SPHERE_SEGMENTS {
+                self.draw_world_line_on_top(point(segment), point(segment + 1), BOUNDING_SPHERE_COLOR);
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{console_error, console_log, utils::{math::Transform, utils::get_time}};
+
+use super::{game::{Game, GameStatus}, object_id::ObjectId, scene_graph::SceneGraph, scene_object::{MaterialState, SceneObject}};
+
+// Every edit made through Game's edit functions (moving, adding and deleting objects, changing
+// their material) is recorded as an EditCommand that holds what it takes to both revert and
+// redo it. Commands are only ever undone in the reverse order they were made in (and redone
+// in the order they were undone in), so each one can count on the scene being exactly as it
+// left it.
+//
+// Objects taken out of the scene by an undo (or a delete) are kept in their command with
+// their ids detached rather than freed, so they come back as the same ObjectId.
+
+/// How many edits can be undone
+pub const MAX_UNDO_STEPS: usize = 100;
+/// Edits of the same thing made closer together than this (in ms) are undone as one,
+/// so dragging a color picker across the palette doesn't take dozens of undos to revert
+const MERGE_WINDOW_MS: f64 = 500.0;
+
+/// What a transform edit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditTarget {
+    Object(ObjectId),
+    /// A scene graph node, its whole subtree moves with it
+    Group(usize),
+}
+
+#[derive(Clone)]
+pub enum EditCommand {
+    /// World transforms of the target before and after the edit
+    Transform { target: EditTarget, before: Transform, after: Transform },
+    /// The object's material, colors and lights before and after the edit
+    Material { id: ObjectId, before: Box<MaterialState>, after: Box<MaterialState> },
+    /// While the add is undone, the objects wait in removed along with where they were
+    AddObjects { ids: Vec<ObjectId>, removed: Vec<(usize, SceneObject)>, graph_before: SceneGraph, graph_after: SceneGraph },
+    /// Until the delete is undone, the objects wait in removed along with where they were
+    DeleteObjects { ids: Vec<ObjectId>, removed: Vec<(usize, SceneObject)>, graph_before: SceneGraph, graph_after: SceneGraph },
+    /// One edit made to several selected objects at once
+    Batch(Vec<EditCommand>),
+}
+
+impl EditCommand {
+    /// True if both edit the same thing the same way
+    fn can_merge(&self, next: &EditCommand) -> bool {
+        match (self, next) {
+            (EditCommand::Transform { target, .. }, EditCommand::Transform { target: next_target, .. }) => {
+                return target == next_target;
+            },
+            (EditCommand::Material { id, .. }, EditCommand::Material { id: next_id, .. }) => {
+                return id == next_id;
+            },
+            (EditCommand::Batch(commands), EditCommand::Batch(next_commands)) => {
+                return commands.len() == next_commands.len() && commands.iter().zip(next_commands).all(|(command, next)| command.can_merge(next));
+            },
+            _ => {
+                return false;
+            }
+        }
+    }
+
+    /// Folds next into self, which has to be checked with can_merge() first
+    fn merge(&mut self, next: EditCommand) {
+        match (self, next) {
+            (EditCommand::Transform { after, .. }, EditCommand::Transform { after: next_after, .. }) => {
+                *after = next_after;
+            },
+            (EditCommand::Material { after, .. }, EditCommand::Material { after: next_after, .. }) => {
+                *after = next_after;
+            },
+            (EditCommand::Batch(commands), EditCommand::Batch(next_commands)) => {
+                for (command, next) in commands.iter_mut().zip(next_commands) {
+                    command.merge(next);
+                }
+            },
+            _ => {
+                console_error!("EditCommand::merge() called with commands that can't be merged");
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct EditHistory {
+    undo_stack: Vec<EditCommand>,
+    redo_stack: Vec<EditCommand>,
+    /// When the top of undo_stack was last pushed or merged into
+    last_push_time: f64,
+}
+
+impl EditHistory {
+    pub fn new() -> EditHistory {
+        return EditHistory { undo_stack: Vec::new(), redo_stack: Vec::new(), last_push_time: f64::NEG_INFINITY };
+    }
+
+    /// Records an edit that was just made, which makes everything undone so far impossible to redo.
+    /// Returns the commands that dropped out of the history.
+    pub fn push(&mut self, command: EditCommand) -> Vec<EditCommand> {
+        let now = get_time();
+        let mut dropped: Vec<EditCommand> = self.redo_stack.drain(..).collect();
+
+        match self.undo_stack.last_mut() {
+            Some(last) if dropped.is_empty() && now - self.last_push_time < MERGE_WINDOW_MS && last.can_merge(&command) => {
+                last.merge(command);
+            },
+            _ => {
+                self.undo_stack.push(command);
+            }
+        }
+        self.last_push_time = now;
+
+        if self.undo_stack.len() > MAX_UNDO_STEPS {
+            let excess = self.undo_stack.len() - MAX_UNDO_STEPS;
+            dropped.extend(self.undo_stack.drain(..excess));
+        }
+        return dropped;
+    }
+
+    /// The next command to undo. Put it back with push_undone() once it's reverted.
+    pub fn pop_undo(&mut self) -> Option<EditCommand> {
+        self.last_push_time = f64::NEG_INFINITY;
+        return self.undo_stack.pop();
+    }
+    pub fn push_undone(&mut self, command: EditCommand) {
+        self.redo_stack.push(command);
+    }
+
+    /// The next command to redo. Put it back with push_redone() once it's reapplied.
+    pub fn pop_redo(&mut self) -> Option<EditCommand> {
+        self.last_push_time = f64::NEG_INFINITY;
+        return self.redo_stack.pop();
+    }
+    pub fn push_redone(&mut self, command: EditCommand) {
+        self.undo_stack.push(command);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        return !self.undo_stack.is_empty();
+    }
+    pub fn can_redo(&self) -> bool {
+        return !self.redo_stack.is_empty();
+    }
+
+    /// Forgets everything, returns what was dropped
+    pub fn clear(&mut self) -> Vec<EditCommand> {
+        self.last_push_time = f64::NEG_INFINITY;
+        let mut dropped: Vec<EditCommand> = self.undo_stack.drain(..).collect();
+        dropped.append(&mut self.redo_stack);
+        return dropped;
+    }
+}
+
+impl Game {
+    /// Reverts the last edit, false if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        if !self.can_use_edit_history("undo") {
+            return false;
+        }
+        let Some(mut command) = self.edit_history.pop_undo() else {
+            console_log!("Nothing to undo");
+            return false;
+        };
+        self.apply_command(&mut command, true);
+        self.edit_history.push_undone(command);
+        self.post_edit_history_change();
+        return true;
+    }
+
+    /// Reapplies the last undone edit, false if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        if !self.can_use_edit_history("redo") {
+            return false;
+        }
+        let Some(mut command) = self.edit_history.pop_redo() else {
+            console_log!("Nothing to redo");
+            return false;
+        };
+        self.apply_command(&mut command, false);
+        self.edit_history.push_redone(command);
+        self.post_edit_history_change();
+        return true;
+    }
+
+    /// Adds an edit that was just made to the history
+    pub fn record_edit(&mut self, command: EditCommand) {
+        let dropped = self.edit_history.push(command);
+        self.forget_commands(dropped);
+    }
+
+    /// Records edits made together as one, if there are any
+    pub fn record_edits(&mut self, mut commands: Vec<EditCommand>) {
+        match commands.len() {
+            0 => {},
+            1 => self.record_edit(commands.pop().unwrap()),
+            _ => self.record_edit(EditCommand::Batch(commands)),
+        }
+    }
+
+    /// Records objects that were just added, graph_before being the scene graph from before they were
+    pub fn record_added_objects(&mut self, ids: Vec<ObjectId>, graph_before: SceneGraph) {
+        let graph_after = self.scene_graph.clone();
+        self.record_edit(EditCommand::AddObjects { ids, removed: Vec::new(), graph_before, graph_after });
+    }
+
+    /// Clears the history, e.g. when a new scene replaces the objects it refers to
+    pub fn clear_edit_history(&mut self) {
+        let dropped = self.edit_history.clear();
+        self.forget_commands(dropped);
+    }
+
+    /// What transform edits of the selection apply to: the selected group, or the selected objects
+    /// that don't just move along with another selected object
+    pub fn get_selected_edit_targets(&self) -> Vec<EditTarget> {
+        if let Some(group) = self.selected_group {
+            return vec![EditTarget::Group(group)];
+        }
+        return self.get_selection_roots()
+            .into_iter()
+            .filter_map(|index| self.get_object_id(index))
+            .map(EditTarget::Object)
+            .collect();
+    }
+
+    /// Call before editing the selection's transform, pass the result to end_transform_edit() after
+    pub fn begin_transform_edit(&self) -> Vec<(EditTarget, Transform)> {
+        return self.get_selected_edit_targets()
+            .into_iter()
+            .filter_map(|target| Some((target, self.get_target_transform(target)?)))
+            .collect();
+    }
+
+    /// Records the transform edit started by begin_transform_edit(), if it changed anything
+    pub fn end_transform_edit(&mut self, start: Vec<(EditTarget, Transform)>) {
+        let commands = start
+            .into_iter()
+            .filter_map(|(target, before)| {
+                let after = self.get_target_transform(target)?;
+                return (after != before).then_some(EditCommand::Transform { target, before, after });
+            })
+            .collect();
+        self.record_edits(commands);
+    }
+
+    fn get_target_transform(&self, target: EditTarget) -> Option<Transform> {
+        match target {
+            EditTarget::Object(id) => {
+                let index = self.get_object_index(id)?;
+                return Some(self.scene_objects.read().unwrap()[index].transform);
+            },
+            EditTarget::Group(node) => {
+                if !self.scene_graph.contains(node) {
+                    return None;
+                }
+                return Some(self.scene_graph.get_world_transform(node));
+            }
+        }
+    }
+
+    /// Moves the object, or the group's node along with everything under it, to a world transform
+    pub fn set_target_transform(&mut self, target: EditTarget, transform: Transform) {
+        match target {
+            EditTarget::Object(id) => {
+                let Some(index) = self.get_object_index(id) else {
+                    console_error!("Game::set_target_transform() called with deleted object {}", id);
+                    return;
+                };
+                {
+                    let scene_obj = &mut self.scene_objects.write().unwrap()[index];
+                    scene_obj.set_transform(transform);
+                    self.bvh.mark_object_moved(index);
+                }
+                self.update_object_node(index);
+            },
+            EditTarget::Group(node) => {
+                self.edit_node_transform(node, |node_transform, _| *node_transform = transform);
+            }
+        }
+    }
+
+    /// Reverts the command if undo is set, reapplies it otherwise
+    fn apply_command(&mut self, command: &mut EditCommand, undo: bool) {
+        let adding = matches!(command, EditCommand::AddObjects { .. }) != undo;
+        match command {
+            EditCommand::Transform { target, before, after } => {
+                let transform = if undo { *before } else { *after };
+                self.set_target_transform(*target, transform);
+            },
+            EditCommand::Material { id, before, after } => {
+                let state = if undo { before } else { after };
+                self.set_object_material_state(*id, state);
+            },
+            EditCommand::AddObjects { ids, removed, graph_before, graph_after } | EditCommand::DeleteObjects { ids, removed, graph_before, graph_after } => {
+                if adding {
+                    self.put_back_objects(std::mem::take(removed));
+                } else {
+                    *removed = self.take_objects(ids);
+                }
+                self.scene_graph = if undo { graph_before.clone() } else { graph_after.clone() };
+            },
+            EditCommand::Batch(commands) => {
+                if undo {
+                    for command in commands.iter_mut().rev() {
+                        self.apply_command(command, true);
+                    }
+                } else {
+                    for command in commands.iter_mut() {
+                        self.apply_command(command, false);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Objects only the dropped commands were holding on to are gone for good
+    fn forget_commands(&mut self, commands: Vec<EditCommand>) {
+        for command in commands {
+            match command {
+                EditCommand::AddObjects { removed, .. } | EditCommand::DeleteObjects { removed, .. } => {
+                    let removed_objects: Vec<SceneObject> = removed.into_iter().map(|(_, scene_obj)| scene_obj).collect();
+                    self.forget_objects(&removed_objects);
+                },
+                EditCommand::Batch(commands) => {
+                    self.forget_commands(commands);
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn can_use_edit_history(&self, action: &str) -> bool {
+        match self.status {
+            GameStatus::RasterizingNoLighting | GameStatus::RasterizingWithLighting => {
+                return true;
+            },
+            _ => {
+                console_error!("Can't {} while in {:?}", action, self.status);
+                return false;
+            }
+        }
+    }
+
+    fn post_edit_history_change(&mut self) {
+        // the selection may have been deleted, or had its material changed
+        if let Some(group) = self.selected_group {
+            if !self.scene_graph.contains(group) {
+                self.deselect_object();
+            }
+        } else if !self.selected_objects.is_empty() {
+            let remaining: Vec<ObjectId> = self.selected_objects.iter().copied().filter(|&id| self.contains_object(id)).collect();
+            self.select_objects(remaining);
+        }
+
+        // edit mode has no shadows, they're recalculated when it's left
+        if self.status == GameStatus::RasterizingWithLighting {
+            self.extract_raster_lights_from_scene_objects();
+            self.recalculate_shadow_maps();
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::utils::utils::get_time;
+
+use super::game::{Game, GameStatus};
+
+// Where the time goes each frame, measured rather than guessed: per stage times for the
+// rasterizer, triangle counts, and the ray tracer's throughput. The stats of a frame are
+// gathered while it runs and published once it's done, so readers always see a whole frame.
+// Shadow map and BVH rebuilds are counted in the frame they happened in, or the next one when
+// they're set off between frames (by the UI, say).
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    /// All of game_loop, input included
+    pub frame_time_ms: f64,
+    /// From the start of the frame before to the start of this one, 0 for the first frame
+    pub frame_interval_ms: f64,
+
+    // rasterizing, 0 in ray tracing mode
+    pub clear_ms: f64,
+    /// Placing the objects in the world and moving them into camera space
+    pub transform_ms: f64,
+    pub rasterize_opaque_ms: f64,
+    pub rasterize_transparent_ms: f64,
+    pub dof_ms: f64,
+    pub triangles_submitted: usize,
+    /// Back facing or all behind the camera
+    pub triangles_culled: usize,
+    pub triangles_drawn: usize,
+
+    // set off by edits, 0 on frames without one
+    pub shadow_map_rebuild_ms: f64,
+    pub bvh_build_ms: f64,
+
+    // ray tracing, 0 when rasterizing
+    pub ray_tracing_ms: f64,
+    /// Rays cast into the scene this frame: camera rays, their bounces and shadow rays
+    pub rays_traced: usize,
+    /// Rays traced per second spent tracing
+    pub rays_per_second: f64,
+    pub samples_accumulated: usize,
+}
+
+impl FrameStats {
+    pub fn frames_per_second(&self) -> f64 {
+        if self.frame_interval_ms <= 0.0 {
+            return 0.0;
+        }
+        return 1000.0 / self.frame_interval_ms;
+    }
+}
+
+impl std::fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "frame {:.2}ms", self.frame_time_ms)?;
+        if self.frame_interval_ms > 0.0 {
+            write!(f, " ({:.1} fps)", self.frames_per_second())?;
+        }
+        if self.triangles_submitted > 0 {
+            write!(
+                f,
+                ", clear {:.2}ms, transform {:.2}ms, opaque {:.2}ms, transparent {:.2}ms, dof {:.2}ms, triangles {} submitted {} culled {} drawn",
+                self.clear_ms,
+                self.transform_ms,
+                self.rasterize_opaque_ms,
+                self.rasterize_transparent_ms,
+                self.dof_ms,
+                self.triangles_submitted,
+                self.triangles_culled,
+                self.triangles_drawn,
+            )?;
+        }
+        if self.ray_tracing_ms > 0.0 {
+            write!(f, ", ray tracing {:.2}ms, {} rays ({:.0}/s), {} samples", self.ray_tracing_ms, self.rays_traced, self.rays_per_second, self.samples_accumulated)?;
+        }
+        if self.shadow_map_rebuild_ms > 0.0 {
+            write!(f, ", shadow maps {:.2}ms", self.shadow_map_rebuild_ms)?;
+        }
+        if self.bvh_build_ms > 0.0 {
+            write!(f, ", bvh {:.2}ms", self.bvh_build_ms)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Game {
+    /// Starts gathering the stats of a new frame, everything since the last one was published counts toward it.
+    /// game_loop calls this and end_frame_stats itself, native callers running passes on their own can too.
+    pub fn begin_frame_stats(&mut self) {
+        let now = get_time();
+        if self.frame_start_time > 0.0 {
+            self.frame_stats_in_progress.frame_interval_ms = now - self.frame_start_time;
+        }
+        self.frame_start_time = now;
+    }
+
+    /// Publishes the stats gathered since begin_frame_stats as frame_stats
+    pub fn end_frame_stats(&mut self) {
+        let mut stats = std::mem::take(&mut self.frame_stats_in_progress);
+        stats.frame_time_ms = get_time() - self.frame_start_time;
+        if self.status == GameStatus::RayTracing {
+            stats.samples_accumulated = self.ray_samples_accumulated;
+        }
+        if stats.ray_tracing_ms > 0.0 {
+            stats.rays_per_second = stats.rays_traced as f64 / (stats.ray_tracing_ms / 1000.0);
+        }
+        self.frame_stats = stats;
+    }
+}
@@ -1,13 +1,10 @@
-use std::{cell::RefCell, collections::HashSet, f32::consts::{E, PI}, sync::RwLock};
-
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{console, js_sys};
+use std::{cell::RefCell, collections::HashSet, f32::consts::{E, PI}, sync::{Arc, RwLock}};
 
 use rayon::prelude::*;
 
-use crate::{console_error, console_log, console_warn, utils::{math::{degrees_to_radians, radians_to_degrees, Vec3}, utils::{clamp_color, gamma_correct_color, get_time, shift_color}}, wasm::wasm::{js_update_dof_strength, js_update_focal_distance, js_update_follow_camera, js_update_fov, js_update_game_status, js_update_scene_loading, js_update_selected_obj_mat_props, MaterialProperties}};
+use crate::{console_error, console_log, console_warn, utils::{math::{degrees_to_radians, radians_to_degrees, Quat, Transform, Vec3}, utils::{clamp_color, gamma_correct_color, get_time, shift_color}}, wasm::wasm::MaterialProperties};
 
-use super::{buffers::{PixelBuf, ZBuffer}, camera::Camera, gltf_parser::{extract_combined_mesh_from_gltf, extract_combined_mesh_from_raw_glb_bytes}, lighting::Light, mesh::{Mesh, PhongProperties}, ray_tracing::{bvh::{BVHNode, FlattenedBVH}, hittable::Hittable, material::{Dielectric, Lambertian, Material, Metal}}, scene_object::SceneObject};
+use super::{buffers::{ObjectIdBuffer, PixelBuf, ZBuffer}, camera::Camera, gltf_parser::extract_node_tree_from_raw_glb_bytes, lighting::Light, ui_bridge::{default_ui_bridge, UiBridge}, ray_tracing::{bvh::BVHBuildOptions, hittable::Hittable, scene_bvh::SceneBVH, bvh_heatmap::{BVHHeatmap, BVHHeatmapSummary}}, scene_file::ShapeDescription, scene_graph::SceneGraph, scene_object::{MaterialState, SceneObject}, object_id::{ObjectId, ObjectIds}, edit_history::{EditCommand, EditHistory, EditTarget}, clipboard::Clipboard, gizmo::{Gizmo, GizmoMode}, grid::GridSettings, debug_overlay::DebugOverlays, frame_stats::FrameStats, texture::{TextureFilter, TriangleColor}, shading::{ShadingNormalMap, TriangleShading}, normal_map::Tangent};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum GameStatus {
@@ -17,9 +14,39 @@ pub enum GameStatus {
     Paused,
 }
 
+/// One row of a triangle being filled: where its two edges cross the row, the pixels
+/// between them, and the inverse depths at the edges
+#[derive(Clone, Copy)]
+struct ScanlineRow {
+    y: usize,
+    x1: f32,
+    x2: f32,
+    left: usize,
+    right: usize,
+    inv_left_depth: f32,
+    inv_right_depth: f32,
+}
+
+impl ScanlineRow {
+    /// Inverse depth is what's linear across the screen, not depth
+    fn depth_at(&self, x: usize) -> f32 {
+        let q = (x as f32 - self.x1) / (self.x2 - self.x1);
+        let inv_depth = self.inv_left_depth * (1.0 - q) + self.inv_right_depth * q;
+        return 1.0 / inv_depth;
+    }
+}
+
 
 pub struct Game {
     pub scene_objects: RwLock<Vec<SceneObject>>,
+    /// Maps each object's ObjectId to where the object is in scene_objects
+    object_ids: ObjectIds,
+    /// Groups objects so they can be moved together, see scene_graph.rs
+    pub scene_graph: SceneGraph,
+    /// Undo/redo, see edit_history.rs
+    pub edit_history: EditHistory,
+    /// What was last copied, kept across scene loads, see clipboard.rs
+    pub clipboard: Clipboard,
     lights: Vec<Light>,
 
     pub camera: Camera,
@@ -32,30 +59,63 @@ pub struct Game {
 
     pub pixel_buf: PixelBuf,
     pub zbuf: ZBuffer,
+    pub id_buf: ObjectIdBuffer,
 
     pub keys_currently_pressed: HashSet<String>,
     pub keys_pressed_last_frame: HashSet<String>,
     pub mouse_move: Vec3,
     pub mouse_clicked_last_frame: bool,
+    /// Whether the left mouse button is held, so Shift-clicking doesn't also move the camera down
+    pub mouse_down: bool,
 
-    pub looking_at: RwLock<Option<(usize, Vec3)>>,
+    /// The object under the crosshair, the point on it and the surface normal there, written by the rasterizer
+    pub looking_at: RwLock<Option<(ObjectId, Vec3, Vec3)>>,
     pub follow_camera: bool,
+    /// Where the selection was when it started following the camera, so the move can be undone
+    follow_edit_start: Vec<(EditTarget, Transform)>,
 
     pub status: GameStatus,
-    pub selected_object_index: Option<usize>,
+    /// The last one is the active object, whose material the UI shows and which follows the camera
+    pub selected_objects: Vec<ObjectId>,
+    /// Scene graph node whose whole subtree is selected, only set while selected_objects is empty
+    pub selected_group: Option<usize>,
+    /// Rectangle being dragged out for box selection (two corners, in render pixels), drawn in edit mode
+    pub selection_box: Option<(usize, usize, usize, usize)>,
+    /// Handles for moving the selection with the mouse, see gizmo.rs
+    pub gizmo: Gizmo,
+    /// Ground grid, world axes and snapping in edit mode, see grid.rs
+    pub grid: GridSettings,
+    /// Wireframe, normals, bounding spheres and BVH boxes drawn over the frame, see debug_overlay.rs
+    pub debug_overlays: DebugOverlays,
+    /// How both renderers sample mesh textures, see texture.rs
+    pub texture_filter: TextureFilter,
     pub ray_samples_accumulated: usize,
 
     // ray-tracing variables
     // pub bvh: Option<BVHNode>,
-    pub bvh: Option<FlattenedBVH>,
+    pub bvh: SceneBVH,
+    pub bvh_build_options: BVHBuildOptions,
+    /// Colors the ray traced image by BVH traversal cost instead of shading it, see bvh_heatmap.rs
+    pub bvh_heatmap: Option<BVHHeatmap>,
+    /// Averages from the last heatmap traced
+    pub bvh_heatmap_summary: Option<BVHHeatmapSummary>,
     rt_lights: Vec<Box<dyn Hittable>>,
     pub ray_max_depth: usize,
+    /// Seeds every path traced sample, the same seed gives the same image
+    pub rt_seed: u64,
 
     pub defocus_angle: f32,
     pub focus_dist: f32,
 
     // debug stuff
     pub rt_start_time: f64,
+    /// Where the time went in the last whole frame, see frame_stats.rs
+    pub frame_stats: FrameStats,
+    pub(super) frame_stats_in_progress: FrameStats,
+    pub(super) frame_start_time: f64,
+
+    // where UI state changes get reported to (the JS bridge in the browser)
+    ui_bridge: Box<dyn UiBridge>,
 
     // testing
 }
@@ -63,9 +123,19 @@ pub struct Game {
 impl Game {
 
     pub fn new() -> Game {
-        let mut game = Game {
+        return Game::new_with_ui_bridge(default_ui_bridge());
+    }
+
+    /// Same as Game::new(), but UI notifications go to the given bridge.
+    /// Headless/native users pass a HeadlessUiBridge (or their own).
+    pub fn new_with_ui_bridge(ui_bridge: Box<dyn UiBridge>) -> Game {
+        let game = Game {
 
             scene_objects: RwLock::new(Vec::new()),
+            object_ids: ObjectIds::new(),
+            scene_graph: SceneGraph::new(),
+            edit_history: EditHistory::new(),
+            clipboard: Clipboard::new(),
             lights: Vec::new(),
 
             camera: Camera::new(Vec3::new(0.001, 0.001, 0.501), 0.001, 0.001, PI/2.0, 500, 500),
@@ -78,29 +148,47 @@ impl Game {
 
             pixel_buf: PixelBuf::new(500, 500),
             zbuf: ZBuffer::new(500, 500),
+            id_buf: ObjectIdBuffer::new(500, 500),
 
             keys_currently_pressed: HashSet::new(),
             keys_pressed_last_frame: HashSet::new(),
             mouse_move: Vec3::new(0.0, 0.0, 0.0),
             mouse_clicked_last_frame: false,
+            mouse_down: false,
 
             looking_at: RwLock::new(None),
             follow_camera: false,
+            follow_edit_start: Vec::new(),
 
             status: GameStatus::RasterizingNoLighting,
-            selected_object_index: None,
+            selected_objects: Vec::new(),
+            selected_group: None,
+            selection_box: None,
+            gizmo: Gizmo::new(),
+            grid: GridSettings::new(),
+            debug_overlays: DebugOverlays::new(),
+            texture_filter: TextureFilter::default(),
             ray_samples_accumulated: 0,
 
             // ray tracing variables
-            bvh: None,
+            bvh: SceneBVH::new(),
+            bvh_build_options: BVHBuildOptions::default(),
+            bvh_heatmap: None,
+            bvh_heatmap_summary: None,
             rt_lights: Vec::new(),
             ray_max_depth: 20,
+            rt_seed: 0,
 
             defocus_angle: 0.0,
             focus_dist: 10.0,
 
             // debug stuff
             rt_start_time: 0.0,
+            frame_stats: FrameStats::default(),
+            frame_stats_in_progress: FrameStats::default(),
+            frame_start_time: 0.0,
+
+            ui_bridge,
 
             // testing
         };
@@ -155,15 +243,16 @@ impl Game {
         // game.create_rt_test_scene_spheres();
 
         // Update JS initial states where needed
-        js_update_fov(radians_to_degrees(game.camera.get_fov()));
-        js_update_focal_distance(game.focus_dist);
-        js_update_dof_strength(game.defocus_angle);
+        game.ui_bridge.update_fov(radians_to_degrees(game.camera.get_fov()));
+        game.ui_bridge.update_focal_distance(game.focus_dist);
+        game.ui_bridge.update_dof_strength(game.defocus_angle);
 
         return game;
     }
 
 
     pub fn game_loop(&mut self) {
+        self.begin_frame_stats();
 
         // process these no matter the game state
         self.process_all_input();
@@ -172,12 +261,15 @@ impl Game {
             GameStatus::RasterizingNoLighting => {
                 self.pre_raster_render_logic();
                 self.render_frame();
+                self.draw_selection_box();
                 // self.gamma_correct_post_processing(); // already done in wasm.rs by get_gamma_corrected_buf_as_u8
             },
             GameStatus::RasterizingWithLighting => {
                 self.pre_raster_render_logic();
                 self.render_frame();
+                let dof_start_time = get_time();
                 self.depth_of_field_post_processing();
+                self.frame_stats_in_progress.dof_ms = get_time() - dof_start_time;
                 // self.gamma_correct_post_processing(); // already done in wasm.rs by get_gamma_corrected_buf_as_u8
             },
             GameStatus::RayTracing => {
@@ -187,6 +279,7 @@ impl Game {
 
             }
         }
+        self.end_frame_stats();
     }
 
     fn set_game_status(&mut self, status: GameStatus) {
@@ -200,30 +293,40 @@ impl Game {
             GameStatus::RayTracing => 2,
             GameStatus::Paused => 0, // TODO: check this
         };
-        js_update_game_status(game_status_number);
+        self.ui_bridge.update_game_status(game_status_number);
     }
 
     pub fn set_fov(&mut self, fov: f32) {
         self.camera.set_fov(fov);
         let fov_degrees = radians_to_degrees(fov);
-        js_update_fov(fov_degrees);
+        self.ui_bridge.update_fov(fov_degrees);
     }
 
     pub fn set_focal_dist(&mut self, dist: f32) {
         self.focus_dist = dist;
-        js_update_focal_distance(dist);
+        self.ui_bridge.update_focal_distance(dist);
     }
     pub fn set_defocus_angle(&mut self, angle: f32) {
         self.defocus_angle = angle;
-        js_update_dof_strength(angle);
+        self.ui_bridge.update_dof_strength(angle);
+    }
+
+    /// Resizes the camera and the pixel/depth buffers. Clears any accumulated ray tracing samples.
+    pub fn set_resolution(&mut self, width: usize, height: usize) {
+        self.camera.width = width;
+        self.camera.height = height;
+        self.pixel_buf = PixelBuf::new(width, height);
+        self.zbuf = ZBuffer::new(width, height);
+        self.id_buf = ObjectIdBuffer::new(width, height);
+        self.ray_samples_accumulated = 0;
     }
 
     pub fn js_update_ui(&self) {
         // update UI elements in JS
         let fov_degrees = radians_to_degrees(self.camera.get_fov());
-        js_update_fov(fov_degrees);
-        js_update_focal_distance(self.focus_dist);
-        js_update_dof_strength(self.defocus_angle);
+        self.ui_bridge.update_fov(fov_degrees);
+        self.ui_bridge.update_focal_distance(self.focus_dist);
+        self.ui_bridge.update_dof_strength(self.defocus_angle);
     }
 
     pub fn enter_edit_mode(&mut self) {
@@ -232,14 +335,12 @@ impl Game {
     }
 
     pub fn enter_ray_tracing_mode(&mut self) {
-        js_update_game_status(2);
+        self.ui_bridge.update_game_status(2);
         self.status = GameStatus::RayTracing;
         self.ray_samples_accumulated = 0;
         self.rt_start_time = get_time();
 
-        if self.bvh.is_none() {
-            self.rebuild_bvh();
-        }
+        self.update_bvh();
         self.extract_rt_lights_from_scene_objects();
     }
 
@@ -264,28 +365,52 @@ impl Game {
     }
 
     pub fn set_follow_camera(&mut self, follow: bool) {
+        if follow && !self.follow_camera {
+            self.follow_edit_start = self.begin_transform_edit();
+        } else if !follow {
+            self.end_follow_edit();
+        }
         self.follow_camera = follow;
-        js_update_follow_camera(follow);
+        self.ui_bridge.update_follow_camera(follow);
+    }
+
+    /// Records everything the selection moved while following the camera as one edit
+    fn end_follow_edit(&mut self) {
+        let start = std::mem::take(&mut self.follow_edit_start);
+        self.end_transform_edit(start);
     }
 
+    /// Applies to every selected object. Objects whose material isn't editable are skipped,
+    /// unless it's the active one (the UI only lets it be edited when it is).
     pub fn set_selected_object_material_properties(&mut self, props: MaterialProperties) {
         if self.status == GameStatus::RasterizingNoLighting {
-            if let Some(selected_index) = self.selected_object_index {
-                console_log!("WASM: Set selected object material properties with props: {:?}", props);
-
-                // let selected_obj = &mut self.scene_objects.borrow_mut()[selected_index];
-                let selected_obj = &mut self.scene_objects.write().unwrap()[selected_index];
+            if let (Some(active_id), Some(active_index)) = (self.get_active_object(), self.get_active_object_index()) {
+                console_log!("WASM: Set material properties of {} selected objects with props: {:?}", self.selected_objects.len(), props);
                 let color = Vec3::new(props.r, props.g, props.b);
-                let material_type = props.material_type;
-                let extra_prop = props.extra_prop;
-
-                selected_obj.set_color(color);
-                selected_obj.set_material_properties(material_type, extra_prop, color);
-
-                self.bvh = None; // invalidate bvh if obj is changed
+                let selected_objects = self.selected_objects.clone();
+                let mut commands = Vec::new();
+                {
+                    let mut scene_objects = self.scene_objects.write().unwrap();
+                    for id in selected_objects {
+                        let Some(index) = self.object_ids.get_index(id) else {
+                            continue;
+                        };
+                        // let selected_obj = &mut self.scene_objects.borrow_mut()[selected_index];
+                        let selected_obj = &mut scene_objects[index];
+                        if !selected_obj.mat_is_editable && id != active_id {
+                            continue;
+                        }
+                        let before = Box::new(selected_obj.get_material_state());
+                        selected_obj.set_color(color);
+                        selected_obj.set_material_properties(props.material_type, props.extra_prop, color);
+                        self.bvh.mark_object_changed(index);
+                        commands.push(EditCommand::Material { id, before, after: Box::new(selected_obj.get_material_state()) });
+                    }
 
-                let props = self.parse_selected_obj_mat_props(selected_obj);
-                js_update_selected_obj_mat_props(Some(props));
+                    let props = self.parse_selected_obj_mat_props(&scene_objects[active_index]);
+                    self.ui_bridge.update_selected_obj_mat_props(Some(props));
+                }
+                self.record_edits(commands);
             } else {
                 console_error!("Game::set_selected_object_material_properties() called but no object is selected");
             }
@@ -294,53 +419,78 @@ impl Game {
         }
     }
 
+    /// With grid snapping on, the middle of the selection ends up on the grid lines it moves toward
     pub fn translate_selected_obj(&mut self, x: f32, y: f32, z: f32) {
-        if self.status == GameStatus::RasterizingNoLighting {
-            if let Some(selected_index) = self.selected_object_index {
-                // let selected_obj = &mut self.scene_objects.borrow_mut()[selected_index];
-                let selected_obj = &mut self.scene_objects.write().unwrap()[selected_index];
-                let offset = Vec3::new(x,y,z);
-                selected_obj.translate_by(offset);
-                self.bvh = None; // invalidate bvh if obj is changed
-            } else {
-                console_error!("Game::translate_selected_obj() called but no object is selected");
-            }
-        } else {
-            console_error!("Game::translate_selected_obj() called but not in EditMode with obj selected, got GameStatus: {:?}", self.status);
-        }
+        let offset = self.snap_translation(Vec3::new(x,y,z));
+        self.edit_selection_transform("translate_selected_obj", |transform, _| transform.translation += offset);
     }
 
+    /// Each angle is in degrees, rounded to whole angle steps when angles snap
     pub fn rotate_selected_obj(&mut self, x: f32, y: f32, z: f32) {
-        let x_rad = degrees_to_radians(x);
-        let y_rad = degrees_to_radians(y);
-        let z_rad = degrees_to_radians(z);
-        if self.status == GameStatus::RasterizingNoLighting {
-            if let Some(selected_index) = self.selected_object_index {
-                // let selected_obj = &mut self.scene_objects.borrow_mut()[selected_index];
-                let selected_obj = &mut self.scene_objects.write().unwrap()[selected_index];
-                selected_obj.rotate_around_center(z_rad, y_rad);
-                self.bvh = None; // invalidate bvh if obj is changed
-            } else {
-                console_error!("Game::rotate_selected_obj() called but no object is selected");
-            }
-        } else {
-            console_error!("Game::rotate_selected_obj() called but not in EditMode with obj selected, got GameStatus: {:?}", self.status);
-        }
+        let x_rad = degrees_to_radians(self.grid.snap_angle_step(x));
+        let y_rad = degrees_to_radians(self.grid.snap_angle_step(y));
+        let z_rad = degrees_to_radians(self.grid.snap_angle_step(z));
+        // z first, then y, then x, all around world axes
+        let rotation = Quat::rotation_x(x_rad) * Quat::rotation_y(y_rad) * Quat::rotation_z(z_rad);
+        self.edit_selection_transform("rotate_selected_obj", |transform, center| transform.rotate_around(center, rotation));
     }
 
     pub fn scale_selected_obj(&mut self, scale_factor: f32) {
-        if self.status == GameStatus::RasterizingNoLighting {
-            if let Some(selected_index) = self.selected_object_index {
-                // let selected_obj = &mut self.scene_objects.borrow_mut()[selected_index];
-                let selected_obj = &mut self.scene_objects.write().unwrap()[selected_index];
-                selected_obj.scale_by(scale_factor);
-                self.bvh = None; // invalidate bvh if obj is changed
-            } else {
-                console_error!("Game::scale_selected_obj() called but no object is selected");
+        self.edit_selection_transform("scale_selected_obj", |transform, center| transform.scale_around(center, scale_factor));
+    }
+
+    /// Right handed rotation around an axis through the selection's center
+    pub fn rotate_selected_obj_around_axis(&mut self, axis: Vec3, degrees: f32) {
+        let rotation = Quat::from_axis_angle(axis, degrees_to_radians(self.grid.snap_angle_step(degrees)));
+        self.edit_selection_transform("rotate_selected_obj_around_axis", |transform, center| transform.rotate_around(center, rotation));
+    }
+
+    /// For a selected group, the transform of its node in the world. With several objects selected, the active one's.
+    pub fn get_selected_obj_transform(&self) -> Option<Transform> {
+        if let Some(group) = self.selected_group {
+            return Some(self.scene_graph.get_world_transform(group));
+        }
+        let active_index = self.get_active_object_index()?;
+        return self.scene_objects.read().unwrap().get(active_index).map(|obj| obj.transform);
+    }
+
+    /// Replaces the selected object's transform instead of adding to it. With several objects
+    /// selected, that's the active one, and the others keep where they are relative to it.
+    pub fn set_selected_obj_transform(&mut self, transform: Transform) {
+        let Some(current) = self.get_selected_obj_transform() else {
+            console_error!("Game::set_selected_obj_transform() called but no object is selected");
+            return;
+        };
+        // as a matrix, a non-uniform scale in it can shear the others
+        let Some(change) = current.to_mat4().affine_inverse().map(|inverse| transform.to_mat4() * inverse) else {
+            console_error!("Game::set_selected_obj_transform() the selection's transform {:?} can't be undone", current);
+            return;
+        };
+        self.edit_selection_transform("set_selected_obj_transform", |selected_transform, _| {
+            if *selected_transform == current {
+                *selected_transform = transform;
+            } else if let Some(changed) = Transform::from_mat4(&(change * selected_transform.to_mat4())) {
+                *selected_transform = changed;
             }
+        });
+    }
+
+    /// Applies edit to the selected group's transform, or to every selected object's as if they were
+    /// grouped. The center passed in is the middle of the selection. Recorded as one edit.
+    fn edit_selection_transform(&mut self, caller: &str, edit: impl Fn(&mut Transform, Vec3)) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::{}() called but not in EditMode with obj selected, got GameStatus: {:?}", caller, self.status);
+            return;
+        }
+        let edit_start = self.begin_transform_edit();
+        if let Some(group) = self.selected_group {
+            self.edit_node_transform(group, edit);
+        } else if self.get_active_object().is_some() {
+            self.edit_selected_objects(edit);
         } else {
-            console_error!("Game::scale_selected_obj() called but not in EditMode with obj selected, got GameStatus: {:?}", self.status);
+            console_error!("Game::{}() called but no object is selected", caller);
         }
+        self.end_transform_edit(edit_start);
     }
 
     pub fn add_sphere(&mut self, radius: f32) {
@@ -352,18 +502,19 @@ impl Game {
                 3,
                 SceneObject::new_diffuse_mat(),
             );
+            new_sphere.source = Some(Arc::new(ShapeDescription::Sphere { center: [0.0, 0.0, 0.0], radius, color: [0.7, 0.7, 0.7], subdivisions: 3 }));
 
-            if let Some((_, looking_at_pos)) = *self.looking_at.read().unwrap() {
+            if let Some((_, looking_at_pos, _)) = *self.looking_at.read().unwrap() {
                 new_sphere.translate_to(looking_at_pos);
             } else {
-                let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * new_sphere.mesh.radius);
+                let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * new_sphere.get_radius());
                 self.camera.vertex_screen_to_world_space(&mut looking_at_pos);
                 new_sphere.translate_to(looking_at_pos);
             }
 
-            // self.scene_objects.borrow_mut().push(new_sphere);
-            self.scene_objects.write().unwrap().push(new_sphere);
-            self.bvh = None; // invalidate bvh if obj is added
+            let graph_before = self.scene_graph.clone();
+            let id = self.add_scene_object(new_sphere);
+            self.record_added_objects(vec![id], graph_before);
         } else {
             console_error!("Game::add_sphere() called but not in Rasterizing state");
         }
@@ -377,29 +528,92 @@ impl Game {
                 Vec3::new(0.7, 0.7, 0.7),
                 SceneObject::new_diffuse_mat(),
             );
+            let half_size = [0.5 * x, 0.5 * y, 0.5 * z];
+            new_box.source = Some(Arc::new(ShapeDescription::Box { corner1: half_size.map(|h| -h), corner2: half_size, color: [0.7, 0.7, 0.7] }));
 
-            if let Some((_, looking_at_pos)) = *self.looking_at.read().unwrap() {
+            if let Some((_, looking_at_pos, _)) = *self.looking_at.read().unwrap() {
                 new_box.translate_to(looking_at_pos);
             } else {
-                let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * new_box.mesh.radius);
+                let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * new_box.get_radius());
                 self.camera.vertex_screen_to_world_space(&mut looking_at_pos);
                 new_box.translate_to(looking_at_pos);
             }
 
-            // self.scene_objects.borrow_mut().push(new_box);
-            self.scene_objects.write().unwrap().push(new_box);
-            self.bvh = None; // invalidate bvh if obj is added
+            let graph_before = self.scene_graph.clone();
+            let id = self.add_scene_object(new_box);
+            self.record_added_objects(vec![id], graph_before);
         } else {
             console_error!("Game::add_sphere() called but not in Rasterizing state");
         }
     }
 
+    /// Adds an instance of the active object where the user is looking. The copy shares
+    /// the selected object's geometry, so it costs next to no memory however big the mesh is.
+    pub fn instance_selected_obj(&mut self) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::instance_selected_obj() called but not in EditMode, got GameStatus: {:?}", self.status);
+            return;
+        }
+        let (Some(selected_id), Some(selected_index)) = (self.get_active_object(), self.get_active_object_index()) else {
+            console_error!("Game::instance_selected_obj() called but no object is selected");
+            return;
+        };
+
+        let (center, radius) = {
+            let scene_objects = self.scene_objects.read().unwrap();
+            (scene_objects[selected_index].get_center(), scene_objects[selected_index].get_radius())
+        };
+        let destination = if let Some((_, looking_at_pos, _)) = *self.looking_at.read().unwrap() {
+            looking_at_pos
+        } else {
+            let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * radius);
+            self.camera.vertex_screen_to_world_space(&mut looking_at_pos);
+            looking_at_pos
+        };
+
+        let mut transform = self.scene_objects.read().unwrap()[selected_index].transform;
+        transform.translation += destination - center;
+        let graph_before = self.scene_graph.clone();
+        if let Some(id) = self.add_instance(selected_id, transform) {
+            self.record_added_objects(vec![id], graph_before);
+        }
+    }
+
+    /// Adds an instance of the source object, placed in the world by transform.
+    /// If the source is in a group, so is the instance. Returns the id of the new object.
+    pub fn add_instance(&mut self, source: ObjectId, transform: Transform) -> Option<ObjectId> {
+        let Some(source_index) = self.get_object_index(source) else {
+            console_error!("Game::add_instance() called with deleted source object {}", source);
+            return None;
+        };
+        let (new_obj, source_node) = {
+            let source_obj = &self.scene_objects.read().unwrap()[source_index];
+            let mut new_obj = source_obj.new_instance();
+            new_obj.set_transform(transform);
+            (new_obj, source_obj.node)
+        };
+        let new_id = self.add_scene_object(new_obj);
+
+        let source_parent = source_node.and_then(|node| self.scene_graph.get(node)).and_then(|node| node.parent);
+        if let Some(parent) = source_parent {
+            self.set_object_parent(new_id, Some(parent));
+        }
+        return Some(new_id);
+    }
+
+    /// Adds a GLB model where the user is looking, as a group holding one object per node of the file
     pub fn add_custom_object(&mut self, glb_bytes: &[u8]) {
-        match extract_combined_mesh_from_raw_glb_bytes(glb_bytes) {
-            Ok(mut mesh) => {
+        match extract_node_tree_from_raw_glb_bytes(glb_bytes) {
+            Ok(tree) => {
+                let graph_before = self.scene_graph.clone();
+                let group = self.add_gltf_node_tree(tree, "Imported model", Transform::identity());
+                let Some((center, radius)) = self.get_group_bounds(group) else {
+                    console_error!("GLB has no meshes to add");
+                    self.scene_graph.remove_subtree(group);
+                    return;
+                };
 
-                // scale mesh to be between 1 and 100 radius
-                let radius = mesh.radius;
+                // scale model to be between 1 and 100 radius
                 let scale_factor = if radius > 100.0 {
                     100.0 / radius
                 } else if radius < 1.0 {
@@ -407,23 +621,19 @@ impl Game {
                 } else {
                     1.0
                 };
-                mesh.scale_by(scale_factor);
 
-                // move mesh to where user is looking
+                // move model to where user is looking
                 let mut looking_at_dir = *self.camera.get_looking_dir();
                 looking_at_dir.normalize();
-                looking_at_dir *= 1.5 * mesh.radius; // move it 1.5x its radius away from camera
-
-                mesh.set_center(self.camera.pos + looking_at_dir);
-
-                let new_obj = SceneObject::new_from_mesh(
-                    mesh,
-                    Lambertian::default().clone_box(),
-                    false
-                );
-
-                self.scene_objects.write().unwrap().push(new_obj);
-                self.bvh = None; // invalidate bvh if obj is added
+                looking_at_dir *= 1.5 * radius * scale_factor; // move it 1.5x its radius away from camera
+                let destination = self.camera.pos + looking_at_dir;
+
+                self.edit_node_transform(group, |transform, _| {
+                    transform.scale_around(center, scale_factor);
+                    transform.translation += destination - center;
+                });
+                let ids = self.get_subtree_objects(group);
+                self.record_added_objects(ids, graph_before);
             },
             Err(e) => {
                 console_error!("Failed to extract mesh from glb bytes: {}", e);
@@ -431,6 +641,28 @@ impl Game {
         }
     }
 
+    /// Records a keydown. Letters are held in lowercase, since the key reported on keyup
+    /// depends on whether Shift is still down then
+    pub fn key_down(&mut self, key: String) {
+        self.keys_currently_pressed.insert(Self::held_key(&key));
+        self.keys_pressed_last_frame.insert(key);
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.keys_currently_pressed.remove(&Self::held_key(key));
+        // macs don't send keyups for letters let go while Cmd is down
+        if key == "Meta" {
+            self.keys_currently_pressed.retain(|held| held.chars().count() != 1);
+        }
+    }
+
+    fn held_key(key: &str) -> String {
+        if key.chars().count() == 1 {
+            return key.to_lowercase();
+        }
+        return key.to_string();
+    }
+
     fn process_all_input(&mut self) {
 
         match self.status {
@@ -443,6 +675,34 @@ impl Game {
             _ => {} 
         }
 
+        // Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes (Cmd instead of Ctrl on macs)
+        if self.keys_currently_pressed.contains("Control") || self.keys_currently_pressed.contains("Meta") {
+            if self.keys_pressed_last_frame.contains("z") {
+                self.undo();
+            } else if self.keys_pressed_last_frame.contains("y") || self.keys_pressed_last_frame.contains("Z") {
+                self.redo();
+            }
+            // Ctrl+C copies, Ctrl+V pastes, Ctrl+D duplicates, in edit mode
+            if self.status == GameStatus::RasterizingNoLighting {
+                if self.keys_pressed_last_frame.contains("c") {
+                    self.copy_selection();
+                } else if self.keys_pressed_last_frame.contains("v") {
+                    self.paste();
+                } else if self.keys_pressed_last_frame.contains("d") {
+                    self.duplicate_selection_beside();
+                }
+            }
+        }
+
+        // 1, 2 and 3 switch the gizmo between moving, rotating and scaling, 0 hides it
+        if self.status == GameStatus::RasterizingNoLighting {
+            for (key, mode) in [("0", GizmoMode::Hidden), ("1", GizmoMode::Translate), ("2", GizmoMode::Rotate), ("3", GizmoMode::Scale)] {
+                if self.keys_pressed_last_frame.contains(key) {
+                    self.set_gizmo_mode(mode);
+                }
+            }
+        }
+
         if self.keys_pressed_last_frame.contains("p") {
             console_log!("Pausing or unpausing");
             if self.status == GameStatus::Paused {
@@ -477,15 +737,17 @@ impl Game {
                     let looking_at = {
                         *self.looking_at.read().unwrap()
                     };
-                    if let Some((looking_at_index, _)) = looking_at { // if clicked on something
-                        if let Some(selected_index) = self.selected_object_index {
-                            if looking_at_index == selected_index { // if clicked on already-selected object
-                                self.deselect_object();
-                            } else { // if clicked on non-selected object
-                                self.select_object(looking_at_index);
-                            }
-                        } else { // if clicked on something and nothing selected
-                            self.select_object(looking_at_index);
+                    if self.keys_currently_pressed.contains("Shift") { // shift-click adds or removes one object
+                        // looking_at skips selected objects, the id buffer doesn't
+                        let clicked = self.id_buf.get_id(self.camera.width / 2, self.camera.height / 2);
+                        if let Some(clicked_id) = clicked {
+                            self.toggle_object_selection(clicked_id);
+                        }
+                    } else if let Some((looking_at_id, _, _)) = looking_at { // if clicked on something
+                        if self.selected_objects == [looking_at_id] { // if clicked on already-selected object
+                            self.deselect_object();
+                        } else { // if clicked on non-selected object
+                            self.select_object(looking_at_id);
                         }
                     } else { // if clicked on nothing
                         self.deselect_object();
@@ -499,34 +761,115 @@ impl Game {
         }
     }
 
-    fn select_object(&mut self, index: usize) {
+    /// Selects just this object
+    pub fn select_object(&mut self, id: ObjectId) {
+        if !self.contains_object(id) {
+            console_error!("Game::select_object() called with deleted object {}", id);
+            return;
+        }
+        self.select_objects(vec![id]);
+    }
+
+    /// Replaces the selection, the last object becomes the active one. Deselects for an empty list.
+    pub fn select_objects(&mut self, ids: Vec<ObjectId>) {
         match self.status {
             GameStatus::RasterizingNoLighting => {
-                console_log!("WASM: Selected object with index: {}", index);
-                self.selected_object_index = Some(index);
+                let mut selected_objects: Vec<ObjectId> = Vec::with_capacity(ids.len());
+                for id in ids {
+                    if self.contains_object(id) && !selected_objects.contains(&id) {
+                        selected_objects.push(id);
+                    }
+                }
+                let Some(&active_id) = selected_objects.last() else {
+                    self.deselect_object();
+                    return;
+                };
+                let active_index = self.get_object_index(active_id).unwrap();
+                console_log!("WASM: Selected {} objects, active object {} (index {})", selected_objects.len(), active_id, active_index);
+                self.end_follow_edit();
+                self.end_gizmo_drag();
+                self.selected_objects = selected_objects;
+                self.selected_group = None;
                 self.follow_camera = false;
                 // let selected_obj = &self.scene_objects.borrow()[index];
-                let selected_obj = &self.scene_objects.read().unwrap()[index];
+                let selected_obj = &self.scene_objects.read().unwrap()[active_index];
                 // notify JS of changes:
                 let props = self.parse_selected_obj_mat_props(selected_obj);
-                js_update_follow_camera(false);
-                js_update_selected_obj_mat_props(Some(props));
-                js_update_game_status(1); // this is just for redundancy
+                self.ui_bridge.update_selected_group(None);
+                self.ui_bridge.update_selected_object_id(Some(active_id));
+                self.ui_bridge.update_selected_object_count(self.selected_objects.len());
+                self.ui_bridge.update_follow_camera(false);
+                self.ui_bridge.update_selected_obj_mat_props(Some(props));
+                self.ui_bridge.update_game_status(1); // this is just for redundancy
             },
             _ => {
-                console_error!("Game::select_object() called but not in RasterizingNoLighting state, got {:?}", self.status);
+                console_error!("Game::select_objects() called but not in RasterizingNoLighting state, got {:?}", self.status);
                 return;
             }
         }
+    }
 
+    /// Adds the object to the selection, or takes it out if it's already in it.
+    /// A selected group is turned into a selection of the objects in it first.
+    pub fn toggle_object_selection(&mut self, id: ObjectId) {
+        let mut ids = self.get_selection_as_objects();
+        if let Some(position) = ids.iter().position(|&selected_id| selected_id == id) {
+            ids.remove(position);
+        } else {
+            ids.push(id);
+        }
+        self.select_objects(ids);
+    }
+
+    /// Selects every object showing between the two corners (in render pixels) in the last frame,
+    /// adding them to the current selection if add is set
+    pub fn box_select(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, add: bool) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::box_select() called but not in EditMode, got GameStatus: {:?}", self.status);
+            return;
+        }
+        let mut ids = if add { self.get_selection_as_objects() } else { Vec::new() };
+        ids.extend(self.id_buf.get_ids_in_rect(x0, y0, x1, y1));
+        self.select_objects(ids);
+    }
+
+    /// The selected objects, or the objects in the selected group
+    pub fn get_selection_as_objects(&self) -> Vec<ObjectId> {
+        if let Some(group) = self.selected_group {
+            return self.get_subtree_objects(group);
+        }
+        return self.selected_objects.clone();
+    }
+
+    /// Outlines the box selection being dragged out
+    fn draw_selection_box(&mut self) {
+        let Some((x0, y0, x1, y1)) = self.selection_box else {
+            return;
+        };
+        if self.pixel_buf.width == 0 || self.pixel_buf.height == 0 {
+            return;
+        }
+        let color = Vec3::new(1.0, 1.0, 1.0);
+        let (x_min, x_max) = (x0.min(x1).min(self.pixel_buf.width - 1), x0.max(x1).min(self.pixel_buf.width - 1));
+        let (y_min, y_max) = (y0.min(y1).min(self.pixel_buf.height - 1), y0.max(y1).min(self.pixel_buf.height - 1));
+        for y in y_min..=y_max {
+            let mut pixel_row = self.pixel_buf.get_row_guard(y).lock().unwrap();
+            if y == y_min || y == y_max {
+                pixel_row[x_min..=x_max].fill(color);
+            } else {
+                pixel_row[x_min] = color;
+                pixel_row[x_max] = color;
+            }
+        }
     }
 
     fn parse_selected_obj_mat_props(&self, selected_obj: &SceneObject) -> MaterialProperties {
+        let color = selected_obj.get_triangle_color(0);
         let props = MaterialProperties {
             mat_is_editable: selected_obj.mat_is_editable,
-            r: selected_obj.mesh.colors[0].x,
-            g: selected_obj.mesh.colors[0].y,
-            b: selected_obj.mesh.colors[0].z,
+            r: color.x,
+            g: color.y,
+            b: color.z,
             material_type: selected_obj.get_material_number(),
             extra_prop: selected_obj.get_material_extra_prop(),
         };
@@ -535,24 +878,81 @@ impl Game {
 
     pub fn deselect_object(&mut self) {
         console_log!("WASM: Deselected object");
+        self.end_follow_edit();
+        self.end_gizmo_drag();
         self.follow_camera = false;
-        self.selected_object_index = None;
+        self.selected_objects.clear();
+        self.selected_group = None;
         
         // notify JS of changes:
-        js_update_follow_camera(false);
-        js_update_selected_obj_mat_props(None);
+        self.ui_bridge.update_follow_camera(false);
+        self.ui_bridge.update_selected_object_id(None);
+        self.ui_bridge.update_selected_object_count(0);
+        self.ui_bridge.update_selected_obj_mat_props(None);
+        self.ui_bridge.update_selected_group(None);
+    }
+
+    /// Selects the group the selection is in: the parent of the active object's node,
+    /// or of the selected group. Nothing changes if there isn't one.
+    pub fn select_parent_group(&mut self) {
+        if self.status != GameStatus::RasterizingNoLighting {
+            console_error!("Game::select_parent_group() called but not in EditMode, got GameStatus: {:?}", self.status);
+            return;
+        }
+        let current_node = match (self.selected_group, self.get_active_object_index()) {
+            (Some(group), _) => Some(group),
+            (None, Some(object_index)) => self.scene_objects.read().unwrap().get(object_index).and_then(|obj| obj.node),
+            (None, None) => None,
+        };
+        let Some(parent) = current_node.and_then(|node| self.scene_graph.get(node)).and_then(|node| node.parent) else {
+            console_log!("Selection isn't in a group");
+            return;
+        };
+        self.select_group(parent);
+    }
+
+    pub fn select_group(&mut self, node: usize) {
+        if !self.scene_graph.contains(node) {
+            console_error!("Game::select_group() called with missing node {}", node);
+            return;
+        }
+        console_log!("WASM: Selected group {} ({})", node, self.scene_graph.get(node).unwrap().name);
+        self.deselect_object();
+        self.selected_group = Some(node);
+        self.ui_bridge.update_selected_group(Some(self.scene_graph.get(node).unwrap().name.clone()));
+    }
+
+    pub fn set_gizmo_mode(&mut self, mode: GizmoMode) {
+        self.end_gizmo_drag();
+        self.gizmo.mode = mode;
+        self.gizmo.hovered = None;
+        self.ui_bridge.update_gizmo_mode(mode.to_number());
     }
 
     pub fn delete_selected_object(&mut self) {
         // Only allow deletion if in RasterizingNoLighting state
         match self.status {
             GameStatus::RasterizingNoLighting => {
-                if let Some(selected_index) = self.selected_object_index {
-                    console_log!("Deleting object with index: {}", selected_index);
-                    // self.scene_objects.borrow_mut().remove(selected_index);
-                    self.scene_objects.write().unwrap().remove(selected_index);
+                if let Some(group) = self.selected_group {
+                    console_log!("Deleting group {} and everything in it", group);
+                    self.deselect_object();
+                    let graph_before = self.scene_graph.clone();
+                    let ids = self.get_subtree_objects(group);
+                    // kept in the history, so the delete can be undone
+                    let removed = self.take_objects(&ids);
+                    self.scene_graph.remove_subtree(group);
+                    self.record_edit(EditCommand::DeleteObjects { ids, removed, graph_before, graph_after: self.scene_graph.clone() });
+                } else if !self.selected_objects.is_empty() {
+                    console_log!("Deleting {} objects", self.selected_objects.len());
+                    let ids = self.selected_objects.clone();
                     self.deselect_object();
-                    self.bvh = None; // invalidate bvh if obj is deleted
+                    let graph_before = self.scene_graph.clone();
+                    let removed = self.take_objects(&ids);
+                    // whatever hung under the objects stays where it is
+                    for node in removed.iter().filter_map(|(_, obj)| obj.node) {
+                        self.scene_graph.remove_node(node);
+                    }
+                    self.record_edit(EditCommand::DeleteObjects { ids, removed, graph_before, graph_after: self.scene_graph.clone() });
                 } else {
                     console_error!("Game::delete_selected_object() called while in edit mode but no object is selected");
                     return;
@@ -570,24 +970,29 @@ impl Game {
         const ROTATE_SPEED: f32 = 0.005;
         const KEY_ROTATE_SPEED: f32 = 0.03;
 
+        // keys held for a shortcut (Ctrl+D, Ctrl+Shift+Z) don't move the camera
+        let shortcut_held = self.keys_currently_pressed.contains("Control") || self.keys_currently_pressed.contains("Meta");
+
         let mut move_dir = Vec3::new(0.0, 0.0, 0.0);
-        if self.keys_currently_pressed.contains("w") {
-            move_dir.x += 1.0;
-        }
-        if self.keys_currently_pressed.contains("s") {
-            move_dir.x -= 1.0;
-        }
-        if self.keys_currently_pressed.contains("a") {
-            move_dir.y += 1.0;
-        }
-        if self.keys_currently_pressed.contains("d") {
-            move_dir.y -= 1.0;
-        }
-        if self.keys_currently_pressed.contains(" ") {
-            move_dir.z += 1.0;
-        }
-        if self.keys_currently_pressed.contains("Shift") {
-            move_dir.z -= 1.0;
+        if !shortcut_held {
+            if self.keys_currently_pressed.contains("w") {
+                move_dir.x += 1.0;
+            }
+            if self.keys_currently_pressed.contains("s") {
+                move_dir.x -= 1.0;
+            }
+            if self.keys_currently_pressed.contains("a") {
+                move_dir.y += 1.0;
+            }
+            if self.keys_currently_pressed.contains("d") {
+                move_dir.y -= 1.0;
+            }
+            if self.keys_currently_pressed.contains(" ") {
+                move_dir.z += 1.0;
+            }
+            if self.keys_currently_pressed.contains("Shift") && !self.mouse_down && !self.mouse_clicked_last_frame { // Shift-click selects
+                move_dir.z -= 1.0;
+            }
         }
         move_dir.rotate_z(self.camera.get_theta_z());
         self.camera.pos += move_dir * MOVE_SPEED;
@@ -616,21 +1021,14 @@ impl Game {
 
     fn pre_raster_render_logic(&mut self) {
         if self.follow_camera && self.status == GameStatus::RasterizingNoLighting {
-            if let Some(selected_index) = self.selected_object_index {
-                let selected_obj = &mut self.scene_objects.write().unwrap()[selected_index];
-                if let Some((_, looking_at_pos)) = *self.looking_at.read().unwrap(){
-                    console_log!("looking at object, translating to {:?}", looking_at_pos);
-                    selected_obj.translate_to(looking_at_pos);
-                } else {
-                    let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * selected_obj.mesh.radius);
-                    self.camera.vertex_screen_to_world_space(&mut looking_at_pos);
-                    console_log!("not looking at anything, translating to {:?}", looking_at_pos);
-                    selected_obj.translate_to(looking_at_pos);
-                }
-                self.bvh = None; // invalidate bvh if obj is moved
+            // the active object goes where the user is looking, the rest of the selection comes along
+            if let Some(active_index) = self.get_active_object_index() {
+                let offset = self.get_follow_offset(active_index);
+                self.edit_selected_objects(|transform, _| transform.translation += offset);
             }
         }
         *self.looking_at.write().unwrap() = None;
+        self.prepare_debug_overlays();
     }
 
     #[inline(never)]
@@ -720,7 +1118,7 @@ impl Game {
         // let output_pixel_buf = 
     }
 
-    fn render_frame(&mut self) {
+    pub fn render_frame(&mut self) {
         // curr time is ~73ms - finished adding lighting)
         // ~57ms (20% improvement)- after precomputing sin/cos)
         // almost 30% improvement from using powi over powf
@@ -736,6 +1134,9 @@ impl Game {
         // clear buffers
         self.clear_pixel_buf_to_sky();
         self.zbuf.clear();
+        self.id_buf.clear();
+
+        let t2 = get_time();
 
         // Also, for fixing the alpha blending sorting not applying within an
         // object's triangles (since only objects are sorted, not invidivual triangles),
@@ -746,96 +1147,124 @@ impl Game {
         // let scene_objects = self.scene_objects.take();
         let scene_objects = self.scene_objects.read().unwrap();
 
+        // instances are placed in the world here, their shared mesh is left as is
+        let transformed: Vec<_> = scene_objects.par_iter().map(|scene_obj| {
+            let vertices = scene_obj.get_world_vertices();
+            return (self.camera.vertices_world_to_camera_space(&vertices), scene_obj.get_world_normals(), scene_obj.get_world_vertex_normals(), scene_obj.get_world_tangents());
+        }).collect();
+
+        let t3 = get_time();
+
         // opaque objects
-        scene_objects.par_iter().enumerate().for_each(|(scene_obj_index, scene_obj)| {
-            let mesh = &scene_obj.mesh;
-            if mesh.properties.alpha == 1.0 {
-                let vertices = &mesh.vertices;
-                let transformed_vertices = self.camera.vertices_world_to_camera_space(&vertices);
-                let indices = &mesh.indices;
-                let colors = &mesh.colors;
-                let normals = &mesh.normals;
-
-                if colors.len() > 200 {
-                    (0..colors.len()).into_par_iter().for_each(|i| {
-                        let v1 = transformed_vertices[indices[i*3]];
-                        let v2 = transformed_vertices[indices[i*3+1]];
-                        let v3 = transformed_vertices[indices[i*3+2]];
-                        let color = colors[i];
-                        let normal = normals[i];
-                        self.render_triangle_from_transformed_vertices(v1, v2, v3, normal, color, &scene_obj, scene_obj_index);
-                    });
-                } else {
-                    for i in 0..colors.len() {
-                        let v1 = transformed_vertices[indices[i*3]];
-                        let v2 = transformed_vertices[indices[i*3+1]];
-                        let v3 = transformed_vertices[indices[i*3+2]];
-                        let color = colors[i];
-                        let normal = normals[i];
-                        self.render_triangle_from_transformed_vertices(v1, v2, v3, normal, color, &scene_obj, scene_obj_index);
-                    }
-                }
+        let opaque_drawn: usize = scene_objects.par_iter().zip(transformed.par_iter()).map(|(scene_obj, (transformed_vertices, normals, vertex_normals, tangents))| {
+            if scene_obj.get_properties().alpha < 1.0 {
+                return 0;
             }
-        });
+            return self.render_object_triangles(scene_obj, transformed_vertices, normals, vertex_normals, tangents);
+        }).sum();
+
+        let t4 = get_time();
 
         // transparent objects
-        scene_objects.par_iter().rev().enumerate().for_each(|(idx, scene_obj)| {
-            let scene_obj_index = scene_objects.len() - 1 - idx;
-            let mesh = &scene_obj.mesh;
-            if mesh.properties.alpha < 1.0 {
-                let vertices = &mesh.vertices;
-                let transformed_vertices = self.camera.vertices_world_to_camera_space(&vertices);
-                let indices = &mesh.indices;
-                let colors = &mesh.colors;
-                let normals = &mesh.normals;
-                
-                if colors.len() > 200 {
-                    (0..colors.len()).into_par_iter().for_each(|i| {
-                        let v1 = transformed_vertices[indices[i*3]];
-                        let v2 = transformed_vertices[indices[i*3+1]];
-                        let v3 = transformed_vertices[indices[i*3+2]];
-                        let color = colors[i];
-                        let normal = normals[i];
-                        self.render_triangle_from_transformed_vertices(v1, v2, v3, normal, color, &scene_obj, scene_obj_index);
-                    });
-                } else {
-                    for i in 0..colors.len() {
-                        let v1 = transformed_vertices[indices[i*3]];
-                        let v2 = transformed_vertices[indices[i*3+1]];
-                        let v3 = transformed_vertices[indices[i*3+2]];
-                        let color = colors[i];
-                        let normal = normals[i];
-                        self.render_triangle_from_transformed_vertices(v1, v2, v3, normal, color, &scene_obj, scene_obj_index);
-                    }
-                }
+        let transparent_drawn: usize = scene_objects.par_iter().zip(transformed.par_iter()).rev().map(|(scene_obj, (transformed_vertices, normals, vertex_normals, tangents))| {
+            if scene_obj.get_properties().alpha == 1.0 {
+                return 0;
             }
-        });
+            return self.render_object_triangles(scene_obj, transformed_vertices, normals, vertex_normals, tangents);
+        }).sum();
 
+        let t5 = get_time();
+
+        let triangles_submitted = scene_objects.iter().map(|scene_obj| scene_obj.mesh.colors.len()).sum();
+        drop(transformed);
         // self.scene_objects.replace(scene_objects);
+        drop(scene_objects);
+
+        let stats = &mut self.frame_stats_in_progress;
+        stats.clear_ms = t2 - t1;
+        stats.transform_ms = t3 - t2;
+        stats.rasterize_opaque_ms = t4 - t3;
+        stats.rasterize_transparent_ms = t5 - t4;
+        stats.triangles_submitted = triangles_submitted;
+        stats.triangles_drawn = opaque_drawn + transparent_drawn;
+        stats.triangles_culled = triangles_submitted - stats.triangles_drawn;
+
+        self.draw_grid();
+        self.draw_debug_overlays();
+
+        // drawn over everything, so the handles can be grabbed even inside other objects
+        self.draw_gizmo();
+    }
 
-        let t2 = get_time();
-        // console_log!("Frame time: {}", t2 - t1);
+    /// Renders each triangle of the object, from its vertices in camera space and its normals in world space
+    /// (vertex_normals is empty for flat shading, tangents without normal maps). Returns how many weren't culled.
+    fn render_object_triangles(&self, scene_obj: &SceneObject, transformed_vertices: &[Vec3], normals: &[Vec3], vertex_normals: &[Vec3], tangents: &[Tangent]) -> usize {
+        let indices = &scene_obj.mesh.indices;
+        let render = |i: usize| -> bool {
+            let v1 = transformed_vertices[indices[i*3]];
+            let v2 = transformed_vertices[indices[i*3+1]];
+            let v3 = transformed_vertices[indices[i*3+2]];
+            let triangle_vertex_normals = if vertex_normals.is_empty() {
+                None
+            } else {
+                Some([vertex_normals[i*3], vertex_normals[i*3+1], vertex_normals[i*3+2]])
+            };
+            let normal_map = self.get_triangle_normal_map(scene_obj, i, tangents);
+            let shading = TriangleShading::new(self.get_triangle_color(scene_obj, i), normals[i], triangle_vertex_normals, normal_map, v1, v2, v3);
+            return self.render_triangle_from_transformed_vertices(v1, v2, v3, &shading, scene_obj);
+        };
+        let triangle_count = scene_obj.mesh.colors.len();
+        if triangle_count > 200 {
+            return (0..triangle_count).into_par_iter().filter(|&i| render(i)).count();
+        }
+        return (0..triangle_count).filter(|&i| render(i)).count();
     }
 
-    fn render_triangle(&self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, color: Vec3, scene_obj: &SceneObject, scene_obj_index: usize) {
-        // render triangle from transformed vertices
-        let normal = (v3 - v1).cross(v2 - v1).normalized();
-        self.camera.three_vertices_world_to_camera_space(&mut v1, &mut v2, &mut v3);
-        self.render_triangle_from_transformed_vertices(v1, v2, v3, normal, color, scene_obj, scene_obj_index);
+    /// Textured triangles are sampled per pixel, unless the object's color is overridden
+    #[inline(always)]
+    fn get_triangle_color<'a>(&self, scene_obj: &'a SceneObject, triangle_index: usize) -> TriangleColor<'a> {
+        let overridden = scene_obj.material_override.as_ref().is_some_and(|m| m.color.is_some());
+        if !overridden {
+            if let Some((texture, uvs)) = scene_obj.mesh.get_triangle_texture(triangle_index) {
+                return TriangleColor::Textured {
+                    texture,
+                    uvs,
+                    tint: scene_obj.mesh.texture_tint,
+                    filter: self.texture_filter,
+                };
+            }
+        }
+        return TriangleColor::Flat(scene_obj.get_triangle_color(triangle_index));
     }
 
-    fn render_triangle_from_transformed_vertices(&self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, mut normal: Vec3, color: Vec3, scene_obj: &SceneObject, scene_obj_index: usize) {
+    /// tangents are the object's, in world space
+    #[inline(always)]
+    fn get_triangle_normal_map<'a>(&self, scene_obj: &'a SceneObject, triangle_index: usize, tangents: &[Tangent]) -> Option<ShadingNormalMap<'a>> {
+        let (normal_map, uvs) = scene_obj.mesh.get_triangle_normal_map(triangle_index)?;
+        let i = triangle_index * 3;
+        return Some(ShadingNormalMap {
+            normal_map,
+            uvs,
+            tangents: [tangents[i], tangents[i+1], tangents[i+2]],
+            filter: self.texture_filter,
+        });
+    }
+
+    /// Returns false if the triangle was culled, facing away or behind the camera
+    fn render_triangle_from_transformed_vertices(&self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, shading: &TriangleShading, scene_obj: &SceneObject) -> bool {
 
         // do not render if normal is pointing away from cam - BACK FACE CULLING
         // only applies to opaque objects
-        if scene_obj.mesh.properties.alpha == 1.0 {
+        let properties = scene_obj.get_properties();
+        let mut shading = *shading;
+        if properties.alpha == 1.0 {
             let cam_normal = (v3 - v1).cross(v2 - v1);
             let cam_to_tri = v1;
             if cam_to_tri.dot(cam_normal) > 0.0 {
-                if scene_obj.mesh.properties.cull_faces {
-                    return; // cull triangle
+                if properties.cull_faces {
+                    return false; // cull triangle
                 } else {
-                    normal *= -1.0; // ensure normal points towards cam if not culling faces
+                    shading.flip_normals(); // ensure normal points towards cam if not culling faces
                 }
             }
         }
@@ -856,7 +1285,7 @@ impl Game {
         const NEAR_PLANE: f32 = 0.001;
         if v1.x > 0.0 { // all vertices in view
             self.camera.vertices_camera_to_screen_space(&mut v1, &mut v2, &mut v3);
-            self.fill_triangle(v1, v2, v3, normal, color, scene_obj, scene_obj_index);
+            self.fill_triangle(v1, v2, v3, &shading, scene_obj);
         } else if v2.x > 0.0 { // 2 vertices in view
             let q = (NEAR_PLANE - v2.x) / (v1.x - v2.x);
             let mut v1_new_1 = v2 + (v1 - v2) * q;
@@ -865,8 +1294,8 @@ impl Game {
 
             self.camera.vertices_camera_to_screen_space(&mut v1_new_1, &mut v2, &mut v3);
             self.camera.vertex_camera_to_screen_space(&mut v1_new_2);
-            self.fill_triangle(v1_new_1, v2, v3, normal, color, scene_obj, scene_obj_index);
-            self.fill_triangle(v1_new_1, v1_new_2, v3, normal, color, scene_obj, scene_obj_index);
+            self.fill_triangle(v1_new_1, v2, v3, &shading, scene_obj);
+            self.fill_triangle(v1_new_1, v1_new_2, v3, &shading, scene_obj);
         } else if v3.x > 0.0 { // 1 vertex in view
             let q = (NEAR_PLANE - v2.x) / (v3.x - v2.x);
             let mut v2_new = v2 + (v3 - v2) * q;
@@ -874,14 +1303,15 @@ impl Game {
             let mut v1_new = v1 + (v3 - v1) * q;
 
             self.camera.vertices_camera_to_screen_space(&mut v1_new, &mut v2_new, &mut v3);
-            self.fill_triangle(v1_new, v2_new, v3, normal, color, scene_obj, scene_obj_index);
+            self.fill_triangle(v1_new, v2_new, v3, &shading, scene_obj);
         } else { // no vertices in view
-            return;
+            return false;
         }
+        return true;
     }
 
 
-    fn fill_triangle(&self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, normal: Vec3, color: Vec3, scene_obj: &SceneObject, scene_obj_index: usize) {
+    fn fill_triangle(&self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, shading: &TriangleShading, scene_obj: &SceneObject) {
         // depth calculations from https://www.scratchapixel.com/lessons/3d-basic-rendering/rasterization-practical-implementation/visibility-problem-depth-buffer-depth-interpolation.html#:~:text=As%20previously%20mentioned%2C%20the%20correct,z%20%3D%201%20V%200.
 
         // sort vertices by y (v1 has lowest y, v3 has highest y)
@@ -907,13 +1337,7 @@ impl Game {
             return;
         }
 
-        let looking_at_selected = self.status == GameStatus::RasterizingNoLighting && {
-            if let Some(selected_index) = self.selected_object_index {
-                selected_index == scene_obj_index
-            } else {
-                false
-            }
-        };
+        let looking_at_selected = self.status == GameStatus::RasterizingNoLighting && self.is_object_selected(scene_obj);
 
         // calculate starting and ending x values
         let top = v1.y.ceil().max(0.0);
@@ -941,12 +1365,13 @@ impl Game {
                 let q2 = (y as f32 - v1.y) / (v3.y - v1.y);
                 let inv_right_depth = (1.0 / v1.z) * (1.0 - q2) + (1.0 / v3.z) * q2;
 
+                let row = ScanlineRow { y, x1, x2, left, right, inv_left_depth, inv_right_depth };
                 match self.status {
                     GameStatus::RasterizingNoLighting => {
-                        self.fill_triangle_scanline_row_no_lighting(y, x1, x2, left, right, inv_left_depth, inv_right_depth, looking_at_selected, top as usize, color, normal, scene_obj, scene_obj_index);
+                        self.fill_triangle_scanline_row_no_lighting(&row, looking_at_selected, top as usize, shading, scene_obj);
                     },
                     GameStatus::RasterizingWithLighting => {
-                        self.fill_triangle_scanline_row_with_lighting(y, x1, x2, left, right, inv_left_depth, inv_right_depth, color, normal, scene_obj);
+                        self.fill_triangle_scanline_row_with_lighting(&row, shading, scene_obj);
                     },
                     _ => {
                         console_error!("Game::fill_triangle() called but not in Rasterizing state, got {:?}", self.status);
@@ -982,12 +1407,13 @@ impl Game {
                 let q2 = (y as f32 - v1.y) / (v3.y - v1.y);
                 let inv_right_depth = (1.0 / v1.z) * (1.0 - q2) + (1.0 / v3.z) * q2;
 
+                let row = ScanlineRow { y, x1, x2, left, right, inv_left_depth, inv_right_depth };
                 match self.status {
                     GameStatus::RasterizingNoLighting => {
-                        self.fill_triangle_scanline_row_no_lighting(y, x1, x2, left, right, inv_left_depth, inv_right_depth, looking_at_selected, bottom as usize, color, normal, scene_obj, scene_obj_index);
+                        self.fill_triangle_scanline_row_no_lighting(&row, looking_at_selected, bottom as usize, shading, scene_obj);
                     },
                     GameStatus::RasterizingWithLighting => {
-                        self.fill_triangle_scanline_row_with_lighting(y, x1, x2, left, right, inv_left_depth, inv_right_depth, color, normal, scene_obj);
+                        self.fill_triangle_scanline_row_with_lighting(&row, shading, scene_obj);
                     },
                     _ => {
                         console_error!("Game::fill_triangle() called but not in Rasterizing state, got {:?}", self.status);
@@ -998,21 +1424,21 @@ impl Game {
         }
     }
 
-    fn fill_triangle_scanline_row_with_lighting(&self, y: usize, x1: f32, x2: f32, left: usize, right: usize, inv_left_depth: f32, inv_right_depth: f32, color: Vec3, normal: Vec3, scene_obj: &SceneObject) {
-        let properties = scene_obj.mesh.properties;
+    fn fill_triangle_scanline_row_with_lighting(&self, row: &ScanlineRow, shading: &TriangleShading, scene_obj: &SceneObject) {
+        let ScanlineRow { y, left, right, .. } = *row;
+        let properties = *scene_obj.get_properties();
         let mut zbuf_row = self.zbuf.get_row_guard(y as usize).lock().unwrap();
         let mut pixel_row = self.pixel_buf.get_row_guard(y as usize).lock().unwrap();
         for x in left..=right {
 
-            let q3 = (x as f32 - x1) / (x2 - x1);
-            let inv_depth = inv_left_depth * (1.0 - q3) + inv_right_depth * q3;
-            let depth = 1.0 / inv_depth;
+            let depth = row.depth_at(x);
             let bias = if properties.alpha == 1.0 {0.0} else {0.01};
 
             if depth - bias < zbuf_row[x] {
 
                 let mut world_pos = Vec3::new(x as f32, y as f32, depth);
                 self.camera.vertex_screen_to_camera_space(&mut world_pos);
+                let (color, normal) = shading.at(world_pos);
                 self.camera.vertex_camera_to_world_space(&mut world_pos);
 
                 if properties.is_light {
@@ -1042,26 +1468,30 @@ impl Game {
         }
     }
 
-    fn fill_triangle_scanline_row_no_lighting(&self, y: usize, x1: f32, x2: f32, left: usize, right: usize, inv_left_depth: f32, inv_right_depth: f32, looking_at_selected: bool, y_extremity: usize, color: Vec3, normal: Vec3, scene_obj: &SceneObject, scene_obj_index: usize) {
-        let properties = scene_obj.mesh.properties;
+    fn fill_triangle_scanline_row_no_lighting(&self, row: &ScanlineRow, looking_at_selected: bool, y_extremity: usize, shading: &TriangleShading, scene_obj: &SceneObject) {
+        let ScanlineRow { y, left, right, .. } = *row;
+        let properties = *scene_obj.get_properties();
         let mut zbuf_row = self.zbuf.get_row_guard(y as usize).lock().unwrap();
         let mut pixel_row = self.pixel_buf.get_row_guard(y as usize).lock().unwrap();
+        let mut id_row = self.id_buf.get_row_guard(y).lock().unwrap();
 
         for x in left..=right {
 
-            let q3 = (x as f32 - x1) / (x2 - x1);
-            let inv_depth = inv_left_depth * (1.0 - q3) + inv_right_depth * q3;
-            let depth = 1.0 / inv_depth;
+            let depth = row.depth_at(x);
             let bias = if properties.alpha == 1.0 {0.0} else {0.01};
 
             if depth - bias < zbuf_row[x] {
+                id_row[x] = scene_obj.id; // for box selection and shift-click
 
                 let mut world_pos = Vec3::new(x as f32, y as f32, depth);
                 self.camera.vertex_screen_to_camera_space(&mut world_pos);
+                let (color, normal) = shading.at(world_pos);
                 self.camera.vertex_camera_to_world_space(&mut world_pos);
 
                 if !looking_at_selected && x == self.camera.width / 2 && y == self.camera.height / 2 {
-                    *self.looking_at.write().unwrap() = Some((scene_obj_index, world_pos));
+                    if let Some(id) = scene_obj.id {
+                        *self.looking_at.write().unwrap() = Some((id, world_pos, normal));
+                    }
                 }
 
                 if looking_at_selected && (x == left || x == right || y == y_extremity) {
@@ -1114,13 +1544,16 @@ impl Game {
         }
     }
 
-    pub fn sort_meshes_by_distance_to_camera(&self) {
+    pub fn sort_meshes_by_distance_to_camera(&mut self) {
         let camera_pos = self.camera.pos;
-        self.scene_objects.write().unwrap().sort_by(|a, b| {
-            let d1 = (a.mesh.center - camera_pos).len_squared();
-            let d2 = (b.mesh.center - camera_pos).len_squared();
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        scene_objects.sort_by(|a, b| {
+            let d1 = (a.get_center() - camera_pos).len_squared();
+            let d2 = (b.get_center() - camera_pos).len_squared();
             return d1.total_cmp(&d2);
         });
+        self.object_ids.reindex(&scene_objects);
+        self.bvh.clear(); // objects are matched to their trees by index
     }
 
     pub fn add_scene_objs_to_shadow_maps(&mut self, scene_objs: &Vec<SceneObject>) {
@@ -1129,9 +1562,105 @@ impl Game {
         }
     }
 
-    pub fn add_scene_object(&mut self, scene_obj: SceneObject) {
+    /// Returns the id the object can be looked up by from now on
+    pub fn add_scene_object(&mut self, mut scene_obj: SceneObject) -> ObjectId {
         // self.scene_objects.borrow_mut().push(scene_obj);
-        self.scene_objects.write().unwrap().push(scene_obj);
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        let id = self.object_ids.allocate(scene_objects.len());
+        scene_obj.id = Some(id);
+        scene_objects.push(scene_obj);
+        self.bvh.insert_object(scene_objects.len() - 1);
+        return id;
+    }
+
+    /// Takes the object out of the scene, after which its id is no longer valid.
+    /// Doesn't touch the scene graph.
+    pub fn remove_scene_object(&mut self, id: ObjectId) -> Option<SceneObject> {
+        let index = self.get_object_index(id)?;
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        let removed_obj = scene_objects.remove(index);
+        self.object_ids.free(id);
+        self.object_ids.reindex(&scene_objects);
+        self.bvh.remove_object(index);
+        return Some(removed_obj);
+    }
+
+    /// Takes the objects out of the scene, remembering where each one was so put_back_objects()
+    /// can return them. Their ids stop resolving, but aren't handed out again unless forget_objects() frees them.
+    pub fn take_objects(&mut self, ids: &[ObjectId]) -> Vec<(usize, SceneObject)> {
+        let mut indices: Vec<(usize, ObjectId)> = ids.iter().filter_map(|&id| Some((self.get_object_index(id)?, id))).collect();
+        indices.sort_by_key(|&(index, _)| index);
+        let mut taken = Vec::with_capacity(indices.len());
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        // back to front, so the indices still to go don't shift
+        for &(index, id) in indices.iter().rev() {
+            taken.push((index, scene_objects.remove(index)));
+            self.object_ids.detach(id);
+            self.bvh.remove_object(index);
+        }
+        self.object_ids.reindex(&scene_objects);
+        taken.reverse();
+        return taken;
+    }
+
+    /// Returns objects from take_objects() to where they were, under the same ids
+    pub fn put_back_objects(&mut self, taken: Vec<(usize, SceneObject)>) {
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        // front to back, so every index means what it did when its object was taken
+        for (index, mut scene_obj) in taken {
+            let index = index.min(scene_objects.len());
+            if !scene_obj.id.is_some_and(|id| self.object_ids.attach(id, index)) {
+                scene_obj.id = Some(self.object_ids.allocate(index));
+            }
+            scene_objects.insert(index, scene_obj);
+            self.bvh.insert_object(index);
+        }
+        self.object_ids.reindex(&scene_objects);
+    }
+
+    /// Frees the ids of objects from take_objects() that won't be put back
+    pub fn forget_objects(&mut self, scene_objects: &[SceneObject]) {
+        for id in scene_objects.iter().filter_map(|obj| obj.id) {
+            self.object_ids.free(id);
+        }
+    }
+
+    /// Puts the object's material back the way it was, like from before an edit
+    pub fn set_object_material_state(&mut self, id: ObjectId, state: &MaterialState) {
+        let Some(index) = self.get_object_index(id) else {
+            console_error!("Game::set_object_material_state() called with deleted object {}", id);
+            return;
+        };
+        self.scene_objects.write().unwrap()[index].set_material_state(state);
+        self.bvh.mark_object_changed(index);
+    }
+
+    /// Where the object currently is in scene_objects, None if it was removed.
+    /// Indices shift whenever an earlier object is removed, so don't hold on to them.
+    pub fn get_object_index(&self, id: ObjectId) -> Option<usize> {
+        return self.object_ids.get_index(id);
+    }
+
+    pub fn get_object_id(&self, index: usize) -> Option<ObjectId> {
+        return self.scene_objects.read().unwrap().get(index).and_then(|obj| obj.id);
+    }
+
+    pub fn contains_object(&self, id: ObjectId) -> bool {
+        return self.object_ids.contains(id);
+    }
+
+    /// Ids of every object, in scene_objects order
+    pub fn get_object_ids(&self) -> Vec<ObjectId> {
+        return self.scene_objects.read().unwrap().iter().filter_map(|obj| obj.id).collect();
+    }
+
+    /// The last selected object
+    pub fn get_active_object(&self) -> Option<ObjectId> {
+        return self.selected_objects.last().copied().filter(|&id| self.contains_object(id));
+    }
+
+    pub fn get_active_object_index(&self) -> Option<usize> {
+        return self.get_active_object().and_then(|id| self.get_object_index(id));
     }
 
     pub fn get_lights(&self) -> &Vec<Light> {
@@ -1160,588 +1689,59 @@ impl Game {
             .unwrap()
             .iter()
             .filter(|s| s.is_light())
-            .flat_map(|s| s.hittables.iter().map(|h| h.clone_box()))
+            .inspect(|s| if s.has_inexact_world_hittables() {
+                console_warn!("Light {:?} has a scale of {:?}, its light is sampled from a sphere but it's hit as an ellipsoid", s.id, s.transform.scale);
+            })
+            .flat_map(|s| s.get_world_hittables())
             .collect();
     }
 
     pub fn recalculate_shadow_maps(&mut self) {
+        let start_time = get_time();
         for light in self.lights.iter_mut() {
             light.clear_shadow_map();
             light.add_scene_objects_to_shadow_map(&self.scene_objects.read().unwrap());
         }
+        self.frame_stats_in_progress.shadow_map_rebuild_ms += get_time() - start_time;
     }
 
+    /// Rebuilds every object's BVH and the top level from scratch
     pub fn rebuild_bvh(&mut self) {
-        let rt_objects = self
-            .scene_objects
-            .read()
-            .unwrap()
-            .iter()
-            .flat_map(|s| s.hittables.iter().map(|h| h.clone_box()))
-            .collect();
-        // self.bvh = Some(BVHNode::new(rt_objects));
-        self.bvh = Some(FlattenedBVH::new(rt_objects));
+        self.bvh.clear();
+        self.update_bvh();
+    }
+
+    /// Refits or rebuilds the BVHs of objects changed since the last update, then the top level
+    pub fn update_bvh(&mut self) {
+        let start_time = get_time();
+        self.bvh.update(&self.scene_objects.read().unwrap(), &self.bvh_build_options);
+        self.frame_stats_in_progress.bvh_build_ms += get_time() - start_time;
     }
 
     pub fn pre_scene_load(&mut self) {
-        // js_update_scene_loading(true);
+        // self.ui_bridge.update_scene_loading(true);
         self.scene_objects.write().unwrap().clear();
-        self.bvh = None;
+        self.scene_graph.clear();
+        self.bvh.clear();
         self.lights.clear();
         self.rt_lights.clear();
         self.looking_at.write().unwrap().take(); // clear looking at
         self.defocus_angle = 0.0; // reset defocus angle
-        self.selected_object_index = None; // clear selected object
+        self.selected_objects.clear(); // clear selected objects
+        self.selected_group = None;
+        self.selection_box = None;
+        self.follow_edit_start.clear();
+        self.gizmo.cancel_drag();
+        self.clear_edit_history(); // it refers to the objects being replaced
+        self.object_ids.clear();
     }
 
     pub fn post_scene_load(&mut self) {
-        self.bvh = None; // invalidate bvh
+        self.object_ids.assign_all(&mut self.scene_objects.write().unwrap());
+        self.bvh.clear(); // the objects were replaced wholesale
         self.extract_raster_lights_from_scene_objects();
         self.extract_rt_lights_from_scene_objects();
         self.js_update_ui();
-        js_update_scene_loading(false);
-    }
-
-
-    // LOADING SCENES
-    pub fn load_scene_fantasy_book(&mut self, glb_bytes: &[u8]) {
-        // https://sketchfab.com/3d-models/medieval-fantasy-book-06d5a80a04fc4c5ab552759e9a97d91a
-        match extract_combined_mesh_from_raw_glb_bytes(glb_bytes) {
-            Ok(mut mesh) => {
-
-                self.pre_scene_load();
-
-                mesh.properties.ambient = 0.8;
-                mesh.properties.diffuse = 0.2; // Maximize diffuse reflection
-                mesh.properties.specular = 0.1; // Keep specular low for a non-shiny book
-                mesh.properties.shininess = 4;  // Low shininess
-
-                let light = Light::new_looking_at(
-                    Vec3::new(50.0, 200.0, 300.0),
-                    Vec3::new(0.0, 0.0, 0.0),
-                    PI / 12.0,
-                    // sun color
-                    2400.0 * Vec3::new(0.8, 0.7, 0.6),
-                    10.0,
-                    1000,
-                    1000,
-                );
-                let light_scene_obj = SceneObject::new_sphere_custom_light(2, light);
-                
-                let mut scene_obj = SceneObject::new_from_mesh(mesh, Lambertian::default().clone_box(), false);
-                scene_obj.set_center(Vec3::new(0.0, 0.0, 0.0));
-                scene_obj.rotate_around_center(0.0, degrees_to_radians(-90.0));
-
-                {
-                    let mut scene_objects = self.scene_objects.write().unwrap();
-                    scene_objects.clear();
-                    scene_objects.push(light_scene_obj);
-                    scene_objects.push(scene_obj);
-                }
-
-                // Daytime blue sky colors
-                self.max_sky_color = Vec3::new(0.22, 0.48, 1.0); // deeper blue zenith
-                self.min_sky_color = Vec3::new(0.80, 0.90, 1.0); // slightly bluer horizon
-
-                self.rt_max_sky_color = Vec3::new(0.22, 0.48, 1.0);
-                self.rt_min_sky_color = Vec3::new(0.80, 0.90, 1.0);
-
-                self.ray_max_depth = 20;
-
-                self.bvh = None; // invalidate bvh
-                self.set_fov(degrees_to_radians(90.0));
-
-                self.post_scene_load();
-            }
-            Err(e) => {
-                console_error!("Error loading fantasy book scene: {}", e);
-            }
-        }
+        self.ui_bridge.update_scene_loading(false);
     }
-
-    pub fn load_scene_magic_bridge(&mut self, glb_bytes: &[u8]) {
-        // https://sketchfab.com/3d-models/magical-help-73fcb7197ba441419c768105c7db5d17
-        match extract_combined_mesh_from_raw_glb_bytes(glb_bytes) {
-            Ok(mesh) => {
-
-                self.pre_scene_load();
-
-                let mut scene_obj = SceneObject::new_from_mesh(mesh, Lambertian::default().clone_box(), false);
-                scene_obj.set_center(Vec3::new(0.0, 0.0, 0.0));
-                scene_obj.mesh.properties.cull_faces = false;
-                
-                // Key Light - slightly off-center, warm color
-                let key_light_color = Vec3::new(1.0, 0.8, 0.4);
-                let key_light_intensity = 700.0;
-                let key_light_scene_obj = SceneObject::new_sphere_omni_light(
-                    Vec3::new(5.0, 5.0, 8.0), // Moved slightly
-                    0.5, // Smaller visual representation
-                    key_light_intensity * key_light_color,
-                    3,
-                    1000
-                );
-
-                // night time setting
-                self.max_sky_color = 0.5 * Vec3::new(0.07, 0.10, 0.22);
-                self.min_sky_color = 0.5 * Vec3::new(0.03, 0.04, 0.1);
-
-                self.rt_max_sky_color = 0.2 * Vec3::new(0.07, 0.10, 0.22);
-                self.rt_min_sky_color = 0.2 * Vec3::new(0.03, 0.04, 0.1);
-
-                self.ray_max_depth = 20;
-
-                {
-                    let mut scene_objects = self.scene_objects.write().unwrap();
-                    scene_objects.clear();
-                    scene_objects.push(scene_obj);
-                    scene_objects.push(key_light_scene_obj);
-                }
-
-                self.bvh = None; // invalidate bvh
-                self.set_fov(degrees_to_radians(75.0)); // Slightly narrower FOV can feel more cinematic
-                self.camera.pos = Vec3::new(-15.0, 0.0, 5.0); // Example camera position
-                self.camera.look_at(&Vec3::new(0.0,0.0,2.0)); // Make camera look towards the bridge center
-
-                self.post_scene_load();
-            }
-            Err(e) => {
-                console_error!("Error loading fantasy book scene: {}", e);
-            }
-        }
-    }
-
-    pub fn load_scene_gandalf_bust(&mut self, stl_bytes: &[u8]) {
-        self.pre_scene_load();
-
-        let gandalf_color = Vec3::new(0.8, 0.8, 0.8);
-        let (gandalf_phong, gandalf_mat) = SceneObject::new_glossy_mat(1.6);
-        // let (gandalf_phong, gandalf_mat) = SceneObject::new_diffuse_mat();
-        let mut gandalf_mesh = Mesh::new_from_stl_bytes(stl_bytes, gandalf_color, gandalf_phong);
-
-        gandalf_mesh.set_center(Vec3::new(0.0, 0.0, 0.0));
-        gandalf_mesh.scale_by(0.05);
-        gandalf_mesh.rotate_around_center(-PI/2.0, PI/2.0);
-        gandalf_mesh.rotate_around_center(degrees_to_radians(15.0), 0.0);
-
-        let gandalf_obj = SceneObject::new_from_mesh(gandalf_mesh, gandalf_mat, true);
-        self.add_scene_object(gandalf_obj);
-
-        // ground sphere
-        let ground_plane = SceneObject::new_rectangle(
-            Vec3::new(-100.0, -100.0, -5.0), 
-            Vec3::new(200.0, 0.0, 0.0), 
-            Vec3::new(0.0, 200.0, 0.0), 
-            Vec3::new(0.05, 0.05, 0.18),
-            SceneObject::new_diffuse_mat(),
-            false
-        );
-        self.add_scene_object(ground_plane);
-
-        let test_sphere = SceneObject::new_sphere(
-            Vec3::new(0.0, 0.0, 0.0), 
-            5.0, 
-            Vec3::new(1.0, 1.0, 1.0), 
-            3,
-            SceneObject::new_glossy_mat(1.5)
-        );
-        // self.add_scene_object(test_sphere);
-
-        // left (red) light
-        let light_1 = SceneObject::new_sphere_omni_light(
-            Vec3::new(2.0, -10.0, 5.0), 
-            5.0, 
-            3.0 * Vec3::new(1.0, 0.08, 0.08), 
-            2, 
-            1000);
-
-        // right (blue) light
-        let light_2 = SceneObject::new_sphere_omni_light(
-            Vec3::new(2.0, 10.0, 5.0), 
-            5.0, 
-            3.0 * Vec3::new(0.05, 0.05, 1.0), 
-            2, 
-            1000);
-
-        // top (white) light
-        let light_3 = SceneObject::new_sphere_omni_light(
-            Vec3::new(-10.0, 0.0, 20.0), 
-            10.0, 
-            5.0 * Vec3::new(1.0, 1.0, 1.0), 
-            2, 
-            1000);
-
-        self.add_scene_object(light_1);
-        self.add_scene_object(light_2);
-        self.add_scene_object(light_3);
-
-        self.camera.pos = Vec3::new(10.0, 0.0, 2.0);
-        self.camera.look_at(&Vec3::zero());
-        self.camera.set_fov(degrees_to_radians(60.0));
-
-        self.max_sky_color = Vec3::new(0.05, 0.05, 0.16);
-        self.min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-
-        self.rt_max_sky_color = Vec3::new(0.05, 0.05, 0.16);
-        self.rt_min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-
-        self.post_scene_load();
-    }
-
-    pub fn load_scene_roza_bust(&mut self, glb_bytes: &[u8]) {
-        // https://sketchfab.com/3d-models/sculpture-bust-of-roza-loewenfeld-fc6e731a0131471ba8e45511c7ea9996#download
-        match extract_combined_mesh_from_raw_glb_bytes(glb_bytes) {
-            Ok(mut mesh) => {
-
-                self.pre_scene_load();
-
-                // let bust_color = Vec3::new(0.8, 0.8, 0.8);
-                let (bust_phong, bust_mat) = SceneObject::new_glossy_mat(1.6);
-                mesh.properties = bust_phong;
-
-                mesh.set_center(Vec3::new(0.0, 0.0, 0.0));
-                mesh.scale_by(0.014);
-                mesh.rotate_around_center(0.0, -PI/2.0);
-                mesh.rotate_around_center(-PI, 0.0);
-                mesh.rotate_around_center(degrees_to_radians(40.0), 0.0);
-
-                for color in mesh.colors.iter_mut() {
-                    *color *= 0.6;
-                }
-
-                let bust_obj = SceneObject::new_from_mesh(mesh, bust_mat, true);
-                self.add_scene_object(bust_obj);
-
-                // ground plane
-                let ground_plane = SceneObject::new_rectangle(
-                    Vec3::new(-100.0, -100.0, -4.0), 
-                    Vec3::new(200.0, 0.0, 0.0), 
-                    Vec3::new(0.0, 200.0, 0.0), 
-                    Vec3::new(0.05, 0.05, 0.18),
-                    SceneObject::new_diffuse_mat(),
-                    false
-                );
-                // self.add_scene_object(ground_plane);
-
-
-                // left (red) light
-                let light_1 = SceneObject::new_sphere_omni_light(
-                    Vec3::new(4.0, -8.0, 8.0), 
-                    1.0, 
-                    30.0 * Vec3::new(1.0, 0.08, 0.08), 
-                    2, 
-                    1000
-                );
-
-                // right (blue) light
-                let light_2 = SceneObject::new_sphere_omni_light(
-                    Vec3::new(4.0, 8.0, 5.0), 
-                    1.0, 
-                    30.0 * Vec3::new(0.05, 0.05, 1.0), 
-                    2, 
-                    1000
-                );
-
-                // top (white) light
-                let light_3 = SceneObject::new_sphere_omni_light(
-                    Vec3::new(-5.0, -2.0, 6.5), 
-                    1.0, 
-                    35.0 * Vec3::new(1.0, 1.0, 1.0), 
-                    2, 
-                    1000
-                );
-
-                self.add_scene_object(light_1);
-                self.add_scene_object(light_2);
-                self.add_scene_object(light_3);
-
-                self.camera.pos = Vec3::new(30.0, 0.0, -3.0);
-                self.camera.look_at(&Vec3::new(0.0, 0.0, 0.75));
-                self.camera.set_fov(degrees_to_radians(10.5));
-
-                self.max_sky_color = Vec3::new(0.05, 0.05, 0.1);
-                self.min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-                self.rt_max_sky_color = Vec3::new(0.01, 0.01, 0.02);
-                self.rt_min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-
-                self.post_scene_load();
-            },
-            Err(e) => {
-                console_error!("Error loading Roza bust scene: {}", e);
-            }
-        }
-    }
-
-    pub fn load_scene_dragon(&mut self, stl_bytes: &[u8]) {
-        // https://www.cgtrader.com/free-3d-models/animals/other/dragon-free-model-blender
-
-        self.pre_scene_load();
-
-        let dragon_color = Vec3::new(1.0, 1.0, 1.0);
-        let (dragon_phong, dragon_mat) = SceneObject::new_glossy_mat(1.6);
-        let mut dragon_mesh = Mesh::new_from_stl_bytes(stl_bytes, dragon_color, dragon_phong);
-
-        dragon_mesh.set_center(Vec3::zero());
-        dragon_mesh.scale_by(0.5);
-
-        dragon_mesh.rotate_around_center(PI/2.0, -PI/2.0);
-        dragon_mesh.rotate_around_center(PI, 0.0);
-
-        let mut min_z: f32 = 10.0;
-        for v in &dragon_mesh.vertices {
-            min_z = min_z.min(v.z);
-        }
-        console_log!("Min Z of dragon mesh: {}", min_z);
-
-        let ground_plane = SceneObject::new_rectangle(
-            Vec3::new(-100.0, -100.0, min_z + 0.01), 
-            Vec3::new(200.0, 0.0, 0.0), 
-            Vec3::new(0.0, 200.0, 0.0), 
-            Vec3::new(1.0, 1.0, 1.0),
-            // SceneObject::new_diffuse_mat(),
-            SceneObject::new_metal_mat(0.02),
-            false
-        );
-        self.add_scene_object(ground_plane);
-
-        let dragon_obj = SceneObject::new_from_mesh(dragon_mesh, dragon_mat, true);
-        self.add_scene_object(dragon_obj);
-
-        self.camera.pos = Vec3::new(-5.5, -1.1, 1.1);
-        self.camera.look_at(&Vec3::zero());
-        self.set_fov(degrees_to_radians(30.0));
-
-        // left light (cream)
-        let light_1 = SceneObject::new_sphere_omni_light(
-            Vec3::new(0.5, 3.0, 1.0), 
-            0.5, 
-            12.0 * Vec3::new(1.0, 0.8, 0.6), 
-            2, 
-            1000
-        );
-
-        // right light (white)
-        let light_2 = SceneObject::new_sphere_omni_light(
-            Vec3::new(-1.5, -3.0, 2.3), 
-            0.5, 
-            14.0 * Vec3::new(1.0, 1.0, 1.0), 
-            2, 
-            1000
-        );
-
-        self.add_scene_object(light_1);
-        self.add_scene_object(light_2);
-
-        self.max_sky_color = Vec3::new(0.05, 0.05, 0.05);
-        self.min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-        self.rt_max_sky_color = Vec3::new(0.0, 0.0, 0.0);
-        self.rt_min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-
-        self.post_scene_load();
-    }
-
-    pub fn load_scene_mirror_box(&mut self, skull_stl_bytes: &[u8], sculpture_stl_bytes: &[u8]) {
-        // https://www.thingiverse.com/thing:1781327
-        // https://www.thingiverse.com/thing:5700
-        self.pre_scene_load();
-
-        // skull
-        let skull_color = Vec3::new(0.8, 0.7, 0.5); // gold color
-        let (skull_phong, skull_mat) = SceneObject::new_metal_mat(0.05);
-
-        let mut skull_mesh = Mesh::new_from_stl_bytes(skull_stl_bytes, skull_color, skull_phong);
-        skull_mesh.rotate_around_center(-PI/2.0, PI/2.0);
-        skull_mesh.rotate_around_center(-PI/4.0, 0.0);
-
-        let scale_factor = 3.5 / skull_mesh.radius;
-        skull_mesh.scale_by(scale_factor);
-
-        skull_mesh.set_center(Vec3::new(-1.0, 1.0, 0.0));
-        let mut min_z: f32 = 100.0;
-        for v in &skull_mesh.vertices {
-            min_z = min_z.min(v.z);
-        }
-        skull_mesh.translate_by(Vec3::new(0.0, 0.0, -min_z));
-
-        let skull_obj = SceneObject::new_from_mesh(skull_mesh, skull_mat, true);
-        self.add_scene_object(skull_obj);
-
-
-        // sculpture
-        let sculpture_color = 1.0 * Vec3::new(1.0, 0.5, 0.0); // bright orange
-        let (sculpture_phong, sculpture_mat) = SceneObject::new_light_mat();
-        let mut sculpture_mesh = Mesh::new_from_stl_bytes(sculpture_stl_bytes, sculpture_color, sculpture_phong);
-
-        sculpture_mesh.set_center(Vec3::new(1.0, 1.0, 0.8));
-        sculpture_mesh.scale_by(0.02);
-        sculpture_mesh.rotate_around_center(-PI/2.0, PI/2.0);
-
-        sculpture_mesh.rotate_around_center(0.0, -degrees_to_radians(35.0));
-        sculpture_mesh.rotate_around_center(PI/4.0, 0.0);
-
-        let sculpture_obj = SceneObject::new_from_mesh(sculpture_mesh, sculpture_mat, true);
-        self.add_scene_object(sculpture_obj);
-
-
-        // glass sphere
-        let glass_mat = SceneObject::new_glass_mat(0.5, 1.7);
-        let glass_color = Vec3::new(0.9, 0.9, 1.0);
-        let sphere_obj = SceneObject::new_sphere(
-            Vec3::new(1.5, -1.5, 1.0),
-            1.0,
-            glass_color,
-            3,
-            glass_mat
-        );
-        self.add_scene_object(sphere_obj);
-
-
-        // add mirror walls
-        let height = 7.0;
-        let mirror_color = Vec3::new(0.9, 0.9, 0.9);
-        let unified_mirror_mat = SceneObject::new_metal_mat(0.0);
-        let wall_1 = SceneObject::new_rectangle(
-            Vec3::new(-5.0, -5.0, 0.0),
-            Vec3::new(10.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, height),
-            mirror_color,
-            unified_mirror_mat.clone(),
-            false
-        );
-        self.add_scene_object(wall_1);
-        let wall_2 = SceneObject::new_rectangle(
-            Vec3::new(-5.0, -5.0, 0.0),
-            Vec3::new(0.0, 10.0, 0.0),
-            Vec3::new(0.0, 0.0, height),
-            mirror_color,
-            unified_mirror_mat.clone(),
-            false
-        );
-        self.add_scene_object(wall_2);
-        let wall_3 = SceneObject::new_rectangle(
-            Vec3::new(5.0, 5.0, 0.0),
-            Vec3::new(-10.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, height),
-            mirror_color,
-            unified_mirror_mat.clone(),
-            false
-        );
-        self.add_scene_object(wall_3);
-        let wall_4 = SceneObject::new_rectangle(
-            Vec3::new(5.0, 5.0, 0.0),
-            Vec3::new(0.0, -10.0, 0.0),
-            Vec3::new(0.0, 0.0, height),
-            mirror_color,
-            unified_mirror_mat,
-            false
-        );
-        self.add_scene_object(wall_4);
-
-        // add top diffuse surface
-        let top_color = Vec3::new(0.2, 0.2, 0.2);
-        let unified_top_mat = SceneObject::new_diffuse_mat();
-        let top_obj = SceneObject::new_rectangle(
-            Vec3::new(-5.0, -5.0, height),
-            Vec3::new(10.0, 0.0, 0.0),
-            Vec3::new(0.0, 10.0, 0.0),
-            top_color,
-            unified_top_mat,
-            false
-        );
-        self.add_scene_object(top_obj);
-
-        // add bottom diffuse surface
-        let bottom_color = Vec3::new(0.2, 0.2, 0.2);
-        let unified_bottom_mat = SceneObject::new_diffuse_mat();
-        let bottom_obj = SceneObject::new_rectangle(
-            Vec3::new(-5.0, -5.0, 0.0),
-            Vec3::new(10.0, 0.0, 0.0),
-            Vec3::new(0.0, 10.0, 0.0),
-            bottom_color,
-            unified_bottom_mat,
-            false
-        );
-        self.add_scene_object(bottom_obj);
-
-        // add light
-        let light_color = Vec3::new(1.0, 1.0, 1.0);
-        let light_obj = SceneObject::new_sphere_omni_light(
-            Vec3::new(0.0, 0.0, height),
-            0.5,
-            35.0 * light_color,
-            3,
-            1000
-        );
-        self.add_scene_object(light_obj);
-
-        self.camera.pos = Vec3::new(-0.0, -4.0, 4.0);
-        self.camera.look_at(&Vec3::new(0.0, 0.0, 2.0));
-        self.set_fov(degrees_to_radians(90.0));
-
-        // daylight colors
-        self.max_sky_color = Vec3::new(0.5, 0.7, 1.0);
-        self.min_sky_color = Vec3::new(1.0, 1.0, 1.0);
-        self.rt_max_sky_color = Vec3::new(0.5, 0.7, 1.0);
-        self.rt_min_sky_color = Vec3::new(1.0, 1.0, 1.0);
-        self.ray_max_depth = 100;
-
-        self.post_scene_load();
-    }
-
-    pub fn load_scene_suzanne_monkey(&mut self, stl_bytes: &[u8]) {
-
-        self.pre_scene_load();
-
-        let suzanne_color = Vec3::new(1.0, 1.0, 1.0);
-        let (suzanne_phong, suzanne_mat) = SceneObject::new_glass_mat(0.5, 1.6);
-        let mut suzanne_mesh = Mesh::new_from_stl_bytes(stl_bytes, suzanne_color, suzanne_phong);
-
-        suzanne_mesh.scale_by(1.0);
-        suzanne_mesh.set_center(Vec3::new(0.0, 0.0, 0.0));
-
-        suzanne_mesh.rotate_around_center(-PI/2.0, PI/2.0);
-        suzanne_mesh.rotate_around_center(-degrees_to_radians(110.0), 0.0);
-
-        let mut min_z: f32 = 10.0;
-        for v in &suzanne_mesh.vertices {
-            min_z = min_z.min(v.z);
-        }
-
-        let suzanne_obj = SceneObject::new_from_mesh(suzanne_mesh, suzanne_mat, true);
-        self.add_scene_object(suzanne_obj);
-
-        let ground_color = Vec3::new(0.2, 0.2, 0.2);
-        let ground_unified_mat = SceneObject::new_diffuse_mat();
-        let ground_plane = SceneObject::new_rectangle(
-            Vec3::new(-100.0, -100.0, min_z), 
-            Vec3::new(200.0, 0.0, 0.0), 
-            Vec3::new(0.0, 200.0, 0.0), 
-            ground_color,
-            ground_unified_mat,
-            false
-        );
-        self.add_scene_object(ground_plane);
-
-        let light_color = Vec3::new(0.6, 0.8, 1.0);
-        let light = SceneObject::new_sphere_omni_light(
-            Vec3::new(2.0, 5.0, 2.0),
-            0.1,
-            3000.0 * light_color,
-            2,
-            1000
-        );
-        self.add_scene_object(light);
-
-        self.set_fov(degrees_to_radians(35.0));
-        self.camera.pos = Vec3::new(-5.0, -5.0, 5.0);
-        self.camera.look_at(&Vec3::new(0.0, -1.0, 0.0));
-
-        self.max_sky_color = Vec3::new(0.1, 0.1, 0.1);
-        self.min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-        self.rt_max_sky_color = Vec3::new(0.0, 0.0, 0.0);
-        self.rt_min_sky_color = Vec3::new(0.0, 0.0, 0.0);
-
-        self.ray_max_depth = 50;
-
-        self.post_scene_load();
-    }
-}
\ No newline at end of file
+}
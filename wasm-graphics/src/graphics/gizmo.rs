@@ -0,0 +1,400 @@
+use crate::{console_log, utils::math::{degrees_to_radians, radians_to_degrees, Quat, Transform, Vec3}};
+
+use super::{camera::Camera, edit_history::EditTarget, game::{Game, GameStatus}, grid::GridSettings};
+
+// Handles drawn over the selection in edit mode, which can be dragged with the mouse (while the
+// pointer isn't locked) to move the selection along an axis or in a plane, rotate it around an
+// axis, or scale it along one of its own axes or uniformly.
+//
+// Dragging is worked out on the screen: a handle's world axis is projected to the screen and the
+// mouse movement along that projection becomes the movement along the axis. The projection is
+// the one from when the drag started, and each update is applied to the transforms the
+// selection had then, so the handles moving along with the selection don't feed back into it.
+// With snapping on (see grid.rs), moves put the middle of the selection on grid lines and
+// turns go in whole angle steps.
+
+/// Length of the axis handles, as a fraction of half the screen width
+const GIZMO_SCREEN_SIZE: f32 = 0.3;
+/// How close (in pixels) the mouse has to be to a handle to grab it
+const HANDLE_HIT_DISTANCE: f32 = 6.0;
+/// Half the size (in pixels) of the square in the middle of the scale gizmo
+const UNIFORM_HANDLE_SIZE: f32 = 6.0;
+const RING_SEGMENTS: usize = 48;
+/// Smallest factor one scale drag can scale by
+const MIN_DRAG_SCALE: f32 = 0.01;
+
+pub const AXIS_COLORS: [Vec3; 3] = [Vec3 { x: 0.9, y: 0.15, z: 0.15 }, Vec3 { x: 0.15, y: 0.8, z: 0.15 }, Vec3 { x: 0.2, y: 0.3, z: 1.0 }];
+const UNIFORM_COLOR: Vec3 = Vec3 { x: 0.9, y: 0.9, z: 0.9 };
+const ACTIVE_COLOR: Vec3 = Vec3 { x: 1.0, y: 0.85, z: 0.0 };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoMode {
+    Hidden,
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    /// 0 = Hidden, 1 = Translate, 2 = Rotate, 3 = Scale, as the UI numbers them
+    pub fn from_number(number: u32) -> Option<GizmoMode> {
+        return match number {
+            0 => Some(GizmoMode::Hidden),
+            1 => Some(GizmoMode::Translate),
+            2 => Some(GizmoMode::Rotate),
+            3 => Some(GizmoMode::Scale),
+            _ => None,
+        };
+    }
+    pub fn to_number(self) -> u32 {
+        return match self {
+            GizmoMode::Hidden => 0,
+            GizmoMode::Translate => 1,
+            GizmoMode::Rotate => 2,
+            GizmoMode::Scale => 3,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoHandle {
+    /// Moves or scales along the axis
+    Axis(usize),
+    /// Moves in the plane of the other two axes
+    Plane(usize),
+    /// Rotates around the axis
+    Ring(usize),
+    /// Scales the same along every axis
+    Uniform,
+}
+
+/// Where the gizmo is, in the world and on the screen
+#[derive(Debug, Clone, Copy)]
+pub struct GizmoFrame {
+    pub center: Vec3,
+    /// Unit axes, the world axes except for the scale gizmo, which follows the selection's rotation
+    pub axes: [Vec3; 3],
+    pub rotation: Quat,
+    /// World length of the axis handles
+    pub length: f32,
+    pub screen_center: (f32, f32),
+    /// Screen vector from the center to the tip of each axis handle, None if the tip is behind the camera
+    pub screen_axes: [Option<(f32, f32)>; 3],
+    /// The camera position, for telling which way a ring is turned
+    pub eye: Vec3,
+}
+
+#[derive(Debug, Clone)]
+struct GizmoDrag {
+    handle: GizmoHandle,
+    start_mouse: (f32, f32),
+    frame: GizmoFrame,
+    edit_start: Vec<(EditTarget, Transform)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    /// The handle under the mouse, drawn highlighted
+    pub hovered: Option<GizmoHandle>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Gizmo {
+    pub fn new() -> Gizmo {
+        return Gizmo { mode: GizmoMode::Translate, hovered: None, drag: None };
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        return self.drag.is_some();
+    }
+
+    /// Lets go of the handle without recording anything, for when what it was moving is gone
+    pub fn cancel_drag(&mut self) {
+        self.drag = None;
+    }
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        return Gizmo::new();
+    }
+}
+
+fn unit_axis(i: usize) -> Vec3 {
+    return match i {
+        0 => Vec3::new(1.0, 0.0, 0.0),
+        1 => Vec3::new(0.0, 1.0, 0.0),
+        _ => Vec3::new(0.0, 0.0, 1.0),
+    };
+}
+
+fn with_component_scaled(v: Vec3, i: usize, factor: f32) -> Vec3 {
+    return match i {
+        0 => Vec3::new(v.x * factor, v.y, v.z),
+        1 => Vec3::new(v.x, v.y * factor, v.z),
+        _ => Vec3::new(v.x, v.y, v.z * factor),
+    };
+}
+
+/// Pixel coordinates of a world point, None if it's behind the camera
+fn project(camera: &Camera, p: Vec3) -> Option<(f32, f32)> {
+    let mut v = p;
+    camera.vertex_world_to_camera_space(&mut v);
+    if v.x < 1e-3 {
+        return None;
+    }
+    camera.vertex_camera_to_screen_space(&mut v);
+    return Some((v.x, v.y));
+}
+
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let len_squared = abx * abx + aby * aby;
+    let t = if len_squared > 0.0 { (((p.0 - a.0) * abx + (p.1 - a.1) * aby) / len_squared).clamp(0.0, 1.0) } else { 0.0 };
+    let (dx, dy) = (p.0 - (a.0 + t * abx), p.1 - (a.1 + t * aby));
+    return (dx * dx + dy * dy).sqrt();
+}
+
+fn is_inside_polygon(p: (f32, f32), polygon: &[(f32, f32)]) -> bool {
+    let mut inside = false;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0) {
+            inside = !inside;
+        }
+    }
+    return inside;
+}
+
+/// How far along the screen vector axis the mouse moved, 1.0 being the whole vector
+fn drag_along(mouse_move: (f32, f32), axis: (f32, f32)) -> f32 {
+    let len_squared = axis.0 * axis.0 + axis.1 * axis.1;
+    if len_squared < 1.0 {
+        return 0.0; // the axis points (almost) straight at the camera
+    }
+    return (mouse_move.0 * axis.0 + mouse_move.1 * axis.1) / len_squared;
+}
+
+impl GizmoFrame {
+    /// Screen polylines of every handle of the mode, the closed shapes ending where they started
+    fn get_handle_lines(&self, camera: &Camera, mode: GizmoMode) -> Vec<(GizmoHandle, Vec<(f32, f32)>)> {
+        let mut lines = Vec::new();
+        let world_line = |points: Vec<Vec3>| -> Vec<(f32, f32)> {
+            return points.into_iter().filter_map(|p| project(camera, p)).collect();
+        };
+        match mode {
+            GizmoMode::Hidden => {},
+            GizmoMode::Translate | GizmoMode::Scale => {
+                for i in 0..3 {
+                    lines.push((GizmoHandle::Axis(i), world_line(vec![self.center, self.center + self.axes[i] * self.length])));
+                }
+                if mode == GizmoMode::Translate {
+                    for i in 0..3 {
+                        let (a, b) = (self.axes[(i + 1) % 3] * self.length, self.axes[(i + 2) % 3] * self.length);
+                        let (near, far) = (0.25, 0.4);
+                        lines.push((GizmoHandle::Plane(i), world_line(vec![
+                            self.center + a * near + b * near,
+                            self.center + a * far + b * near,
+                            self.center + a * far + b * far,
+                            self.center + a * near + b * far,
+                            self.center + a * near + b * near,
+                        ])));
+                    }
+                } else {
+                    let (x, y) = self.screen_center;
+                    let s = UNIFORM_HANDLE_SIZE;
+                    lines.push((GizmoHandle::Uniform, vec![(x - s, y - s), (x + s, y - s), (x + s, y + s), (x - s, y + s), (x - s, y - s)]));
+                }
+            },
+            GizmoMode::Rotate => {
+                for i in 0..3 {
+                    let (a, b) = (self.axes[(i + 1) % 3] * self.length, self.axes[(i + 2) % 3] * self.length);
+                    let ring = (0..=RING_SEGMENTS)
+                        .map(|segment| {
+                            let angle = segment as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                            self.center + a * angle.cos() + b * angle.sin()
+                        })
+                        .collect();
+                    lines.push((GizmoHandle::Ring(i), world_line(ring)));
+                }
+            },
+        }
+        return lines;
+    }
+
+    /// The handle at the pixel, the closest one if several are
+    fn hit_test(&self, camera: &Camera, mode: GizmoMode, mouse: (f32, f32)) -> Option<GizmoHandle> {
+        let mut closest: Option<(GizmoHandle, f32)> = None;
+        for (handle, line) in self.get_handle_lines(camera, mode) {
+            let distance = if matches!(handle, GizmoHandle::Plane(_) | GizmoHandle::Uniform) && is_inside_polygon(mouse, &line) {
+                0.0
+            } else {
+                line.windows(2).map(|segment| distance_to_segment(mouse, segment[0], segment[1])).fold(f32::MAX, f32::min)
+            };
+            if distance <= HANDLE_HIT_DISTANCE && closest.is_none_or(|(_, closest_distance)| distance < closest_distance) {
+                closest = Some((handle, distance));
+            }
+        }
+        return closest.map(|(handle, _)| handle);
+    }
+
+    /// The change the drag from start to mouse makes to a transform that started out as start_transform
+    fn get_dragged_transform(&self, handle: GizmoHandle, mode: GizmoMode, grid: &GridSettings, start: (f32, f32), mouse: (f32, f32), start_transform: Transform) -> Transform {
+        let mouse_move = (mouse.0 - start.0, mouse.1 - start.1);
+        let mut transform = start_transform;
+        match (mode, handle) {
+            (GizmoMode::Translate, GizmoHandle::Axis(i)) => {
+                let Some(screen_axis) = self.screen_axes[i] else {
+                    return transform;
+                };
+                let movement = self.axes[i] * (drag_along(mouse_move, screen_axis) * self.length);
+                transform.translation += self.snap_movement(grid, movement, [0, 1, 2].map(|axis| axis == i));
+            },
+            (GizmoMode::Translate, GizmoHandle::Plane(i)) => {
+                // split the mouse movement between the plane's two screen axes
+                let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+                let (Some(a), Some(b)) = (self.screen_axes[j], self.screen_axes[k]) else {
+                    return transform;
+                };
+                let determinant = a.0 * b.1 - a.1 * b.0;
+                if determinant.abs() < 1.0 {
+                    return transform; // the plane is seen edge on
+                }
+                let along_a = (mouse_move.0 * b.1 - mouse_move.1 * b.0) / determinant;
+                let along_b = (a.0 * mouse_move.1 - a.1 * mouse_move.0) / determinant;
+                let movement = (self.axes[j] * along_a + self.axes[k] * along_b) * self.length;
+                transform.translation += self.snap_movement(grid, movement, [0, 1, 2].map(|axis| axis != i));
+            },
+            (GizmoMode::Rotate, GizmoHandle::Ring(i)) => {
+                let start_angle = (start.1 - self.screen_center.1).atan2(start.0 - self.screen_center.0);
+                let angle = (mouse.1 - self.screen_center.1).atan2(mouse.0 - self.screen_center.0);
+                // screen y points down, so a growing angle turns clockwise on the screen, which is a
+                // right handed turn around an axis pointing away from the camera
+                let facing = if Vec3::dot(self.axes[i], self.center - self.eye) >= 0.0 { 1.0 } else { -1.0 };
+                let turn = degrees_to_radians(grid.snap_angle(radians_to_degrees((angle - start_angle) * facing)));
+                transform.rotate_around(self.center, Quat::from_axis_angle(self.axes[i], turn));
+            },
+            (GizmoMode::Scale, GizmoHandle::Axis(i)) => {
+                let Some(screen_axis) = self.screen_axes[i] else {
+                    return transform;
+                };
+                let factor = (1.0 + drag_along(mouse_move, screen_axis)).max(MIN_DRAG_SCALE);
+                // scales along the gizmo's axis, which is the object's own axis i for the active object
+                let offset = self.rotation.conjugate().rotate_vector(start_transform.translation - self.center);
+                transform.translation = self.center + self.rotation.rotate_vector(with_component_scaled(offset, i, factor));
+                transform.scale = with_component_scaled(start_transform.scale, i, factor);
+            },
+            (GizmoMode::Scale, GizmoHandle::Uniform) => {
+                let start_distance = ((start.0 - self.screen_center.0).powi(2) + (start.1 - self.screen_center.1).powi(2)).sqrt();
+                let distance = ((mouse.0 - self.screen_center.0).powi(2) + (mouse.1 - self.screen_center.1).powi(2)).sqrt();
+                let factor = if start_distance > 1.0 { (distance / start_distance).max(MIN_DRAG_SCALE) } else { 1.0 };
+                transform.scale_around(self.center, factor);
+            },
+            _ => {},
+        }
+        return transform;
+    }
+
+    /// movement changed so the gizmo's center ends up on the grid, along the (world) axes it moves on
+    fn snap_movement(&self, grid: &GridSettings, movement: Vec3, axes: [bool; 3]) -> Vec3 {
+        if !grid.snap_to_grid {
+            return movement;
+        }
+        return grid.snap_point(self.center + movement, axes) - self.center;
+    }
+}
+
+impl Game {
+    /// The gizmo around the selection as it is now, None without a selection or with the gizmo hidden
+    pub fn get_gizmo_frame(&self) -> Option<GizmoFrame> {
+        if self.gizmo.mode == GizmoMode::Hidden || self.status != GameStatus::RasterizingNoLighting {
+            return None;
+        }
+        let (center, rotation) = if let Some(group) = self.selected_group {
+            let (center, _) = self.get_group_bounds(group)?;
+            (center, self.scene_graph.get_world_transform(group).rotation)
+        } else {
+            let (center, _) = self.get_selection_bounds()?;
+            let active_index = self.get_active_object_index()?;
+            (center, self.scene_objects.read().unwrap()[active_index].transform.rotation)
+        };
+        let rotation = if self.gizmo.mode == GizmoMode::Scale { rotation } else { Quat::identity() };
+
+        let mut center_in_camera_space = center;
+        self.camera.vertex_world_to_camera_space(&mut center_in_camera_space);
+        let screen_center = project(&self.camera, center)?;
+        // the same size on the screen however far away the selection is
+        let length = GIZMO_SCREEN_SIZE * center_in_camera_space.x * self.camera.max_plane_coord;
+        let axes = [0, 1, 2].map(|i| rotation.rotate_vector(unit_axis(i)));
+        let screen_axes = axes.map(|axis| {
+            return project(&self.camera, center + axis * length).map(|tip| (tip.0 - screen_center.0, tip.1 - screen_center.1));
+        });
+        return Some(GizmoFrame { center, axes, rotation, length, screen_center, screen_axes, eye: self.camera.pos });
+    }
+
+    /// Starts dragging the handle at the pixel, if there is one. Returns false if there isn't,
+    /// the click is then free for other things.
+    pub fn gizmo_mouse_down(&mut self, x: f32, y: f32) -> bool {
+        if self.follow_camera {
+            return false;
+        }
+        let Some(frame) = self.get_gizmo_frame() else {
+            return false;
+        };
+        let Some(handle) = frame.hit_test(&self.camera, self.gizmo.mode, (x, y)) else {
+            return false;
+        };
+        console_log!("Dragging gizmo handle {:?}", handle);
+        let edit_start = self.begin_transform_edit();
+        self.gizmo.hovered = Some(handle);
+        self.gizmo.drag = Some(GizmoDrag { handle, start_mouse: (x, y), frame, edit_start });
+        return true;
+    }
+
+    /// Drags the grabbed handle to the pixel, or highlights the handle under it if none is grabbed
+    pub fn gizmo_mouse_move(&mut self, x: f32, y: f32) {
+        let Some(drag) = self.gizmo.drag.clone() else {
+            self.gizmo.hovered = self.get_gizmo_frame().and_then(|frame| frame.hit_test(&self.camera, self.gizmo.mode, (x, y)));
+            return;
+        };
+        for &(target, start_transform) in drag.edit_start.iter() {
+            let transform = drag.frame.get_dragged_transform(drag.handle, self.gizmo.mode, &self.grid, drag.start_mouse, (x, y), start_transform);
+            if transform.get_min_scale() > 0.0 {
+                self.set_target_transform(target, transform);
+            }
+        }
+    }
+
+    /// Lets go of the handle, the whole drag is recorded as one edit
+    pub fn gizmo_mouse_up(&mut self) {
+        self.end_gizmo_drag();
+    }
+
+    pub fn end_gizmo_drag(&mut self) {
+        if let Some(drag) = self.gizmo.drag.take() {
+            self.end_transform_edit(drag.edit_start);
+        }
+    }
+
+    /// Draws the handles over the frame, the hovered or grabbed one highlighted
+    pub fn draw_gizmo(&self) {
+        let Some(frame) = self.get_gizmo_frame() else {
+            return;
+        };
+        for (handle, line) in frame.get_handle_lines(&self.camera, self.gizmo.mode) {
+            let color = if self.gizmo.hovered == Some(handle) {
+                ACTIVE_COLOR
+            } else {
+                match handle {
+                    GizmoHandle::Axis(i) | GizmoHandle::Plane(i) | GizmoHandle::Ring(i) => AXIS_COLORS[i],
+                    GizmoHandle::Uniform => UNIFORM_COLOR,
+                }
+            };
+            let thickness = if matches!(handle, GizmoHandle::Axis(_)) { 3 } else { 2 };
+            for segment in line.windows(2) {
+                self.pixel_buf.draw_line(segment[0], segment[1], color, thickness);
+            }
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::{borrow::Cow, sync::Arc};
+
+use gltf::binary::{Glb, Header};
+use serde_json::{json, Map, Value};
+
+use crate::{console_log, utils::{math::{Mat4, Vec3}, utils::flip_indices_winding}};
+
+use super::{game::Game, mesh::Mesh, scene_object::{MaterialOverride, SceneObject}, scene_snapshot::MaterialSnapshot};
+
+// Writes scene objects out as glTF 2.0, one node + mesh + material per SceneObject.
+// The engine is z-up, glTF is y-up: the importer (gltf_parser.rs) maps a glTF position p
+// to (-p[2], -p[0], p[1]) and flips the winding, so the exporter does the inverse of both.
+// Per-face colors become vertex colors (triangles are unwelded for that), unless the whole
+// mesh is one color, in which case it goes into the material's base color instead.
+// Smooth shaded meshes are unwelded too, their vertex normals are per index.
+// Instances share their geometry's accessors and get the transform as the node's matrix.
+
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// engine (z-up) -> glTF (y-up), inverse of the mapping in gltf_parser::parse_gltf_mesh()
+fn to_gltf_axes(v: Vec3) -> [f32; 3] {
+    return [-v.y, v.z, -v.x];
+}
+
+/// An engine transform as a glTF node matrix (column major, in glTF's axes)
+fn to_gltf_matrix(transform: &Mat4) -> [f32; 16] {
+    let axes = Mat4::new([
+        [0.0, -1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    // axes is a rotation, so its transpose is its inverse
+    let gltf_transform = axes * *transform * axes.transposed();
+    let mut matrix = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            matrix[col * 4 + row] = gltf_transform.m[row][col];
+        }
+    }
+    return matrix;
+}
+
+/// Accessors already written for a mesh, so instances of it can point at the same data
+struct GeometryAccessors {
+    mesh: Arc<Mesh>,
+    with_vertex_colors: bool,
+    attributes: Map<String, Value>,
+    indices: usize,
+}
+
+/// Accumulates the binary buffer and the accessors/buffer views pointing into it
+struct GltfBuilder {
+    bin: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    meshes: Vec<Value>,
+    materials: Vec<Value>,
+    nodes: Vec<Value>,
+    extensions_used: Vec<&'static str>,
+    geometries: Vec<GeometryAccessors>,
+}
+
+impl GltfBuilder {
+    fn new() -> GltfBuilder {
+        return GltfBuilder {
+            bin: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            nodes: Vec::new(),
+            extensions_used: Vec::new(),
+            geometries: Vec::new(),
+        };
+    }
+
+    fn use_extension(&mut self, name: &'static str) {
+        if !self.extensions_used.contains(&name) {
+            self.extensions_used.push(name);
+        }
+    }
+
+    /// Appends data to the buffer as a new buffer view, returns its index
+    fn push_buffer_view(&mut self, data: &[u8], target: u32) -> usize {
+        // everything written is 4 byte aligned already, but keep views aligned regardless
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        self.bin.extend_from_slice(data);
+        return self.buffer_views.len() - 1;
+    }
+
+    fn push_vec3_accessor(&mut self, values: &[[f32; 3]], with_bounds: bool) -> usize {
+        let bytes: Vec<u8> = values.iter().flatten().flat_map(|f| f.to_le_bytes()).collect();
+        let view = self.push_buffer_view(&bytes, GLTF_ARRAY_BUFFER);
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": GLTF_FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+        });
+        if with_bounds {
+            // POSITION accessors are required to have min/max
+            let mut min = [f32::INFINITY; 3];
+            let mut max = [f32::NEG_INFINITY; 3];
+            for v in values {
+                for i in 0..3 {
+                    min[i] = min[i].min(v[i]);
+                    max[i] = max[i].max(v[i]);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        return self.accessors.len() - 1;
+    }
+
+    fn push_index_accessor(&mut self, indices: &[usize]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|i| (*i as u32).to_le_bytes()).collect();
+        let view = self.push_buffer_view(&bytes, GLTF_ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": GLTF_UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        return self.accessors.len() - 1;
+    }
+
+    /// Returns the attributes and the index accessor, writing the geometry only the first time
+    fn push_geometry(&mut self, mesh: &Arc<Mesh>, with_vertex_colors: bool) -> (Map<String, Value>, usize) {
+        let existing = self.geometries
+            .iter()
+            .find(|g| Arc::ptr_eq(&g.mesh, mesh) && g.with_vertex_colors == with_vertex_colors);
+        if let Some(geometry) = existing {
+            return (geometry.attributes.clone(), geometry.indices);
+        }
+
+        let mut attributes = Map::new();
+        let smooth = !mesh.vertex_normals.is_empty();
+        let indices = if !with_vertex_colors && !smooth {
+            let positions: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| to_gltf_axes(*v)).collect();
+            attributes.insert(String::from("POSITION"), json!(self.push_vec3_accessor(&positions, true)));
+
+            let mut indices = mesh.indices.clone();
+            flip_indices_winding(&mut indices);
+            indices
+        } else {
+            // vertex colors can't be per face, so every triangle gets its own three vertices
+            let positions: Vec<[f32; 3]> = mesh.indices.iter().map(|i| to_gltf_axes(mesh.vertices[*i])).collect();
+            attributes.insert(String::from("POSITION"), json!(self.push_vec3_accessor(&positions, true)));
+            if with_vertex_colors {
+                let colors: Vec<[f32; 3]> = mesh.colors
+                    .iter()
+                    .flat_map(|c| {
+                        let c = [c.x.clamp(0.0, 1.0), c.y.clamp(0.0, 1.0), c.z.clamp(0.0, 1.0)];
+                        [c, c, c]
+                    })
+                    .collect();
+                attributes.insert(String::from("COLOR_0"), json!(self.push_vec3_accessor(&colors, false)));
+            }
+            if smooth {
+                let normals: Vec<[f32; 3]> = mesh.vertex_normals.iter().map(|n| to_gltf_axes(*n)).collect();
+                attributes.insert(String::from("NORMAL"), json!(self.push_vec3_accessor(&normals, false)));
+            }
+
+            let mut indices: Vec<usize> = (0..positions.len()).collect();
+            flip_indices_winding(&mut indices);
+            indices
+        };
+        let indices_accessor = self.push_index_accessor(&indices);
+
+        self.geometries.push(GeometryAccessors {
+            mesh: Arc::clone(mesh),
+            with_vertex_colors,
+            attributes: attributes.clone(),
+            indices: indices_accessor,
+        });
+        return (attributes, indices_accessor);
+    }
+
+    /// Returns the index of the glTF mesh
+    fn push_mesh(&mut self, scene_obj: &SceneObject, material_index: usize) -> usize {
+        let (attributes, indices_accessor) = self.push_geometry(&scene_obj.mesh, !is_single_color(scene_obj));
+
+        // flat shaded meshes leave normals out, viewers use flat normals then, same as the engine
+        self.meshes.push(json!({
+            "primitives": [{
+                "attributes": attributes,
+                "indices": indices_accessor,
+                "material": material_index,
+            }],
+        }));
+        return self.meshes.len() - 1;
+    }
+
+    fn push_material(&mut self, scene_obj: &SceneObject, name: &str) -> usize {
+        let properties = scene_obj.get_properties();
+        let base_color = if is_single_color(scene_obj) { scene_obj.get_triangle_color(0) } else { Vec3::new(1.0, 1.0, 1.0) };
+
+        let material_snapshot = match scene_obj.get_material() {
+            Some(material) => material.to_snapshot(),
+            None => MaterialSnapshot::Lambertian,
+        };
+        // the emitted color of lights is the hittable color, which can go above 1
+        let emission = match &scene_obj.material_override {
+            Some(material_override) => material_override.color.unwrap_or(base_color),
+            None => scene_obj.hittables.first().map(|h| h.get_color()).unwrap_or(base_color),
+        };
+
+        let mut material = json!({
+            "name": name,
+            "doubleSided": !properties.cull_faces,
+        });
+        self.fill_material(&mut material, &material_snapshot, emission);
+
+        // transmission already makes glass see-through, blending on top of it would double it up
+        let is_transmissive = matches!(material_snapshot, MaterialSnapshot::Dielectric { .. });
+        let alpha = if is_transmissive { 1.0 } else { properties.alpha.clamp(0.0, 1.0) };
+        if alpha < 1.0 {
+            material["alphaMode"] = json!("BLEND");
+        }
+        material["pbrMetallicRoughness"]["baseColorFactor"] = json!([
+            base_color.x.clamp(0.0, 1.0),
+            base_color.y.clamp(0.0, 1.0),
+            base_color.z.clamp(0.0, 1.0),
+            alpha,
+        ]);
+
+        self.materials.push(material);
+        return self.materials.len() - 1;
+    }
+
+    /// Maps the ray tracing material onto metallic-roughness + KHR extensions
+    fn fill_material(&mut self, material: &mut Value, snapshot: &MaterialSnapshot, emission: Vec3) {
+        match snapshot {
+            MaterialSnapshot::Lambertian => {
+                material["pbrMetallicRoughness"]["metallicFactor"] = json!(0.0);
+                material["pbrMetallicRoughness"]["roughnessFactor"] = json!(1.0);
+            },
+            MaterialSnapshot::Metal { fuzz } => {
+                material["pbrMetallicRoughness"]["metallicFactor"] = json!(1.0);
+                material["pbrMetallicRoughness"]["roughnessFactor"] = json!(fuzz.clamp(0.0, 1.0));
+            },
+            MaterialSnapshot::Dielectric { index_of_refrac } => {
+                material["pbrMetallicRoughness"]["metallicFactor"] = json!(0.0);
+                material["pbrMetallicRoughness"]["roughnessFactor"] = json!(0.0);
+                material["extensions"]["KHR_materials_transmission"] = json!({ "transmissionFactor": 1.0 });
+                material["extensions"]["KHR_materials_ior"] = json!({ "ior": index_of_refrac });
+                self.use_extension("KHR_materials_transmission");
+                self.use_extension("KHR_materials_ior");
+            },
+            MaterialSnapshot::DiffuseLight => {
+                // emissiveFactor is capped at 1, anything brighter goes into the strength
+                let strength = emission.max_component().max(1.0);
+                let emissive = emission / strength;
+                material["pbrMetallicRoughness"]["metallicFactor"] = json!(0.0);
+                material["pbrMetallicRoughness"]["roughnessFactor"] = json!(1.0);
+                material["emissiveFactor"] = json!([emissive.x, emissive.y, emissive.z]);
+                if strength > 1.0 {
+                    material["extensions"]["KHR_materials_emissive_strength"] = json!({ "emissiveStrength": strength });
+                    self.use_extension("KHR_materials_emissive_strength");
+                }
+            },
+            MaterialSnapshot::ClearCoat { base_material, .. } => {
+                // glTF's clear coat has a fixed ior of 1.5, so the coat's ior can't be carried over
+                self.fill_material(material, base_material, emission);
+                material["extensions"]["KHR_materials_clearcoat"] = json!({
+                    "clearcoatFactor": 1.0,
+                    "clearcoatRoughnessFactor": 0.0,
+                });
+                self.use_extension("KHR_materials_clearcoat");
+            },
+        }
+    }
+
+    fn push_scene_object(&mut self, scene_obj: &SceneObject, index: usize) {
+        let material_index = self.push_material(scene_obj, &format!("material_{}", index));
+        let mesh_index = self.push_mesh(scene_obj, material_index);
+        let mut node = json!({
+            "name": format!("object_{}", index),
+            "mesh": mesh_index,
+        });
+        if !scene_obj.transform.is_identity() {
+            node["matrix"] = json!(to_gltf_matrix(&scene_obj.transform.to_mat4()));
+        }
+        self.nodes.push(node);
+    }
+
+    /// The glTF JSON document, with the buffer's uri set if given (None for GLB)
+    fn to_json(&self, buffer_uri: Option<String>) -> Value {
+        let mut buffer = json!({ "byteLength": self.bin.len() });
+        if let Some(uri) = buffer_uri {
+            buffer["uri"] = json!(uri);
+        }
+
+        let mut root = json!({
+            "asset": { "version": "2.0", "generator": "wasm-graphics" },
+            "scene": 0,
+            "scenes": [{ "nodes": (0..self.nodes.len()).collect::<Vec<usize>>() }],
+            "nodes": self.nodes,
+            "meshes": self.meshes,
+            "materials": self.materials,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+            "buffers": [buffer],
+        });
+        if !self.extensions_used.is_empty() {
+            root["extensionsUsed"] = json!(self.extensions_used);
+        }
+        return root;
+    }
+}
+
+/// A color override always makes the whole object one color
+fn is_single_color(scene_obj: &SceneObject) -> bool {
+    if let Some(MaterialOverride { color: Some(_), .. }) = &scene_obj.material_override {
+        return true;
+    }
+    let colors = &scene_obj.mesh.colors;
+    return colors.iter().all(|c| *c == colors[0]);
+}
+
+fn build_gltf(scene_objects: &[SceneObject]) -> Result<GltfBuilder, String> {
+    if scene_objects.is_empty() {
+        return Err(String::from("Scene has no objects to export"));
+    }
+    let mut builder = GltfBuilder::new();
+    for (i, scene_obj) in scene_objects.iter().enumerate() {
+        builder.push_scene_object(scene_obj, i);
+    }
+    console_log!("Exported {} objects to glTF ({} bytes of buffer data)", builder.nodes.len(), builder.bin.len());
+    return Ok(builder);
+}
+
+/// Binary glTF (.glb) with everything in one file
+pub fn export_scene_objects_to_glb(scene_objects: &[SceneObject]) -> Result<Vec<u8>, String> {
+    let builder = build_gltf(scene_objects)?;
+    let json = serde_json::to_vec(&builder.to_json(None)).map_err(|e| e.to_string())?;
+    let glb = Glb {
+        // the header is filled in by to_vec()
+        header: Header { magic: *b"glTF", version: 2, length: 0 },
+        json: Cow::Owned(json),
+        bin: Some(Cow::Owned(builder.bin)),
+    };
+    return glb.to_vec().map_err(|e| format!("Failed to write GLB: {}", e));
+}
+
+/// Text glTF (.gltf), the buffer is embedded as a base64 data uri
+pub fn export_scene_objects_to_gltf(scene_objects: &[SceneObject]) -> Result<String, String> {
+    let builder = build_gltf(scene_objects)?;
+    let uri = format!("data:application/octet-stream;base64,{}", base64::encode(&builder.bin));
+    return serde_json::to_string_pretty(&builder.to_json(Some(uri))).map_err(|e| e.to_string());
+}
+
+impl Game {
+    pub fn export_scene_glb(&self) -> Result<Vec<u8>, String> {
+        return export_scene_objects_to_glb(&self.scene_objects.read().unwrap());
+    }
+    pub fn export_scene_gltf(&self) -> Result<String, String> {
+        return export_scene_objects_to_gltf(&self.scene_objects.read().unwrap());
+    }
+}
@@ -1,12 +1,12 @@
 use gltf::{buffer::{Data, Source}, image, json::extensions::material, mesh::{util::{tex_coords, ReadColors}, Reader}, scene, Gltf, Primitive};
-use ::image::{load_from_memory, GenericImageView};
-use wasm_bindgen::prelude::*;
+use ::image::load_from_memory;
+use std::{collections::HashMap, sync::Arc};
 use data_url;
-use crate::{console_error, console_log, utils::utils::flip_indices_winding, wasm::wasm::GAME_INSTANCE};
+use crate::{console_error, console_log, utils::utils::flip_indices_winding};
 
-use crate::utils::math::Vec3;
+use crate::utils::math::{Mat4, Transform, Vec3};
 
-use super::{mesh::{Mesh, PhongProperties}, ray_tracing::material::{Lambertian, Material}, scene_object::SceneObject};
+use super::{mesh::{Mesh, PhongProperties}, normal_map::{NormalMap, NormalMapKind, Tangent}, texture::{interpolate_uv, Texture, TextureFilter}};
 
 pub fn extract_combined_mesh_from_raw_glb_bytes(glb_bytes: &[u8]) -> Result<Mesh, String> {
     match decode_glb_bytes(glb_bytes) {
@@ -21,41 +21,6 @@ pub fn extract_combined_mesh_from_raw_glb_bytes(glb_bytes: &[u8]) -> Result<Mesh
     }
 }
 
-#[wasm_bindgen]
-pub fn load_glb_model(glb_bytes: &[u8]) -> bool {
-    match decode_glb_bytes(glb_bytes) {
-        Ok((gltf, buffers)) => {
-
-            let mut combined_mesh = match extract_combined_mesh_from_gltf(&gltf, &buffers) {
-                Ok(mesh) => mesh,
-                Err(e) => {
-                    console_error!("GLTF parse error on extract_combined_mesh_from_gltf(): {}", e);
-                    return false;
-                }
-            };
-
-            // center the mesh
-            combined_mesh.translate_to(Vec3::new(0.0, 0.0, 0.0));
-            if combined_mesh.radius > 50.0 {
-                let scale_factor = 50.0 / combined_mesh.radius;
-                combined_mesh.scale_by(scale_factor);
-            }
-
-            let combined_scene_obj = SceneObject::new_from_mesh(combined_mesh, Lambertian::default().clone_box(), false);
-            GAME_INSTANCE.with(|game_instance| {
-                let mut g = game_instance.borrow_mut();
-                g.scene_objects.write().unwrap().push(combined_scene_obj);
-                g.bvh = None; // invalidate the bvh
-            });
-            true
-        },
-        Err(e) => {
-            console_error!("GLB error on decode_glb_bytes(): {}", e);
-            false
-        }
-    }
-}
-
 pub fn decode_glb_bytes(glb_bytes: &[u8]) -> Result<(Gltf, Vec<Data>), String> {
     // Parse the GLB data - this works with both GLB and GLTF formats
     let gltf = match Gltf::from_slice(glb_bytes) {
@@ -90,22 +55,7 @@ pub fn extract_combined_mesh_from_gltf(gltf: &Gltf, buffers: &[Data]) -> Result<
         Ok(meshes) => {
 
             // combine meshes into a one mesh
-            let combined_vertices: Vec<Vec3> = meshes.iter().flat_map(|m| m.vertices.clone()).collect();
-            let combined_colors: Vec<Vec3> = meshes.iter().flat_map(|m| m.colors.clone()).collect();
-            let mut combined_indices = Vec::new();
-
-            let mut vertex_offset = 0;
-            for mesh in meshes {
-                combined_indices.extend(mesh.indices.iter().map(|i| i + vertex_offset));
-                vertex_offset += mesh.vertices.len();
-            }
-
-            // log lengths of buffers
-            console_log!("combined_vertices.len(): {:?}", combined_vertices.len());
-            console_log!("combined_indices.len(): {:?}", combined_indices.len());
-            console_log!("combined_colors.len(): {:?}", combined_colors.len());
-
-            let combined_mesh = Mesh::new(combined_vertices, combined_indices, combined_colors, PhongProperties::rt_default());
+            let combined_mesh = combine_meshes(meshes);
             console_log!("Extracted mesh from GLTF with {} vertices and {} faces", 
                 combined_mesh.vertices.len(),  
                 combined_mesh.colors.len());
@@ -118,19 +68,210 @@ pub fn extract_combined_mesh_from_gltf(gltf: &Gltf, buffers: &[Data]) -> Result<
     }
 }
 
+/// A GLB's node hierarchy, with every mesh in its node's own space rather than combined
+pub struct GltfNodeTree {
+    /// One per glTF mesh that has triangles, nodes refer to these by index
+    pub meshes: Vec<Mesh>,
+    /// The top level nodes of the file's default scene
+    pub roots: Vec<GltfNode>,
+}
+
+pub struct GltfNode {
+    pub name: String,
+    /// Relative to the parent node, already in our axes
+    pub local_transform: Transform,
+    /// The file's own matrix for local_transform, which keeps any shear local_transform loses
+    pub local_matrix: Mat4,
+    /// Index into GltfNodeTree::meshes, None for nodes that only group others
+    pub mesh: Option<usize>,
+    pub children: Vec<GltfNode>,
+}
+
+impl GltfNodeTree {
+    /// Number of nodes that draw a mesh
+    pub fn mesh_node_count(&self) -> usize {
+        fn count(node: &GltfNode) -> usize {
+            return node.mesh.is_some() as usize + node.children.iter().map(count).sum::<usize>();
+        }
+        return self.roots.iter().map(count).sum();
+    }
+    /// How many nodes draw each of meshes
+    pub fn get_mesh_use_counts(&self) -> Vec<usize> {
+        fn count(node: &GltfNode, uses: &mut [usize]) {
+            if let Some(mesh_index) = node.mesh {
+                uses[mesh_index] += 1;
+            }
+            for child in node.children.iter() {
+                count(child, uses);
+            }
+        }
+        let mut uses = vec![0; self.meshes.len()];
+        for root in self.roots.iter() {
+            count(root, &mut uses);
+        }
+        return uses;
+    }
+}
+
+pub fn extract_node_tree_from_raw_glb_bytes(glb_bytes: &[u8]) -> Result<GltfNodeTree, String> {
+    let (gltf, buffers) = decode_glb_bytes(glb_bytes)?;
+    return extract_node_tree_from_gltf(&gltf, &buffers);
+}
+
+/// Unlike extract_combined_mesh_from_gltf(), keeps the file's nodes and their transforms.
+/// A mesh used by several nodes is only parsed once.
+pub fn extract_node_tree_from_gltf(gltf: &Gltf, buffers: &[Data]) -> Result<GltfNodeTree, String> {
+    let mut meshes = Vec::new();
+    let mut mesh_indices = vec![None; gltf.meshes().len()];
+    let mut textures = TextureCache::new();
+    for mesh in gltf.meshes() {
+        let primitives = parse_gltf_mesh(gltf, mesh.clone(), buffers, &mut textures)?;
+        let combined_mesh = combine_meshes(primitives);
+        if !combined_mesh.indices.is_empty() {
+            mesh_indices[mesh.index()] = Some(meshes.len());
+            meshes.push(combined_mesh);
+        }
+    }
+
+    let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) else {
+        return Err("GLTF has no scenes".to_string());
+    };
+    let roots = scene.nodes().map(|node| parse_gltf_node(node, &mesh_indices)).collect::<Result<Vec<_>, String>>()?;
+    let tree = GltfNodeTree { meshes, roots };
+    console_log!("Extracted {} meshes used by {} nodes from GLTF", tree.meshes.len(), tree.mesh_node_count());
+    return Ok(tree);
+}
+
+fn parse_gltf_node(node: gltf::Node, mesh_indices: &[Option<usize>]) -> Result<GltfNode, String> {
+    let name = node.name().map(String::from).unwrap_or_else(|| format!("Node {}", node.index()));
+    let local_matrix = from_gltf_matrix(node.transform().matrix());
+    let local_transform = Transform::from_mat4(&local_matrix).ok_or(format!("node {} has a transform that squashes it flat", name))?;
+    let mesh = node.mesh().and_then(|mesh| mesh_indices[mesh.index()]);
+    let children = node.children().map(|child| parse_gltf_node(child, mesh_indices)).collect::<Result<Vec<_>, String>>()?;
+    return Ok(GltfNode { name, local_transform, local_matrix, mesh, children });
+}
+
+/// A glTF node matrix (column major, in glTF's axes) as an engine transform
+fn from_gltf_matrix(columns: [[f32; 4]; 4]) -> Mat4 {
+    // maps engine axes to glTF's, the inverse of what parse_gltf_mesh() does to vertices
+    let axes = Mat4::new([
+        [0.0, -1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    let mut rows = [[0.0; 4]; 4];
+    for col in 0..4 {
+        for row in 0..4 {
+            rows[row][col] = columns[col][row];
+        }
+    }
+    return axes.transposed() * Mat4::new(rows) * axes;
+}
+
+fn combine_meshes(meshes: Vec<Mesh>) -> Mesh {
+    let combined_vertices: Vec<Vec3> = meshes.iter().flat_map(|m| m.vertices.clone()).collect();
+    let combined_colors: Vec<Vec3> = meshes.iter().flat_map(|m| m.colors.clone()).collect();
+    let mut combined_indices = Vec::new();
+
+    // meshes without textures get placeholder UVs, so every vertex still has one
+    let any_textured = meshes.iter().any(|m| m.has_textures());
+    let any_normal_mapped = meshes.iter().any(|m| m.has_normal_maps());
+    let mut combined_uvs = Vec::new();
+    let mut combined_textures: Vec<Arc<Texture>> = Vec::new();
+    let mut combined_triangle_textures = Vec::new();
+    let mut combined_normal_maps: Vec<Arc<NormalMap>> = Vec::new();
+    let mut combined_triangle_normal_maps = Vec::new();
+    let mut combined_tangents = Vec::new();
+
+    // and flat shaded ones get their face normal at each corner, if any others are smooth
+    let any_smooth = meshes.iter().any(|m| !m.vertex_normals.is_empty());
+    let mut combined_vertex_normals = Vec::new();
+
+    let mut vertex_offset = 0;
+    for mesh in meshes {
+        combined_indices.extend(mesh.indices.iter().map(|i| i + vertex_offset));
+        vertex_offset += mesh.vertices.len();
+
+        if any_smooth {
+            if mesh.vertex_normals.is_empty() {
+                combined_vertex_normals.extend((0..mesh.indices.len()).map(|i| mesh.normals[i / 3]));
+            } else {
+                combined_vertex_normals.extend(mesh.vertex_normals.iter().copied());
+            }
+        }
+
+        if any_normal_mapped {
+            if mesh.has_normal_maps() {
+                // the same image with the same scale is the same normal map
+                let normal_map_indices: Vec<usize> = mesh.normal_maps.iter().map(|normal_map| {
+                    let same = |m: &Arc<NormalMap>| Arc::ptr_eq(&m.texture, &normal_map.texture) && m.kind == normal_map.kind;
+                    if let Some(i) = combined_normal_maps.iter().position(same) {
+                        return i;
+                    }
+                    combined_normal_maps.push(normal_map.clone());
+                    return combined_normal_maps.len() - 1;
+                }).collect();
+                combined_triangle_normal_maps.extend(mesh.triangle_normal_maps.iter().map(|m| m.map(|i| normal_map_indices[i])));
+                combined_tangents.extend(mesh.tangents.iter().copied());
+            } else {
+                combined_triangle_normal_maps.extend(std::iter::repeat_n(None, mesh.indices.len() / 3));
+                combined_tangents.extend(std::iter::repeat_n(Tangent::default(), mesh.indices.len()));
+            }
+        }
+
+        if any_textured || any_normal_mapped {
+            if mesh.uvs.len() == mesh.vertices.len() {
+                combined_uvs.extend(mesh.uvs.iter().copied());
+            } else {
+                combined_uvs.extend(std::iter::repeat_n([0.0, 0.0], mesh.vertices.len()));
+            }
+        }
+        if !any_textured {
+            continue;
+        }
+        if mesh.has_textures() {
+            // primitives often share a texture, which only needs to be listed once
+            let texture_indices: Vec<usize> = mesh.textures.iter().map(|texture| {
+                if let Some(i) = combined_textures.iter().position(|t| Arc::ptr_eq(t, texture)) {
+                    return i;
+                }
+                combined_textures.push(texture.clone());
+                return combined_textures.len() - 1;
+            }).collect();
+            combined_triangle_textures.extend(mesh.triangle_textures.iter().map(|t| t.map(|i| texture_indices[i])));
+        } else {
+            combined_triangle_textures.extend(std::iter::repeat_n(None, mesh.indices.len() / 3));
+        }
+    }
+    let mut combined_mesh = Mesh::new(combined_vertices, combined_indices, combined_colors, PhongProperties::rt_default());
+    combined_mesh.vertex_normals = combined_vertex_normals;
+    combined_mesh.uvs = combined_uvs;
+    combined_mesh.textures = combined_textures;
+    combined_mesh.triangle_textures = combined_triangle_textures;
+    combined_mesh.normal_maps = combined_normal_maps;
+    combined_mesh.triangle_normal_maps = combined_triangle_normal_maps;
+    combined_mesh.tangents = combined_tangents;
+    return combined_mesh;
+}
+
 pub fn parse_gltf_objects(gltf: &Gltf, buffers: &[Data]) -> Result<Vec<Mesh>, String> {
     
     let mut meshes = Vec::new();
+    let mut textures = TextureCache::new();
 
     for mesh in gltf.meshes() {
-        let mesh_objects = parse_gltf_mesh(&gltf, mesh, buffers)?;
+        let mesh_objects = parse_gltf_mesh(&gltf, mesh, buffers, &mut textures)?;
         meshes.extend(mesh_objects);
     }
 
     Ok(meshes)
 }
 
-fn parse_gltf_mesh(gltf: &Gltf, mesh: gltf::Mesh, buffers: &[Data]) -> Result<Vec<Mesh>, String> {
+/// Decoded textures by glTF image index, so primitives that share an image only decode it once
+type TextureCache = HashMap<usize, Arc<Texture>>;
+
+fn parse_gltf_mesh(gltf: &Gltf, mesh: gltf::Mesh, buffers: &[Data], textures: &mut TextureCache) -> Result<Vec<Mesh>, String> {
     let mut vertex_objects = Vec::new();
     for primitive in mesh.primitives() {
 
@@ -147,47 +288,94 @@ fn parse_gltf_mesh(gltf: &Gltf, mesh: gltf::Mesh, buffers: &[Data]) -> Result<Ve
         };
         flip_indices_winding(&mut indices);
 
+        // in the same axes as the positions, and read out per index to match Mesh::vertex_normals
+        let normals: Vec<Vec3> = match reader.read_normals() {
+            Some(normals) => normals.map(|n| Vec3::new(-n[2], -n[0], n[1])).collect(),
+            None => vec![],
+        };
+
         // Get material properties
         let phong_properties = PhongProperties::default();
         let pbr = primitive.material().pbr_metallic_roughness();
         let pbr_base_color = pbr.base_color_factor();
 
         let base_color = Vec3::new(pbr_base_color[0], pbr_base_color[1], pbr_base_color[2]);
-        let read_colors = reader.read_colors(0);
 
         // get_colors_from_vertex_colors() is unused, models often have meaningless vertex colors
 
-        let colors =  {
-            // Try to get colors from texture
-            let tex_coords_vec = match reader.read_tex_coords(0) {
-                Some(tc) => tc.into_f32().collect(),
-                None => vec![] // Empty if no texture coords
-            };
-
-            let indices_for_texture = match reader.read_indices() {
-                Some(idx) => idx.into_u32().collect(),
-                None => vec![] // Empty if no indices
-            };
-
-            if !tex_coords_vec.is_empty() && !indices_for_texture.is_empty() {
-                match get_colors_from_texture(gltf, &primitive, tex_coords_vec, indices_for_texture, buffers) {
-                    Ok(texture_colors) => {
-                        console_log!("using texture colors");
-                        texture_colors
-                    },
-                    Err(_) => {
-                        console_log!("Failed to get colors from texture, using base color");
-                        vec![base_color; indices.len() / 3]
-                    }
-                }
-            } else {
-                console_log!("No texture coordinates or indices found, using base color");
-                vec![base_color; indices.len() / 3]
+        let texture = match get_base_color_texture(gltf, &primitive, buffers, textures) {
+            Ok(texture) => texture,
+            Err(_) => {
+                console_log!("Failed to get colors from texture, using base color");
+                None
+            },
+        };
+        let mut normal_map = match get_normal_map(gltf, &primitive, buffers, textures) {
+            Ok(normal_map) => normal_map,
+            Err(_) => {
+                console_log!("Failed to get normal map, using the mesh's own normals");
+                None
+            },
+        };
+        // Mesh only has one set of UVs, which the base color texture gets first say over
+        if let (Some((_, texture_set)), Some((_, normal_set))) = (&texture, &normal_map) {
+            if texture_set != normal_set {
+                console_log!("Normal map uses different texture coordinates than the base color, leaving it out");
+                normal_map = None;
+            }
+        }
+        let tex_coord_set = texture.as_ref().map(|(_, set)| *set).or(normal_map.as_ref().map(|(_, set)| *set));
+        let uvs: Vec<[f32; 2]> = match tex_coord_set.and_then(|set| reader.read_tex_coords(set)) {
+            Some(tc) => tc.into_f32().collect(),
+            None => vec![] // Empty if no texture coords
+        };
+        let (texture, normal_map) = if uvs.len() == vertices.len() {
+            (texture.map(|(texture, _)| texture), normal_map.map(|(normal_map, _)| normal_map))
+        } else {
+            if tex_coord_set.is_some() {
+                console_log!("No texture coordinates found, using base color");
             }
+            (None, None)
+        };
+        let tangents: Vec<Tangent> = match reader.read_tangents() {
+            Some(tangents) if normal_map.is_some() => tangents.map(|t| Tangent {
+                dir: Vec3::new(-t[2], -t[0], t[1]),
+                sign: if t[3] < 0.0 { -1.0 } else { 1.0 },
+            }).collect(),
+            _ => vec![],
         };
-        // console_log!("colors: {:?}", colors);
 
-        let vertex_object = Mesh::new(vertices, indices, colors, phong_properties);
+        let mut vertex_object = match &texture {
+            Some(texture) => {
+                // the flat colors are still used wherever textures aren't, like in exported files
+                let colors = indices.chunks(3).map(|t| {
+                    let triangle_uvs = [uvs[t[0]], uvs[t[1]], uvs[t[2]]];
+                    return texture.sample(interpolate_uv(&triangle_uvs, [1.0 / 3.0; 3]), TextureFilter::Nearest);
+                }).collect();
+                Mesh::new(vertices, indices, colors, phong_properties)
+            },
+            None => {
+                let colors = vec![base_color; indices.len() / 3];
+                Mesh::new(vertices, indices, colors, phong_properties)
+            },
+        };
+        set_vertex_normals(&mut vertex_object, &normals);
+        if !uvs.is_empty() {
+            vertex_object.uvs = uvs;
+        }
+        if let Some(texture) = texture {
+            vertex_object.textures = vec![texture];
+            vertex_object.triangle_textures = vec![Some(0); vertex_object.indices.len() / 3];
+        }
+        if let Some(normal_map) = normal_map {
+            // missing or mismatched tangents get generated by set_normal_map()
+            if tangents.len() == vertex_object.vertices.len() {
+                vertex_object.tangents = vertex_object.indices.iter().map(|&i| tangents[i]).collect();
+            }
+            if let Err(e) = vertex_object.set_normal_map(normal_map) {
+                console_log!("Couldn't use normal map: {}", e);
+            }
+        }
         
         vertex_objects.push(vertex_object);
     }
@@ -195,77 +383,81 @@ fn parse_gltf_mesh(gltf: &Gltf, mesh: gltf::Mesh, buffers: &[Data]) -> Result<Ve
     Ok(vertex_objects)
 }
 
-fn get_colors_from_texture(
+/// Leaves the mesh flat shaded if the file's normals don't match its vertices
+fn set_vertex_normals(mesh: &mut Mesh, normals: &[Vec3]) {
+    if normals.len() != mesh.vertices.len() {
+        if !normals.is_empty() {
+            console_log!("Mesh has {} normals for {} vertices, using flat shading", normals.len(), mesh.vertices.len());
+        }
+        return;
+    }
+    mesh.vertex_normals = mesh.indices.iter().map(|&i| normals[i].normalized()).collect();
+}
+
+/// The primitive's base color texture, or the diffuse one of the older specular-glossiness
+/// materials, along with which set of texture coordinates it uses. None if it has neither.
+fn get_base_color_texture(
     gltf: &Gltf,
     primitive: &gltf::Primitive,
-    tex_coords_vec: Vec<[f32; 2]>,
-    indices: Vec<u32>,
-    buffers: &[Data]
-) -> Result<Vec<Vec3>, String> {
+    buffers: &[Data],
+    textures: &mut TextureCache,
+) -> Result<Option<(Arc<Texture>, u32)>, String> {
 
-    // Get the material and check if it has a base color texture
     let material = primitive.material();
-    let pbr = material.pbr_metallic_roughness();
-    
-    if let Some(texture) = pbr.base_color_texture() {
-        // Get the texture and its source image
-        let texture_info = texture.texture();
-        let image = texture_info.source();
-        
-        // Get the image data
-        let image_data = match get_image_data(gltf, &image, buffers) {
-            Ok(data) => data,
-            Err(e) => {
-                console_log!("Failed to get image data for texture: {}", e);
-                return Err(format!("Failed to get image data for texture: {}", e));
-            },
-        };
+    let texture_info = material.pbr_metallic_roughness().base_color_texture()
+        .or_else(|| material.pbr_specular_glossiness().and_then(|sg| sg.diffuse_texture()));
+    let Some(texture_info) = texture_info else {
+        return Ok(None);
+    };
+    let texture = load_texture(gltf, &texture_info.texture(), buffers, textures)?;
+    return Ok(Some((texture, texture_info.tex_coord())));
+}
 
-        // Cache the decoded image to avoid repeatedly decoding it
-        let decoded_image = match load_from_memory(&image_data) {
-            Ok(img) => img,
-            Err(e) => {
-                console_log!("Failed to decode the received texture image: {}", e);
-                return Err(format!("Failed to decode the received texture image: {}", e));
-            },
-        };
-        let (width, height) = decoded_image.dimensions();
-        
-        // For each triangle, sample the texture at the first vertex
-        let mut colors = Vec::new();
-        
-        for chunk in indices.chunks(3) {
-            if chunk.len() == 3 {
-                // Get the UV coordinates of the first vertex of the triangle
-                let vertex_idx = chunk[0] as usize;
-                if vertex_idx < tex_coords_vec.len() {
-                    let uv = tex_coords_vec[vertex_idx];
-
-                    // Calculate pixel coordinates from UV (handle wrapping)
-                    let mut x = (uv[0] * width as f32) as u32;
-                    let mut y = (uv[1] * height as f32) as u32;
-                    
-                    x = x % width;
-                    y = y % height;
-
-                    // Get the pixel color
-                    let pixel = decoded_image.get_pixel(x, y);
-                    colors.push(Vec3::new(
-                        pixel[0] as f32 / 255.0,
-                        pixel[1] as f32 / 255.0,
-                        pixel[2] as f32 / 255.0
-                    ));
-                } else {
-                    colors.push(Vec3::new(1.0, 0.0, 1.0)); // Fallback to magenta
-                }
-            }
-        }
-        
-        Ok(colors)
-    } else {
-        // No base color texture, return an error
-        Err("No base color texture found".to_string())
+/// The primitive's normal map, along with which set of texture coordinates it uses
+fn get_normal_map(
+    gltf: &Gltf,
+    primitive: &gltf::Primitive,
+    buffers: &[Data],
+    textures: &mut TextureCache,
+) -> Result<Option<(Arc<NormalMap>, u32)>, String> {
+
+    let material = primitive.material();
+    let Some(normal_texture) = material.normal_texture() else {
+        return Ok(None);
+    };
+    let texture = load_texture(gltf, &normal_texture.texture(), buffers, textures)?;
+    let normal_map = NormalMap { texture, kind: NormalMapKind::Normal { scale: normal_texture.scale() } };
+    return Ok(Some((Arc::new(normal_map), normal_texture.tex_coord())));
+}
+
+/// Decodes the texture's image, or takes it from the cache if another primitive already did
+fn load_texture(gltf: &Gltf, texture: &gltf::Texture, buffers: &[Data], textures: &mut TextureCache) -> Result<Arc<Texture>, String> {
+    let image = texture.source();
+
+    if let Some(texture) = textures.get(&image.index()) {
+        return Ok(texture.clone());
     }
+
+    // Get the image data
+    let image_data = match get_image_data(gltf, &image, buffers) {
+        Ok(data) => data,
+        Err(e) => {
+            console_log!("Failed to get image data for texture: {}", e);
+            return Err(format!("Failed to get image data for texture: {}", e));
+        },
+    };
+
+    let decoded_image = match load_from_memory(&image_data) {
+        Ok(img) => img,
+        Err(e) => {
+            console_log!("Failed to decode the received texture image: {}", e);
+            return Err(format!("Failed to decode the received texture image: {}", e));
+        },
+    };
+    let texture = Arc::new(Texture::from_image(&decoded_image));
+    console_log!("Decoded {}x{} texture", texture.width, texture.height);
+    textures.insert(image.index(), texture.clone());
+    return Ok(texture);
 }
 
 // Helper function to get image data from a GLTF image
@@ -349,40 +541,6 @@ fn get_colors_from_vertex_colors(read_colors: Option<ReadColors>) -> Result<Vec<
     }
 }
 
-fn sample_texture_at_uv(
-    image_data: &[u8], 
-    u: f32, 
-    v: f32
-) -> Result<Vec3, String> {
-    match load_from_memory(image_data) {
-        Ok(img) => {
-            let (width, height) = img.dimensions();
-            
-            // Calculate pixel coordinates from UV (handle wrapping)
-            let mut x = (u * width as f32) as u32;
-            let mut y = (v * height as f32) as u32;
-            
-            // Ensure coordinates are within bounds (wrap around for texture repeating)
-            x = x % width;
-            y = y % height;
-            
-            // Get the pixel at the calculated position
-            let pixel = img.get_pixel(x, y);
-            
-            // Return the color as Vec3 (normalized to 0.0-1.0)
-            Ok(Vec3::new(
-                pixel[0] as f32 / 255.0,
-                pixel[1] as f32 / 255.0,
-                pixel[2] as f32 / 255.0
-            ))
-        },
-        Err(e) => {
-            console_log!("Failed to decode image: {}", e);
-            Err(format!("Failed to decode image: {}", e))
-        }
-    }
-}
-
 // fn parse_gltf_lights(gltf: &Gltf) -> Result<Vec<Light>, String> {
 //     console_log!("Parsing lights");
 //     // let lights = Vec::new();
@@ -0,0 +1,252 @@
+use crate::{console_error, console_log, utils::math::Vec3};
+
+use super::{game::{Game, GameStatus}, gizmo::AXIS_COLORS};
+
+// Aids for placing things in edit mode: a grid on the ground (z = 0) and the world axes, drawn
+// into the frame behind whatever is in front of them, and snapping. Moves snap the middle of the
+// selection onto grid lines, turns snap to whole angle steps, and an object following the camera
+// can be made to rest on the surface it's put on, or be centered on the nearest vertex there.
+
+/// How many grid lines are drawn on each side of the point under the camera
+const GRID_HALF_LINES: i32 = 50;
+/// Every this many lines, one is drawn darker
+const GRID_MAJOR_EVERY: f32 = 10.0;
+const GRID_COLOR: Vec3 = Vec3 { x: 0.45, y: 0.45, z: 0.45 };
+const GRID_MAJOR_COLOR: Vec3 = Vec3 { x: 0.2, y: 0.2, z: 0.2 };
+/// Lines are cut off this close in front of the camera
+const NEAR_PLANE: f32 = 0.001;
+/// A surface normal component below this means the axis runs along the surface
+const ALONG_SURFACE: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectSnap {
+    Off,
+    /// An object following the camera rests on the surface it's put on instead of sinking into it
+    Surface,
+    /// An object following the camera is centered on the nearest vertex of the object it's put on
+    Vertex,
+}
+
+impl ObjectSnap {
+    /// 0 = Off, 1 = Surface, 2 = Vertex, as the UI numbers them
+    pub fn from_number(number: u32) -> Option<ObjectSnap> {
+        return match number {
+            0 => Some(ObjectSnap::Off),
+            1 => Some(ObjectSnap::Surface),
+            2 => Some(ObjectSnap::Vertex),
+            _ => None,
+        };
+    }
+    pub fn to_number(self) -> u32 {
+        return match self {
+            ObjectSnap::Off => 0,
+            ObjectSnap::Surface => 1,
+            ObjectSnap::Vertex => 2,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSettings {
+    pub show_grid: bool,
+    pub show_axes: bool,
+    /// Distance between grid lines, which is also what moves snap to
+    pub spacing: f32,
+    pub snap_to_grid: bool,
+    pub snap_angles: bool,
+    /// Degrees rotations snap to multiples of
+    pub angle_step: f32,
+    pub object_snap: ObjectSnap,
+}
+
+impl GridSettings {
+    /// Everything off, so edit mode looks and behaves as it does without the aids
+    pub fn new() -> GridSettings {
+        return GridSettings {
+            show_grid: false,
+            show_axes: false,
+            spacing: 1.0,
+            snap_to_grid: false,
+            snap_angles: false,
+            angle_step: 15.0,
+            object_snap: ObjectSnap::Off,
+        };
+    }
+
+    /// p moved onto the nearest grid line along each axis that's set
+    pub fn snap_point(&self, p: Vec3, axes: [bool; 3]) -> Vec3 {
+        let snap = |value: f32, axis: bool| if axis { (value / self.spacing).round() * self.spacing } else { value };
+        return Vec3::new(snap(p.x, axes[0]), snap(p.y, axes[1]), snap(p.z, axes[2]));
+    }
+
+    /// offset changed so that moving from by it ends on grid lines, along the axes it moves on.
+    /// Small offsets go to the next line over rather than back to where from already is.
+    pub fn snap_step(&self, from: Vec3, offset: Vec3) -> Vec3 {
+        let snap = |from: f32, offset: f32| -> f32 {
+            if offset == 0.0 {
+                return 0.0;
+            }
+            let mut to = ((from + offset) / self.spacing).round() * self.spacing;
+            if (to - from) * offset.signum() < self.spacing * 1e-3 {
+                to += self.spacing * offset.signum();
+            }
+            return to - from;
+        };
+        return Vec3::new(snap(from.x, offset.x), snap(from.y, offset.y), snap(from.z, offset.z));
+    }
+
+    /// degrees rounded to the nearest angle step, if angles snap
+    pub fn snap_angle(&self, degrees: f32) -> f32 {
+        if !self.snap_angles {
+            return degrees;
+        }
+        return (degrees / self.angle_step).round() * self.angle_step;
+    }
+
+    /// Like snap_angle, but a turn smaller than a step becomes a whole step rather than nothing
+    pub fn snap_angle_step(&self, degrees: f32) -> f32 {
+        let snapped = self.snap_angle(degrees);
+        if snapped == 0.0 && degrees != 0.0 {
+            return self.angle_step.copysign(degrees);
+        }
+        return snapped;
+    }
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        return GridSettings::new();
+    }
+}
+
+impl Game {
+    pub fn set_grid_settings(&mut self, settings: GridSettings) {
+        if !(settings.spacing > 0.0 && settings.spacing.is_finite()) {
+            console_error!("Game::set_grid_settings() called with grid spacing {}, it must be above 0", settings.spacing);
+            return;
+        }
+        if !(settings.angle_step > 0.0 && settings.angle_step.is_finite()) {
+            console_error!("Game::set_grid_settings() called with angle step {}, it must be above 0", settings.angle_step);
+            return;
+        }
+        self.grid = settings;
+    }
+
+    /// The offset a move of the selection by offset becomes with grid snapping
+    pub fn snap_translation(&self, offset: Vec3) -> Vec3 {
+        if !self.grid.snap_to_grid {
+            return offset;
+        }
+        let bounds = match self.selected_group {
+            Some(group) => self.get_group_bounds(group),
+            None => self.get_selection_bounds(),
+        };
+        let Some((center, _)) = bounds else {
+            return offset;
+        };
+        return self.grid.snap_step(center, offset);
+    }
+
+    /// How far the object at active_index has to move to be where the user is looking, snapped
+    /// to the grid and the object under the crosshair as set
+    pub fn get_follow_offset(&self, active_index: usize) -> Vec3 {
+        let scene_objects = self.scene_objects.read().unwrap();
+        let active_obj = &scene_objects[active_index];
+        let center = active_obj.get_center();
+        let Some((looking_at_id, looking_at_pos, normal)) = *self.looking_at.read().unwrap() else {
+            let mut looking_at_pos = Vec3::new(self.camera.width as f32 / 2.0, self.camera.height as f32 / 2.0, 8.0 * active_obj.get_radius());
+            self.camera.vertex_screen_to_world_space(&mut looking_at_pos);
+            if self.grid.snap_to_grid {
+                looking_at_pos = self.grid.snap_point(looking_at_pos, [true; 3]);
+            }
+            console_log!("not looking at anything, translating to {:?}", looking_at_pos);
+            return looking_at_pos - center;
+        };
+
+        let mut destination = looking_at_pos;
+        match self.grid.object_snap {
+            ObjectSnap::Off => {},
+            ObjectSnap::Surface => {
+                // pushed out along the normal until the object only touches the surface
+                let depth = active_obj.get_world_vertices().iter().fold(0.0_f32, |depth, &v| depth.max(Vec3::dot(center - v, normal)));
+                destination = looking_at_pos + normal * depth;
+            },
+            ObjectSnap::Vertex => {
+                let target = self.get_object_index(looking_at_id).and_then(|index| scene_objects.get(index));
+                if let Some(target) = target {
+                    let nearest = target.get_world_vertices().iter().copied().min_by(|a, b| {
+                        return (*a - looking_at_pos).len_squared().total_cmp(&(*b - looking_at_pos).len_squared());
+                    });
+                    destination = nearest.unwrap_or(looking_at_pos);
+                }
+            },
+        }
+        if self.grid.snap_to_grid && self.grid.object_snap != ObjectSnap::Vertex {
+            // only along the surface, so the object stays on it
+            destination = self.grid.snap_point(destination, [normal.x, normal.y, normal.z].map(|n| n.abs() < ALONG_SURFACE));
+        }
+        console_log!("looking at object, translating to {:?}", destination);
+        return destination - center;
+    }
+
+    /// Draws the ground grid and the world axes over the frame, in edit mode
+    pub fn draw_grid(&self) {
+        if self.status != GameStatus::RasterizingNoLighting || !(self.grid.show_grid || self.grid.show_axes) {
+            return;
+        }
+        let spacing = self.grid.spacing;
+        // whole lines, around the point under the camera
+        let first_x = (self.camera.pos.x / spacing).round() - GRID_HALF_LINES as f32;
+        let first_y = (self.camera.pos.y / spacing).round() - GRID_HALF_LINES as f32;
+        let last_x = first_x + 2.0 * GRID_HALF_LINES as f32;
+        let last_y = first_y + 2.0 * GRID_HALF_LINES as f32;
+
+        if self.grid.show_grid {
+            let line_color = |line: f32| if line.rem_euclid(GRID_MAJOR_EVERY) == 0.0 { GRID_MAJOR_COLOR } else { GRID_COLOR };
+            for i in 0..=2 * GRID_HALF_LINES {
+                let (line_x, line_y) = (first_x + i as f32, first_y + i as f32);
+                self.draw_world_line(Vec3::new(line_x * spacing, first_y * spacing, 0.0), Vec3::new(line_x * spacing, last_y * spacing, 0.0), line_color(line_x));
+                self.draw_world_line(Vec3::new(first_x * spacing, line_y * spacing, 0.0), Vec3::new(last_x * spacing, line_y * spacing, 0.0), line_color(line_y));
+            }
+        }
+        if self.grid.show_axes {
+            let half_length = GRID_HALF_LINES as f32 * spacing;
+            self.draw_world_line(Vec3::new(first_x * spacing, 0.0, 0.0), Vec3::new(last_x * spacing, 0.0, 0.0), AXIS_COLORS[0]);
+            self.draw_world_line(Vec3::new(0.0, first_y * spacing, 0.0), Vec3::new(0.0, last_y * spacing, 0.0), AXIS_COLORS[1]);
+            self.draw_world_line(Vec3::new(0.0, 0.0, -half_length), Vec3::new(0.0, 0.0, half_length), AXIS_COLORS[2]);
+        }
+    }
+
+    /// Draws a line between two world points, hidden where something is in front of it
+    pub fn draw_world_line(&self, from: Vec3, to: Vec3, color: Vec3) {
+        if let Some((a, b)) = self.project_world_line(from, to) {
+            self.pixel_buf.draw_line_depth_tested(a, b, color, &self.zbuf);
+        }
+    }
+
+    /// Draws a line between two world points over everything
+    pub fn draw_world_line_on_top(&self, from: Vec3, to: Vec3, color: Vec3) {
+        if let Some((a, b)) = self.project_world_line(from, to) {
+            self.pixel_buf.draw_line((a.x, a.y), (b.x, b.y), color, 1);
+        }
+    }
+
+    /// The line in screen space (with depth in z), cut off where it goes behind the camera.
+    /// None if it's all behind the camera.
+    fn project_world_line(&self, from: Vec3, to: Vec3) -> Option<(Vec3, Vec3)> {
+        let (mut a, mut b) = (from, to);
+        self.camera.vertex_world_to_camera_space(&mut a);
+        self.camera.vertex_world_to_camera_space(&mut b);
+        if a.x < NEAR_PLANE && b.x < NEAR_PLANE {
+            return None;
+        }
+        if a.x < NEAR_PLANE {
+            a = a + (b - a) * ((NEAR_PLANE - a.x) / (b.x - a.x));
+        } else if b.x < NEAR_PLANE {
+            b = b + (a - b) * ((NEAR_PLANE - b.x) / (a.x - b.x));
+        }
+        self.camera.vertex_camera_to_screen_space(&mut a);
+        self.camera.vertex_camera_to_screen_space(&mut b);
+        return Some((a, b));
+    }
+}
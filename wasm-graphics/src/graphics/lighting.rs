@@ -80,8 +80,17 @@ impl Light {
     }
 
     pub fn add_scene_object_to_shadow_map(&mut self, scene_obj: &SceneObject) {
-        let mesh = &scene_obj.mesh;
-        self.add_mesh_to_shadow_map(mesh);
+        // instances and material overrides are applied here, the shared mesh is left as is
+        let vertices = scene_obj.get_world_vertices();
+        let indices = &scene_obj.mesh.indices;
+        let properties = scene_obj.get_properties();
+        for i in 0..scene_obj.mesh.colors.len() {
+            let v1 = vertices[indices[i*3]];
+            let v2 = vertices[indices[i*3+1]];
+            let v3 = vertices[indices[i*3+2]];
+            let color = scene_obj.get_triangle_color(i);
+            self.add_triangle_to_shadow_map(v1, v2, v3, color, properties);
+        }
     }
     pub fn add_scene_objects_to_shadow_map(&mut self, scene_objs: &Vec<SceneObject>) {
         for scene_obj in scene_objs {
@@ -99,7 +108,7 @@ impl Light {
             let v2 = vertices[indices[i*3+1]];
             let v3 = vertices[indices[i*3+2]];
             let color = colors[i];
-            self.add_triangle_to_shadow_map(v1, v2, v3, color, mesh);
+            self.add_triangle_to_shadow_map(v1, v2, v3, color, &mesh.properties);
         }
     }
 
@@ -109,13 +118,13 @@ impl Light {
         }
     }
 
-    fn add_triangle_to_shadow_map(&mut self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, color: Vec3, mesh: &Mesh) {
+    fn add_triangle_to_shadow_map(&mut self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, color: Vec3, properties: &PhongProperties) {
 
         // do not render if normal is pointing toward light - FRONT FACE CULLING
         let normal = (v3 - v1).cross(v2 - v1).normalized();
         let cam_to_triangle = v1 - self.camera.pos;
 
-        if mesh.properties.cull_faces && normal.dot(cam_to_triangle) < 0.0 {
+        if properties.cull_faces && normal.dot(cam_to_triangle) < 0.0 {
             return;
         }
 
@@ -138,7 +147,7 @@ impl Light {
         const NEAR_PLANE: f32 = 0.001;
         if v1.x > 0.0 { // all vertices in view
             self.camera.vertices_camera_to_screen_space(&mut v1, &mut v2, &mut v3);
-            self.fill_triangle(v1, v2, v3, color, mesh);
+            self.fill_triangle(v1, v2, v3, color, properties);
         } else if v2.x > 0.0 { // 2 vertices in view
             let q = (NEAR_PLANE - v2.x) / (v1.x - v2.x);
             let mut v1_new_1 = v2 + (v1 - v2) * q;
@@ -147,8 +156,8 @@ impl Light {
 
             self.camera.vertices_camera_to_screen_space(&mut v1_new_1, &mut v2, &mut v3);
             self.camera.vertex_camera_to_screen_space(&mut v1_new_2);
-            self.fill_triangle(v1_new_1, v2, v3, color, mesh);
-            self.fill_triangle(v1_new_1, v1_new_2, v3, color, mesh);
+            self.fill_triangle(v1_new_1, v2, v3, color, properties);
+            self.fill_triangle(v1_new_1, v1_new_2, v3, color, properties);
         } else if v3.x > 0.0 { // 1 vertex in view
             let q = (NEAR_PLANE - v2.x) / (v3.x - v2.x);
             let mut v2_new = v2 + (v3 - v2) * q;
@@ -156,16 +165,15 @@ impl Light {
             let mut v1_new = v1 + (v3 - v1) * q;
 
             self.camera.vertices_camera_to_screen_space(&mut v1_new, &mut v2_new, &mut v3);
-            self.fill_triangle(v1_new, v2_new, v3, color, mesh);
+            self.fill_triangle(v1_new, v2_new, v3, color, properties);
         } else { // no vertices in view
             return;
         }
     }
 
-    fn fill_triangle(&mut self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, color: Vec3, mesh: &Mesh) {
+    fn fill_triangle(&mut self, mut v1: Vec3, mut v2: Vec3, mut v3: Vec3, color: Vec3, properties: &PhongProperties) {
         // depth calculations from https://www.scratchapixel.com/lessons/3d-basic-rendering/rasterization-practical-implementation/visibility-problem-depth-buffer-depth-interpolation.html#:~:text=As%20previously%20mentioned%2C%20the%20correct,z%20%3D%201%20V%200.
 
-        let properties = &mesh.properties;
 
         // sort vertices by y (v1 has lowest y, v3 has highest y)
         if v1.y > v2.y {
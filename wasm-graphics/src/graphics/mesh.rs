@@ -1,9 +1,12 @@
 use gltf::json::extensions::material;
 
-use crate::{console_log, utils::math::Vec3, graphics::ray_tracing::rt::Ray};
-use std::{collections::HashMap, fmt::Debug, io::Cursor, sync::atomic::{AtomicUsize, Ordering}, vec};
+use crate::{console_log, utils::math::{degrees_to_radians, Vec3}};
+use std::{collections::HashMap, fmt::Debug, io::Cursor, sync::Arc, vec};
 
-use super::ray_tracing::{bvh::AABoundingBox, hittable::{Hittable, Triangle}, material::Material, rt::HitRecord};
+use super::{normal_map::{NormalMap, Tangent, TriangleNormalMap}, ray_tracing::{hittable::{Hittable, Triangle}, material::Material}, texture::{Texture, TriangleTexture}};
+
+/// Faces of an STL mesh meeting at more than this are shaded with a hard edge between them
+pub const STL_CREASE_ANGLE_DEGREES: f32 = 60.0;
 
 #[derive(Debug, Clone, Copy)]
 pub struct PhongProperties {
@@ -50,6 +53,25 @@ pub struct Mesh {
     pub indices: Vec<usize>,
     pub colors: Vec<Vec3>,
     pub normals: Vec<Vec3>,
+    /// For smooth shading, one per index rather than per vertex so that faces meeting at a
+    /// crease keep their own. Empty for flat shading.
+    pub vertex_normals: Vec<Vec3>,
+
+    /// Per vertex, empty for meshes without textures
+    pub uvs: Vec<[f32; 2]>,
+    pub textures: Vec<Arc<Texture>>,
+    /// Per triangle index into textures, empty for meshes without textures. The triangle's
+    /// color is still kept, for wherever textures aren't used.
+    pub triangle_textures: Vec<Option<usize>>,
+    /// Multiplies texture samples, like colors can be multiplied
+    pub texture_tint: Vec3,
+
+    /// Normal and bump maps, which use the same UVs as textures
+    pub normal_maps: Vec<Arc<NormalMap>>,
+    /// Per triangle index into normal_maps, empty for meshes without any
+    pub triangle_normal_maps: Vec<Option<usize>>,
+    /// Per index like vertex_normals, empty for meshes without normal maps
+    pub tangents: Vec<Tangent>,
 
     pub properties: PhongProperties,
 
@@ -91,6 +113,14 @@ impl Mesh {
             indices,
             colors,
             normals, 
+            vertex_normals: Vec::new(),
+            uvs: Vec::new(),
+            textures: Vec::new(),
+            triangle_textures: Vec::new(),
+            texture_tint: Vec3::new(1.0, 1.0, 1.0),
+            normal_maps: Vec::new(),
+            triangle_normal_maps: Vec::new(),
+            tangents: Vec::new(),
             properties,
             center: center,
             radius,
@@ -111,7 +141,10 @@ impl Mesh {
     }
 
     pub fn new_from_stl_bytes(stl_bytes: &[u8], color: Vec3, properties: PhongProperties) -> Mesh {
-        let indexed_mesh = stl_io::read_stl(&mut Cursor::new(stl_bytes)).expect(format!("Failed to read STL from bytes: {:?}", stl_bytes).as_str());
+        return Mesh::try_new_from_stl_bytes(stl_bytes, color, properties).unwrap();
+    }
+    pub fn try_new_from_stl_bytes(stl_bytes: &[u8], color: Vec3, properties: PhongProperties) -> Result<Mesh, String> {
+        let indexed_mesh = stl_io::read_stl(&mut Cursor::new(stl_bytes)).map_err(|e| format!("Failed to read STL from bytes: {}", e))?;
         let vertices = indexed_mesh.vertices.iter()
             .map(|v| Vec3::new(v[0], v[2], v[1]))
             .collect::<Vec<Vec3>>();
@@ -119,9 +152,11 @@ impl Mesh {
             .flat_map(|f| vec![f.vertices[0], f.vertices[1], f.vertices[2]])
             .collect::<Vec<usize>>();
 
-        let mesh = Mesh::new_with_color(vertices, indices, color, properties);
+        let mut mesh = Mesh::new_with_color(vertices, indices, color, properties);
+        // STL has no vertex normals of its own, its facet normals are just the flat ones
+        mesh.compute_vertex_normals(degrees_to_radians(STL_CREASE_ANGLE_DEGREES));
         console_log!("Mesh created from STL with {} vertices and {} faces", mesh.vertices.len(), mesh.colors.len());
-        return mesh;
+        return Ok(mesh);
     }
 
     
@@ -262,6 +297,113 @@ impl Mesh {
         return mesh;
     }
 
+    /// The triangle's texture and its vertices' UVs, if it has one
+    #[inline(always)]
+    pub fn get_triangle_texture(&self, triangle_index: usize) -> Option<(&Arc<Texture>, [[f32; 2]; 3])> {
+        let texture_index = (*self.triangle_textures.get(triangle_index)?)?;
+        let i = triangle_index * 3;
+        let uvs = [self.uvs[self.indices[i]], self.uvs[self.indices[i+1]], self.uvs[self.indices[i+2]]];
+        return Some((&self.textures[texture_index], uvs));
+    }
+    pub fn has_textures(&self) -> bool {
+        return !self.textures.is_empty();
+    }
+    /// The triangle's normal map and its vertices' UVs, if it has one
+    #[inline(always)]
+    pub fn get_triangle_normal_map(&self, triangle_index: usize) -> Option<(&Arc<NormalMap>, [[f32; 2]; 3])> {
+        let normal_map_index = (*self.triangle_normal_maps.get(triangle_index)?)?;
+        let i = triangle_index * 3;
+        let uvs = [self.uvs[self.indices[i]], self.uvs[self.indices[i+1]], self.uvs[self.indices[i+2]]];
+        return Some((&self.normal_maps[normal_map_index], uvs));
+    }
+    pub fn has_normal_maps(&self) -> bool {
+        return !self.normal_maps.is_empty();
+    }
+    /// Puts the map on every triangle, over any it had. Needs UVs.
+    pub fn set_normal_map(&mut self, normal_map: Arc<NormalMap>) -> Result<(), String> {
+        if self.uvs.len() != self.vertices.len() {
+            return Err(String::from("normal and bump maps need a mesh with texture coordinates"));
+        }
+        self.normal_maps = vec![normal_map];
+        self.triangle_normal_maps = vec![Some(0); self.indices.len() / 3];
+        if self.tangents.len() != self.indices.len() {
+            self.compute_tangents();
+        }
+        return Ok(());
+    }
+    /// The triangle's normals at each of its vertices, None if the mesh is flat shaded
+    #[inline(always)]
+    pub fn get_triangle_vertex_normals(&self, triangle_index: usize) -> Option<[Vec3; 3]> {
+        if self.vertex_normals.is_empty() {
+            return None;
+        }
+        let i = triangle_index * 3;
+        return Some([self.vertex_normals[i], self.vertex_normals[i+1], self.vertex_normals[i+2]]);
+    }
+
+    /// Smooth normals at every corner: the area weighted average of the faces around the
+    /// vertex, leaving out those meeting this corner's face at more than crease_angle
+    /// (radians) so that hard edges stay sharp
+    pub fn compute_vertex_normals(&mut self, crease_angle: f32) {
+        let cos_crease = crease_angle.cos();
+        // not normalized, so bigger faces count for more
+        let area_normals: Vec<Vec3> = self.indices.chunks(3).map(|t| {
+            let (v1, v2, v3) = (self.vertices[t[0]], self.vertices[t[1]], self.vertices[t[2]]);
+            return (v3 - v1).cross(v2 - v1);
+        }).collect();
+        let mut faces_at_vertex = vec![Vec::new(); self.vertices.len()];
+        for (i, &vertex) in self.indices.iter().enumerate() {
+            faces_at_vertex[vertex].push(i / 3);
+        }
+        self.vertex_normals = self.indices.iter().enumerate().map(|(i, &vertex)| {
+            let face = i / 3;
+            let mut sum = Vec3::zero();
+            for &other in faces_at_vertex[vertex].iter() {
+                if self.normals[other].dot(self.normals[face]) >= cos_crease {
+                    sum += area_normals[other];
+                }
+            }
+            return if sum.len_squared() > 0.0 { sum.normalized() } else { self.normals[face] };
+        }).collect();
+    }
+
+    /// Tangents at every corner from how the UVs run across the faces around it, averaged
+    /// like the vertex normals are and made perpendicular to the corner's normal
+    /// (MikkTSpace does the same, give or take how it weighs the faces)
+    pub fn compute_tangents(&mut self) {
+        // along U and against V, not normalized so bigger faces count for more
+        let mut u_dirs = vec![Vec3::zero(); self.vertices.len()];
+        let mut v_dirs = vec![Vec3::zero(); self.vertices.len()];
+        for t in self.indices.chunks(3) {
+            let (p1, p2, p3) = (self.vertices[t[0]], self.vertices[t[1]], self.vertices[t[2]]);
+            let (uv1, uv2, uv3) = (self.uvs[t[0]], self.uvs[t[1]], self.uvs[t[2]]);
+            let (edge1, edge2) = (p2 - p1, p3 - p1);
+            let (du1, dv1) = (uv2[0] - uv1[0], uv2[1] - uv1[1]);
+            let (du2, dv2) = (uv3[0] - uv1[0], uv3[1] - uv1[1]);
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < 1e-12 {
+                continue; // UVs squashed to a line or a point
+            }
+            let area = edge1.cross(edge2).len();
+            let along_u = (edge1 * dv2 - edge2 * dv1) / det;
+            let along_v = (edge2 * du1 - edge1 * du2) / det;
+            for &vertex in t {
+                u_dirs[vertex] += along_u.normalized() * area;
+                v_dirs[vertex] -= along_v.normalized() * area;
+            }
+        }
+        self.tangents = self.indices.iter().enumerate().map(|(i, &vertex)| {
+            let normal = self.vertex_normals.get(i).copied().unwrap_or(self.normals[i / 3]);
+            let dir = u_dirs[vertex] - normal * normal.dot(u_dirs[vertex]);
+            if dir.len_squared() < 1e-12 {
+                return Tangent::default();
+            }
+            let dir = dir.normalized();
+            let sign = if normal.cross(dir).dot(v_dirs[vertex]) < 0.0 { -1.0 } else { 1.0 };
+            return Tangent { dir, sign };
+        }).collect();
+    }
+
     pub fn to_rt_triangles(&self, material: &dyn Material) -> Vec<Triangle> {
         let mut triangles = Vec::with_capacity(self.indices.len() / 3);
         for i in (0..self.indices.len()).step_by(3) {
@@ -270,7 +412,18 @@ impl Mesh {
             let v3 = self.vertices[self.indices[i+2]];
             let color = self.colors[i / 3];
 
-            let triangle = Triangle::new_from_vertices(v1, v2, v3, color, material);
+            let mut triangle = Triangle::new_from_vertices(v1, v2, v3, color, material);
+            triangle.texture = self.get_triangle_texture(i / 3).map(|(texture, uvs)| Box::new(TriangleTexture {
+                texture: texture.clone(),
+                uvs,
+                tint: self.texture_tint,
+            }));
+            triangle.vertex_normals = self.get_triangle_vertex_normals(i / 3).map(Box::new);
+            triangle.normal_map = self.get_triangle_normal_map(i / 3).map(|(normal_map, uvs)| Box::new(TriangleNormalMap {
+                normal_map: normal_map.clone(),
+                uvs,
+                tangents: [self.tangents[i], self.tangents[i+1], self.tangents[i+2]],
+            }));
             triangles.push(triangle);
         }
         // console_log!("{:?}", self.colors.len());
@@ -310,7 +463,8 @@ impl Mesh {
             v.rotate_y_fast(sin_y, cos_y);
             *v += center_of_rotation;
         }
-        for n in self.normals.iter_mut() {
+        let tangent_dirs = self.tangents.iter_mut().map(|t| &mut t.dir);
+        for n in self.normals.iter_mut().chain(self.vertex_normals.iter_mut()).chain(tangent_dirs) {
             n.rotate_z_fast(sin_z, cos_z);
             n.rotate_y_fast(sin_y, cos_y);
         }
@@ -336,10 +490,20 @@ impl Mesh {
         self.scale_around(self.center, scale_factor);
     }
 
+    /// Also drops the mesh's textures, which would cover the color up. Normal maps stay.
     pub fn set_color(&mut self, color: Vec3) {
         for c in self.colors.iter_mut() {
             *c = color;
         }
+        self.textures.clear();
+        self.triangle_textures.clear();
+    }
+    /// Scales the colors and whatever the textures give
+    pub fn multiply_colors(&mut self, multiplier: f32) {
+        for c in self.colors.iter_mut() {
+            *c *= multiplier;
+        }
+        self.texture_tint *= multiplier;
     }
 }
 
@@ -5,6 +5,21 @@ pub mod scene_object;
 pub mod buffers;
 pub mod lighting;
 pub mod gltf_parser;
+pub mod gltf_exporter;
+pub mod ui_bridge;
+pub mod scene_file;
+pub mod scene_snapshot;
+pub mod scene_graph;
+pub mod object_id;
+pub mod edit_history;
+pub mod clipboard;
+pub mod gizmo;
+pub mod grid;
+pub mod debug_overlay;
+pub mod frame_stats;
+pub mod texture;
+pub mod shading;
+pub mod normal_map;
 
 pub mod ray_tracing;
 // pub mod rt;
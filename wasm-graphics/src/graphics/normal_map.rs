@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use crate::utils::math::Vec3;
+
+use super::texture::{interpolate_uv, Texture, TextureFilter};
+
+// Normal maps and bump (height) maps, which bend the shading normal to add detail the
+// geometry doesn't have. Both give a normal in tangent space: x along the tangent (where
+// U increases), y along the bitangent (where V decreases, V runs down the image like glTF's)
+// and z out of the surface. glTF files can bring their own tangents, otherwise
+// Mesh::compute_tangents() works them out from the UVs.
+//
+// The rasterizer bends its normals per pixel in TriangleShading::at(), before they're lit,
+// and the path tracer per hit in HitRecord::apply_texture().
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalMapKind {
+    /// RGB is the tangent space normal, with x and y multiplied by scale (glTF's normalTexture.scale)
+    Normal { scale: f32 },
+    /// Gray levels are heights, strength is how steep going from black to white over one texel is
+    Bump { strength: f32 },
+}
+
+#[derive(Debug)]
+pub struct NormalMap {
+    pub texture: Arc<Texture>,
+    pub kind: NormalMapKind,
+}
+
+impl NormalMap {
+    /// Normal in tangent space at uv, not normalized
+    pub fn sample(&self, uv: [f32; 2], filter: TextureFilter) -> Vec3 {
+        return match self.kind {
+            NormalMapKind::Normal { scale } => {
+                let c = self.texture.sample(uv, filter);
+                Vec3::new((c.x * 2.0 - 1.0) * scale, (c.y * 2.0 - 1.0) * scale, c.z * 2.0 - 1.0)
+            },
+            NormalMapKind::Bump { strength } => {
+                // slopes from the texels on either side, the map is gray so any channel will do
+                let du = 1.0 / self.texture.width as f32;
+                let dv = 1.0 / self.texture.height as f32;
+                let height = |u: f32, v: f32| self.texture.sample([u, v], filter).x;
+                let slope_x = (height(uv[0] + du, uv[1]) - height(uv[0] - du, uv[1])) * 0.5;
+                let slope_y = (height(uv[0], uv[1] - dv) - height(uv[0], uv[1] + dv)) * 0.5;
+                Vec3::new(-slope_x * strength, -slope_y * strength, 1.0)
+            },
+        };
+    }
+}
+
+/// Per corner, like Mesh::vertex_normals
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tangent {
+    pub dir: Vec3,
+    /// 1 or -1, the bitangent is normal.cross(dir) * sign (glTF's TANGENT.w)
+    pub sign: f32,
+}
+
+impl Tangent {
+    /// The same frame seen from the back of the surface, for when the normal gets flipped
+    pub fn flipped(self) -> Tangent {
+        return Tangent { dir: -self.dir, sign: -self.sign };
+    }
+
+    #[inline(always)]
+    pub fn interpolate(tangents: &[Tangent; 3], barycentric: [f32; 3]) -> Tangent {
+        let dir = tangents[0].dir * barycentric[0] + tangents[1].dir * barycentric[1] + tangents[2].dir * barycentric[2];
+        // the sign only flips where UVs are mirrored, the corners of one triangle rarely disagree
+        return Tangent { dir, sign: tangents[0].sign };
+    }
+
+    /// Bends normal, which has to be normalized, towards a normal in this tangent space
+    #[inline(always)]
+    pub fn perturb(&self, normal: Vec3, tangent_space_normal: Vec3) -> Vec3 {
+        // interpolated tangents aren't perpendicular to the normal anymore
+        let tangent = self.dir - normal * normal.dot(self.dir);
+        if tangent.len_squared() < 1e-12 {
+            return normal;
+        }
+        let tangent = tangent.normalized();
+        let bitangent = normal.cross(tangent) * self.sign;
+        let perturbed = tangent * tangent_space_normal.x + bitangent * tangent_space_normal.y + normal * tangent_space_normal.z;
+        if perturbed.len_squared() < 1e-12 {
+            return normal;
+        }
+        return perturbed.normalized();
+    }
+}
+
+/// A path tracer triangle's normal map, with its own copy of its vertices' UVs and tangents
+#[derive(Debug, Clone)]
+pub struct TriangleNormalMap {
+    pub normal_map: Arc<NormalMap>,
+    /// In the order of the vertices given to Triangle::new_from_vertices()
+    pub uvs: [[f32; 2]; 3],
+    pub tangents: [Tangent; 3],
+}
+
+impl TriangleNormalMap {
+    #[inline(always)]
+    pub fn sample(&self, barycentric: [f32; 3], filter: TextureFilter) -> Vec3 {
+        return self.normal_map.sample(interpolate_uv(&self.uvs, barycentric), filter);
+    }
+}
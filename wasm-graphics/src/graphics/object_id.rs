@@ -0,0 +1,129 @@
+use std::fmt::Display;
+
+use super::scene_object::SceneObject;
+
+/// Stable handle to one of Game's scene objects. Unlike an index into Game::scene_objects it
+/// survives other objects being removed or reordered, and once its object is deleted it stays
+/// invalid: its slot moves on to a new generation before it's handed out again.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId {
+    slot: u32,
+    generation: u32,
+}
+
+impl ObjectId {
+    /// The id as a single number, for JS and other tooling outside the crate
+    pub fn to_bits(self) -> u64 {
+        return ((self.generation as u64) << 32) | self.slot as u64;
+    }
+    pub fn from_bits(bits: u64) -> ObjectId {
+        return ObjectId { slot: bits as u32, generation: (bits >> 32) as u32 };
+    }
+}
+
+impl Display for ObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.slot, self.generation)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IdSlot {
+    generation: u32,
+    /// Where the object with this slot's current id is in Game::scene_objects, None if the slot is free
+    object_index: Option<usize>,
+}
+
+/// Hands out ObjectIds and keeps track of where each one's object currently is
+#[derive(Debug, Clone, Default)]
+pub struct ObjectIds {
+    slots: Vec<IdSlot>,
+    free_slots: Vec<u32>,
+}
+
+impl ObjectIds {
+    pub fn new() -> ObjectIds {
+        return ObjectIds { slots: Vec::new(), free_slots: Vec::new() };
+    }
+
+    /// A new id for the object at object_index
+    pub fn allocate(&mut self, object_index: usize) -> ObjectId {
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(IdSlot { generation: 0, object_index: None });
+                (self.slots.len() - 1) as u32
+            },
+        };
+        self.slots[slot as usize].object_index = Some(object_index);
+        return ObjectId { slot, generation: self.slots[slot as usize].generation };
+    }
+
+    /// The id stops being valid for good, nothing else is affected
+    pub fn free(&mut self, id: ObjectId) {
+        // a freed slot has moved on to the next generation already
+        let Some(slot) = self.slots.get_mut(id.slot as usize).filter(|slot| slot.generation == id.generation) else {
+            return;
+        };
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.object_index = None;
+        self.free_slots.push(id.slot);
+    }
+
+    /// The id stops resolving, but its slot isn't handed out again, so attach() can bring it
+    /// back. For objects that are only out of the scene for now, like ones an undo can restore.
+    pub fn detach(&mut self, id: ObjectId) {
+        if let Some(slot) = self.slots.get_mut(id.slot as usize).filter(|slot| slot.generation == id.generation) {
+            slot.object_index = None;
+        }
+    }
+
+    /// Makes a detached id resolve to object_index again, false if the id was freed since
+    pub fn attach(&mut self, id: ObjectId, object_index: usize) -> bool {
+        let Some(slot) = self.slots.get_mut(id.slot as usize).filter(|slot| slot.generation == id.generation) else {
+            return false;
+        };
+        slot.object_index = Some(object_index);
+        return true;
+    }
+
+    /// Frees every id, detached ones included
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.generation = slot.generation.wrapping_add(1);
+            slot.object_index = None;
+        }
+        self.free_slots = (0..self.slots.len() as u32).rev().collect();
+    }
+
+    pub fn get_index(&self, id: ObjectId) -> Option<usize> {
+        let slot = self.slots.get(id.slot as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        return slot.object_index;
+    }
+
+    pub fn contains(&self, id: ObjectId) -> bool {
+        return self.get_index(id).is_some();
+    }
+
+    /// Points each object's id at where the object is now, after objects were removed or reordered
+    pub fn reindex(&mut self, scene_objects: &[SceneObject]) {
+        for (i, scene_obj) in scene_objects.iter().enumerate() {
+            if let Some(id) = scene_obj.id {
+                if let Some(slot) = self.slots.get_mut(id.slot as usize).filter(|slot| slot.generation == id.generation) {
+                    slot.object_index = Some(i);
+                }
+            }
+        }
+    }
+
+    /// Frees every id and gives each object a new one, for when the objects were replaced wholesale
+    pub fn assign_all(&mut self, scene_objects: &mut [SceneObject]) {
+        self.clear();
+        for (i, scene_obj) in scene_objects.iter_mut().enumerate() {
+            scene_obj.id = Some(self.allocate(i));
+        }
+    }
+}
@@ -1,10 +1,8 @@
-use std::{cell::RefCell, usize::MAX};
+use std::cell::RefCell;
 
-use web_sys::console;
+use crate::{console_error, console_log, utils::{math::Vec3, utils::get_time}};
 
-use crate::{console_log, graphics::mesh::Mesh, utils::{math::Vec3, utils::random_int}};
-
-use super::{hittable::{self, Hittable}, material::Material, rt::{HitRecord, Ray}};
+use super::{hittable::Hittable, rt::{HitRecord, Ray}};
 
 /// Axis-Aligned Bounding Box (AABB)
 /// A bounding box defined by two points: the minimum and maximum corners.
@@ -63,6 +61,21 @@ impl AABoundingBox {
     pub fn get_center(&self) -> Vec3 {
         return 0.5 * (self.min + self.max);
     }
+    pub fn get_surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        if size.x < 0.0 || size.y < 0.0 || size.z < 0.0 {
+            return 0.0; // empty box
+        }
+        return 2.0 * (size.x * size.y + size.y * size.z + size.z * size.x);
+    }
+    pub fn expand_to_include(&mut self, other: &AABoundingBox) {
+        self.min = self.min.min_elementwise(other.min);
+        self.max = self.max.max_elementwise(other.max);
+    }
+    pub fn expand_to_include_point(&mut self, p: Vec3) {
+        self.min = self.min.min_elementwise(p);
+        self.max = self.max.max_elementwise(p);
+    }
 
     pub fn get_longest_axis(&self) -> i32 {
         if self.max.x - self.min.x > self.max.y - self.min.y {
@@ -97,7 +110,7 @@ impl AABoundingBox {
     }
 
     #[inline(always)]
-    fn hit(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> bool {
+    pub(super) fn hit(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> bool {
 
         // ray.origin + t * ray.direction = pos
         // t = (pos - ray.origin) / ray.direction
@@ -226,71 +239,214 @@ thread_local! {
     static TRAVERSAL_STACK: RefCell<Vec<usize>> = RefCell::new(Vec::with_capacity(1024));
 }
 
-#[derive(Debug)]
-pub struct FlattenedBVH {
-    nodes: Vec<FlattenedBVHNode>,
-    hittables: Vec<Box<dyn Hittable>>,
+// relative costs used by the surface area heuristic, only their ratio matters
+const SAH_TRAVERSAL_COST: f32 = 1.0;
+const SAH_INTERSECTION_COST: f32 = 1.0;
+const SAH_MAX_BINS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BVHSplitMethod {
+    /// Splits at the median centroid along the longest axis
+    Median,
+    /// Surface area heuristic, evaluated at `bins` evenly spaced candidate planes per axis (at most 64)
+    BinnedSAH { bins: usize },
 }
 
-impl FlattenedBVH {
-    pub fn new(mut hittables: Vec<Box<dyn Hittable>>) -> Self {
-        let mut flattened_bvh = FlattenedBVH {
-            nodes: Vec::with_capacity(2 * hittables.len()),
-            hittables: Vec::with_capacity(hittables.len()),
-        };
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BVHBuildOptions {
+    pub split_method: BVHSplitMethod,
+    /// Nodes with this many hittables or fewer may become leaves
+    pub max_leaf_size: usize,
+}
 
-        flattened_bvh.build(&mut hittables, 0);
-        flattened_bvh.hittables = hittables;
-        console_log!("flattened bvh built with {} nodes, {} hittables", flattened_bvh.nodes.len(), flattened_bvh.hittables.len());
-        return flattened_bvh;
+impl Default for BVHBuildOptions {
+    fn default() -> Self {
+        return BVHBuildOptions {
+            split_method: BVHSplitMethod::BinnedSAH { bins: 16 },
+            max_leaf_size: 4,
+        };
     }
+}
 
-    fn build(&mut self, hittables: &mut [Box<dyn Hittable>], base_index: usize) -> usize {
+#[derive(Debug, Clone, Default)]
+pub struct BVHBuildStats {
+    pub build_time_ms: f64,
+    pub hittable_count: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub max_leaf_size: usize,
+    /// Expected cost of a random ray under the surface area heuristic, lower is better.
+    /// Comparable between trees over the same hittables.
+    pub sah_cost: f32,
+}
 
-        // empty node
-        if hittables.is_empty() {
-            return MAX;
+impl BVHBuildStats {
+    pub fn avg_leaf_size(&self) -> f32 {
+        if self.leaf_count == 0 {
+            return 0.0;
         }
+        return self.hittable_count as f32 / self.leaf_count as f32;
+    }
+}
 
-        // leaf node
-        if hittables.len() == 1 {
-            let mut bounding_box = hittables[0].get_bounding_box().clone();
-            bounding_box.pad_to_minimums();
-            // self.hittables.push(hittables[0].clone_box());
-            // let idx = self.hittables.len() - 1;
-            let node = FlattenedBVHNode::Leaf { 
-                bounding_box, 
-                hittable_index: base_index
+impl std::fmt::Display for BVHBuildStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} hittables, {} nodes, {} leaves (avg {:.2}, max {} hittables), depth {}, SAH cost {:.2}, built in {:.1}ms",
+            self.hittable_count,
+            self.node_count,
+            self.leaf_count,
+            self.avg_leaf_size(),
+            self.max_leaf_size,
+            self.max_depth,
+            self.sah_cost,
+            self.build_time_ms,
+        )
+    }
+}
+
+/// What tracing one ray cost, counted by the hit_with_stats variants of the traversals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraversalStats {
+    /// Nodes taken off the traversal stack, in the top level and the objects' trees
+    pub nodes_visited: usize,
+    /// Calls to a primitive's own hit test
+    pub hittables_tested: usize,
+}
+
+impl std::ops::AddAssign for TraversalStats {
+    fn add_assign(&mut self, other: TraversalStats) {
+        self.nodes_visited += other.nodes_visited;
+        self.hittables_tested += other.hittables_tested;
+    }
+}
+
+/// Per-primitive data cached during the build so the trait objects aren't queried over and over
+#[derive(Clone)]
+struct BuildPrimitive {
+    bounding_box: AABoundingBox,
+    centroid: Vec3,
+    index: usize,
+}
+
+/// Node layout shared by the per-object BVHs (over hittables) and the top level BVH (over objects).
+/// Nodes are stored children first, so the root is the last node.
+/// Leaves refer to contiguous ranges of `order`, which holds the index each primitive had in the build input.
+#[derive(Debug, Clone, Default)]
+pub(super) struct BVHTree {
+    pub(super) nodes: Vec<FlattenedBVHNode>,
+    pub(super) order: Vec<usize>,
+    pub(super) stats: BVHBuildStats,
+}
+
+impl BVHTree {
+    pub(super) fn build(bounding_boxes: &[AABoundingBox], options: &BVHBuildOptions) -> BVHTree {
+        let start_time = get_time();
+
+        let mut prims: Vec<BuildPrimitive> = bounding_boxes
+            .iter()
+            .enumerate()
+            .map(|(i, bounding_box)| {
+                BuildPrimitive { centroid: bounding_box.get_center(), bounding_box: bounding_box.clone(), index: i }
+            })
+            .collect();
+
+        let mut tree = BVHTree {
+            nodes: Vec::with_capacity(2 * prims.len()),
+            order: Vec::new(),
+            stats: BVHBuildStats::default(),
+        };
+        let options = BVHBuildOptions { max_leaf_size: options.max_leaf_size.max(1), ..*options };
+        tree.build_node(&mut prims, 0, &options, 1);
+        tree.order = prims.iter().map(|p| p.index).collect();
+
+        tree.stats.build_time_ms = get_time() - start_time;
+        tree.stats.hittable_count = prims.len();
+        tree.stats.node_count = tree.nodes.len();
+        tree.stats.sah_cost = tree.calculate_sah_cost();
+        return tree;
+    }
+
+    pub(super) fn get_bounding_box(&self) -> Option<&AABoundingBox> {
+        return self.nodes.last().map(|root| root.get_bounding_box());
+    }
+
+    /// Recomputes every node's box bottom up for primitives that moved, keeping the tree's structure.
+    /// bounding_boxes are in the order of the build input.
+    pub(super) fn refit(&mut self, bounding_boxes: &[AABoundingBox]) {
+        // children always come before their parent, so one pass in order is enough
+        for i in 0..self.nodes.len() {
+            let new_box = match &self.nodes[i] {
+                FlattenedBVHNode::Leaf { first_hittable_index, hittable_count, .. } => {
+                    let mut leaf_box = AABoundingBox::empty();
+                    for slot in *first_hittable_index..*first_hittable_index + *hittable_count {
+                        leaf_box.expand_to_include(&bounding_boxes[self.order[slot]]);
+                    }
+                    leaf_box.pad_to_minimums();
+                    leaf_box
+                },
+                FlattenedBVHNode::Internal { left_index, right_index, .. } => {
+                    let mut internal_box = AABoundingBox::empty();
+                    for child_index in [*left_index, *right_index] {
+                        if child_index < i {
+                            internal_box.expand_to_include(self.nodes[child_index].get_bounding_box());
+                        }
+                    }
+                    internal_box
+                },
             };
-            self.nodes.push(node);
-            return self.nodes.len() - 1;
+            match &mut self.nodes[i] {
+                FlattenedBVHNode::Leaf { bounding_box, .. } => *bounding_box = new_box,
+                FlattenedBVHNode::Internal { bounding_box, .. } => *bounding_box = new_box,
+            }
         }
+        self.stats.sah_cost = self.calculate_sah_cost();
+    }
+
+    /// Builds the subtree over prims (which start at first_index in the final order),
+    /// returns the index of its root node
+    fn build_node(&mut self, prims: &mut [BuildPrimitive], first_index: usize, options: &BVHBuildOptions, depth: usize) -> usize {
+
+        // empty node
+        if prims.is_empty() {
+            return usize::MAX;
+        }
+        self.stats.max_depth = self.stats.max_depth.max(depth);
 
-        // internal node
         let mut bounding_box = AABoundingBox::empty();
-        for hittable in hittables.iter() {
-            bounding_box = AABoundingBox::from_sub_boxes(&bounding_box, hittable.get_bounding_box());
+        let mut centroid_bounds = AABoundingBox::empty();
+        for prim in prims.iter() {
+            bounding_box.expand_to_include(&prim.bounding_box);
+            centroid_bounds.expand_to_include_point(prim.centroid);
         }
         bounding_box.pad_to_minimums();
 
-        let axis = bounding_box.get_longest_axis();
-        hittables.sort_by(|obj1, obj2| {
-            let c1 = obj1.get_bounding_box().get_center();
-            let c2 = obj2.get_bounding_box().get_center();
-            match axis {
-                0 => (c1.x).partial_cmp(&c2.x).unwrap(),
-                1 => (c1.y).partial_cmp(&c2.y).unwrap(),
-                2 => (c1.z).partial_cmp(&c2.z).unwrap(),
-                _ => unreachable!("invalid axis to sort by"),
-            }
-        });
-        
-        let mid = hittables.len() / 2;
+        // leaf node
+        if prims.len() == 1 {
+            return self.push_leaf(bounding_box, first_index, 1);
+        }
+
+        let mid = match options.split_method {
+            BVHSplitMethod::Median => {
+                if prims.len() <= options.max_leaf_size {
+                    return self.push_leaf(bounding_box, first_index, prims.len());
+                }
+                Self::partition_median(prims, &centroid_bounds)
+            },
+            BVHSplitMethod::BinnedSAH { bins } => {
+                match Self::partition_sah(prims, &bounding_box, &centroid_bounds, bins.clamp(2, SAH_MAX_BINS), options.max_leaf_size) {
+                    Some(mid) => mid,
+                    None => return self.push_leaf(bounding_box, first_index, prims.len()),
+                }
+            },
+        };
 
-        let (left_vec, right_vec) = hittables.split_at_mut(mid);
+        let (left_prims, right_prims) = prims.split_at_mut(mid);
 
-        let left_child_index = self.build(left_vec, base_index);
-        let right_child_index = self.build(right_vec, base_index + mid);
+        let left_child_index = self.build_node(left_prims, first_index, options, depth + 1);
+        let right_child_index = self.build_node(right_prims, first_index + mid, options, depth + 1);
 
         let node = FlattenedBVHNode::Internal { 
             bounding_box, 
@@ -301,26 +457,264 @@ impl FlattenedBVH {
         return self.nodes.len() - 1;
     }
 
+    fn push_leaf(&mut self, bounding_box: AABoundingBox, first_index: usize, count: usize) -> usize {
+        self.stats.leaf_count += 1;
+        self.stats.max_leaf_size = self.stats.max_leaf_size.max(count);
+        self.nodes.push(FlattenedBVHNode::Leaf {
+            bounding_box,
+            first_hittable_index: first_index,
+            hittable_count: count,
+        });
+        return self.nodes.len() - 1;
+    }
+
+    /// Puts the half with smaller centroids along the longest axis first, returns the split point
+    fn partition_median(prims: &mut [BuildPrimitive], centroid_bounds: &AABoundingBox) -> usize {
+        let axis = centroid_bounds.get_longest_axis() as usize;
+        let mid = prims.len() / 2;
+        prims.select_nth_unstable_by(mid, |p1, p2| {
+            p1.centroid[axis].partial_cmp(&p2.centroid[axis]).unwrap()
+        });
+        return mid;
+    }
+
+    /// Finds the cheapest split plane by the surface area heuristic and partitions prims around it.
+    /// Returns None if making a leaf is cheaper (only allowed for max_leaf_size hittables or fewer).
+    fn partition_sah(prims: &mut [BuildPrimitive], bounding_box: &AABoundingBox, centroid_bounds: &AABoundingBox, bins: usize, max_leaf_size: usize) -> Option<usize> {
+        let leaf_cost = prims.len() as f32 * SAH_INTERSECTION_COST;
+        let parent_area = bounding_box.get_surface_area().max(1e-12);
+
+        let mut best_cost = f32::INFINITY;
+        let mut best_axis = 0;
+        let mut best_split = 0; // bins [0, best_split] go left
+
+        let mut bin_counts = vec![0usize; bins];
+        let mut bin_boxes = vec![AABoundingBox::empty(); bins];
+        let mut right_areas = vec![0.0; bins];
+        let mut right_counts = vec![0usize; bins];
+
+        for axis in 0..3 {
+            let axis_min = centroid_bounds.min[axis];
+            let axis_extent = centroid_bounds.max[axis] - axis_min;
+            if axis_extent <= 1e-12 {
+                continue; // all centroids in one plane, can't split along this axis
+            }
+            let bin_scale = bins as f32 / axis_extent;
+
+            bin_counts.fill(0);
+            bin_boxes.fill(AABoundingBox::empty());
+            for prim in prims.iter() {
+                let bin = (((prim.centroid[axis] - axis_min) * bin_scale) as usize).min(bins - 1);
+                bin_counts[bin] += 1;
+                bin_boxes[bin].expand_to_include(&prim.bounding_box);
+            }
+
+            // sweep from the right to get the area and count of everything right of each plane
+            let mut right_box = AABoundingBox::empty();
+            let mut right_count = 0;
+            for i in (1..bins).rev() {
+                right_box.expand_to_include(&bin_boxes[i]);
+                right_count += bin_counts[i];
+                right_areas[i] = right_box.get_surface_area();
+                right_counts[i] = right_count;
+            }
+
+            let mut left_box = AABoundingBox::empty();
+            let mut left_count = 0;
+            for split in 0..bins - 1 {
+                left_box.expand_to_include(&bin_boxes[split]);
+                left_count += bin_counts[split];
+                if left_count == 0 || right_counts[split + 1] == 0 {
+                    continue;
+                }
+                let cost = SAH_TRAVERSAL_COST + SAH_INTERSECTION_COST
+                    * (left_box.get_surface_area() * left_count as f32 + right_areas[split + 1] * right_counts[split + 1] as f32)
+                    / parent_area;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best_axis = axis;
+                    best_split = split;
+                }
+            }
+        }
+
+        if prims.len() <= max_leaf_size && leaf_cost <= best_cost {
+            return None;
+        }
+        if best_cost == f32::INFINITY {
+            // every centroid is in the same spot, no plane separates them
+            if prims.len() <= max_leaf_size {
+                return None;
+            }
+            return Some(prims.len() / 2);
+        }
+
+        let axis_min = centroid_bounds.min[best_axis];
+        let bin_scale = bins as f32 / (centroid_bounds.max[best_axis] - axis_min);
+        let goes_left = |prim: &BuildPrimitive| {
+            (((prim.centroid[best_axis] - axis_min) * bin_scale) as usize).min(bins - 1) <= best_split
+        };
+
+        let mut mid = 0;
+        for i in 0..prims.len() {
+            if goes_left(&prims[i]) {
+                prims.swap(i, mid);
+                mid += 1;
+            }
+        }
+        return Some(mid);
+    }
+
+    /// SAH cost of the whole tree: for every node, the chance a ray through the root also hits the node,
+    /// times the cost of traversing it (internal) or intersecting its hittables (leaf)
+    fn calculate_sah_cost(&self) -> f32 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        let root_area = self.nodes[self.nodes.len() - 1].get_bounding_box().get_surface_area().max(1e-12);
+        return self.nodes
+            .iter()
+            .map(|node| {
+                let area_ratio = node.get_bounding_box().get_surface_area() / root_area;
+                match node {
+                    FlattenedBVHNode::Leaf { hittable_count, .. } => area_ratio * *hittable_count as f32 * SAH_INTERSECTION_COST,
+                    FlattenedBVHNode::Internal { .. } => area_ratio * SAH_TRAVERSAL_COST,
+                }
+            })
+            .sum();
+    }
+
+    /// Every node's box with how far below the root it is (the root being 0), parents first
+    pub(super) fn get_node_boxes(&self) -> Vec<(AABoundingBox, usize)> {
+        let mut node_boxes = Vec::with_capacity(self.nodes.len());
+        if self.nodes.is_empty() {
+            return node_boxes;
+        }
+        let mut stack = vec![(self.nodes.len() - 1, 0)];
+        while let Some((node_index, depth)) = stack.pop() {
+            if node_index >= self.nodes.len() {
+                continue;
+            }
+            let node = &self.nodes[node_index];
+            node_boxes.push((node.get_bounding_box().clone(), depth));
+            if let FlattenedBVHNode::Internal { left_index, right_index, .. } = node {
+                stack.push((*right_index, depth + 1));
+                stack.push((*left_index, depth + 1));
+            }
+        }
+        return node_boxes;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FlattenedBVH {
+    tree: BVHTree,
+    hittables: Vec<Box<dyn Hittable>>,
+}
+
+impl FlattenedBVH {
+    pub fn new(hittables: Vec<Box<dyn Hittable>>) -> Self {
+        return FlattenedBVH::new_with_options(hittables, &BVHBuildOptions::default());
+    }
+
+    pub fn new_with_options(hittables: Vec<Box<dyn Hittable>>, options: &BVHBuildOptions) -> Self {
+        let start_time = get_time();
+
+        let bounding_boxes: Vec<AABoundingBox> = hittables.iter().map(|h| h.get_bounding_box().clone()).collect();
+        let tree = BVHTree::build(&bounding_boxes, options);
+
+        // leaves refer to contiguous ranges, so put the hittables in the order the build left the primitives in
+        let mut hittables: Vec<Option<Box<dyn Hittable>>> = hittables.into_iter().map(Some).collect();
+        let hittables = tree.order
+            .iter()
+            .map(|&i| hittables[i].take().unwrap())
+            .collect();
+
+        let mut flattened_bvh = FlattenedBVH { tree, hittables };
+        flattened_bvh.tree.stats.build_time_ms = get_time() - start_time;
+        console_log!("flattened bvh built ({:?}): {}", options.split_method, flattened_bvh.tree.stats);
+        return flattened_bvh;
+    }
+
+    pub fn get_stats(&self) -> &BVHBuildStats {
+        return &self.tree.stats;
+    }
+
+    pub fn get_hittable_count(&self) -> usize {
+        return self.hittables.len();
+    }
+
+    /// Bounds of everything in the tree, None if it's empty
+    pub fn get_bounding_box(&self) -> Option<&AABoundingBox> {
+        return self.tree.get_bounding_box();
+    }
+
+    /// Every node's box with its depth below the root, for drawing the tree
+    pub fn get_node_boxes(&self) -> Vec<(AABoundingBox, usize)> {
+        return self.tree.get_node_boxes();
+    }
+
+    /// Takes new copies of the hittables the tree was built from (same count, same order as passed to new)
+    /// and refits the node boxes around them instead of rebuilding.
+    /// Much cheaper than a rebuild, but the tree gets worse the more its hittables move relative to each other.
+    pub fn refit(&mut self, hittables: &[Box<dyn Hittable>]) {
+        if hittables.len() != self.hittables.len() {
+            console_error!("FlattenedBVH::refit() called with {} hittables, the tree was built over {}", hittables.len(), self.hittables.len());
+            return;
+        }
+        let bounding_boxes: Vec<AABoundingBox> = hittables.iter().map(|h| h.get_bounding_box().clone()).collect();
+        for (slot, &i) in self.tree.order.iter().enumerate() {
+            self.hittables[slot] = hittables[i].clone_box();
+        }
+        self.tree.refit(&bounding_boxes);
+    }
+
     #[inline(always)]
-    pub fn hit<'a>(&'a self, ray: &Ray, t_min: f32, mut t_max: f32, hit_record: &mut HitRecord<'a>) -> bool {
+    pub fn hit<'a>(&'a self, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord<'a>) -> bool {
+        return self.traverse::<false>(ray, t_min, t_max, hit_record, &mut TraversalStats::default());
+    }
+
+    /// Same as hit, but also adds what the traversal cost to stats
+    pub fn hit_with_stats<'a>(&'a self, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord<'a>, stats: &mut TraversalStats) -> bool {
+        return self.traverse::<true>(ray, t_min, t_max, hit_record, stats);
+    }
+
+    // COUNT is a constant so the counting compiles away from the plain hit
+    #[inline(always)]
+    pub(super) fn traverse<'a, const COUNT: bool>(&'a self, ray: &Ray, t_min: f32, mut t_max: f32, hit_record: &mut HitRecord<'a>, stats: &mut TraversalStats) -> bool {
 
         let mut hit_anything = false;
+        if self.tree.nodes.is_empty() {
+            return false;
+        }
 
         TRAVERSAL_STACK.with(|stack_cell| {
             let mut stack = stack_cell.borrow_mut();
             stack.clear();
-            stack.push(self.nodes.len() - 1); // start with the root node
+            stack.push(self.tree.nodes.len() - 1); // start with the root node
 
             while let Some(node_index) = stack.pop() {
-                if node_index >= self.nodes.len() {
+                if node_index >= self.tree.nodes.len() {
                     continue;
                 }
-                let node = &self.nodes[node_index];
+                if COUNT {
+                    stats.nodes_visited += 1;
+                }
+                let node = &self.tree.nodes[node_index];
                 match node {
-                    FlattenedBVHNode::Leaf { bounding_box: _, hittable_index } => {
-                        if self.hittables[*hittable_index].hit(ray, t_min, t_max, hit_record) {
-                            t_max = hit_record.t; // update closest hit so far
-                            hit_anything = true;
+                    FlattenedBVHNode::Leaf { bounding_box, first_hittable_index, hittable_count } => {
+                        // with a single hittable, its own hit test is about as cheap as the box test
+                        if *hittable_count > 1 && !bounding_box.hit(ray, t_min, t_max) {
+                            continue;
+                        }
+                        if COUNT {
+                            stats.hittables_tested += *hittable_count;
+                        }
+                        for hittable in &self.hittables[*first_hittable_index..*first_hittable_index + *hittable_count] {
+                            if hittable.hit(ray, t_min, t_max, hit_record) {
+                                t_max = hit_record.t; // update closest hit so far
+                                hit_anything = true;
+                            }
                         }
                     },
                     FlattenedBVHNode::Internal { bounding_box, left_index, right_index } => {
@@ -338,15 +732,25 @@ impl FlattenedBVH {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FlattenedBVHNode {
     Leaf {
         bounding_box: AABoundingBox,
-        hittable_index: usize,
+        first_hittable_index: usize,
+        hittable_count: usize,
     },
     Internal {
         bounding_box: AABoundingBox,
         left_index: usize,
         right_index: usize,
     }
-}
\ No newline at end of file
+}
+
+impl FlattenedBVHNode {
+    pub fn get_bounding_box(&self) -> &AABoundingBox {
+        match self {
+            FlattenedBVHNode::Leaf { bounding_box, .. } => bounding_box,
+            FlattenedBVHNode::Internal { bounding_box, .. } => bounding_box,
+        }
+    }
+}
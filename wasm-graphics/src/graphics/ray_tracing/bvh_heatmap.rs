@@ -0,0 +1,160 @@
+use rayon::prelude::*;
+
+use crate::{console_error, console_log, graphics::game::Game, utils::{math::Vec3, utils::get_time}};
+
+use super::{bvh::TraversalStats, rt::HitRecord};
+
+// A diagnostic view for the path tracer: instead of shading, each pixel is colored by how much
+// work its primary ray took to get through the BVH, counted by SceneBVH::hit_with_stats. Hot
+// spots show which objects make the tree degenerate (long thin triangles, overlapping boxes,
+// huge leaves), and the summary of the averages is what to compare between BVH builder changes.
+
+/// From no work to the most work, the heat goes through these
+const HEATMAP_COLORS: [Vec3; 5] = [
+    Vec3 { x: 0.0, y: 0.0, z: 0.15 },
+    Vec3 { x: 0.0, y: 0.3, z: 1.0 },
+    Vec3 { x: 0.0, y: 0.9, z: 0.3 },
+    Vec3 { x: 1.0, y: 0.9, z: 0.0 },
+    Vec3 { x: 1.0, y: 0.0, z: 0.0 },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    /// BVH nodes visited, both levels
+    NodesVisited,
+    /// Primitive hit tests run
+    HittablesTested,
+}
+
+impl HeatmapMetric {
+    /// 0 = NodesVisited, 1 = HittablesTested, as the UI numbers them
+    pub fn from_number(number: u32) -> Option<HeatmapMetric> {
+        return match number {
+            0 => Some(HeatmapMetric::NodesVisited),
+            1 => Some(HeatmapMetric::HittablesTested),
+            _ => None,
+        };
+    }
+    pub fn to_number(self) -> u32 {
+        return match self {
+            HeatmapMetric::NodesVisited => 0,
+            HeatmapMetric::HittablesTested => 1,
+        };
+    }
+
+    fn get_count(self, stats: &TraversalStats) -> usize {
+        return match self {
+            HeatmapMetric::NodesVisited => stats.nodes_visited,
+            HeatmapMetric::HittablesTested => stats.hittables_tested,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BVHHeatmap {
+    pub metric: HeatmapMetric,
+    /// The count drawn fully red, None scales to the most expensive pixel of the frame.
+    /// Set it to compare images of different trees.
+    pub max_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BVHHeatmapSummary {
+    pub ray_count: usize,
+    /// Rays that hit something
+    pub hit_count: usize,
+    pub avg_nodes_visited: f32,
+    pub max_nodes_visited: usize,
+    pub avg_hittables_tested: f32,
+    pub max_hittables_tested: usize,
+    pub render_time_ms: f64,
+}
+
+impl std::fmt::Display for BVHHeatmapSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} primary rays ({} hit), nodes visited avg {:.2} max {}, hittables tested avg {:.2} max {}, traced in {:.1}ms",
+            self.ray_count,
+            self.hit_count,
+            self.avg_nodes_visited,
+            self.max_nodes_visited,
+            self.avg_hittables_tested,
+            self.max_hittables_tested,
+            self.render_time_ms,
+        )
+    }
+}
+
+/// heat in [0, 1] along HEATMAP_COLORS
+fn get_heat_color(heat: f32) -> Vec3 {
+    let position = heat.clamp(0.0, 1.0) * (HEATMAP_COLORS.len() - 1) as f32;
+    let i = (position as usize).min(HEATMAP_COLORS.len() - 2);
+    let t = position - i as f32;
+    return HEATMAP_COLORS[i] * (1.0 - t) + HEATMAP_COLORS[i + 1] * t;
+}
+
+impl Game {
+    /// Turns the heatmap on (Some) or off (None) in ray tracing mode, starting the image over
+    pub fn set_bvh_heatmap(&mut self, heatmap: Option<BVHHeatmap>) {
+        if heatmap.is_some_and(|heatmap| heatmap.max_count == Some(0)) {
+            console_error!("Game::set_bvh_heatmap() called with a max count of 0, it must be at least 1");
+            return;
+        }
+        self.bvh_heatmap = heatmap;
+        self.ray_samples_accumulated = 0;
+    }
+
+    /// Traces one ray through the middle of each pixel and colors the pixel by what it cost.
+    /// There's nothing random in it, so one pass is the whole image.
+    pub fn render_bvh_heatmap(&mut self) {
+        let Some(heatmap) = self.bvh_heatmap else {
+            console_error!("Game::render_bvh_heatmap() called with the heatmap off");
+            return;
+        };
+        if self.bvh.is_empty() {
+            console_log!("No RT objects in the scene, can't raytrace!");
+            return;
+        }
+        let start_time = get_time();
+
+        let rows: Vec<Vec<(TraversalStats, bool)>> = (0..self.camera.height).into_par_iter().map(|y| {
+            return (0..self.camera.width).map(|x| {
+                let ray = self.get_ray_at_pixel(x, y);
+                let mut hit_record = HitRecord::default();
+                let mut stats = TraversalStats::default();
+                let hit = self.bvh.hit_with_stats(&ray, 0.001, 5000.0, &mut hit_record, &mut stats);
+                return (stats, hit);
+            }).collect();
+        }).collect();
+
+        let mut summary = BVHHeatmapSummary::default();
+        let mut total = TraversalStats::default();
+        for &(stats, hit) in rows.iter().flatten() {
+            summary.ray_count += 1;
+            summary.hit_count += hit as usize;
+            summary.max_nodes_visited = summary.max_nodes_visited.max(stats.nodes_visited);
+            summary.max_hittables_tested = summary.max_hittables_tested.max(stats.hittables_tested);
+            total += stats;
+        }
+        if summary.ray_count > 0 {
+            summary.avg_nodes_visited = total.nodes_visited as f32 / summary.ray_count as f32;
+            summary.avg_hittables_tested = total.hittables_tested as f32 / summary.ray_count as f32;
+        }
+
+        let max_count = heatmap.max_count.unwrap_or(match heatmap.metric {
+            HeatmapMetric::NodesVisited => summary.max_nodes_visited,
+            HeatmapMetric::HittablesTested => summary.max_hittables_tested,
+        }).max(1);
+        rows.par_iter().enumerate().for_each(|(y, row)| {
+            let mut pixel_row = self.pixel_buf.get_row_guard(y).lock().unwrap();
+            for (x, (stats, _)) in row.iter().enumerate() {
+                pixel_row[x] = get_heat_color(heatmap.metric.get_count(stats) as f32 / max_count as f32);
+            }
+        });
+
+        summary.render_time_ms = get_time() - start_time;
+        console_log!("BVH heatmap ({:?}): {}", heatmap.metric, summary);
+        self.bvh_heatmap_summary = Some(summary);
+    }
+}
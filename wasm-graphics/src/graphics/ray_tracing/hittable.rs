@@ -1,12 +1,12 @@
 use std::{f32::consts::PI, fmt::Debug};
 
-use crate::{console_log, graphics::mesh::{Mesh, PhongProperties}, utils::{math::Vec3, utils::random_float}};
+use crate::{graphics::{mesh::{Mesh, PhongProperties}, normal_map::{Tangent, TriangleNormalMap}, scene_snapshot::{HittableSnapshot, SnapshotTextures}, texture::TriangleTexture}, utils::{math::{Mat4, Transform, Vec3}, rng::SampleRng}};
 
 use super::{bvh::AABoundingBox, material::{Dielectric, DiffuseLight, Lambertian, Material, Metal}, rt::{HitRecord, Ray}};
 
 pub trait Hittable: Debug + Send + Sync {
     fn hit<'a>(&'a self, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord<'a>) -> bool;
-    fn sample_random_point(&self) -> Vec3;
+    fn sample_random_point(&self, rng: &mut SampleRng) -> Vec3;
     fn get_area(&self) -> f32;
     fn get_normal(&self, p: Vec3) -> Vec3;
     fn get_color(&self) -> Vec3;
@@ -15,6 +15,11 @@ pub trait Hittable: Debug + Send + Sync {
     fn get_material(&self) -> &dyn Material;
     fn get_mut_material(&mut self) -> &mut dyn Material;
     fn set_material(&mut self, material: Box<dyn Material>);
+    /// The texture covering the color, only triangles have them
+    fn get_texture(&self) -> Option<&TriangleTexture> {
+        return None;
+    }
+    fn set_texture(&mut self, _texture: Option<Box<TriangleTexture>>) {}
     /// used ONLY for interaction with JS
     fn set_material_type(&mut self, mat_type: u32) {
         self.set_material(match mat_type {
@@ -26,10 +31,22 @@ pub trait Hittable: Debug + Send + Sync {
         });
     }
     fn translate_by(&mut self, offset: Vec3);
-    fn rotate_around(&mut self, center_of_rotation: Vec3, theta_z: f32, theta_y: f32);
-    fn scale_around(&mut self, center_of_scale: Vec3, scale_factor: f32);
+    /// Spheres stay spheres, so they're scaled by the largest scale factor of the transform
+    fn transform_by(&mut self, transform: &Mat4);
+    /// False if transform_by() would only place it roughly, like a sphere under a non-uniform scale
+    fn can_transform_exactly(&self, _transform: &Transform) -> bool {
+        return true;
+    }
 
     fn clone_box(&self) -> Box<dyn Hittable>;
+    /// used for saving/restoring the scene (see scene_snapshot.rs), the textures it uses are added to textures
+    fn to_snapshot(&self, textures: &mut SnapshotTextures) -> HittableSnapshot;
+}
+
+impl Clone for Box<dyn Hittable> {
+    fn clone(&self) -> Box<dyn Hittable> {
+        self.as_ref().clone_box()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -90,13 +107,16 @@ impl Hittable for Sphere {
             hit_record.set_face_normal(ray, outward_normal);
             hit_record.material = Some(self.material.as_ref());
             hit_record.surface_color = self.color; // assuming sphere is one color
+            hit_record.texture = None;
+            hit_record.normal_map = None;
+            hit_record.reflection_offset = Vec3::zero();
 
             return true;
         }
     }
 
-    fn sample_random_point(&self) -> Vec3 {
-        return self.center + self.radius * Vec3::random_on_unit_sphere();
+    fn sample_random_point(&self, rng: &mut SampleRng) -> Vec3 {
+        return self.center + self.radius * rng.random_on_unit_sphere();
     }
     fn get_area(&self) -> f32 {
         return 4.0 * PI * self.radius * self.radius;
@@ -127,27 +147,22 @@ impl Hittable for Sphere {
         let r_vector = self.radius * Vec3::ones();
         self.bounding_box = AABoundingBox::new_from_sorted(self.center - r_vector, self.center + r_vector);
     }
-    fn rotate_around(&mut self, center_of_rotation: Vec3, theta_z: f32, theta_y: f32) {
-        self.center -= center_of_rotation;
-        let (sin_z, cos_z) = theta_z.sin_cos();
-        let (sin_y, cos_y) = theta_y.sin_cos();
-        self.center.rotate_z_fast(sin_z, cos_z);
-        self.center.rotate_y_fast(sin_y, cos_y);
-        self.center += center_of_rotation;
+    fn transform_by(&mut self, transform: &Mat4) {
+        self.center = transform.transform_point(self.center);
+        self.radius *= transform.get_max_scale();
         let r_vector = self.radius * Vec3::ones();
         self.bounding_box = AABoundingBox::new_from_sorted(self.center - r_vector, self.center + r_vector);
     }
-    fn scale_around(&mut self, center_of_scale: Vec3, scale_factor: f32) {
-        self.center -= center_of_scale;
-        self.radius *= scale_factor;
-        self.center += center_of_scale;
-        let r_vector = self.radius * Vec3::ones();
-        self.bounding_box = AABoundingBox::new_from_sorted(self.center - r_vector, self.center + r_vector);
+    fn can_transform_exactly(&self, transform: &Transform) -> bool {
+        return transform.has_uniform_scale();
     }
 
     fn clone_box(&self) -> Box<dyn Hittable> {
         return Box::new(self.clone());
     }
+    fn to_snapshot(&self, _textures: &mut SnapshotTextures) -> HittableSnapshot {
+        return HittableSnapshot::sphere(self);
+    }
 }
 
 
@@ -160,6 +175,13 @@ pub struct Triangle {
     pub normal: Vec3,
     pub d: f32,
     pub color: Vec3,
+    /// Covers color where set, boxed so untextured triangles stay small
+    pub texture: Option<Box<TriangleTexture>>,
+    /// Normals blended across the triangle for smooth shading, in the same order as the UVs.
+    /// Boxed for the same reason as texture.
+    pub vertex_normals: Option<Box<[Vec3; 3]>>,
+    /// Boxed like texture
+    pub normal_map: Option<Box<TriangleNormalMap>>,
     pub material: Box<dyn Material>,
     pub bounding_box: AABoundingBox,
 }
@@ -174,6 +196,9 @@ impl Triangle {
             normal: Vec3::zero(),
             d: 0.0,
             color,
+            texture: None,
+            vertex_normals: None,
+            normal_map: None,
             material: material.clone_box(),
             bounding_box: AABoundingBox::empty(),
         };
@@ -213,6 +238,30 @@ impl Triangle {
         return (t1, t2);
     }
 
+    /// Sets the hit's normal to the vertex normals blended at the hit, and how far bounces have to
+    /// start off the flat triangle so they don't land in its shadow (Hanika, "Hacking the Shadow Terminator").
+    #[inline(always)]
+    fn apply_vertex_normals(&self, vertex_normals: &[Vec3; 3], ray: &Ray, hit_record: &mut HitRecord) {
+        let side = if hit_record.front_face { 1.0 } else { -1.0 };
+        let [w1, w2, w3] = hit_record.barycentric;
+        let n1 = vertex_normals[0] * side;
+        let n2 = vertex_normals[1] * side;
+        let n3 = vertex_normals[2] * side;
+
+        let blended = n1 * w1 + n2 * w2 + n3 * w3;
+        // on the silhouette the blend can point away from the ray, the flat normal is better there
+        if blended.len_squared() > 1e-12 && blended.dot(ray.direction) < 0.0 {
+            hit_record.normal = blended.normalized();
+        }
+
+        // each vertex's tangent plane, the point is pushed out to the ones it is under
+        let p = hit_record.pos;
+        let under = |vertex: Vec3, normal: Vec3| -> Vec3 {
+            return normal * (p - vertex).dot(normal).min(0.0);
+        };
+        hit_record.reflection_offset = -(under(self.origin, n1) * w1 + under(self.origin + self.v, n2) * w2 + under(self.origin + self.u, n3) * w3);
+    }
+
     #[inline(always)]
     fn intersection_is_interior(alpha: f32, beta: f32) -> bool {
         return alpha > 0.0 && beta > 0.0 && alpha + beta < 1.0;
@@ -261,13 +310,25 @@ impl Hittable for Triangle {
         hit_record.material = Some(self.material.as_ref());
         hit_record.set_face_normal(ray, self.normal);
         hit_record.surface_color = self.color;
+        // origin is the first vertex, origin + v the second and origin + u the third
+        hit_record.barycentric = [1.0 - alpha - beta, beta, alpha];
+        hit_record.texture = self.texture.as_deref();
+        hit_record.reflection_offset = Vec3::zero();
+        if let Some(vertex_normals) = &self.vertex_normals {
+            self.apply_vertex_normals(vertex_normals, ray, hit_record);
+        }
+        hit_record.normal_map = self.normal_map.as_deref();
+        if let Some(normal_map) = &self.normal_map {
+            let tangent = Tangent::interpolate(&normal_map.tangents, hit_record.barycentric);
+            hit_record.tangent = if hit_record.front_face { tangent } else { tangent.flipped() };
+        }
 
         return true;
     }
 
-    fn sample_random_point(&self) -> Vec3 {
-        let mut alpha = random_float();
-        let mut beta = random_float();
+    fn sample_random_point(&self, rng: &mut SampleRng) -> Vec3 {
+        let mut alpha = rng.random_float();
+        let mut beta = rng.random_float();
         if alpha + beta > 1.0 {
             alpha = 1.0 - alpha;
             beta = 1.0 - beta;
@@ -285,6 +346,13 @@ impl Hittable for Triangle {
     }
     fn set_color(&mut self, color: Vec3) {
         self.color = color;
+        self.texture = None;
+    }
+    fn get_texture(&self) -> Option<&TriangleTexture> {
+        return self.texture.as_deref();
+    }
+    fn set_texture(&mut self, texture: Option<Box<TriangleTexture>>) {
+        self.texture = texture;
     }
     fn get_bounding_box(&self) -> &AABoundingBox {
         &self.bounding_box
@@ -304,35 +372,29 @@ impl Hittable for Triangle {
         // self.bounding_box.max += offset;
         // self.bounding_box.min += offset;
     }
-    fn rotate_around(&mut self, center_of_rotation: Vec3, theta_z: f32, theta_y: f32) {
-        let (sin_z, cos_z) = theta_z.sin_cos();
-        let (sin_y, cos_y) = theta_y.sin_cos();
-
-        self.origin -= center_of_rotation;
-        self.origin.rotate_z_fast(sin_z, cos_z);
-        self.origin.rotate_y_fast(sin_y, cos_y);
-        self.origin += center_of_rotation;
-
-        self.u.rotate_z_fast(sin_z, cos_z);
-        self.u.rotate_y_fast(sin_y, cos_y);
-
-        self.v.rotate_z_fast(sin_z, cos_z);
-        self.v.rotate_y_fast(sin_y, cos_y);
-
-        self.update_geometry();
-    }
-    fn scale_around(&mut self, center_of_scale: Vec3, scale_factor: f32) {
-        self.origin -= center_of_scale;
-        self.origin *= scale_factor;
-        self.origin += center_of_scale;
-        
-        self.u *= scale_factor;
-        self.v *= scale_factor;
-
+    fn transform_by(&mut self, transform: &Mat4) {
+        self.origin = transform.transform_point(self.origin);
+        self.u = transform.transform_vector(self.u);
+        self.v = transform.transform_vector(self.v);
+        if let Some(vertex_normals) = &mut self.vertex_normals {
+            let normal_matrix = transform.normal_matrix();
+            for n in vertex_normals.iter_mut() {
+                *n = normal_matrix.transform_vector(*n).normalized();
+            }
+        }
+        // like SceneObject::get_world_tangents(), they get normalized when they're used
+        if let Some(normal_map) = &mut self.normal_map {
+            for tangent in normal_map.tangents.iter_mut() {
+                tangent.dir = transform.transform_vector(tangent.dir);
+            }
+        }
         self.update_geometry();
     }
 
     fn clone_box(&self) -> Box<dyn Hittable> {
         return Box::new(self.clone());
     }
+    fn to_snapshot(&self, textures: &mut SnapshotTextures) -> HittableSnapshot {
+        return HittableSnapshot::triangle(self, textures);
+    }
 }
\ No newline at end of file
@@ -1,18 +1,18 @@
 use std::{f32::consts::PI, fmt::Debug};
 
-use crate::{console_log, utils::{math::Vec3, utils::{random_float, random_int, random_range}}};
+use crate::{console_log, graphics::scene_snapshot::MaterialSnapshot, utils::{math::Vec3, rng::SampleRng}};
 
 use super::{bvh::BVHNode, hittable::Hittable, rt::{HitRecord, Ray}};
 
 pub trait Material: Debug + Send + Sync {
     /// scatters the inbound ray and returns a tuple of the the attenuation color and the new ray.
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> (bool, Vec3, Ray);
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SampleRng) -> (bool, Vec3, Ray);
 
     /// returns (successful_scatter, indirect_attenuation, scattered_ray, Option<(direct_attenuation, shadow_ray, light_dist)>.
     /// to use the results, attenuation must be multiplied by L_i, the incoming radiance.
     /// The incoming radiance is calculated from the full scattered ray and the shadow 
     /// ray if it exists (direct light sampling).
-    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
+    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>, rng: &mut SampleRng) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
         return (false, Vec3::zero(), Ray::new(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0)), None);
     }
 
@@ -24,6 +24,9 @@ pub trait Material: Debug + Send + Sync {
     fn get_material_number(&self) -> u32;
     fn get_material_prop(&self) -> f32;
     fn set_material_prop(&mut self, prop: f32);
+
+    /// used for saving/restoring the scene (see scene_snapshot.rs)
+    fn to_snapshot(&self) -> MaterialSnapshot;
 }
 
 impl Clone for Box<dyn Material> {
@@ -40,14 +43,14 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> (bool, Vec3, Ray) {
-        let reflected_dir = hit_record.normal + Vec3::random_on_unit_sphere();
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SampleRng) -> (bool, Vec3, Ray) {
+        let reflected_dir = hit_record.normal + rng.random_on_unit_sphere();
 
         if reflected_dir.near_zero() {
             console_log!("reflected_dir near zero");
         }
 
-        let reflected_ray = Ray::new(hit_record.pos, reflected_dir);
+        let reflected_ray = Ray::new(hit_record.reflection_origin(), reflected_dir);
         let attenuation = hit_record.surface_color;
         return (true, attenuation, reflected_ray)
     }
@@ -56,15 +59,15 @@ impl Material for Lambertian {
     /// to use the results, attenuation must be multiplied by L_i, the incoming radiance.
     /// The incoming radiance is calculated from the full scattered ray and the shadow 
     /// ray if it exists (direct light sampling).
-    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
+    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>, rng: &mut SampleRng) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
         if lights.len() == 0 {
-            let (successful_scatter, attenuation, scattered_ray) = self.scatter(ray, hit_record);
+            let (successful_scatter, attenuation, scattered_ray) = self.scatter(ray, hit_record, rng);
             return (successful_scatter, attenuation, scattered_ray, None);
         }
-        let random_light = &lights[random_int(0, lights.len() as i32 - 1) as usize];
-        let random_light_sample_point = random_light.sample_random_point();
+        let random_light = &lights[rng.random_int(0, lights.len() as i32 - 1) as usize];
+        let random_light_sample_point = random_light.sample_random_point(rng);
 
-        let cosine_dir = (hit_record.normal + Vec3::random_on_unit_sphere()).normalized();
+        let cosine_dir = (hit_record.normal + rng.random_on_unit_sphere()).normalized();
 
         let mut random_light_dir = random_light_sample_point - hit_record.pos;
         let random_light_r_squared = random_light_dir.len_squared();
@@ -76,7 +79,7 @@ impl Material for Lambertian {
         let cosine_pdf = hit_record.normal.dot(cosine_dir).clamp(0.0, 1.0) / PI; // dot(n,w) / pi
 
         let mut light_pdf = 0.0;
-        let point_to_light_ray = Ray::new(hit_record.pos, cosine_dir);
+        let point_to_light_ray = Ray::new(hit_record.reflection_origin(), cosine_dir);
         for light in lights {
             let mut temp_hit_record = HitRecord::default();
             let hit_light = light.hit(&point_to_light_ray, 0.001, 5000.0, &mut temp_hit_record);
@@ -107,7 +110,7 @@ impl Material for Lambertian {
         let cos_wi = hit_record.normal.dot(cosine_dir).max(0.0);
         let indirect_attenuation = brdf * cos_wi / pdf_mix;
         
-        let scattered_ray = Ray::new(hit_record.pos, cosine_dir);
+        let scattered_ray = Ray::new(hit_record.reflection_origin(), cosine_dir);
 
 
         // 2) direct lighting (light sample distribution)
@@ -123,7 +126,7 @@ impl Material for Lambertian {
         let mut direct_attenuation = brdf * cos_wi / pdf_mix;
         direct_attenuation.mul_elementwise_inplace(random_light.get_color()); // TODO: is this correct?
         
-        let shadow_ray = Ray::new(hit_record.pos, random_light_dir);
+        let shadow_ray = Ray::new(hit_record.reflection_origin(), random_light_dir);
 
         let light_dist = random_light_r_squared.sqrt();
         let light_option = Some((direct_attenuation, shadow_ray, light_dist));
@@ -146,6 +149,9 @@ impl Material for Lambertian {
     fn set_material_prop(&mut self, prop: f32) {
         // do nothing
     }
+    fn to_snapshot(&self) -> MaterialSnapshot {
+        return MaterialSnapshot::Lambertian;
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -160,21 +166,21 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> (bool, Vec3, Ray) {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SampleRng) -> (bool, Vec3, Ray) {
         let mut reflected_dir = ray.direction.reflect(hit_record.normal);
         reflected_dir.normalize();
-        reflected_dir += self.fuzz * Vec3::random_on_unit_sphere();
+        reflected_dir += self.fuzz * rng.random_on_unit_sphere();
 
         if reflected_dir.dot(hit_record.normal) < 0.0 {
             return (false, Vec3::zero(), Ray::default());
         } else  {
-            let reflected_ray = Ray::new(hit_record.pos, reflected_dir);
+            let reflected_ray = Ray::new(hit_record.reflection_origin(), reflected_dir);
             let attenuation = hit_record.surface_color;
             return (true, attenuation, reflected_ray)
         }
     }
-    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
-        let (successful_scatter, attenuation, scattered_ray) = self.scatter(ray, hit_record);
+    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>, rng: &mut SampleRng) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
+        let (successful_scatter, attenuation, scattered_ray) = self.scatter(ray, hit_record, rng);
         return (successful_scatter, attenuation, scattered_ray, None);
     }
     fn emitted(&self, hit_record: &HitRecord) -> Vec3 {
@@ -193,6 +199,9 @@ impl Material for Metal {
     fn set_material_prop(&mut self, prop: f32) {
         self.fuzz = prop;
     }
+    fn to_snapshot(&self) -> MaterialSnapshot {
+        return MaterialSnapshot::Metal { fuzz: self.fuzz };
+    }
 }
 
 
@@ -217,7 +226,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> (bool, Vec3, Ray) {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SampleRng) -> (bool, Vec3, Ray) {
         let attenuation;
         let n1;
         let n2;
@@ -245,17 +254,18 @@ impl Material for Dielectric {
         let cannot_refract = n1_over_n2 * sin_theta > 1.0;
         let reflectance = self.reflectance(cos_theta, n1, n2);
 
-        let refracted_dir = if cannot_refract || reflectance > random_float()  {
-            ray_dir.reflect(hit_record.normal)
+        // only reflections stay on the normal's side, so only they start off the smoothed surface
+        let (origin, refracted_dir) = if cannot_refract || reflectance > rng.random_float()  {
+            (hit_record.reflection_origin(), ray_dir.reflect(hit_record.normal))
         } else {
-            ray_dir.refract(hit_record.normal, n1_over_n2)
+            (hit_record.pos, ray_dir.refract(hit_record.normal, n1_over_n2))
         };
 
-        let refracted_ray = Ray::new(hit_record.pos, refracted_dir);
+        let refracted_ray = Ray::new(origin, refracted_dir);
         return (true, attenuation, refracted_ray);
     }
-    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
-        let (successful_scatter, attenuation, scattered_ray) = self.scatter(ray, hit_record);
+    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>, rng: &mut SampleRng) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
+        let (successful_scatter, attenuation, scattered_ray) = self.scatter(ray, hit_record, rng);
         return (successful_scatter, attenuation, scattered_ray, None);
     }
     fn emitted(&self, hit_record: &HitRecord) -> Vec3 {
@@ -274,6 +284,9 @@ impl Material for Dielectric {
     fn set_material_prop(&mut self, prop: f32) {
         self.index_of_refrac = prop;
     }
+    fn to_snapshot(&self) -> MaterialSnapshot {
+        return MaterialSnapshot::Dielectric { index_of_refrac: self.index_of_refrac };
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -284,7 +297,7 @@ impl DiffuseLight {
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> (bool, Vec3, Ray) {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SampleRng) -> (bool, Vec3, Ray) {
         return (false, Vec3::zero(), ray.clone());
     }
     fn emitted(&self, hit_record: &HitRecord) -> Vec3 {
@@ -303,6 +316,9 @@ impl Material for DiffuseLight {
     fn set_material_prop(&mut self, prop: f32) {
         // do nothing
     }
+    fn to_snapshot(&self) -> MaterialSnapshot {
+        return MaterialSnapshot::DiffuseLight;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -329,7 +345,7 @@ impl ClearCoat {
 }
 
 impl Material for ClearCoat {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> (bool, Vec3, Ray) {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SampleRng) -> (bool, Vec3, Ray) {
         let n1 = 1.0; // air
         let n2 = self.index_of_refraction;
         let n1_over_n2 = n1 / n2;
@@ -343,19 +359,19 @@ impl Material for ClearCoat {
         // total internal reflection
         let cannot_refract = n1_over_n2 * sin_theta > 1.0;
 
-        if cannot_refract || reflectance > random_float() {
+        if cannot_refract || reflectance > rng.random_float() {
             // case 1: specular reflection from clear coat surface
             let reflected_dir = ray.direction.reflect(hit_record.normal);
-            let refracted_ray = Ray::new(hit_record.pos, reflected_dir);
+            let refracted_ray = Ray::new(hit_record.reflection_origin(), reflected_dir);
             let attenuation = Vec3::white();
             return (true, attenuation, refracted_ray);
         } else {
             // case 2: scatter from base material
-            return self.base_material.scatter(ray, hit_record);
+            return self.base_material.scatter(ray, hit_record, rng);
         }
     }
 
-    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
+    fn scatter_mis(&self, ray: &Ray, hit_record: &HitRecord, lights: &Vec<Box<dyn Hittable>>, rng: &mut SampleRng) -> (bool, Vec3, Ray, Option<(Vec3, Ray, f32)>) {
         let n1 = 1.0; // air
         let n2 = self.index_of_refraction;
         let n1_over_n2 = n1 / n2;
@@ -369,15 +385,15 @@ impl Material for ClearCoat {
         // total internal reflection
         let cannot_refract = n1_over_n2 * sin_theta > 1.0;
         
-        if cannot_refract || reflectance > random_float() {
+        if cannot_refract || reflectance > rng.random_float() {
             // case 1: specular reflection from clear coat surface
             let reflected_dir = ray.direction.reflect(hit_record.normal);
-            let refracted_ray = Ray::new(hit_record.pos, reflected_dir);
+            let refracted_ray = Ray::new(hit_record.reflection_origin(), reflected_dir);
             let attenuation = Vec3::white();
             return (true, attenuation, refracted_ray, None); // there is no direct light sampling for specular reflection
         } else {
             // case 2: scatter from base material
-            return self.base_material.scatter_mis(ray, hit_record, lights);
+            return self.base_material.scatter_mis(ray, hit_record, lights, rng);
         }
     }
 
@@ -400,5 +416,12 @@ impl Material for ClearCoat {
     fn set_material_prop(&mut self, prop: f32) {
         console_log!("set_material_prop called for ClearCoat but not implemented for ClearCoat");
     }
+
+    fn to_snapshot(&self) -> MaterialSnapshot {
+        return MaterialSnapshot::ClearCoat {
+            base_material: Box::new(self.base_material.to_snapshot()),
+            index_of_refraction: self.index_of_refraction,
+        };
+    }
 }
 
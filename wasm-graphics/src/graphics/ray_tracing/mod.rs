@@ -1,4 +1,6 @@
 pub mod rt;
 pub mod material;
 pub mod hittable;
-pub mod bvh;
\ No newline at end of file
+pub mod bvh;
+pub mod scene_bvh;
+pub mod bvh_heatmap;
\ No newline at end of file
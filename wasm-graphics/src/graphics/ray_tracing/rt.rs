@@ -1,10 +1,10 @@
-use std::{cell::RefCell, f32::consts::PI, fmt::Debug, sync::RwLock};
+use std::fmt::Debug;
 
 use rayon::prelude::*;
 
-use crate::{console_log, graphics::{buffers::{PixelBuf, ZBuffer}, camera::Camera, game::GameStatus, lighting::Light, scene_object::SceneObject}, utils::{math::{degrees_to_radians, Vec3}, utils::{gamma_correct_color, get_time, random_float, random_range, sample_circle, sample_square}}};
+use crate::{console_log, graphics::{normal_map::{Tangent, TriangleNormalMap}, texture::{TextureFilter, TriangleTexture}}, utils::{math::Vec3, rng::SampleRng, utils::get_time}};
 
-use super::{super::{game::Game, mesh::{Mesh, PhongProperties}}, bvh::BVHNode, hittable::{Hittable, Sphere, Triangle}, material::{Dielectric, DiffuseLight, Lambertian, Material, Metal}};
+use super::{super::game::Game, material::Material};
 
 // const SAMPLES: usize = 10; // 10
 // const MAX_DEPTH: usize = 10; // 10
@@ -32,6 +32,15 @@ pub struct HitRecord<'a> {
     pub front_face: bool,
     pub surface_color: Vec3,
     pub material: Option<&'a dyn Material>,
+    /// Weights of the hit triangle's vertices at pos, left as is by spheres
+    pub barycentric: [f32; 3],
+    /// Sampled into surface_color by apply_texture(), once the closest hit is known
+    pub texture: Option<&'a TriangleTexture>,
+    /// Bends normal in apply_texture(), along with the tangent at pos
+    pub normal_map: Option<&'a TriangleNormalMap>,
+    pub tangent: Tangent,
+    /// Added to pos for rays leaving the surface on the normal's side, nonzero on smooth shaded triangles
+    pub reflection_offset: Vec3,
 }
 
 impl HitRecord<'_> {
@@ -39,6 +48,24 @@ impl HitRecord<'_> {
         self.front_face = ray.direction.dot(outward_normal) < 0.0;
         self.normal = if self.front_face { outward_normal } else { -outward_normal };
     }
+    #[inline(always)]
+    pub fn reflection_origin(&self) -> Vec3 {
+        return self.pos + self.reflection_offset;
+    }
+    /// Also applies the normal map
+    #[inline(always)]
+    pub fn apply_texture(&mut self, ray: &Ray, filter: TextureFilter) {
+        if let Some(texture) = self.texture {
+            self.surface_color = texture.sample(self.barycentric, filter);
+        }
+        if let Some(normal_map) = self.normal_map {
+            let perturbed = self.tangent.perturb(self.normal, normal_map.sample(self.barycentric, filter));
+            // a bump seen from behind would send bounces into the surface
+            if perturbed.dot(ray.direction) < 0.0 {
+                self.normal = perturbed;
+            }
+        }
+    }
 }
 
 impl Game {
@@ -46,6 +73,8 @@ impl Game {
     pub fn render_ray_tracing(&mut self) {
 
         // BENCHMARKING
+        // the numbers below were timed by hand in the browser, for ones that can be rerun on any
+        // commit see benches/ (cargo bench)
         // perf tips here: https://users.rust-lang.org/t/peter-shirleys-ray-tracing-in-one-weekend-implementation-in-rust/26972/11
 
         // benchmark conditions:
@@ -89,41 +118,26 @@ impl Game {
         // After adding Rayon parallelism:
         // 0.720 second avg
 
-        if self.bvh.is_none() {
+        if self.bvh.is_empty() {
             console_log!("No RT objects in the scene, can't raytrace!");
             return;
         }
 
+        // the heatmap comes out the same every pass, so it's only traced once
+        if self.bvh_heatmap.is_some() {
+            if self.ray_samples_accumulated == 0 {
+                self.render_bvh_heatmap();
+                self.ray_samples_accumulated = 1;
+            }
+            return;
+        }
+
         console_log!("Rendering ray tracing");
 
         let start_time = get_time();
-        let samples_per_pixel_per_pass = 1;
 
         loop {
-
-            self.ray_samples_accumulated += samples_per_pixel_per_pass;
-
-            (0..self.camera.height).into_par_iter().for_each(|y| {
-                let mut pixel_row = self.pixel_buf.get_row_guard(y).lock().unwrap();
-                for x in 0..pixel_row.len() {
-
-                    let mut new_color = Vec3::zero();
-                    for _ in 0..samples_per_pixel_per_pass {
-                        let ray = self.get_rand_ray_at_pixel_with_defocus(x, y);
-                        // let ray_color = self.ray_trace(ray, self.ray_max_depth);
-                        let ray_color = self.ray_trace_mis(ray, self.ray_max_depth);
-                        new_color += ray_color;
-                    }
-
-                    let existing_color = pixel_row[x];
-                    let updated_color = (
-                        existing_color * (self.ray_samples_accumulated as f32 - samples_per_pixel_per_pass as f32)
-                        + new_color * samples_per_pixel_per_pass as f32)
-                        / self.ray_samples_accumulated as f32;
-
-                    pixel_row[x] = updated_color;
-                }
-            });
+            self.render_ray_tracing_pass();
 
             let curr_time = get_time();
             if curr_time - start_time > 0.5 {
@@ -132,53 +146,50 @@ impl Game {
         }
     }
 
-    fn ray_trace(&self, mut ray: Ray, mut depth: usize) -> Vec3 {
-
-        // start with identity for multiplication = 1
-        let mut pixel_color = Vec3::new(1.0, 1.0, 1.0);
-
-        while depth > 0 {
-            depth -= 1;
-            let mut hit_record = HitRecord::default();
-            let mut hit_anything = false;
-
-            if self.bvh.as_ref().unwrap().hit(&ray, 0.001, 5000.0, &mut hit_record) {
-                hit_anything = true;
-            }
-
-            if hit_anything {
-                let successful_scatter;
-                let attenuation;
-                let scattered_ray;
-
-                if let Some(material) = hit_record.material {
-
-                    let emitted_color = material.emitted(&hit_record);
-                    (successful_scatter, attenuation, scattered_ray) = material.scatter(&ray, &hit_record);
-                    if successful_scatter {
-                        pixel_color += emitted_color;
-                        pixel_color.mul_elementwise_inplace(attenuation);
-                        ray = scattered_ray;
-                    } else {
-                        return pixel_color.mul_elementwise(emitted_color);
-                        // no scatter, terminate here with current emitted color
-                    }
+    /// Traces one more sample per pixel and averages it into the pixel buffer.
+    /// Sample n of pixel (x, y) always uses the same random numbers for a given rt_seed,
+    /// so the result doesn't depend on how rayon splits up the rows.
+    pub fn render_ray_tracing_pass(&mut self) {
+        if self.bvh.is_empty() {
+            console_log!("No RT objects in the scene, can't raytrace!");
+            return;
+        }
 
-                } else {
-                    return Vec3::zero(); // no material, return black
+        let start_time = get_time();
+        let samples_per_pixel_per_pass = 1;
+        let first_sample_index = self.ray_samples_accumulated;
+        self.ray_samples_accumulated += samples_per_pixel_per_pass;
+
+        let rays_traced: usize = (0..self.camera.height).into_par_iter().map(|y| {
+            let mut pixel_row = self.pixel_buf.get_row_guard(y).lock().unwrap();
+            let mut row_rays_traced = 0;
+            for x in 0..pixel_row.len() {
+
+                let mut new_color = Vec3::zero();
+                for sample in 0..samples_per_pixel_per_pass {
+                    let mut rng = SampleRng::for_pixel_sample(self.rt_seed, x, y, first_sample_index + sample);
+                    let ray = self.get_rand_ray_at_pixel_with_defocus(x, y, &mut rng);
+                    let ray_color = self.ray_trace_mis(ray, self.ray_max_depth, &mut rng, &mut row_rays_traced);
+                    new_color += ray_color;
                 }
 
-            } else { // if hit_anything == false, then ray hit nothing, goes off into sky
-                let sky_color = self.get_rt_sky_color(&ray.direction.normalized());
-                return pixel_color.mul_elementwise(sky_color);
+                let existing_color = pixel_row[x];
+                let updated_color = (
+                    existing_color * (self.ray_samples_accumulated as f32 - samples_per_pixel_per_pass as f32)
+                    + new_color * samples_per_pixel_per_pass as f32)
+                    / self.ray_samples_accumulated as f32;
+
+                pixel_row[x] = updated_color;
             }
-        }
+            return row_rays_traced;
+        }).sum();
 
-        // max depth reached, return black
-        return Vec3::zero();
+        self.frame_stats_in_progress.ray_tracing_ms += get_time() - start_time;
+        self.frame_stats_in_progress.rays_traced += rays_traced;
     }
 
-    fn ray_trace_mis(&self, mut ray: Ray, mut depth: usize) -> Vec3 {
+    /// Adds every ray it casts into the BVH to rays_traced: the camera ray, its bounces and the shadow rays
+    fn ray_trace_mis(&self, mut ray: Ray, mut depth: usize, rng: &mut SampleRng, rays_traced: &mut usize) -> Vec3 {
 
         let use_direct = true;
 
@@ -193,20 +204,22 @@ impl Game {
             let mut hit_anything = false;
 
             // russian-roulette optimization
-            if depth >= self.ray_max_depth - 3 {
+            if depth >= self.ray_max_depth.saturating_sub(3) {
                 // skip russian roulette on first bounces
             } else {
                 // use luminance
                 let lum = 0.2126*throughput.x + 0.7152*throughput.y + 0.0722*throughput.z;
                 let p_continue = lum.clamp(0.05, 1.0);
-                if random_float() > p_continue {
+                if rng.random_float() > p_continue {
                     break;
                 }
                 throughput /= p_continue;
             }
 
-            if self.bvh.as_ref().unwrap().hit(&ray, 0.001, 5000.0, &mut hit_record) {
+            *rays_traced += 1;
+            if self.bvh.hit(&ray, 0.001, 5000.0, &mut hit_record) {
                 hit_anything = true;
+                hit_record.apply_texture(&ray, self.texture_filter);
             }
 
             if hit_anything {
@@ -218,14 +231,15 @@ impl Game {
                 if let Some(material) = hit_record.material {
 
                     let emitted_color = material.emitted(&hit_record);
-                    (successful_scatter, attenuation, scattered_ray, light_sampling_option) = material.scatter_mis(&ray, &hit_record, self.get_rt_lights());
+                    (successful_scatter, attenuation, scattered_ray, light_sampling_option) = material.scatter_mis(&ray, &hit_record, self.get_rt_lights(), rng);
                     if successful_scatter {
 
                         let mut direct_lighting = Vec3::zero();
                         if use_direct {
                             if let Some((direct_attenuation, shadow_ray, light_dist)) = light_sampling_option {
                                 let mut shadow_ray_hit_record = HitRecord::default();
-                                let light_is_occluded = self.bvh.as_ref().unwrap().hit(&shadow_ray, 0.001, light_dist - 0.001, &mut shadow_ray_hit_record);
+                                *rays_traced += 1;
+                                let light_is_occluded = self.bvh.hit(&shadow_ray, 0.001, light_dist - 0.001, &mut shadow_ray_hit_record);
                                 if !light_is_occluded {
                                     direct_lighting = direct_attenuation;
                                 }
@@ -257,7 +271,7 @@ impl Game {
         return accum_color;
     }
 
-    fn get_ray_at_pixel(&self, x: usize, y: usize) -> Ray {
+    pub(super) fn get_ray_at_pixel(&self, x: usize, y: usize) -> Ray {
         let origin = self.camera.pos;
 
         let mut v = Vec3::new(x as f32, y as f32, 1.0);
@@ -267,20 +281,10 @@ impl Game {
         Ray::new(origin, direction)
     }
 
-    fn get_rand_ray_at_pixel(&self, x: usize, y: usize) -> Ray {
-        let origin = self.camera.pos;
-        let (offset_x, offset_y) = sample_square(1.0);
-        let mut v = Vec3::new(x as f32 + offset_x, y as f32 + offset_y, 1.0);
-        self.camera.vertex_screen_to_world_space(&mut v);
-        let direction = (v - origin).normalized();
-
-        Ray::new(origin, direction)
-    }
-
-    fn get_rand_ray_at_pixel_with_defocus(&self, x: usize, y: usize) -> Ray {
+    fn get_rand_ray_at_pixel_with_defocus(&self, x: usize, y: usize, rng: &mut SampleRng) -> Ray {
         let defocus_disk_radius = (0.5 * self.defocus_angle).tan() * self.focus_dist;
-        let (disk_x, disk_y) = sample_circle(defocus_disk_radius);
-        let (offset_x, offset_y) = sample_square(1.0);
+        let (disk_x, disk_y) = rng.sample_circle(defocus_disk_radius);
+        let (offset_x, offset_y) = rng.sample_square(1.0);
 
 
         let mut point_on_focus_plane = Vec3::new(x as f32 + offset_x, y as f32 + offset_y, self.focus_dist);
@@ -295,454 +299,4 @@ impl Game {
 
         return Ray::new(point_on_defocus_disk, ray_dir);
     }
-
-    // pub fn create_bvh_from_scene_objs(&mut self) {
-    //     let hittables = self.scene_objects
-    //         .borrow()
-    //         .iter()
-    //         .flat_map(|o| o.hittables.iter().map(|h| h.clone_box()))
-    //         .collect();
-    //     self.bvh = Some(BVHNode::new(hittables));
-    // }
-
-    pub fn create_rt_test_scene_spheres(&mut self) {
-
-        self.pre_scene_load();
-
-        self.max_sky_color = Vec3::new(0.5, 0.7, 1.0);
-        self.min_sky_color = Vec3::new(1.0, 1.0, 1.0);
-
-        self.rt_max_sky_color = Vec3::new(0.5, 0.7, 1.0);
-        self.rt_min_sky_color = Vec3::new(1.0, 1.0, 1.0);
-
-        self.ray_max_depth = 20;
-
-        let ground_material = Lambertian::default();
-        let sphere = SceneObject::new_sphere(
-            Vec3::new(0.0, 0.0, -1000.0),
-            1000.0,
-            Vec3::new(0.5, 0.5, 0.5),
-            4,
-            SceneObject::new_diffuse_mat(),
-        );
-        self.add_scene_object(sphere);
-
-        let lim = 11; // 11
-
-        for a in -lim..lim {
-            for b in -lim..lim {
-                let choose_mat = random_float();
-                let center = Vec3::new(a as f32 + 0.9*random_float(), b as f32 + 0.9*random_float(), 0.2);
-
-                if (center - Vec3::new(4.0, 0.0, 0.2)).len() > 0.9 {
-                    let sphere_material: Box<dyn Material>;
-                    let color;
-
-                    let unified_mat;
-
-                    if choose_mat < 0.8 {
-                        // diffuse
-                        color = Vec3::random().mul_elementwise(Vec3::random());
-                        sphere_material = Lambertian::default().clone_box();
-                        unified_mat = SceneObject::new_diffuse_mat();
-                    } else if choose_mat < 0.95 {
-                        // metal
-                        color = Vec3::random() * 0.5 + Vec3::new(0.5, 0.5, 0.5);
-                        let fuzz = random_range(0.0, 0.5);
-                        sphere_material = Metal::new(fuzz).clone_box();
-                        unified_mat = SceneObject::new_metal_mat(fuzz)
-                    } else {
-                        // glass
-                        color = Vec3::new(1.0, 1.0, 1.0);
-                        sphere_material = Dielectric::new(1.5).clone_box();
-                        unified_mat = SceneObject::new_glass_mat(0.5, 1.5);
-                    }
-                    
-                    // let sphere = Sphere::new(center, 0.2, color, 2, sphere_material);
-                    // self.add_mesh(sphere.to_mesh(2, PhongProperties::default()));
-                    
-                    let sphere = SceneObject::new_sphere(center, 0.2, color, 2, unified_mat);
-                    self.add_scene_object(sphere);
-                }
-            }
-        }
-
-        // glass
-        let sphere = SceneObject::new_sphere(
-            Vec3::new(0.0, 0.0, 1.0), 
-            1.0, 
-            Vec3::new(1.0, 1.0, 1.0), 
-            4,
-            SceneObject::new_glass_mat(0.5, 1.5)
-        );
-        self.add_scene_object(sphere);
-
-        // lambertian
-        let sphere = SceneObject::new_sphere(
-            Vec3::new(-4.0, 0.0, 1.0), 
-            1.0 ,
-            Vec3::new(0.4, 0.2, 0.1), 
-            4,
-            SceneObject::new_diffuse_mat(),
-        );
-        self.add_scene_object(sphere);
-
-        // metal
-        let sphere = SceneObject::new_sphere(
-            Vec3::new(4.0, 0.0, 1.0), 
-            1.0, 
-            Vec3::new(0.7, 0.6, 0.5), 
-            4,
-            SceneObject::new_metal_mat(0.0)
-        );
-        self.add_scene_object(sphere);
-
-        self.camera.set_fov(degrees_to_radians(20.0));
-        self.camera.pos = Vec3::new(13.0, 3.0, 2.0);
-        self.camera.look_at(&Vec3::zero());
-
-        self.defocus_angle = degrees_to_radians(0.6);
-        self.focus_dist = 10.0;
-
-        self.post_scene_load();
-    
-    }
-
-    pub fn create_rt_test_scene_quads(&mut self) {
-
-        self.pre_scene_load();
-
-        self.ray_max_depth = 10;
-
-        let origins = vec![
-            Vec3::new(-3.0, 5.0, -2.0),
-            Vec3::new(-2.0, 0.0, -2.0),
-            Vec3::new(3.0, 1.0, -2.0),
-            Vec3::new(-2.0, 1.0, 3.0),
-            Vec3::new(-2.0, 5.0, -3.0),
-        ];
-        let u_vectors = vec![
-            Vec3::new(0.0, -4.0, 0.0),
-            Vec3::new(4.0, 0.0, 0.0),
-            Vec3::new(0.0, 4.0, 0.0),
-            Vec3::new(4.0, 0.0, 0.0),
-            Vec3::new(4.0, 0.0, 0.0),
-        ];
-        let v_vectors = vec![
-            Vec3::new(0.0, 0.0, 4.0),
-            Vec3::new(0.0, 0.0, 4.0),
-            Vec3::new(0.0, 0.0, 4.0),
-            Vec3::new(0.0, 4.0, 0.0),
-            Vec3::new(0.0, -4.0, 0.0),
-        ];
-        let colors = vec![
-            Vec3::new(1.0, 0.2, 0.2),
-            Vec3::new(0.2, 1.0, 0.2),
-            Vec3::new(0.2, 0.2, 1.0),
-            Vec3::new(1.0, 0.5, 0.0),
-            Vec3::new(0.2, 0.8, 0.8),
-        ];
-
-        let mut mesh_vertices = Vec::new();
-        let mut mesh_colors = Vec::new();
-
-        for i in 0..origins.len() {
-            let origin = origins[i];
-            let u = u_vectors[i];
-            let v = v_vectors[i];
-            let color = colors[i];
-
-            mesh_vertices.push(origin);
-            mesh_vertices.push(origin+u);
-            mesh_vertices.push(origin+v);
-
-            mesh_vertices.push(origin+u);
-            mesh_vertices.push(origin+u+v);
-            mesh_vertices.push(origin+v);
-
-            mesh_colors.push(color);
-            mesh_colors.push(color);
-
-            // let t1 = Triangle::new_from_directions(origin, u, v, Vec3::new(1.0, 0.2, 0.2), &lambertian);
-            // let t2 = Triangle::new_from_directions(origin+u+v, -u, -v, Vec3::new(1.0, 0.2, 0.2), &lambertian);
-        }
-
-        let mesh = Mesh::new_from_non_indexed(mesh_vertices, mesh_colors, PhongProperties::default());
-        let scene_obj = SceneObject::new_from_mesh(mesh, Lambertian::default().clone_box(), true);
-        self.add_scene_object(scene_obj);
-
-        self.camera.set_fov(degrees_to_radians(80.0));
-        self.camera.pos = Vec3::new(0.0, 9.0, 0.0);
-        self.camera.look_at(&Vec3::zero());
-
-        self.post_scene_load();
-    }
-
-    pub fn create_rt_test_scene_simple_light(&mut self) {
-
-        self.pre_scene_load();
-
-        self.ray_max_depth = 50;
-
-        let sphere = SceneObject::new_sphere(
-            Vec3::new(0.0, 0.0, -1000.0), 
-            1000.0, 
-            Vec3::new(0.9, 0.9, 0.9), 
-            4,
-            SceneObject::new_diffuse_mat()
-        );
-        self.add_scene_object(sphere);
-
-        let sphere = SceneObject::new_sphere(
-            Vec3::new(0.0, 0.0, 2.0), 
-            2.0, 
-            Vec3::new(0.9, 0.9, 0.9), 
-            4,
-            SceneObject::new_diffuse_mat()
-        );
-        self.add_scene_object(sphere);
-
-        // let (t1, t2) = Triangle::new_quad(
-        //     Vec3::new(3.0, -2.0, 1.0),
-        //     Vec3::new(0.0, 0.0, 2.0),
-        //     Vec3::new(2.0, 0.0, 0.0),
-        //     Vec3::new(4.0, 4.0, 4.0), 
-        //     &DiffuseLight::default()
-        // );
-        // self.add_mesh(t1.to_mesh(PhongProperties::default()));
-        // self.add_mesh(t2.to_mesh(PhongProperties::default()));
-        // rt_objects.push(Box::new(t1.clone()));
-        // rt_objects.push(Box::new(t2.clone()));
-        // self.rt_lights.push(Box::new(t1));
-        // self.rt_lights.push(Box::new(t2));
-        let light_rec = SceneObject::new_rectangle_light(
-            Vec3::new(3.0, -2.0, 1.0),
-            Vec3::new(0.0, 0.0, 2.0),
-            Vec3::new(2.0, 0.0, 0.0),
-            Vec3::new(4.0, 4.0, 4.0),
-            0.1, 1000);
-        self.add_scene_object(light_rec);
-
-        self.max_sky_color = Vec3::new(0.05, 0.05, 0.05);
-        self.min_sky_color = Vec3::zero();
-
-        self.rt_max_sky_color = Vec3::zero();
-        self.rt_min_sky_color = Vec3::zero();
-
-        self.camera.pos = Vec3::new(26.0, 6.0, 3.0);
-        self.camera.look_at(&Vec3::new(0.0, 0.0, 2.0));
-
-        self.camera.set_fov(degrees_to_radians(20.0));
-        self.defocus_angle = 0.0;
-
-        self.post_scene_load();
-    }
-
-    pub fn create_rt_test_scene_cornell(&mut self) {
-        self.pre_scene_load();
-
-        self.ray_max_depth = 20;
-
-        self.max_sky_color = Vec3::new(0.1, 0.1, 0.1);
-        self.min_sky_color = Vec3::zero();
-
-        self.rt_max_sky_color = Vec3::zero();
-        self.rt_min_sky_color = Vec3::zero();
-    
-        let red_color = Vec3::new(0.65, 0.05, 0.05);
-        let green_color = Vec3::new(0.12, 0.45, 0.15);
-        let white_color = Vec3::new(0.73, 0.73, 0.73);
-        let light_color = Vec3::new(15.0, 15.0, 15.0);
-    
-        let lambert_mat = SceneObject::new_diffuse_mat();
-    
-        self.scene_objects = RwLock::new(vec![
-            SceneObject::new_rectangle(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(0.0, 55.5, 0.0),
-                Vec3::new(0.0, 0.0, 55.5),
-                green_color,
-                lambert_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(55.5, 0.0, 0.0),
-                Vec3::new(0.0, 0.0, 55.5),
-                Vec3::new(0.0, 55.5, 0.0),
-                red_color,
-                lambert_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle_light(
-                Vec3::new(36.3, 35.2, 55.4),
-                Vec3::new(0.0, -14.5, 0.0),
-                Vec3::new(-17.0, 0.0, 0.0),
-                light_color,
-                0.1,
-                1000,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(55.5, 0.0, 0.0),
-                Vec3::new(0.0, 55.5, 0.0),
-                white_color,
-                lambert_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(55.5, 55.5, 55.5),
-                Vec3::new(0.0, -55.5, 0.0),
-                Vec3::new(-55.5, 0.0, 0.0),
-                white_color,
-                lambert_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(0.0, 55.5, 0.0),
-                Vec3::new(55.5, 0.0, 0.0),
-                Vec3::new(0.0, 0.0, 55.5),
-                white_color,
-                lambert_mat.clone(),
-                true,
-            ),
-        ]);
-    
-        // Left box
-        let mut left_box = SceneObject::new_box_from_corners(
-            Vec3::new(27.1, 29.5, 0.0),
-            Vec3::new(10.6, 46.0, 33.0),
-            white_color,
-            lambert_mat.clone(),
-        );
-        left_box.rotate_around_center(degrees_to_radians(15.0), 0.0);
-        self.add_scene_object(left_box);
-    
-        // Right box
-        let mut right_box = SceneObject::new_box_from_corners(
-            Vec3::new(45.1, 6.5, 0.0),
-            Vec3::new(28.6, 23.0, 16.5),
-            white_color,
-            lambert_mat.clone(),
-        );
-        right_box.rotate_around_center(degrees_to_radians(-18.0), 0.0);
-        self.add_scene_object(right_box);
-    
-        // Camera
-        self.camera.set_fov(degrees_to_radians(40.0));
-        self.camera.pos = Vec3::new(27.8, -80.0, 27.8);
-        self.camera.look_at(&Vec3::new(27.8, 0.0, 27.8));
-        self.defocus_angle = 0.0;
-    
-        // Updating UI and rebuilding BVH
-        self.post_scene_load();
-    }
-
-
-    pub fn create_rt_test_scene_cornell_plus_plus(&mut self, stl_bytes: &[u8]) {
-        self.pre_scene_load();
-
-        self.ray_max_depth = 50;
-        
-        self.max_sky_color = Vec3::new(0.1, 0.1, 0.1);
-        self.min_sky_color = Vec3::zero();
-
-        self.rt_max_sky_color = Vec3::zero();
-        self.rt_min_sky_color = Vec3::zero();
-    
-        // let red_color = Vec3::new(0.65, 0.05, 0.05);
-        // let green_color = Vec3::new(0.12, 0.45, 0.15);
-        let red_color = Vec3::new(1.0, 0.2, 0.2);
-        let green_color = Vec3::new(0.2, 1.0, 0.2);
-        let grey_color = Vec3::new(0.73, 0.73, 0.73);
-        let light_color = Vec3::new(10.0,  10.0, 10.0);
-    
-        let lambert_mat = SceneObject::new_diffuse_mat();
-        let metal_mat = SceneObject::new_metal_mat(0.0);
-    
-        self.scene_objects = RwLock::new(vec![
-            SceneObject::new_rectangle(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(0.0, 55.5, 0.0),
-                Vec3::new(0.0, 0.0, 55.5),
-                green_color,
-                metal_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(55.5, 0.0, 0.0),
-                Vec3::new(0.0, 0.0, 55.5),
-                Vec3::new(0.0, 55.5, 0.0),
-                red_color,
-                metal_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle_light(
-                Vec3::new(36.3, 35.2, 55.4),
-                Vec3::new(0.0, -14.5, 0.0),
-                Vec3::new(-17.0, 0.0, 0.0),
-                light_color,
-                0.1,
-                1000,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(55.5, 0.0, 0.0),
-                Vec3::new(0.0, 55.5, 0.0),
-                grey_color,
-                lambert_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(55.5, 55.5, 55.5),
-                Vec3::new(0.0, -55.5, 0.0),
-                Vec3::new(-55.5, 0.0, 0.0),
-                grey_color,
-                lambert_mat.clone(),
-                true,
-            ),
-            SceneObject::new_rectangle(
-                Vec3::new(0.0, 55.5, 0.0),
-                Vec3::new(55.5, 0.0, 0.0),
-                Vec3::new(0.0, 0.0, 55.5),
-                grey_color,
-                lambert_mat.clone(),
-                true,
-            ),
-        ]);
-    
-        // Left statue
-        let statue_color = Vec3::new(0.8, 0.8, 0.8);
-        let (statue_phong, statue_mat) = SceneObject::new_diffuse_mat();
-        let mut statue_mesh = Mesh::new_from_stl_bytes(stl_bytes, statue_color, statue_phong);
-        statue_mesh.scale_by(0.15);
-        statue_mesh.rotate_around_center(-PI/2.0, PI/2.0);
-        statue_mesh.set_center(Vec3::new(18.5, 37.0, 24.75));
-        statue_mesh.rotate_around_center(-degrees_to_radians(15.0), 0.0);
-        let statue_obj = SceneObject::new_from_mesh(
-            statue_mesh, 
-            statue_mat.clone_box(),
-            true
-        );
-        self.add_scene_object(statue_obj);
-    
-        // Right sphere
-        let glass_mat = SceneObject::new_glass_mat(0.5, 1.5);
-        let right_sphere = SceneObject::new_sphere(
-            Vec3::new(36.8, 15.0, 12.0), 
-            12.0,
-            Vec3::white(),
-            4, 
-            glass_mat.clone()
-        );
-        self.add_scene_object(right_sphere);
-    
-        // Camera
-        self.camera.set_fov(degrees_to_radians(40.0));
-        self.camera.pos = Vec3::new(27.8, -80.0, 27.8);
-        self.camera.look_at(&Vec3::new(27.8, 0.0, 27.8));
-        self.defocus_angle = 0.0;
-
-        // Updating UI and rebuilding BVH
-        self.post_scene_load();
-    }
-}
\ No newline at end of file
+}
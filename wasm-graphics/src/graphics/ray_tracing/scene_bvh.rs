@@ -0,0 +1,556 @@
+use std::{cell::RefCell, collections::HashMap, sync::{Arc, Weak}};
+
+use rayon::prelude::*;
+
+use crate::{console_log, graphics::scene_object::SceneObject, utils::{math::{Mat4, Vec3}, utils::get_time}};
+
+use super::{bvh::{AABoundingBox, BVHBuildOptions, BVHBuildStats, BVHTree, FlattenedBVH, FlattenedBVHNode, TraversalStats}, hittable::Hittable, material::Material, rt::{HitRecord, Ray}};
+
+thread_local! {
+    // separate from the per-object traversal stack, which is in use while the top level is being walked
+    static TLAS_TRAVERSAL_STACK: RefCell<Vec<usize>> = RefCell::new(Vec::with_capacity(256));
+}
+
+/// A refit is thrown away for a full rebuild once it makes the object's tree
+/// this many times worse (by SAH cost) than it was right after its last build
+const MAX_REFIT_SAH_RATIO: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BLASState {
+    UpToDate,
+    /// Only the object's transform or material override changed, its tree is still good
+    NeedsPlacement,
+    /// The object's hittables moved or changed, but there are still as many of them
+    NeedsRefit,
+    NeedsRebuild,
+}
+
+/// Where an object's BLAS is in the world, and what it looks like there
+#[derive(Debug)]
+struct BLASPlacement {
+    transform: Mat4,
+    inverse: Mat4,
+    normal_matrix: Mat4,
+    material: Option<Box<dyn Material>>,
+    color: Option<Vec3>,
+    /// World space bounds of the transformed BLAS
+    bounding_box: AABoundingBox,
+}
+
+impl BLASPlacement {
+    /// None if the object's hittables can be used as they are
+    fn new(scene_obj: &SceneObject, bvh: &FlattenedBVH) -> Option<Self> {
+        if scene_obj.transform.is_identity() && scene_obj.material_override.is_none() {
+            return None;
+        }
+        let transform = scene_obj.transform.to_mat4();
+        let local_box = bvh.get_bounding_box().cloned().unwrap_or(AABoundingBox::empty());
+        let mut bounding_box = AABoundingBox::empty();
+        for corner in 0..8 {
+            let local_corner = Vec3::new(
+                if corner & 1 == 0 { local_box.min.x } else { local_box.max.x },
+                if corner & 2 == 0 { local_box.min.y } else { local_box.max.y },
+                if corner & 4 == 0 { local_box.min.z } else { local_box.max.z },
+            );
+            bounding_box.expand_to_include_point(transform.transform_point(local_corner));
+        }
+        bounding_box.pad_to_minimums();
+
+        let material_override = scene_obj.material_override.as_ref();
+        return Some(BLASPlacement {
+            transform,
+            inverse: transform.affine_inverse().unwrap_or_default(),
+            normal_matrix: transform.normal_matrix(),
+            material: material_override.map(|m| m.material.clone_box()),
+            color: material_override.and_then(|m| m.color),
+            bounding_box,
+        });
+    }
+}
+
+/// Bottom level BVH of one SceneObject
+#[derive(Debug)]
+struct ObjectBLAS {
+    /// None if the object has nothing to ray trace.
+    /// In the object's own space, objects sharing geometry all point to the same tree.
+    bvh: Option<Arc<FlattenedBVH>>,
+    /// None if the object isn't transformed and has no material override
+    placement: Option<BLASPlacement>,
+    state: BLASState,
+    /// SAH cost right after the last full build, to notice when refits have degraded the tree too much
+    built_sah_cost: f32,
+}
+
+impl ObjectBLAS {
+    fn unbuilt() -> Self {
+        return ObjectBLAS {
+            bvh: None,
+            placement: None,
+            state: BLASState::NeedsRebuild,
+            built_sah_cost: 0.0,
+        };
+    }
+
+    fn get_bounding_box(&self) -> Option<&AABoundingBox> {
+        let bvh_box = self.bvh.as_ref()?.get_bounding_box();
+        return match &self.placement {
+            Some(placement) => Some(&placement.bounding_box),
+            None => bvh_box,
+        };
+    }
+
+    fn update_placement(&mut self, scene_obj: &SceneObject) {
+        self.placement = self.bvh.as_ref().and_then(|bvh| BLASPlacement::new(scene_obj, bvh));
+        self.state = BLASState::UpToDate;
+    }
+
+    fn set_bvh(&mut self, scene_obj: &SceneObject, bvh: Option<Arc<FlattenedBVH>>) {
+        self.built_sah_cost = bvh.as_ref().map_or(0.0, |bvh| bvh.get_stats().sah_cost);
+        self.bvh = bvh;
+        self.update_placement(scene_obj);
+    }
+
+    fn rebuild(&mut self, scene_obj: &SceneObject, options: &BVHBuildOptions) {
+        let bvh = build_bvh(&scene_obj.hittables, options);
+        self.set_bvh(scene_obj, bvh);
+    }
+
+    /// Returns false if the object had to be rebuilt instead
+    fn refit(&mut self, scene_obj: &SceneObject, options: &BVHBuildOptions) -> bool {
+        // a tree other objects use can't be refit in place
+        let own_bvh = match &mut self.bvh {
+            Some(bvh) if Arc::strong_count(bvh) == 1 => Some(Arc::make_mut(bvh)),
+            _ => None,
+        };
+        match own_bvh {
+            Some(bvh) if bvh.get_hittable_count() == scene_obj.hittables.len() => {
+                bvh.refit(&scene_obj.hittables);
+                if bvh.get_stats().sah_cost > MAX_REFIT_SAH_RATIO * self.built_sah_cost {
+                    self.rebuild(scene_obj, options);
+                    return false;
+                }
+                self.update_placement(scene_obj);
+                return true;
+            },
+            _ => {
+                self.rebuild(scene_obj, options);
+                return false;
+            }
+        }
+    }
+}
+
+fn build_bvh(hittables: &[Box<dyn Hittable>], options: &BVHBuildOptions) -> Option<Arc<FlattenedBVH>> {
+    if hittables.is_empty() {
+        return None;
+    }
+    let hittables = hittables.iter().map(|h| h.clone_box()).collect();
+    return Some(Arc::new(FlattenedBVH::new_with_options(hittables, options)));
+}
+
+/// What the last SceneBVH::update() had to do
+#[derive(Debug, Clone, Default)]
+pub struct SceneBVHUpdateStats {
+    pub update_time_ms: f64,
+    pub objects_rebuilt: usize,
+    pub objects_refit: usize,
+    /// Objects that only moved (or had their material overridden)
+    pub objects_placed: usize,
+    /// Objects that got the tree of geometry shared with other objects, without building anything
+    pub objects_shared: usize,
+    pub tlas_rebuilt: bool,
+}
+
+impl std::fmt::Display for SceneBVHUpdateStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} objects rebuilt, {} refit, {} moved, {} reused a shared tree, top level {}, took {:.1}ms",
+            self.objects_rebuilt,
+            self.objects_refit,
+            self.objects_placed,
+            self.objects_shared,
+            if self.tlas_rebuilt { "rebuilt" } else { "unchanged" },
+            self.update_time_ms,
+        )
+    }
+}
+
+/// A node of a SceneBVH, for drawing the tree
+#[derive(Debug, Clone)]
+pub struct BVHNodeBox {
+    pub bounding_box: AABoundingBox,
+    /// Depth in the two levels taken as one tree: the top level's root is 0, an object's root is one below its top level leaf
+    pub depth: usize,
+    /// Index of the object whose tree the node is in, the box is then in that object's own space.
+    /// None for the top level, which is in world space.
+    pub object_index: Option<usize>,
+}
+
+/// A built tree and the hittables it was built from. Weak, so it doesn't keep either alive
+/// or stop the hittables from being edited in place.
+type SharedBLAS = (Weak<Vec<Box<dyn Hittable>>>, Weak<FlattenedBVH>);
+
+/// Two level BVH over the whole scene: one bottom level BVH (BLAS) per SceneObject, in the same order
+/// as Game::scene_objects, and a top level BVH (TLAS) over the bounds of the objects.
+///
+/// Each BLAS is in its object's own space, rays are taken into it when they reach its top level leaf.
+/// Moving an object only changes where its BLAS is placed, and objects sharing geometry (instances)
+/// share one BLAS. Edits only mark the objects they touched, update() then refits or rebuilds just
+/// those and rebuilds the top level, which only has one entry per object so it's cheap.
+#[derive(Debug, Default)]
+pub struct SceneBVH {
+    blases: Vec<ObjectBLAS>,
+    /// Every tree built, by the address of the hittables it was built from
+    shared_blases: HashMap<usize, SharedBLAS>,
+    /// Leaves of the top level refer to blases through tlas.order
+    tlas: BVHTree,
+    tlas_is_dirty: bool,
+    build_options: Option<BVHBuildOptions>,
+    last_update: SceneBVHUpdateStats,
+}
+
+impl SceneBVH {
+    pub fn new() -> Self {
+        return SceneBVH::default();
+    }
+
+    /// Drops everything, the next update() builds from scratch
+    pub fn clear(&mut self) {
+        self.blases.clear();
+        self.shared_blases.clear();
+        self.tlas = BVHTree::default();
+        self.tlas_is_dirty = true;
+    }
+
+    /// True if there's nothing to ray trace (or update() hasn't been called since the scene was loaded)
+    pub fn is_empty(&self) -> bool {
+        return self.tlas.nodes.is_empty();
+    }
+
+    /// The hittables of the object at index changed (moved, or had their material changed)
+    pub fn mark_object_changed(&mut self, index: usize) {
+        if let Some(blas) = self.blases.get_mut(index) {
+            if blas.state == BLASState::UpToDate || blas.state == BLASState::NeedsPlacement {
+                blas.state = BLASState::NeedsRefit;
+            }
+            self.tlas_is_dirty = true;
+        }
+    }
+
+    /// The transform or material override of the object at index changed, but not its hittables
+    pub fn mark_object_moved(&mut self, index: usize) {
+        if let Some(blas) = self.blases.get_mut(index) {
+            if blas.state == BLASState::UpToDate {
+                blas.state = BLASState::NeedsPlacement;
+            }
+            self.tlas_is_dirty = true;
+        }
+    }
+
+    /// An object was inserted into the scene at index
+    pub fn insert_object(&mut self, index: usize) {
+        if index <= self.blases.len() {
+            self.blases.insert(index, ObjectBLAS::unbuilt());
+            // the top level refers to objects by index, which just shifted
+            self.tlas = BVHTree::default();
+            self.tlas_is_dirty = true;
+        }
+    }
+
+    /// The object at index was removed from the scene
+    pub fn remove_object(&mut self, index: usize) {
+        if index < self.blases.len() {
+            self.blases.remove(index);
+            // the top level refers to objects by index, which just shifted
+            self.tlas = BVHTree::default();
+            self.tlas_is_dirty = true;
+        }
+    }
+
+    /// The tree built from exactly these hittables, if there is one
+    fn find_shared_bvh(&self, hittables: &Arc<Vec<Box<dyn Hittable>>>) -> Option<Arc<FlattenedBVH>> {
+        let (shared_hittables, bvh) = self.shared_blases.get(&(Arc::as_ptr(hittables) as usize))?;
+        if !std::ptr::eq(shared_hittables.as_ptr(), Arc::as_ptr(hittables)) {
+            return None;
+        }
+        return bvh.upgrade();
+    }
+
+    /// Brings the BVH up to date with scene_objects, only touching objects marked as changed.
+    /// Everything is rebuilt if the build options changed or the object count doesn't match.
+    pub fn update(&mut self, scene_objects: &[SceneObject], options: &BVHBuildOptions) {
+        let start_time = get_time();
+
+        if self.blases.len() != scene_objects.len() || self.build_options != Some(*options) {
+            self.blases = scene_objects.iter().map(|_| ObjectBLAS::unbuilt()).collect();
+            self.shared_blases.clear();
+            self.build_options = Some(*options);
+            self.tlas_is_dirty = true;
+        }
+        // forget trees that nothing uses anymore, or whose hittables are gone
+        self.shared_blases.retain(|_, (hittables, bvh)| hittables.strong_count() > 0 && bvh.strong_count() > 0);
+
+        // geometry used by several objects is looked up (or built once) here, before the parallel pass.
+        // Changed geometry goes first, so the others don't pick up the tree from before the change.
+        let mut objects_shared = 0;
+        let mut shared_rebuilt = 0;
+        for pass_state in [BLASState::NeedsRefit, BLASState::NeedsRebuild] {
+            for (i, scene_obj) in scene_objects.iter().enumerate() {
+                if self.blases[i].state != pass_state || !scene_obj.shares_geometry() {
+                    continue;
+                }
+                let shared_bvh = if pass_state == BLASState::NeedsRebuild { self.find_shared_bvh(&scene_obj.hittables) } else { None };
+                match shared_bvh {
+                    Some(bvh) => {
+                        self.blases[i].set_bvh(scene_obj, Some(bvh));
+                        objects_shared += 1;
+                    },
+                    None => {
+                        self.blases[i].rebuild(scene_obj, options);
+                        self.register_bvh(i, scene_obj);
+                        shared_rebuilt += 1;
+                    },
+                }
+            }
+        }
+
+        let (objects_rebuilt, objects_refit, objects_placed) = self.blases
+            .par_iter_mut()
+            .zip(scene_objects.par_iter())
+            .map(|(blas, scene_obj)| {
+                match blas.state {
+                    BLASState::UpToDate => (0, 0, 0),
+                    BLASState::NeedsPlacement => {
+                        blas.update_placement(scene_obj);
+                        (0, 0, 1)
+                    },
+                    BLASState::NeedsRefit => if blas.refit(scene_obj, options) { (0, 1, 0) } else { (1, 0, 0) },
+                    BLASState::NeedsRebuild => {
+                        blas.rebuild(scene_obj, options);
+                        (1, 0, 0)
+                    },
+                }
+            })
+            .reduce(|| (0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+
+        // so instances added later find these trees
+        for (i, scene_obj) in scene_objects.iter().enumerate() {
+            self.register_bvh(i, scene_obj);
+        }
+
+        let tlas_rebuilt = self.tlas_is_dirty;
+        if self.tlas_is_dirty {
+            self.rebuild_tlas(options);
+        }
+
+        self.last_update = SceneBVHUpdateStats {
+            update_time_ms: get_time() - start_time,
+            objects_rebuilt: objects_rebuilt + shared_rebuilt,
+            objects_refit,
+            objects_placed,
+            objects_shared,
+            tlas_rebuilt,
+        };
+        console_log!("scene bvh updated: {}", self.last_update);
+    }
+
+    fn register_bvh(&mut self, index: usize, scene_obj: &SceneObject) {
+        let Some(bvh) = &self.blases[index].bvh else {
+            return;
+        };
+        let key = Arc::as_ptr(&scene_obj.hittables) as usize;
+        let is_registered = self.shared_blases
+            .get(&key)
+            .is_some_and(|(_, shared_bvh)| std::ptr::eq(shared_bvh.as_ptr(), Arc::as_ptr(bvh)));
+        if !is_registered {
+            self.shared_blases.insert(key, (Arc::downgrade(&scene_obj.hittables), Arc::downgrade(bvh)));
+        }
+    }
+
+    fn rebuild_tlas(&mut self, options: &BVHBuildOptions) {
+        let (blas_indices, bounding_boxes): (Vec<usize>, Vec<AABoundingBox>) = self.blases
+            .iter()
+            .enumerate()
+            .filter_map(|(i, blas)| {
+                let bounding_box = blas.get_bounding_box()?;
+                Some((i, bounding_box.clone()))
+            })
+            .unzip();
+
+        self.tlas = BVHTree::build(&bounding_boxes, options);
+        for i in self.tlas.order.iter_mut() {
+            *i = blas_indices[*i];
+        }
+        self.tlas_is_dirty = false;
+    }
+
+    /// False if objects were changed, added or removed since the last update()
+    pub fn is_up_to_date(&self, object_count: usize) -> bool {
+        return !self.tlas_is_dirty && self.blases.len() == object_count && self.blases.iter().all(|blas| blas.state == BLASState::UpToDate);
+    }
+
+    /// Every node of the top level, and under each of its leaves every node of the objects in it
+    pub fn get_node_boxes(&self) -> Vec<BVHNodeBox> {
+        let mut node_boxes = Vec::new();
+        if self.tlas.nodes.is_empty() {
+            return node_boxes;
+        }
+        let mut stack = vec![(self.tlas.nodes.len() - 1, 0)];
+        while let Some((node_index, depth)) = stack.pop() {
+            let Some(node) = self.tlas.nodes.get(node_index) else {
+                continue;
+            };
+            node_boxes.push(BVHNodeBox { bounding_box: node.get_bounding_box().clone(), depth, object_index: None });
+            match node {
+                FlattenedBVHNode::Leaf { first_hittable_index, hittable_count, .. } => {
+                    for &blas_index in &self.tlas.order[*first_hittable_index..*first_hittable_index + *hittable_count] {
+                        let Some(bvh) = &self.blases[blas_index].bvh else {
+                            continue;
+                        };
+                        for (bounding_box, blas_depth) in bvh.get_node_boxes() {
+                            node_boxes.push(BVHNodeBox { bounding_box, depth: depth + 1 + blas_depth, object_index: Some(blas_index) });
+                        }
+                    }
+                },
+                FlattenedBVHNode::Internal { left_index, right_index, .. } => {
+                    stack.push((*right_index, depth + 1));
+                    stack.push((*left_index, depth + 1));
+                },
+            }
+        }
+        return node_boxes;
+    }
+
+    pub fn get_last_update(&self) -> &SceneBVHUpdateStats {
+        return &self.last_update;
+    }
+
+    /// Stats over all objects' trees together, as if they were one tree under the top level
+    pub fn get_stats(&self) -> BVHBuildStats {
+        let mut stats = BVHBuildStats {
+            build_time_ms: self.last_update.update_time_ms,
+            node_count: self.tlas.nodes.len(),
+            max_depth: self.tlas.stats.max_depth,
+            ..BVHBuildStats::default()
+        };
+        let root_area = match self.tlas.get_bounding_box() {
+            Some(root_box) => root_box.get_surface_area().max(1e-12),
+            None => return stats,
+        };
+
+        // the top level's inner nodes cost one box test each, its leaves hand the ray straight to the objects
+        stats.sah_cost = self.tlas.nodes
+            .iter()
+            .filter(|node| matches!(node, FlattenedBVHNode::Internal { .. }))
+            .map(|node| node.get_bounding_box().get_surface_area() / root_area)
+            .sum();
+
+        let mut max_blas_depth = 0;
+        for blas in self.blases.iter() {
+            let Some(bvh) = &blas.bvh else {
+                continue;
+            };
+            let blas_stats = bvh.get_stats();
+            stats.hittable_count += blas_stats.hittable_count;
+            stats.node_count += blas_stats.node_count;
+            stats.leaf_count += blas_stats.leaf_count;
+            stats.max_leaf_size = stats.max_leaf_size.max(blas_stats.max_leaf_size);
+            max_blas_depth = max_blas_depth.max(blas_stats.max_depth);
+            if let Some(blas_box) = blas.get_bounding_box() {
+                stats.sah_cost += blas_box.get_surface_area() / root_area * blas_stats.sah_cost;
+            }
+        }
+        stats.max_depth += max_blas_depth;
+        return stats;
+    }
+
+    #[inline(always)]
+    pub fn hit<'a>(&'a self, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord<'a>) -> bool {
+        return self.traverse::<false>(ray, t_min, t_max, hit_record, &mut TraversalStats::default());
+    }
+
+    /// Same as hit, but also adds what the traversal cost to stats, counting the nodes of both levels
+    pub fn hit_with_stats<'a>(&'a self, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord<'a>, stats: &mut TraversalStats) -> bool {
+        return self.traverse::<true>(ray, t_min, t_max, hit_record, stats);
+    }
+
+    #[inline(always)]
+    fn traverse<'a, const COUNT: bool>(&'a self, ray: &Ray, t_min: f32, mut t_max: f32, hit_record: &mut HitRecord<'a>, stats: &mut TraversalStats) -> bool {
+
+        let mut hit_anything = false;
+        if self.tlas.nodes.is_empty() {
+            return false;
+        }
+
+        TLAS_TRAVERSAL_STACK.with(|stack_cell| {
+            let mut stack = stack_cell.borrow_mut();
+            stack.clear();
+            stack.push(self.tlas.nodes.len() - 1); // start with the root node
+
+            while let Some(node_index) = stack.pop() {
+                if node_index >= self.tlas.nodes.len() {
+                    continue;
+                }
+                if COUNT {
+                    stats.nodes_visited += 1;
+                }
+                match &self.tlas.nodes[node_index] {
+                    FlattenedBVHNode::Leaf { bounding_box, first_hittable_index, hittable_count } => {
+                        // each object's tree tests its own root box anyway
+                        if *hittable_count > 1 && !bounding_box.hit(ray, t_min, t_max) {
+                            continue;
+                        }
+                        for &blas_index in &self.tlas.order[*first_hittable_index..*first_hittable_index + *hittable_count] {
+                            let blas = &self.blases[blas_index];
+                            let Some(bvh) = &blas.bvh else {
+                                continue;
+                            };
+                            let hit = match &blas.placement {
+                                Some(placement) => Self::hit_placed::<COUNT>(bvh, placement, ray, t_min, t_max, hit_record, stats),
+                                None => bvh.traverse::<COUNT>(ray, t_min, t_max, hit_record, stats),
+                            };
+                            if hit {
+                                t_max = hit_record.t; // update closest hit so far
+                                hit_anything = true;
+                            }
+                        }
+                    },
+                    FlattenedBVHNode::Internal { bounding_box, left_index, right_index } => {
+                        if bounding_box.hit(ray, t_min, t_max) {
+                            stack.push(*left_index);
+                            stack.push(*right_index);
+                        }
+                    }
+                }
+            }
+        });
+
+        return hit_anything;
+    }
+
+    /// Traces the ray through the object's tree in its own space. The direction isn't normalized
+    /// on the way in, so t means the same thing in both spaces.
+    #[inline(always)]
+    fn hit_placed<'a, const COUNT: bool>(bvh: &'a FlattenedBVH, placement: &'a BLASPlacement, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord<'a>, stats: &mut TraversalStats) -> bool {
+        let local_ray = Ray::new(placement.inverse.transform_point(ray.origin), placement.inverse.transform_vector(ray.direction));
+        if !bvh.traverse::<COUNT>(&local_ray, t_min, t_max, hit_record, stats) {
+            return false;
+        }
+        hit_record.pos = ray.at(hit_record.t);
+        // the face normal already points against the ray, and the normal matrix keeps it that way
+        hit_record.normal = placement.normal_matrix.transform_vector(hit_record.normal).normalized();
+        hit_record.reflection_offset = placement.transform.transform_vector(hit_record.reflection_offset);
+        if hit_record.normal_map.is_some() {
+            hit_record.tangent.dir = placement.transform.transform_vector(hit_record.tangent.dir);
+        }
+        if let Some(material) = &placement.material {
+            hit_record.material = Some(material.as_ref());
+        }
+        if let Some(color) = placement.color {
+            hit_record.surface_color = color;
+            hit_record.texture = None;
+        }
+        return true;
+    }
+}
@@ -0,0 +1,1018 @@
+use std::{collections::HashMap, f32::consts::PI, sync::Arc};
+
+use image::load_from_memory;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::math::{degrees_to_radians, radians_to_degrees, Quat, Transform, Vec3};
+
+use super::{game::Game, gltf_parser::extract_combined_mesh_from_raw_glb_bytes, lighting::Light, mesh::{Mesh, PhongProperties}, normal_map::{NormalMap, NormalMapKind}, ray_tracing::material::Material, scene_graph::SceneGraph, scene_object::{MaterialOverride, SceneObject, MIN_SCALE}, scene_snapshot::MaterialSnapshot, texture::Texture};
+
+// Scene files are JSON, see wasm-graphics/scenes/ for examples.
+// Positions/colors are [x, y, z] / [r, g, b] arrays, angles are in degrees, z is up.
+// Mesh assets are either a path (looked up in the SceneAssets passed to the loader)
+// or embedded as base64. Bump map images are always a path.
+
+/// Raw bytes of the mesh and image files a scene refers to, keyed by the path used in the scene file.
+pub type SceneAssets = HashMap<String, Vec<u8>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub name: String,
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub sky: SkyDescription,
+    #[serde(default = "default_ray_max_depth")]
+    pub ray_max_depth: usize,
+    pub objects: Vec<ObjectDescription>,
+    /// Groups for the scene graph (see scene_graph.rs), parents always come before their children
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<NodeDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub pos: [f32; 3],
+    /// If set, the camera is pointed at this position and yaw/pitch are ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub look_at: Option<[f32; 3]>,
+    #[serde(default)]
+    pub yaw_degrees: f32,
+    #[serde(default)]
+    pub pitch_degrees: f32,
+    #[serde(default = "default_fov_degrees")]
+    pub fov_degrees: f32,
+    #[serde(default)]
+    pub defocus_angle_degrees: f32,
+    #[serde(default = "default_focus_dist")]
+    pub focus_dist: f32,
+}
+
+/// Gradient from min (horizon) to max (straight up), separately for the rasterizer and the ray tracer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkyDescription {
+    pub max_color: [f32; 3],
+    pub min_color: [f32; 3],
+    pub rt_max_color: [f32; 3],
+    pub rt_min_color: [f32; 3],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectDescription {
+    #[serde(flatten)]
+    pub shape: ShapeDescription,
+    /// Ignored for lights. If omitted, the object is diffuse (Lambertian)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialDescription>,
+    /// Overrides for the rasterizer's lighting model, applied after the material's defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phong: Option<PhongDescription>,
+    /// Applied in order after the object is built
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<TransformDescription>,
+    #[serde(default = "default_true")]
+    pub editable: bool,
+    /// Copies that share this object's geometry instead of loading/building it again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<InstanceDescription>,
+    /// Index into SceneDescription::nodes of the node standing for this object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceDescription {
+    /// Applied in order, starting from where the original object ended up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<TransformDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 3]>,
+    /// If either is set, the instance gets its own material instead of sharing the original's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phong: Option<PhongDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDescription {
+    pub name: String,
+    /// Index of an earlier node, left out for the top level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    /// Relative to the parent. Left out for nodes standing for an object, those follow the object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformPartsDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum ShapeDescription {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        color: [f32; 3],
+        #[serde(default = "default_subdivisions")]
+        subdivisions: u32,
+    },
+    Rectangle {
+        origin: [f32; 3],
+        u: [f32; 3],
+        v: [f32; 3],
+        color: [f32; 3],
+        #[serde(default)]
+        cull_faces: bool,
+    },
+    Box {
+        corner1: [f32; 3],
+        corner2: [f32; 3],
+        color: [f32; 3],
+    },
+    Checkerboard {
+        center: [f32; 3],
+        radius: i32,
+        color1: [f32; 3],
+        color2: [f32; 3],
+        #[serde(default)]
+        cull_faces: bool,
+    },
+    /// Non-indexed triangle list, three vertices and one color per triangle
+    Triangles {
+        vertices: Vec<[f32; 3]>,
+        colors: Vec<[f32; 3]>,
+        /// One per vertex for smooth shading, left out for flat shading
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        normals: Vec<[f32; 3]>,
+    },
+    Mesh {
+        asset: MeshAsset,
+        /// Required for STL, optional override for GLB (which has its own colors)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<[f32; 3]>,
+        #[serde(default = "default_one")]
+        color_multiplier: f32,
+        /// Over the mesh's texture coordinates, so only for GLB meshes that have them
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bump_map: Option<BumpMapDescription>,
+    },
+    SphereLight {
+        center: [f32; 3],
+        radius: f32,
+        color: [f32; 3],
+        #[serde(default = "default_subdivisions")]
+        subdivisions: u32,
+    },
+    RectangleLight {
+        origin: [f32; 3],
+        u: [f32; 3],
+        v: [f32; 3],
+        color: [f32; 3],
+        #[serde(default = "default_light_min_dist")]
+        min_dist: f32,
+    },
+    BoxLight {
+        corner1: [f32; 3],
+        corner2: [f32; 3],
+        color: [f32; 3],
+        #[serde(default = "default_light_min_dist")]
+        min_dist: f32,
+    },
+    /// Directional light with a single shadow map, drawn as a small sphere
+    SpotLight {
+        pos: [f32; 3],
+        look_at: [f32; 3],
+        fov_degrees: f32,
+        color: [f32; 3],
+        min_dist: f32,
+        #[serde(default = "default_shadow_map_size")]
+        shadow_map_size: usize,
+        #[serde(default = "default_light_subdivisions")]
+        subdivisions: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeshFormat {
+    Stl,
+    Glb,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshAsset {
+    /// Defaults to the path's extension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<MeshFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Base64 encoded file contents, used instead of path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedded: Option<String>,
+}
+
+/// Grayscale height map, white is high
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BumpMapDescription {
+    pub path: String,
+    #[serde(default = "default_one")]
+    pub strength: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialDescription {
+    Diffuse,
+    Metal {
+        #[serde(default)]
+        fuzz: f32,
+    },
+    Glass {
+        #[serde(default = "default_glass_alpha")]
+        alpha: f32,
+        #[serde(default = "default_ior")]
+        ior: f32,
+    },
+    Light {
+        /// If set, the object also lights the rasterized scene, like objects made lights in the
+        /// editor, and glows with its color times intensity
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        intensity: Option<f32>,
+    },
+    Glossy {
+        #[serde(default = "default_ior")]
+        ior: f32,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhongDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ambient: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diffuse: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub specular: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shininess: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cull_faces: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformDescription {
+    Translate([f32; 3]),
+    /// Moves the object's center to this position
+    Center([f32; 3]),
+    /// Uniform scale around the object's center
+    Scale(f32),
+    /// Scales along the object's own x, y and z axes, keeping its center in place
+    ScaleAxes([f32; 3]),
+    /// Scales uniformly so that the bounding sphere has this radius
+    FitRadius(f32),
+    /// Rotates around the object's center, z (yaw) first, then y, then x (roll)
+    Rotate {
+        #[serde(default)]
+        z_degrees: f32,
+        #[serde(default)]
+        y_degrees: f32,
+        #[serde(default)]
+        x_degrees: f32,
+    },
+    /// Rotates around an axis through the object's center (right handed)
+    RotateAxis {
+        axis: [f32; 3],
+        degrees: f32,
+    },
+    /// Moves the object up/down so its lowest point is at this height
+    RestOnZ(f32),
+    /// Replaces the whole transform, from the space the shape is described in to the world,
+    /// instead of adding to it. Scenes saved from the editor place objects with this.
+    Set(TransformPartsDescription),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TransformPartsDescription {
+    #[serde(default)]
+    pub translation: [f32; 3],
+    /// Quaternion, [w, x, y, z]
+    #[serde(default = "default_rotation")]
+    pub rotation: [f32; 4],
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+}
+
+fn default_ray_max_depth() -> usize { 20 }
+fn default_fov_degrees() -> f32 { 90.0 }
+fn default_focus_dist() -> f32 { 10.0 }
+fn default_true() -> bool { true }
+fn default_one() -> f32 { 1.0 }
+fn default_subdivisions() -> u32 { 3 }
+fn default_light_subdivisions() -> u32 { 2 }
+fn default_light_min_dist() -> f32 { 0.1 }
+fn default_shadow_map_size() -> usize { 1000 }
+fn default_glass_alpha() -> f32 { 0.5 }
+fn default_ior() -> f32 { 1.5 }
+fn default_rotation() -> [f32; 4] { [1.0, 0.0, 0.0, 0.0] }
+fn default_scale() -> [f32; 3] { [1.0, 1.0, 1.0] }
+
+impl Default for SkyDescription {
+    fn default() -> Self {
+        // daylight
+        return SkyDescription {
+            max_color: [0.5, 0.7, 1.0],
+            min_color: [1.0, 1.0, 1.0],
+            rt_max_color: [0.5, 0.7, 1.0],
+            rt_min_color: [1.0, 1.0, 1.0],
+        };
+    }
+}
+
+#[inline(always)]
+fn vec3(a: [f32; 3]) -> Vec3 {
+    return Vec3::new(a[0], a[1], a[2]);
+}
+fn arr(v: Vec3) -> [f32; 3] {
+    return [v.x, v.y, v.z];
+}
+
+// LOADING AND SAVING
+impl SceneDescription {
+    pub fn from_json(json: &str) -> Result<SceneDescription, String> {
+        return serde_json::from_str(json).map_err(|e| format!("Invalid scene file: {}", e));
+    }
+    pub fn from_json_bytes(bytes: &[u8]) -> Result<SceneDescription, String> {
+        return serde_json::from_slice(bytes).map_err(|e| format!("Invalid scene file: {}", e));
+    }
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string_pretty(self).expect("SceneDescription is always serializable");
+    }
+
+    /// Paths of every mesh asset that isn't embedded and every bump map, these need to be in the SceneAssets passed to the loader
+    pub fn asset_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for obj in self.objects.iter() {
+            if let ShapeDescription::Mesh { asset, bump_map, .. } = &obj.shape {
+                let mesh_path = if asset.embedded.is_none() { asset.path.as_ref() } else { None };
+                let bump_map_path = bump_map.as_ref().map(|b| &b.path);
+                for path in mesh_path.into_iter().chain(bump_map_path) {
+                    if !paths.contains(path) {
+                        paths.push(path.clone());
+                    }
+                }
+            }
+        }
+        return paths;
+    }
+
+    /// Reads every asset_paths() entry from disk, relative to asset_root
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_assets(&self, asset_root: &std::path::Path) -> Result<SceneAssets, String> {
+        let mut assets = SceneAssets::new();
+        for path in self.asset_paths() {
+            let full_path = asset_root.join(&path);
+            let bytes = std::fs::read(&full_path).map_err(|e| format!("Couldn't read asset {}: {}", full_path.display(), e))?;
+            assets.insert(path, bytes);
+        }
+        return Ok(assets);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_from_file(path: &std::path::Path) -> Result<SceneDescription, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        return SceneDescription::from_json_bytes(&bytes);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), String> {
+        return std::fs::write(path, self.to_json()).map_err(|e| format!("Couldn't write {}: {}", path.display(), e));
+    }
+}
+
+// BUILT-IN SCENES
+const BUILTIN_SCENES: [(&str, &str); 12] = [
+    ("spheres", include_str!("../../scenes/spheres.json")),
+    ("quads", include_str!("../../scenes/quads.json")),
+    ("simple_light", include_str!("../../scenes/simple_light.json")),
+    ("cornell", include_str!("../../scenes/cornell.json")),
+    ("cornell_plus_plus", include_str!("../../scenes/cornell_plus_plus.json")),
+    ("fantasy_book", include_str!("../../scenes/fantasy_book.json")),
+    ("magic_bridge", include_str!("../../scenes/magic_bridge.json")),
+    ("gandalf_bust", include_str!("../../scenes/gandalf_bust.json")),
+    ("roza_bust", include_str!("../../scenes/roza_bust.json")),
+    ("dragon", include_str!("../../scenes/dragon.json")),
+    ("mirror_box", include_str!("../../scenes/mirror_box.json")),
+    ("suzanne_monkey", include_str!("../../scenes/suzanne_monkey.json")),
+];
+
+pub fn builtin_scene_names() -> Vec<&'static str> {
+    return BUILTIN_SCENES.iter().map(|(name, _)| *name).collect();
+}
+
+pub fn builtin_scene(name: &str) -> Option<SceneDescription> {
+    let (_, json) = BUILTIN_SCENES.iter().find(|(n, _)| *n == name)?;
+    return Some(SceneDescription::from_json(json).expect("Built-in scene files are valid"));
+}
+
+// BUILDING SCENE OBJECTS
+impl MaterialDescription {
+    pub fn to_unified_mat(&self) -> (PhongProperties, Box<dyn Material>) {
+        return match self {
+            MaterialDescription::Diffuse => SceneObject::new_diffuse_mat(),
+            MaterialDescription::Metal { fuzz } => SceneObject::new_metal_mat(*fuzz),
+            MaterialDescription::Glass { alpha, ior } => SceneObject::new_glass_mat(*alpha, *ior),
+            MaterialDescription::Light { .. } => SceneObject::new_light_mat(),
+            MaterialDescription::Glossy { ior } => SceneObject::new_glossy_mat(*ior),
+        };
+    }
+}
+
+/// The material with the phong overrides applied, diffuse if there's no material
+fn to_unified_mat(material: &Option<MaterialDescription>, phong: &Option<PhongDescription>) -> (PhongProperties, Box<dyn Material>) {
+    let (mut properties, material) = match material {
+        Some(material) => material.to_unified_mat(),
+        None => SceneObject::new_diffuse_mat(),
+    };
+    if let Some(phong_overrides) = phong {
+        phong_overrides.apply_to(&mut properties);
+    }
+    return (properties, material);
+}
+
+/// Gives the object lights of its own, the way the editor does, if its material has an intensity
+fn add_material_lights(material: &Option<MaterialDescription>, scene_obj: &mut SceneObject) {
+    if let Some(MaterialDescription::Light { intensity: Some(intensity) }) = material {
+        if scene_obj.mesh.colors.is_empty() {
+            return;
+        }
+        let color = scene_obj.get_triangle_color(0);
+        scene_obj.lights.clear();
+        scene_obj.set_material_properties(4, *intensity, color);
+    }
+}
+
+impl PhongDescription {
+    pub fn apply_to(&self, properties: &mut PhongProperties) {
+        if let Some(alpha) = self.alpha { properties.alpha = alpha; }
+        if let Some(ambient) = self.ambient { properties.ambient = ambient; }
+        if let Some(diffuse) = self.diffuse { properties.diffuse = diffuse; }
+        if let Some(specular) = self.specular { properties.specular = specular; }
+        if let Some(shininess) = self.shininess { properties.shininess = shininess; }
+        if let Some(cull_faces) = self.cull_faces { properties.cull_faces = cull_faces; }
+    }
+
+    /// The overrides that turn defaults into properties, None if there aren't any
+    pub fn from_difference(defaults: &PhongProperties, properties: &PhongProperties) -> Option<PhongDescription> {
+        fn changed<T: PartialEq>(default: T, value: T) -> Option<T> {
+            return if default != value { Some(value) } else { None };
+        }
+        let description = PhongDescription {
+            alpha: changed(defaults.alpha, properties.alpha),
+            ambient: changed(defaults.ambient, properties.ambient),
+            diffuse: changed(defaults.diffuse, properties.diffuse),
+            specular: changed(defaults.specular, properties.specular),
+            shininess: changed(defaults.shininess, properties.shininess),
+            cull_faces: changed(defaults.cull_faces, properties.cull_faces),
+        };
+        return if description == PhongDescription::default() { None } else { Some(description) };
+    }
+}
+
+impl TransformPartsDescription {
+    pub fn new(transform: &Transform) -> TransformPartsDescription {
+        let rotation = transform.rotation;
+        return TransformPartsDescription {
+            translation: arr(transform.translation),
+            rotation: [rotation.w, rotation.x, rotation.y, rotation.z],
+            scale: arr(transform.scale),
+        };
+    }
+    pub fn to_transform(&self) -> Result<Transform, String> {
+        let [w, x, y, z] = self.rotation;
+        let mut rotation = Quat::new(w, x, y, z);
+        if rotation.len() == 0.0 {
+            return Err(String::from("Transform rotations can't be 0"));
+        }
+        // only fix up rotations that aren't unit length, so saved ones come back exactly
+        if (rotation.len() - 1.0).abs() > 1e-4 {
+            rotation = rotation.normalized();
+        }
+        let transform = Transform::new(vec3(self.translation), rotation, vec3(self.scale));
+        if transform.get_min_scale() < MIN_SCALE {
+            return Err(format!("Transform scale {:?} is too close to 0", self.scale));
+        }
+        return Ok(transform);
+    }
+}
+
+impl TransformDescription {
+    pub fn apply_to(&self, scene_obj: &mut SceneObject) -> Result<(), String> {
+        match self {
+            TransformDescription::Translate(offset) => scene_obj.translate_by(vec3(*offset)),
+            TransformDescription::Center(center) => scene_obj.set_center(vec3(*center)),
+            TransformDescription::Scale(factor) => scene_obj.scale_by(*factor),
+            TransformDescription::FitRadius(radius) => {
+                let current_radius = scene_obj.get_radius();
+                if current_radius > 0.0 {
+                    scene_obj.scale_by(*radius / current_radius);
+                }
+            },
+            TransformDescription::ScaleAxes(factors) => scene_obj.scale_axes_by(vec3(*factors)),
+            TransformDescription::Rotate { z_degrees, y_degrees, x_degrees } => {
+                scene_obj.rotate_around_center(degrees_to_radians(*z_degrees), degrees_to_radians(*y_degrees));
+                if *x_degrees != 0.0 {
+                    scene_obj.rotate_around_center_axis(Vec3::new(1.0, 0.0, 0.0), degrees_to_radians(*x_degrees));
+                }
+            },
+            TransformDescription::RotateAxis { axis, degrees } => {
+                scene_obj.rotate_around_center_axis(vec3(*axis), degrees_to_radians(*degrees));
+            },
+            TransformDescription::RestOnZ(z) => {
+                let min_z = scene_obj.get_world_vertices().iter().fold(f32::MAX, |min_z, v| min_z.min(v.z));
+                if min_z < f32::MAX {
+                    scene_obj.translate_by(Vec3::new(0.0, 0.0, *z - min_z));
+                }
+            },
+            TransformDescription::Set(parts) => scene_obj.set_transform(parts.to_transform()?),
+        }
+        return Ok(());
+    }
+}
+
+impl ShapeDescription {
+    pub fn is_light(&self) -> bool {
+        return matches!(
+            self,
+            ShapeDescription::SphereLight { .. } | ShapeDescription::RectangleLight { .. } | ShapeDescription::BoxLight { .. } | ShapeDescription::SpotLight { .. }
+        );
+    }
+
+    /// The same shape as a plain object, for lights whose material was changed
+    pub fn without_light(&self) -> ShapeDescription {
+        return match self {
+            ShapeDescription::SphereLight { center, radius, color, subdivisions } => {
+                ShapeDescription::Sphere { center: *center, radius: *radius, color: *color, subdivisions: *subdivisions }
+            },
+            ShapeDescription::RectangleLight { origin, u, v, color, .. } => {
+                ShapeDescription::Rectangle { origin: *origin, u: *u, v: *v, color: *color, cull_faces: false }
+            },
+            ShapeDescription::BoxLight { corner1, corner2, color, .. } => {
+                ShapeDescription::Box { corner1: *corner1, corner2: *corner2, color: *color }
+            },
+            ShapeDescription::SpotLight { pos, color, min_dist, subdivisions, .. } => {
+                ShapeDescription::Sphere { center: *pos, radius: *min_dist - 0.01, color: *color, subdivisions: *subdivisions }
+            },
+            shape => shape.clone(),
+        };
+    }
+
+    /// Replaces every color of the shape, the way SceneObject::set_color() does to the mesh
+    pub fn set_color(&mut self, new_color: Vec3) {
+        let new_color = arr(new_color);
+        match self {
+            ShapeDescription::Sphere { color, .. }
+            | ShapeDescription::Rectangle { color, .. }
+            | ShapeDescription::Box { color, .. }
+            | ShapeDescription::SphereLight { color, .. }
+            | ShapeDescription::RectangleLight { color, .. }
+            | ShapeDescription::BoxLight { color, .. }
+            | ShapeDescription::SpotLight { color, .. } => *color = new_color,
+            ShapeDescription::Checkerboard { color1, color2, .. } => {
+                *color1 = new_color;
+                *color2 = new_color;
+            },
+            ShapeDescription::Triangles { colors, .. } => {
+                for color in colors.iter_mut() {
+                    *color = new_color;
+                }
+            },
+            ShapeDescription::Mesh { color, color_multiplier, .. } => {
+                *color = Some(new_color);
+                *color_multiplier = 1.0;
+            },
+        }
+    }
+
+    /// The mesh as a triangle list, for objects that weren't built from a scene file shape
+    pub fn from_mesh(mesh: &Mesh) -> ShapeDescription {
+        return ShapeDescription::Triangles {
+            vertices: mesh.indices.iter().map(|i| arr(mesh.vertices[*i])).collect(),
+            colors: mesh.colors.iter().map(|c| arr(*c)).collect(),
+            normals: mesh.vertex_normals.iter().map(|n| arr(*n)).collect(),
+        };
+    }
+}
+
+impl MeshAsset {
+    fn get_format(&self) -> Result<MeshFormat, String> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+        let path = self.path.as_ref().ok_or("Embedded mesh assets need a format")?;
+        let lowercase_path = path.to_lowercase();
+        if lowercase_path.ends_with(".stl") {
+            return Ok(MeshFormat::Stl);
+        } else if lowercase_path.ends_with(".glb") {
+            return Ok(MeshFormat::Glb);
+        }
+        return Err(format!("Can't tell the format of mesh asset {}, set \"format\"", path));
+    }
+
+    fn get_bytes<'a>(&self, assets: &'a SceneAssets, decoded: &'a mut Vec<u8>) -> Result<&'a [u8], String> {
+        if let Some(embedded) = &self.embedded {
+            *decoded = base64::decode(embedded).map_err(|e| format!("Invalid embedded mesh asset: {}", e))?;
+            return Ok(decoded.as_slice());
+        }
+        let path = self.path.as_ref().ok_or("Mesh asset needs a path or embedded data")?;
+        return assets
+            .get(path)
+            .map(|bytes| bytes.as_slice())
+            .ok_or(format!("Missing mesh asset {}", path));
+    }
+
+    pub fn load_mesh(&self, assets: &SceneAssets, color: Option<[f32; 3]>, properties: PhongProperties) -> Result<Mesh, String> {
+        let mut decoded = Vec::new();
+        let bytes = self.get_bytes(assets, &mut decoded)?;
+        return match self.get_format()? {
+            MeshFormat::Stl => {
+                let color = color.ok_or("STL meshes need a color")?;
+                Mesh::try_new_from_stl_bytes(bytes, vec3(color), properties)
+            },
+            MeshFormat::Glb => {
+                let mut mesh = extract_combined_mesh_from_raw_glb_bytes(bytes)?;
+                mesh.properties = properties;
+                if let Some(color) = color {
+                    mesh.set_color(vec3(color));
+                }
+                Ok(mesh)
+            },
+        };
+    }
+}
+
+impl BumpMapDescription {
+    pub fn load(&self, assets: &SceneAssets) -> Result<NormalMap, String> {
+        let bytes = assets.get(&self.path).ok_or(format!("Missing bump map {}", self.path))?;
+        let image = load_from_memory(bytes).map_err(|e| format!("Couldn't decode bump map {}: {}", self.path, e))?;
+        return Ok(NormalMap {
+            texture: Arc::new(Texture::from_image(&image)),
+            kind: NormalMapKind::Bump { strength: self.strength },
+        });
+    }
+}
+
+impl ObjectDescription {
+    pub fn to_scene_object(&self, assets: &SceneAssets) -> Result<SceneObject, String> {
+        let unified_mat = to_unified_mat(&self.material, &self.phong);
+
+        let mut scene_obj = match &self.shape {
+            ShapeDescription::Sphere { center, radius, color, subdivisions } => {
+                SceneObject::new_sphere(vec3(*center), *radius, vec3(*color), *subdivisions, unified_mat)
+            },
+            ShapeDescription::Rectangle { origin, u, v, color, cull_faces } => {
+                SceneObject::new_rectangle(vec3(*origin), vec3(*u), vec3(*v), vec3(*color), unified_mat, *cull_faces)
+            },
+            ShapeDescription::Box { corner1, corner2, color } => {
+                SceneObject::new_box_from_corners(vec3(*corner1), vec3(*corner2), vec3(*color), unified_mat)
+            },
+            ShapeDescription::Checkerboard { center, radius, color1, color2, cull_faces } => {
+                SceneObject::new_checkerboard(vec3(*center), *radius, vec3(*color1), vec3(*color2), unified_mat, *cull_faces)
+            },
+            ShapeDescription::Triangles { vertices, colors, normals } => {
+                if vertices.len() % 3 != 0 || vertices.len() / 3 != colors.len() {
+                    return Err(String::from("Triangles need 3 vertices per color"));
+                }
+                if !normals.is_empty() && normals.len() != vertices.len() {
+                    return Err(String::from("Triangles need a normal for every vertex, or none"));
+                }
+                let vertices = vertices.iter().map(|v| vec3(*v)).collect();
+                let colors = colors.iter().map(|c| vec3(*c)).collect();
+                let mut mesh = Mesh::new_from_non_indexed(vertices, colors, unified_mat.0);
+                mesh.vertex_normals = normals.iter().map(|n| vec3(*n)).collect();
+                SceneObject::new_from_mesh(mesh, unified_mat.1, true)
+            },
+            ShapeDescription::Mesh { asset, color, color_multiplier, bump_map } => {
+                let mut mesh = asset.load_mesh(assets, *color, unified_mat.0)?;
+                if *color_multiplier != 1.0 {
+                    mesh.multiply_colors(*color_multiplier);
+                }
+                if let Some(bump_map) = bump_map {
+                    mesh.set_normal_map(Arc::new(bump_map.load(assets)?))?;
+                }
+                SceneObject::new_from_mesh(mesh, unified_mat.1, true)
+            },
+            ShapeDescription::SphereLight { center, radius, color, subdivisions } => {
+                SceneObject::new_sphere_omni_light(vec3(*center), *radius, vec3(*color), *subdivisions, 1000)
+            },
+            ShapeDescription::RectangleLight { origin, u, v, color, min_dist } => {
+                SceneObject::new_rectangle_light(vec3(*origin), vec3(*u), vec3(*v), vec3(*color), *min_dist, 1000)
+            },
+            ShapeDescription::BoxLight { corner1, corner2, color, min_dist } => {
+                SceneObject::new_box_light_from_corners(vec3(*corner1), vec3(*corner2), vec3(*color), *min_dist, 1000)
+            },
+            ShapeDescription::SpotLight { pos, look_at, fov_degrees, color, min_dist, shadow_map_size, subdivisions } => {
+                let light = Light::new_looking_at(
+                    vec3(*pos),
+                    vec3(*look_at),
+                    degrees_to_radians(*fov_degrees),
+                    vec3(*color),
+                    *min_dist,
+                    *shadow_map_size,
+                    *shadow_map_size,
+                );
+                SceneObject::new_sphere_custom_light(*subdivisions, light)
+            },
+        };
+
+        scene_obj.source = Some(Arc::new(self.shape.clone()));
+
+        for transform in self.transforms.iter() {
+            transform.apply_to(&mut scene_obj)?;
+        }
+        if !self.shape.is_light() {
+            add_material_lights(&self.material, &mut scene_obj);
+        }
+        scene_obj.mat_is_editable = self.editable;
+        scene_obj.node = self.node;
+
+        return Ok(scene_obj);
+    }
+
+    /// The object followed by its instances
+    pub fn to_scene_objects(&self, assets: &SceneAssets) -> Result<Vec<SceneObject>, String> {
+        let scene_obj = self.to_scene_object(assets)?;
+        let mut instances = Vec::with_capacity(self.instances.len());
+        for instance_description in self.instances.iter() {
+            let mut instance = scene_obj.new_instance();
+            for transform in instance_description.transforms.iter() {
+                transform.apply_to(&mut instance)?;
+            }
+            if instance_description.material.is_some() || instance_description.phong.is_some() {
+                instance.set_material_override(to_unified_mat(&instance_description.material, &instance_description.phong));
+            }
+            if let Some(color) = instance_description.color {
+                instance.set_color(vec3(color));
+            }
+            if !self.shape.is_light() {
+                add_material_lights(&instance_description.material, &mut instance);
+            }
+            instance.node = instance_description.node;
+            instances.push(instance);
+        }
+
+        let mut scene_objects = vec![scene_obj];
+        scene_objects.append(&mut instances);
+        return Ok(scene_objects);
+    }
+}
+
+// DESCRIBING THE SCENE AS IT IS
+/// Objects get placed with one Set, instances always, since theirs would otherwise add to the original's
+fn describe_transform(transform: &Transform, always: bool) -> Vec<TransformDescription> {
+    if transform.is_identity() && !always {
+        return Vec::new();
+    }
+    return vec![TransformDescription::Set(TransformPartsDescription::new(transform))];
+}
+
+/// How much brighter than the object its own lights are, None if it has none
+fn get_light_intensity(scene_obj: &SceneObject) -> Option<f32> {
+    let light = scene_obj.lights.first()?;
+    if scene_obj.mesh.colors.is_empty() {
+        return None;
+    }
+    let color = scene_obj.get_triangle_color(0).max_component();
+    return if color > 0.0 { Some(light.color.max_component() / color) } else { None };
+}
+
+/// The object's material, with whatever phong properties differ from that material's defaults
+fn describe_material(scene_obj: &SceneObject) -> (Option<MaterialDescription>, Option<PhongDescription>) {
+    let Some(material) = scene_obj.get_material() else {
+        return (None, None);
+    };
+    let properties = scene_obj.get_properties();
+    let description = match material.to_snapshot() {
+        MaterialSnapshot::Lambertian => MaterialDescription::Diffuse,
+        MaterialSnapshot::Metal { fuzz } => MaterialDescription::Metal { fuzz },
+        MaterialSnapshot::Dielectric { index_of_refrac } => MaterialDescription::Glass { alpha: properties.alpha, ior: index_of_refrac },
+        MaterialSnapshot::DiffuseLight => MaterialDescription::Light { intensity: get_light_intensity(scene_obj) },
+        MaterialSnapshot::ClearCoat { index_of_refraction, .. } => MaterialDescription::Glossy { ior: index_of_refraction },
+    };
+    let phong = PhongDescription::from_difference(&description.to_unified_mat().0, properties);
+    return (Some(description), phong);
+}
+
+impl ObjectDescription {
+    /// The shape is the one the object was built from, or its mesh as triangles if it wasn't
+    /// built from a scene file. node is the object's index in SceneDescription::nodes.
+    pub fn new(scene_obj: &SceneObject, node: Option<usize>) -> ObjectDescription {
+        let mut shape = match &scene_obj.source {
+            Some(shape) => shape.as_ref().clone(),
+            None => ShapeDescription::from_mesh(&scene_obj.mesh),
+        };
+        if shape.is_light() && !scene_obj.is_light() {
+            shape = shape.without_light();
+        }
+        if let Some(MaterialOverride { color: Some(color), .. }) = &scene_obj.material_override {
+            shape.set_color(*color);
+        }
+        let (material, phong) = match shape.is_light() {
+            true => (None, None),
+            false => describe_material(scene_obj),
+        };
+        return ObjectDescription {
+            shape,
+            // leaving it out means diffuse
+            material: material.filter(|material| !matches!(material, MaterialDescription::Diffuse)),
+            phong,
+            transforms: describe_transform(&scene_obj.transform, false),
+            editable: scene_obj.mat_is_editable,
+            instances: Vec::new(),
+            node,
+        };
+    }
+}
+
+impl InstanceDescription {
+    pub fn new(scene_obj: &SceneObject, node: Option<usize>) -> InstanceDescription {
+        let (material, phong) = match &scene_obj.material_override {
+            Some(_) => describe_material(scene_obj),
+            None => (None, None),
+        };
+        return InstanceDescription {
+            transforms: describe_transform(&scene_obj.transform, true),
+            color: scene_obj.material_override.as_ref().and_then(|m| m.color).map(arr),
+            material,
+            phong,
+            node,
+        };
+    }
+}
+
+impl NodeDescription {
+    pub fn to_scene_graph(nodes: &[NodeDescription]) -> Result<SceneGraph, String> {
+        let mut scene_graph = SceneGraph::new();
+        for (i, node) in nodes.iter().enumerate() {
+            if node.parent.is_some_and(|parent| parent >= i) {
+                return Err(format!("Node {}: parent {:?} doesn't come before it", i, node.parent));
+            }
+            let transform = match &node.transform {
+                Some(transform) => transform.to_transform().map_err(|e| format!("Node {}: {}", i, e))?,
+                None => Transform::identity(),
+            };
+            scene_graph.add_node(&node.name, transform, node.parent);
+        }
+        return Ok(scene_graph);
+    }
+}
+
+impl Game {
+    /// Replaces the current scene with the one described. Mesh assets referenced by
+    /// path are looked up in assets. On error the current scene is left untouched.
+    pub fn load_scene_description(&mut self, scene: &SceneDescription, assets: &SceneAssets) -> Result<(), String> {
+        if scene.ray_max_depth == 0 {
+            return Err(String::from("ray_max_depth has to be at least 1"));
+        }
+        let scene_objects: Vec<SceneObject> = scene
+            .objects
+            .iter()
+            .map(|obj| obj.to_scene_objects(assets))
+            .collect::<Result<Vec<Vec<SceneObject>>, String>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let mut scene_graph = NodeDescription::to_scene_graph(&scene.nodes)?;
+        let mut object_transforms = HashMap::new();
+        for scene_obj in scene_objects.iter() {
+            if let Some(node) = scene_obj.node {
+                if node >= scene.nodes.len() {
+                    return Err(format!("Object refers to node {}, which doesn't exist", node));
+                }
+                object_transforms.insert(node, scene_obj.transform);
+            }
+        }
+        // nodes standing for objects go where the objects are, parents first
+        for node in 0..scene.nodes.len() {
+            if let Some(transform) = object_transforms.get(&node) {
+                scene_graph.set_world_transform(node, *transform);
+            }
+        }
+
+        self.pre_scene_load();
+
+        *self.scene_objects.write().unwrap() = scene_objects;
+        self.scene_graph = scene_graph;
+
+        self.max_sky_color = vec3(scene.sky.max_color);
+        self.min_sky_color = vec3(scene.sky.min_color);
+        self.rt_max_sky_color = vec3(scene.sky.rt_max_color);
+        self.rt_min_sky_color = vec3(scene.sky.rt_min_color);
+
+        self.ray_max_depth = scene.ray_max_depth;
+
+        let camera = &scene.camera;
+        self.camera.set_fov(degrees_to_radians(camera.fov_degrees));
+        self.camera.pos = vec3(camera.pos);
+        if let Some(look_at) = camera.look_at {
+            self.camera.look_at(&vec3(look_at));
+        } else {
+            self.camera.set_theta_z(degrees_to_radians(camera.yaw_degrees));
+            self.camera.set_theta_y(degrees_to_radians(camera.pitch_degrees).clamp(-0.5 * PI, 0.5 * PI));
+        }
+        self.defocus_angle = degrees_to_radians(camera.defocus_angle_degrees);
+        self.focus_dist = camera.focus_dist;
+
+        self.post_scene_load();
+        return Ok(());
+    }
+
+    /// The scene as it is now, edits included, which load_scene_description() builds again.
+    /// Objects that weren't built from a scene file (imported GLB models, restored snapshots)
+    /// are written as triangle lists, without their textures.
+    pub fn to_scene_description(&self) -> SceneDescription {
+        let scene_objects = self.scene_objects.read().unwrap();
+
+        let node_order = self.scene_graph.get_nodes_parents_first();
+        let saved_node_indices: HashMap<usize, usize> = node_order.iter().enumerate().map(|(saved, &i)| (i, saved)).collect();
+        let nodes = node_order
+            .iter()
+            .map(|&i| {
+                let node = self.scene_graph.get(i).unwrap();
+                let stands_for_object = scene_objects.iter().any(|obj| obj.node == Some(i));
+                NodeDescription {
+                    name: node.name.clone(),
+                    parent: node.parent.map(|parent| saved_node_indices[&parent]),
+                    transform: match stands_for_object || node.local_transform.is_identity() {
+                        true => None,
+                        false => Some(TransformPartsDescription::new(&node.local_transform)),
+                    },
+                }
+            })
+            .collect();
+
+        // objects sharing geometry become instances of one of them
+        let mut geometry_users: Vec<Vec<&SceneObject>> = Vec::new();
+        for scene_obj in scene_objects.iter() {
+            let users = geometry_users
+                .iter_mut()
+                .find(|users| Arc::ptr_eq(&users[0].mesh, &scene_obj.mesh) && Arc::ptr_eq(&users[0].hittables, &scene_obj.hittables));
+            match users {
+                Some(users) => users.push(scene_obj),
+                None => geometry_users.push(vec![scene_obj]),
+            }
+        }
+        let saved_node = |scene_obj: &SceneObject| scene_obj.node.and_then(|node| saved_node_indices.get(&node).copied());
+        let objects = geometry_users
+            .iter()
+            .map(|users| {
+                // instances without a material of their own get the original's, so that one shouldn't have its own either
+                let original = users
+                    .iter()
+                    .position(|obj| !obj.is_instance() && obj.material_override.is_none())
+                    .or_else(|| users.iter().position(|obj| obj.material_override.is_none()))
+                    .unwrap_or(0);
+                let mut description = ObjectDescription::new(users[original], saved_node(users[original]));
+                description.instances = users
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != original)
+                    .map(|(_, obj)| InstanceDescription::new(obj, saved_node(obj)))
+                    .collect();
+                description
+            })
+            .collect();
+
+        return SceneDescription {
+            name: String::new(),
+            camera: CameraDescription {
+                pos: arr(self.camera.pos),
+                look_at: None,
+                yaw_degrees: radians_to_degrees(self.camera.get_theta_z()),
+                pitch_degrees: radians_to_degrees(self.camera.get_theta_y()),
+                fov_degrees: radians_to_degrees(self.camera.get_fov()),
+                defocus_angle_degrees: radians_to_degrees(self.defocus_angle),
+                focus_dist: self.focus_dist,
+            },
+            sky: SkyDescription {
+                max_color: arr(self.max_sky_color),
+                min_color: arr(self.min_sky_color),
+                rt_max_color: arr(self.rt_max_sky_color),
+                rt_min_color: arr(self.rt_min_sky_color),
+            },
+            ray_max_depth: self.ray_max_depth,
+            objects,
+            nodes,
+        };
+    }
+
+    /// Loads one of the scenes in wasm-graphics/scenes/ by name
+    pub fn load_builtin_scene(&mut self, name: &str, assets: &SceneAssets) -> Result<(), String> {
+        let scene = builtin_scene(name).ok_or(format!("Unknown built-in scene '{}'", name))?;
+        return self.load_scene_description(&scene, assets);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::{console_error, console_log, utils::math::{Mat4, Transform, Vec3}};
+
+use super::{game::Game, gltf_parser::{GltfNode, GltfNodeTree}, ray_tracing::material::{Lambertian, Material}, object_id::ObjectId, scene_object::{SceneObject, MIN_SCALE}};
+
+// Objects can be arranged in a tree of nodes, so that a group of them (a table and
+// everything on it, or the parts of an imported model) moves together. Every node has a
+// transform relative to its parent. A node either stands for a SceneObject, which points
+// back at it with SceneObject::node (so the link survives the object list being reordered),
+// or only groups its children.
+//
+// Objects keep their world transform in SceneObject::transform, which is all rendering and
+// ray tracing look at. The graph is only walked when something in it moves.
+//
+// World transforms are worked out as products of matrices, and only split back into a
+// Transform at the end, so a rotated child under a non-uniformly scaled parent ends up
+// scaled along the right axes. That can shear the child, which a Transform can't hold, so
+// the editor refuses moves and edits that would (see would_shear()). Nodes that come sheared
+// from a file get the closest Transform (see Transform::from_mat4()).
+
+#[derive(Debug, Clone)]
+pub struct SceneNode {
+    pub name: String,
+    /// Relative to the parent, or to the world for top level nodes
+    pub local_transform: Transform,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// Node indices stay valid until the node is removed, removed nodes leave an empty slot
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Option<SceneNode>>,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        return SceneGraph { nodes: Vec::new() };
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SceneNode> {
+        return self.nodes.get(index).and_then(|node| node.as_ref());
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        return self.get(index).is_some();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &SceneNode)> {
+        return self.nodes.iter().enumerate().filter_map(|(i, node)| node.as_ref().map(|node| (i, node)));
+    }
+
+    pub fn node_count(&self) -> usize {
+        return self.iter().count();
+    }
+
+    /// Adds a node under parent (or at the top level), returns its index
+    pub fn add_node(&mut self, name: &str, local_transform: Transform, parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+        let parent = parent.filter(|&p| {
+            let exists = self.contains(p);
+            if !exists {
+                console_error!("SceneGraph::add_node() called with missing parent {}, adding {} at the top level", p, name);
+            }
+            exists
+        });
+        self.nodes.push(Some(SceneNode {
+            name: String::from(name),
+            local_transform,
+            parent,
+            children: Vec::new(),
+        }));
+        if let Some(parent) = parent {
+            self.nodes[parent].as_mut().unwrap().children.push(index);
+        }
+        return index;
+    }
+
+    /// Removes just this node, its children move up to its parent and stay where they are in the world
+    pub fn remove_node(&mut self, index: usize) {
+        let Some(node) = self.get(index) else {
+            return;
+        };
+        let (parent, children) = (node.parent, node.children.clone());
+        for child in children {
+            self.set_parent(child, parent);
+        }
+        self.detach(index);
+        self.nodes[index] = None;
+    }
+
+    /// Removes the node and everything under it, returns the removed indices
+    pub fn remove_subtree(&mut self, index: usize) -> Vec<usize> {
+        let removed = self.get_subtree(index);
+        self.detach(index);
+        for &i in removed.iter() {
+            self.nodes[i] = None;
+        }
+        return removed;
+    }
+
+    fn detach(&mut self, index: usize) {
+        if let Some(parent) = self.get(index).and_then(|node| node.parent) {
+            self.nodes[parent].as_mut().unwrap().children.retain(|&child| child != index);
+        }
+        if let Some(node) = self.nodes[index].as_mut() {
+            node.parent = None;
+        }
+    }
+
+    /// True if moving the node under parent would shear it, so it can't stay where it is in the world
+    pub fn would_shear(&self, index: usize, parent: Option<usize>) -> bool {
+        return self.is_sheared_under(parent, self.get_world_matrix(index));
+    }
+
+    /// True if giving the node this world transform would shear it relative to its parent,
+    /// or anything under it that isn't sheared already
+    pub fn would_shear_at(&self, index: usize, world_transform: Transform) -> bool {
+        let Some(node) = self.get(index) else {
+            return false;
+        };
+        if self.is_sheared_under(node.parent, world_transform.to_mat4()) {
+            return true;
+        }
+        let current = self.get_subtree_world_matrices(index, self.get_world_matrix(index));
+        let edited = self.get_subtree_world_matrices(index, world_transform.to_mat4());
+        return current.iter().zip(edited.iter()).any(|((_, current), (_, edited))| edited.is_sheared() && !current.is_sheared());
+    }
+
+    fn is_sheared_under(&self, parent: Option<usize>, world_matrix: Mat4) -> bool {
+        let Some(parent) = parent else {
+            return false;
+        };
+        return match self.get_world_matrix(parent).affine_inverse() {
+            Some(inverse) => (inverse * world_matrix).is_sheared(),
+            None => true,
+        };
+    }
+
+    /// Moves the node under a new parent (None for the top level), keeping it where it is in
+    /// the world (as close as it can, see would_shear()). Returns false if that's impossible:
+    /// a missing node, or a parent inside the node's own subtree.
+    pub fn set_parent(&mut self, index: usize, parent: Option<usize>) -> bool {
+        if !self.contains(index) {
+            return false;
+        }
+        if let Some(parent) = parent {
+            if !self.contains(parent) || self.is_in_subtree(parent, index) {
+                return false;
+            }
+        }
+        let world_transform = self.get_world_transform(index);
+        self.detach(index);
+        if let Some(parent) = parent {
+            self.nodes[parent].as_mut().unwrap().children.push(index);
+        }
+        self.nodes[index].as_mut().unwrap().parent = parent;
+        self.set_world_transform(index, world_transform);
+        return true;
+    }
+
+    pub fn get_world_transform(&self, index: usize) -> Transform {
+        return to_transform(&self.get_world_matrix(index)).unwrap_or_else(|| {
+            console_error!("SceneGraph::get_world_transform() node {} is squashed flat", index);
+            return self.get(index).map_or(Transform::identity(), |node| node.local_transform);
+        });
+    }
+
+    pub fn get_world_matrix(&self, index: usize) -> Mat4 {
+        let Some(node) = self.get(index) else {
+            return Mat4::identity();
+        };
+        return match node.parent {
+            Some(parent) => self.get_world_matrix(parent) * node.local_transform.to_mat4(),
+            None => node.local_transform.to_mat4(),
+        };
+    }
+
+    pub fn set_world_transform(&mut self, index: usize, world_transform: Transform) {
+        let Some(node) = self.get(index) else {
+            return;
+        };
+        let local_transform = match node.parent {
+            Some(parent) => {
+                let local = self.get_world_matrix(parent).affine_inverse().and_then(|inverse| to_transform(&(inverse * world_transform.to_mat4())));
+                let Some(local) = local else {
+                    console_error!("SceneGraph::set_world_transform() parent {} of node {} is squashed flat", parent, index);
+                    return;
+                };
+                local
+            },
+            None => world_transform,
+        };
+        self.nodes[index].as_mut().unwrap().local_transform = local_transform;
+    }
+
+    pub fn set_local_transform(&mut self, index: usize, local_transform: Transform) {
+        if let Some(node) = self.nodes.get_mut(index).and_then(|node| node.as_mut()) {
+            node.local_transform = local_transform;
+        }
+    }
+
+    /// Every node, top level ones each followed by everything under them
+    pub fn get_nodes_parents_first(&self) -> Vec<usize> {
+        return self
+            .iter()
+            .filter(|(_, node)| node.parent.is_none())
+            .flat_map(|(i, _)| self.get_subtree(i))
+            .collect();
+    }
+
+    /// The node followed by everything under it, parents always before their children
+    pub fn get_subtree(&self, index: usize) -> Vec<usize> {
+        return self.get_subtree_world_transforms(index).into_iter().map(|(i, _)| i).collect();
+    }
+
+    /// Like get_subtree(), with each node's world transform, computed top down in one pass
+    pub fn get_subtree_world_transforms(&self, index: usize) -> Vec<(usize, Transform)> {
+        return self
+            .get_subtree_world_matrices(index, self.get_world_matrix(index))
+            .into_iter()
+            .map(|(i, matrix)| (i, to_transform(&matrix).unwrap_or_else(|| self.get_world_transform(i))))
+            .collect();
+    }
+
+    /// Like get_subtree(), with each node's world matrix if the node's own is root_matrix
+    fn get_subtree_world_matrices(&self, index: usize, root_matrix: Mat4) -> Vec<(usize, Mat4)> {
+        if !self.contains(index) {
+            return Vec::new();
+        }
+        let mut subtree = vec![(index, root_matrix)];
+        let mut next = 0;
+        while next < subtree.len() {
+            let (parent, parent_matrix) = subtree[next];
+            for &child in self.nodes[parent].as_ref().unwrap().children.iter() {
+                let child_matrix = parent_matrix * self.nodes[child].as_ref().unwrap().local_transform.to_mat4();
+                subtree.push((child, child_matrix));
+            }
+            next += 1;
+        }
+        return subtree;
+    }
+
+    /// True if index is root or somewhere under it
+    pub fn is_in_subtree(&self, index: usize, root: usize) -> bool {
+        let mut current = Some(index);
+        while let Some(i) = current {
+            if i == root {
+                return true;
+            }
+            current = self.get(i).and_then(|node| node.parent);
+        }
+        return false;
+    }
+}
+
+/// Transform::from_mat4(), with the scale kept away from 0 the way SceneObject::set_transform() wants it
+fn to_transform(matrix: &Mat4) -> Option<Transform> {
+    return Transform::from_mat4(matrix).filter(|transform| transform.get_min_scale() >= MIN_SCALE);
+}
+
+impl Game {
+    /// Adds an empty group node, returns its index
+    pub fn add_group(&mut self, name: &str, local_transform: Transform, parent: Option<usize>) -> usize {
+        return self.scene_graph.add_node(name, local_transform, parent);
+    }
+
+    /// Gives the object a node at the top level if it doesn't have one yet, returns the node
+    pub fn get_or_add_object_node(&mut self, id: ObjectId) -> Option<usize> {
+        let object_index = self.get_object_index(id)?;
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        let scene_obj = &mut scene_objects[object_index];
+        if let Some(node) = scene_obj.node.filter(|&node| self.scene_graph.contains(node)) {
+            return Some(node);
+        }
+        let node = self.scene_graph.add_node(&format!("Object {}", id), scene_obj.transform, None);
+        scene_obj.node = Some(node);
+        return Some(node);
+    }
+
+    /// Moves the object (and whatever hangs under it) into a group, or to the top level
+    /// for None, without moving it in the world
+    pub fn set_object_parent(&mut self, id: ObjectId, parent: Option<usize>) {
+        let Some(node) = self.get_or_add_object_node(id) else {
+            console_error!("Game::set_object_parent() called with deleted object {}", id);
+            return;
+        };
+        if self.scene_graph.would_shear(node, parent) {
+            console_error!("Game::set_object_parent() moving object {} under node {:?} would shear it, the node's scale isn't uniform", id, parent);
+            return;
+        }
+        if !self.scene_graph.set_parent(node, parent) {
+            console_error!("Game::set_object_parent() can't move object {} under node {:?}", id, parent);
+        }
+    }
+
+    /// The object standing for the node, if any
+    pub fn get_node_object(&self, node: usize) -> Option<ObjectId> {
+        return self.scene_objects.read().unwrap().iter().find(|obj| obj.node == Some(node)).and_then(|obj| obj.id);
+    }
+
+    /// Every object in the node's subtree
+    pub fn get_subtree_objects(&self, node: usize) -> Vec<ObjectId> {
+        return self.scene_objects
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|obj| obj.node.is_some_and(|n| self.scene_graph.is_in_subtree(n, node)))
+            .filter_map(|obj| obj.id)
+            .collect();
+    }
+
+    /// True if the object is selected on its own or as part of the selected group
+    pub fn is_object_selected(&self, scene_obj: &SceneObject) -> bool {
+        if scene_obj.id.is_some_and(|id| self.selected_objects.contains(&id)) {
+            return true;
+        }
+        return match (self.selected_group, scene_obj.node) {
+            (Some(group), Some(node)) => self.scene_graph.is_in_subtree(node, group),
+            _ => false,
+        };
+    }
+
+    /// Indices of the selected objects that don't hang under another selected object in the
+    /// scene graph. Moving just these moves the whole selection, each object exactly once.
+    pub fn get_selection_roots(&self) -> Vec<usize> {
+        let scene_objects = self.scene_objects.read().unwrap();
+        let selected: Vec<(usize, Option<usize>)> = self.selected_objects
+            .iter()
+            .filter_map(|&id| self.get_object_index(id))
+            .map(|index| (index, scene_objects[index].node))
+            .collect();
+        return selected
+            .iter()
+            .filter(|(_, node)| {
+                let Some(node) = *node else {
+                    return true;
+                };
+                return !selected.iter().any(|(_, other)| other.is_some_and(|other| other != node && self.scene_graph.is_in_subtree(node, other)));
+            })
+            .map(|&(index, _)| index)
+            .collect();
+    }
+
+    /// Center and radius of a sphere around every selected object
+    pub fn get_selection_bounds(&self) -> Option<(Vec3, f32)> {
+        return self.get_objects_bounds(|obj| obj.id.is_some_and(|id| self.selected_objects.contains(&id)));
+    }
+
+    /// Applies edit to each selected object's transform, the center passed in being the middle
+    /// of the whole selection, so several objects move as if they were grouped
+    pub fn edit_selected_objects(&mut self, edit: impl Fn(&mut Transform, Vec3)) {
+        let Some((center, _)) = self.get_selection_bounds() else {
+            return;
+        };
+        let roots = self.get_selection_roots();
+        {
+            let mut scene_objects = self.scene_objects.write().unwrap();
+            let edited: Vec<Transform> = roots
+                .iter()
+                .map(|&index| {
+                    let mut transform = scene_objects[index].transform;
+                    edit(&mut transform, center);
+                    transform
+                })
+                .collect();
+            let sheared = roots.iter().zip(edited.iter()).find(|&(&index, &transform)| {
+                scene_objects[index].node.is_some_and(|node| self.scene_graph.would_shear_at(node, transform))
+            });
+            if let Some((&index, transform)) = sheared {
+                console_error!("Game::edit_selected_objects() object {} can't take {:?}, a non-uniform scale would shear it or what's under it", index, transform);
+                return;
+            }
+            for (&index, &transform) in roots.iter().zip(edited.iter()) {
+                scene_objects[index].set_transform(transform);
+                self.bvh.mark_object_moved(index);
+            }
+        }
+        for &index in roots.iter() {
+            self.update_object_node(index);
+        }
+    }
+
+    /// Center and radius of a sphere around every object in the node's subtree
+    pub fn get_group_bounds(&self, node: usize) -> Option<(Vec3, f32)> {
+        return self.get_objects_bounds(|obj| obj.node.is_some_and(|n| self.scene_graph.is_in_subtree(n, node)));
+    }
+
+    fn get_objects_bounds(&self, filter: impl Fn(&SceneObject) -> bool) -> Option<(Vec3, f32)> {
+        let scene_objects = self.scene_objects.read().unwrap();
+        let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vec3::new(f32::MIN, f32::MIN, f32::MIN);
+        let mut spheres = Vec::new();
+        for obj in scene_objects.iter().filter(|obj| filter(obj)) {
+            let (center, radius) = (obj.get_center(), obj.get_radius());
+            min = min.min_elementwise(center - Vec3::new(radius, radius, radius));
+            max = max.max_elementwise(center + Vec3::new(radius, radius, radius));
+            spheres.push((center, radius));
+        }
+        if spheres.is_empty() {
+            return None;
+        }
+        let center = (min + max) * 0.5;
+        let radius = spheres.iter().fold(0.0_f32, |radius, (c, r)| radius.max((*c - center).len() + r));
+        return Some((center, radius));
+    }
+
+    /// Applies edit to the node's world transform (the center passed in is the middle of the
+    /// group), then moves every object under the node along with it
+    pub fn edit_node_transform(&mut self, node: usize, edit: impl FnOnce(&mut Transform, Vec3)) {
+        if !self.scene_graph.contains(node) {
+            console_error!("Game::edit_node_transform() called with missing node {}", node);
+            return;
+        }
+        let mut world_transform = self.scene_graph.get_world_transform(node);
+        let center = self.get_group_bounds(node).map(|(center, _)| center).unwrap_or(world_transform.translation);
+        edit(&mut world_transform, center);
+        if world_transform.get_min_scale() < MIN_SCALE {
+            console_error!("Game::edit_node_transform() would scale node {} to {:?}, too close to 0", node, world_transform.scale);
+            return;
+        }
+        if self.scene_graph.would_shear_at(node, world_transform) {
+            console_error!("Game::edit_node_transform() node {} can't take {:?}, a non-uniform scale would shear it or what's under it", node, world_transform);
+            return;
+        }
+        self.scene_graph.set_world_transform(node, world_transform);
+        self.place_subtree_objects(node, true);
+    }
+
+    /// Call after moving an object directly, so its node follows and the objects under it move too
+    pub fn update_object_node(&mut self, object_index: usize) {
+        let (node, transform) = {
+            let scene_objects = self.scene_objects.read().unwrap();
+            let Some(scene_obj) = scene_objects.get(object_index) else {
+                return;
+            };
+            (scene_obj.node, scene_obj.transform)
+        };
+        if let Some(node) = node.filter(|&node| self.scene_graph.contains(node)) {
+            self.scene_graph.set_world_transform(node, transform);
+            self.place_subtree_objects(node, false);
+        }
+    }
+
+    /// Sets the transform of each object under node to its node's world transform
+    fn place_subtree_objects(&mut self, node: usize, including_node: bool) {
+        let world_transforms: Vec<(usize, Transform)> = self.scene_graph
+            .get_subtree_world_transforms(node)
+            .into_iter()
+            .filter(|&(i, _)| including_node || i != node)
+            .collect();
+        let mut scene_objects = self.scene_objects.write().unwrap();
+        for (i, scene_obj) in scene_objects.iter_mut().enumerate() {
+            let Some(obj_node) = scene_obj.node else {
+                continue;
+            };
+            if let Some((_, world_transform)) = world_transforms.iter().find(|(n, _)| *n == obj_node) {
+                if scene_obj.transform != *world_transform {
+                    scene_obj.set_transform(*world_transform);
+                    self.bvh.mark_object_moved(i);
+                }
+            }
+        }
+    }
+
+    /// Removes the objects under node (including the node's own) and the nodes themselves
+    pub fn delete_node(&mut self, node: usize) {
+        let objects = self.get_subtree_objects(node);
+        for &id in objects.iter() {
+            self.remove_scene_object(id);
+        }
+        let removed_nodes = self.scene_graph.remove_subtree(node);
+        console_log!("Deleted {} nodes and {} objects", removed_nodes.len(), objects.len());
+    }
+
+    /// Adds every mesh node of a GLB's node tree as an object, under a new group node.
+    /// Meshes used by several nodes become instances sharing one geometry. Returns the group.
+    /// Objects are placed by the product of the file's own node matrices.
+    pub fn add_gltf_node_tree(&mut self, tree: GltfNodeTree, name: &str, transform: Transform) -> usize {
+        let group = self.add_group(name, transform, None);
+        let mesh_uses = tree.get_mesh_use_counts();
+        let mesh_objects: Vec<SceneObject> = tree.meshes
+            .into_iter()
+            .map(|mesh| SceneObject::new_from_mesh(mesh, Lambertian::default().clone_box(), false))
+            .collect();
+        let group_matrix = self.scene_graph.get_world_matrix(group);
+        for root in tree.roots.iter() {
+            self.add_gltf_node(root, group, group_matrix, &mesh_objects, &mesh_uses);
+        }
+        return group;
+    }
+
+    fn add_gltf_node(&mut self, gltf_node: &GltfNode, parent: usize, parent_matrix: Mat4, mesh_objects: &[SceneObject], mesh_uses: &[usize]) {
+        let node = self.scene_graph.add_node(&gltf_node.name, gltf_node.local_transform, Some(parent));
+        let world_matrix = parent_matrix * gltf_node.local_matrix;
+        if let Some(mesh_index) = gltf_node.mesh {
+            let mut scene_obj = if mesh_uses[mesh_index] > 1 {
+                mesh_objects[mesh_index].new_instance()
+            } else {
+                mesh_objects[mesh_index].clone()
+            };
+            scene_obj.set_transform(to_transform(&world_matrix).unwrap_or_else(|| self.scene_graph.get_world_transform(node)));
+            scene_obj.node = Some(node);
+            self.add_scene_object(scene_obj);
+        }
+        for child in gltf_node.children.iter() {
+            self.add_gltf_node(child, node, world_matrix, mesh_objects, mesh_uses);
+        }
+    }
+}
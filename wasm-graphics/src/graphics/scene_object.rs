@@ -1,29 +1,194 @@
-use std::f32::consts::PI;
+use std::{borrow::Cow, f32::consts::PI, sync::Arc};
 
-use rand::seq::index;
+use crate::{console_error, utils::math::{Quat, Transform, Vec3}};
 
-use crate::{console_error, utils::math::Vec3};
-
-use super::{lighting::Light, mesh::{Mesh, PhongProperties}, ray_tracing::{hittable::{self, Hittable, Sphere}, material::{ClearCoat, Dielectric, DiffuseLight, Lambertian, Material, Metal}}};
+use super::{lighting::Light, object_id::ObjectId, mesh::{Mesh, PhongProperties}, normal_map::Tangent, scene_file::ShapeDescription, texture::{Texture, TriangleTexture}, ray_tracing::{hittable::{Hittable, Sphere}, material::{ClearCoat, Dielectric, DiffuseLight, Lambertian, Material, Metal}}};
 
+/// Mesh and hittables are shared (copy on write) so instances of the same asset
+/// don't each hold their own copy of the geometry.
+///
+/// Moving, rotating and scaling only change the transform, the geometry itself is never touched
+/// after it's built, so edits can't accumulate float error in the vertices.
 pub struct SceneObject {
-    pub mesh: Mesh,
-    pub hittables: Vec<Box<dyn Hittable>>,
+    pub mesh: Arc<Mesh>,
+    pub hittables: Arc<Vec<Box<dyn Hittable>>>,
     pub lights: Vec<Light>,
     pub mat_is_editable: bool,
+    /// Set by new_instance(). Color and material edits then go into material_override,
+    /// so they don't reach the other objects sharing the geometry.
+    pub instanced: bool,
+    /// Places the mesh and hittables in the world at render and intersection time
+    pub transform: Transform,
+    /// Replaces the shared geometry's material (and optionally color) for this object only
+    pub material_override: Option<MaterialOverride>,
+    /// The scene graph node (see scene_graph.rs) standing for this object, if it's in the graph.
+    /// transform is then the node's world transform.
+    pub node: Option<usize>,
+    /// Given out by Game when the object is added to the scene
+    pub id: Option<ObjectId>,
+    /// The scene file shape the geometry was built from (before the transform), kept up to date
+    /// with color edits so Game::to_scene_description() can write it back out
+    pub source: Option<Arc<ShapeDescription>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaterialOverride {
+    pub properties: PhongProperties,
+    pub material: Box<dyn Material>,
+    pub color: Option<Vec3>,
+}
+
+/// Smallest scale set_transform() accepts on any axis
+pub const MIN_SCALE: f32 = 1e-6;
+
+/// Everything set_color() and set_material_properties() change on an object, so the edit
+/// history can put a material back without keeping (and sharing) the object's geometry
+#[derive(Clone)]
+pub struct MaterialState {
+    /// None for instances, whose edits only go into material_override
+    geometry: Option<GeometryMaterialState>,
+    material_override: Option<MaterialOverride>,
+    lights: Vec<Light>,
+    source: Option<Arc<ShapeDescription>>,
+}
+
+#[derive(Clone)]
+struct GeometryMaterialState {
+    properties: PhongProperties,
+    colors: Vec<Vec3>,
+    textures: Vec<Arc<Texture>>,
+    triangle_textures: Vec<Option<usize>>,
+    /// set_material_properties() gives every hittable the same material, so only one is kept
+    material: Option<Box<dyn Material>>,
+    hittable_colors: Vec<Vec3>,
+    hittable_textures: Vec<Option<Box<TriangleTexture>>>,
 }
 
 impl SceneObject {
     pub fn set_material(mut self, unified_mat: (PhongProperties, Box<dyn Material>)) -> Self {
         let material = unified_mat.1;
-        for h in self.hittables.iter_mut() {
+        if let Some(material_override) = &mut self.material_override {
+            material_override.properties = unified_mat.0;
+            material_override.material = material;
+            return self;
+        }
+        for h in Arc::make_mut(&mut self.hittables).iter_mut() {
             h.set_material(material.clone());
         }
-        self.mesh.properties = unified_mat.0;
+        Arc::make_mut(&mut self.mesh).properties = unified_mat.0;
         return self;
     }
     pub fn new(mesh: Mesh, hittables: Vec<Box<dyn Hittable>>, lights: Vec<Light>, mat_is_editable: bool) -> SceneObject {
-        return SceneObject { mesh, hittables, lights, mat_is_editable };
+        return SceneObject {
+            mesh: Arc::new(mesh),
+            hittables: Arc::new(hittables),
+            lights,
+            mat_is_editable,
+            instanced: false,
+            transform: Transform::identity(),
+            material_override: None,
+            node: None,
+            id: None,
+            source: None,
+        };
+    }
+
+    /// A new object in the same place, sharing this one's geometry. It isn't in the scene graph.
+    pub fn new_instance(&self) -> SceneObject {
+        let mut instance = self.clone();
+        instance.node = None;
+        instance.id = None;
+        instance.instanced = true;
+        return instance;
+    }
+
+    /// True if the object was made by new_instance(), see instanced
+    pub fn is_instance(&self) -> bool {
+        return self.instanced;
+    }
+    /// True if anything else holds this object's geometry right now. That includes instances,
+    /// but also copies kept by the edit history and the clipboard, so it's only good for
+    /// sharing work on the geometry (like BVHs), not for deciding where edits go.
+    pub fn shares_geometry(&self) -> bool {
+        return Arc::strong_count(&self.hittables) > 1 || Arc::strong_count(&self.mesh) > 1;
+    }
+
+    /// Center of the object in world space
+    pub fn get_center(&self) -> Vec3 {
+        return self.transform.transform_point(self.mesh.center);
+    }
+    /// Radius of the object's bounding sphere in world space
+    pub fn get_radius(&self) -> f32 {
+        return self.mesh.radius * self.transform.get_max_scale();
+    }
+    pub fn get_properties(&self) -> &PhongProperties {
+        return match &self.material_override {
+            Some(material_override) => &material_override.properties,
+            None => &self.mesh.properties,
+        };
+    }
+    #[inline(always)]
+    pub fn get_triangle_color(&self, triangle_index: usize) -> Vec3 {
+        if let Some(MaterialOverride { color: Some(color), .. }) = &self.material_override {
+            return *color;
+        }
+        return self.mesh.colors[triangle_index];
+    }
+    pub fn get_world_vertices(&self) -> Cow<'_, [Vec3]> {
+        if self.transform.is_identity() {
+            return Cow::Borrowed(&self.mesh.vertices);
+        }
+        let transform = self.transform.to_mat4();
+        return Cow::Owned(self.mesh.vertices.iter().map(|v| transform.transform_point(*v)).collect());
+    }
+    pub fn get_world_normals(&self) -> Cow<'_, [Vec3]> {
+        if self.transform.is_identity() {
+            return Cow::Borrowed(&self.mesh.normals);
+        }
+        let normal_mat = self.transform.to_mat4().normal_matrix();
+        return Cow::Owned(self.mesh.normals.iter().map(|n| normal_mat.transform_vector(*n).normalized()).collect());
+    }
+    /// Empty if the mesh is flat shaded
+    pub fn get_world_vertex_normals(&self) -> Cow<'_, [Vec3]> {
+        if self.transform.is_identity() {
+            return Cow::Borrowed(&self.mesh.vertex_normals);
+        }
+        let normal_mat = self.transform.to_mat4().normal_matrix();
+        return Cow::Owned(self.mesh.vertex_normals.iter().map(|n| normal_mat.transform_vector(*n).normalized()).collect());
+    }
+    /// Not normalized, empty if the mesh has no normal maps
+    pub fn get_world_tangents(&self) -> Cow<'_, [Tangent]> {
+        if self.transform.is_identity() {
+            return Cow::Borrowed(&self.mesh.tangents);
+        }
+        let transform = self.transform.to_mat4();
+        return Cow::Owned(self.mesh.tangents.iter().map(|t| Tangent { dir: transform.transform_vector(t.dir), sign: t.sign }).collect());
+    }
+    /// True if get_world_hittables() can't match what the ray tracer hits: a sphere light under
+    /// a non-uniform scale gets hit as an ellipsoid, but would be sampled as a sphere
+    pub fn has_inexact_world_hittables(&self) -> bool {
+        return self.hittables.iter().any(|h| !h.can_transform_exactly(&self.transform));
+    }
+    /// Copies of the hittables in world space, with the material override applied
+    pub fn get_world_hittables(&self) -> Vec<Box<dyn Hittable>> {
+        if self.transform.is_identity() && self.material_override.is_none() {
+            return self.hittables.iter().map(|h| h.clone_box()).collect();
+        }
+        let transform = self.transform.to_mat4();
+        return self.hittables
+            .iter()
+            .map(|h| {
+                let mut world_h = h.clone_box();
+                world_h.transform_by(&transform);
+                if let Some(material_override) = &self.material_override {
+                    world_h.set_material(material_override.material.clone());
+                    if let Some(color) = material_override.color {
+                        world_h.set_color(color);
+                    }
+                }
+                world_h
+            })
+            .collect();
     }
     pub fn new_from_mesh(mesh: Mesh, material: Box<dyn Material>, mat_is_editable: bool) -> SceneObject {
         let hittables = mesh.to_rt_hittables(material.as_ref());
@@ -162,17 +327,35 @@ impl SceneObject {
         return (phong, Box::new(mat));
     }
 
-    pub fn translate_by(&mut self, offset: Vec3) {
-        self.mesh.translate_by(offset);
-        for h in self.hittables.iter_mut() {
-            h.translate_by(offset);
+    /// Sets where the object is in the world, lights attached to it move along.
+    /// Transforms with a zero scale are refused, they couldn't be undone. So are non-uniform
+    /// scales on sphere lights, see has_inexact_world_hittables().
+    pub fn set_transform(&mut self, transform: Transform) {
+        if transform.get_min_scale() < MIN_SCALE {
+            console_error!("SceneObject::set_transform() called with a scale of {:?}, too close to 0", transform.scale);
+            return;
+        }
+        if self.is_light() && self.hittables.iter().any(|h| !h.can_transform_exactly(&transform)) {
+            console_error!("SceneObject::set_transform() called with a scale of {:?}, a sphere light can only be scaled uniformly", transform.scale);
+            return;
         }
+        if self.transform == transform {
+            return;
+        }
+        let old_to_new = transform.to_mat4() * self.transform.to_mat4().affine_inverse().unwrap_or_default();
         for l in self.lights.iter_mut() {
-            l.camera.pos += offset;
+            l.camera.pos = old_to_new.transform_point(l.camera.pos);
         }
+        self.transform = transform;
+    }
+
+    pub fn translate_by(&mut self, offset: Vec3) {
+        let mut transform = self.transform;
+        transform.translation += offset;
+        self.set_transform(transform);
     }
     pub fn translate_to(&mut self, destination: Vec3) {
-        let offset = destination - self.mesh.center;
+        let offset = destination - self.get_center();
         self.translate_by(offset);
     }
     pub fn set_center(&mut self, new_center: Vec3) {
@@ -181,38 +364,53 @@ impl SceneObject {
 
     /// Rotates in the z direction first, then y direction
     pub fn rotate_around(&mut self, center_of_rotation: Vec3, theta_z: f32, theta_y: f32) {
-        self.mesh.rotate_around(center_of_rotation, theta_z, theta_y);
-        for h in self.hittables.iter_mut() {
-            h.rotate_around(center_of_rotation, theta_z, theta_y);
-        }
-        for l in self.lights.iter_mut() {
-            l.camera.pos -= center_of_rotation;
-            l.camera.pos.rotate_z(theta_z);
-            l.camera.pos.rotate_y(theta_y);
-            l.camera.pos += center_of_rotation;
-        }
+        self.rotate_around_by(center_of_rotation, Quat::rotation_y(theta_y) * Quat::rotation_z(theta_z));
     }
     /// Rotates in the z direction first, then y direction
     pub fn rotate_around_center(&mut self, theta_z: f32, theta_y: f32) {
-        let center = self.mesh.center;
+        let center = self.get_center();
         self.rotate_around(center, theta_z, theta_y);
     }
+    pub fn rotate_around_by(&mut self, center_of_rotation: Vec3, rotation: Quat) {
+        let mut transform = self.transform;
+        transform.rotate_around(center_of_rotation, rotation);
+        self.set_transform(transform);
+    }
+    /// Right handed rotation around an axis through the object's center
+    pub fn rotate_around_center_axis(&mut self, axis: Vec3, theta: f32) {
+        let center = self.get_center();
+        self.rotate_around_by(center, Quat::from_axis_angle(axis, theta));
+    }
 
     pub fn scale_around(&mut self, center_of_scale: Vec3, scale_factor: f32) {
-        self.mesh.scale_around(center_of_scale, scale_factor);
-        for h in self.hittables.iter_mut() {
-            h.scale_around(center_of_scale, scale_factor);
-        }
+        let mut transform = self.transform;
+        transform.scale_around(center_of_scale, scale_factor);
+        self.set_transform(transform);
     }
     pub fn scale_by(&mut self, scale_factor: f32) {
-        let center = self.mesh.center;
+        let center = self.get_center();
         self.scale_around(center, scale_factor);
     }
+    /// Scales along the object's own axes (before its rotation), keeping its center in place
+    pub fn scale_axes_by(&mut self, scale_factors: Vec3) {
+        let center = self.get_center();
+        let mut transform = self.transform;
+        transform.scale = transform.scale.mul_elementwise(scale_factors);
+        transform.translation += center - transform.transform_point(self.mesh.center);
+        self.set_transform(transform);
+    }
 
     pub fn set_color(&mut self, color: Vec3) {
-        self.mesh.set_color(color);
-        for h in self.hittables.iter_mut() {
-            h.set_color(color);
+        if self.is_instance() {
+            self.get_or_create_material_override().color = Some(color);
+        } else {
+            Arc::make_mut(&mut self.mesh).set_color(color);
+            for h in Arc::make_mut(&mut self.hittables).iter_mut() {
+                h.set_color(color);
+            }
+            if let Some(source) = &mut self.source {
+                Arc::make_mut(source).set_color(color);
+            }
         }
         for l in self.lights.iter_mut() {
             l.color = color;
@@ -223,20 +421,44 @@ impl SceneObject {
         return !self.lights.is_empty();
     }
 
+    /// Starts from the shared geometry's material the first time
+    fn get_or_create_material_override(&mut self) -> &mut MaterialOverride {
+        let (mesh, hittables) = (&self.mesh, &self.hittables);
+        return self.material_override.get_or_insert_with(|| MaterialOverride {
+            properties: mesh.properties,
+            material: hittables.first().map_or(Lambertian::default().clone_box(), |h| h.get_material().clone_box()),
+            color: None,
+        });
+    }
+    /// Gives this object a material of its own, leaving the shared geometry's alone
+    pub fn set_material_override(&mut self, unified_mat: (PhongProperties, Box<dyn Material>)) {
+        let material_override = self.get_or_create_material_override();
+        material_override.properties = unified_mat.0;
+        material_override.material = unified_mat.1;
+    }
+    pub fn get_material(&self) -> Option<&dyn Material> {
+        if let Some(material_override) = &self.material_override {
+            return Some(material_override.material.as_ref());
+        }
+        return self.hittables.first().map(|h| h.get_material());
+    }
+
     // Used for interactions with JS
     /// Lambertian = 1, Metal = 2, Dielectric = 3, DiffuseLight = 4, ClearCoat = 5, ERROR = 999
     pub fn get_material_number(&self) -> u32 {
-        if self.hittables.is_empty() {
-            console_error!("SceneObject::get_material_number() called on empty object");
-            return 999;
+        match self.get_material() {
+            Some(material) => return material.get_material_number(),
+            None => {
+                console_error!("SceneObject::get_material_number() called on empty object");
+                return 999;
+            }
         }
-        return self.hittables[0].get_material().get_material_number();
     }
     pub fn get_material_extra_prop(&self) -> f32 {
         match self.get_material_number() {
             1 => 0.0,
-            2 => self.hittables[0].get_material().get_material_prop(),
-            3 => self.hittables[0].get_material().get_material_prop(),
+            2 => self.get_material().unwrap().get_material_prop(),
+            3 => self.get_material().unwrap().get_material_prop(),
             4 => self.lights[0].color.max_component(),
             _ => {
                 console_error!("SceneObject::get_material_extra_prop() called with invalid type");
@@ -257,35 +479,92 @@ impl SceneObject {
             }
         };
 
-        for h in self.hittables.iter_mut() {
-            // h.set_material_type(mat_type);
-            h.set_material(unified_mat.1.clone());
+        if self.is_instance() {
+            let material_override = self.get_or_create_material_override();
+            material_override.material = unified_mat.1;
+            material_override.properties = unified_mat.0;
+            material_override.properties.is_light = mat_type == 4;
+            material_override.color = Some(if mat_type == 4 { color * extra_prop } else { color });
+        } else {
+            for h in Arc::make_mut(&mut self.hittables).iter_mut() {
+                // h.set_material_type(mat_type);
+                h.set_material(unified_mat.1.clone());
+            }
+            if mat_type == 4 {
+                for h in Arc::make_mut(&mut self.hittables).iter_mut() {
+                    h.set_color(h.get_color() * extra_prop);
+                }
+            }
+            let mesh = Arc::make_mut(&mut self.mesh);
+            mesh.properties = unified_mat.0;
+            mesh.properties.is_light = mat_type == 4;
         }
-        if self.get_material_number() == 4 {
+
+        if mat_type == 4 {
             if self.lights.is_empty() {
-                self.lights = Light::new_omnidirectional(self.mesh.center, color, self.mesh.radius + 0.01, 1000);
+                self.lights = Light::new_omnidirectional(self.get_center(), color, self.get_radius() + 0.01, 1000);
             }
             for l in self.lights.iter_mut() {
                 l.color *= extra_prop;
             }
-            for h in self.hittables.iter_mut() {
-                h.set_color(h.get_color() * extra_prop);
-            }
         } else {
             self.lights.clear();
         }
-        self.mesh.properties = unified_mat.0;
-        self.mesh.properties.is_light = mat_type == 4;
+    }
+
+    pub fn get_material_state(&self) -> MaterialState {
+        let geometry = (!self.is_instance()).then(|| GeometryMaterialState {
+            properties: self.mesh.properties,
+            colors: self.mesh.colors.clone(),
+            textures: self.mesh.textures.clone(),
+            triangle_textures: self.mesh.triangle_textures.clone(),
+            material: self.hittables.first().map(|h| h.get_material().clone_box()),
+            hittable_colors: self.hittables.iter().map(|h| h.get_color()).collect(),
+            hittable_textures: self.hittables.iter().map(|h| h.get_texture().cloned().map(Box::new)).collect(),
+        });
+        return MaterialState {
+            geometry,
+            material_override: self.material_override.clone(),
+            lights: self.lights.clone(),
+            source: self.source.clone(),
+        };
+    }
+    /// Puts back a state from get_material_state(), the geometry has to be the same as it was then
+    pub fn set_material_state(&mut self, state: &MaterialState) {
+        if let Some(geometry) = &state.geometry {
+            let mesh = Arc::make_mut(&mut self.mesh);
+            mesh.properties = geometry.properties;
+            mesh.colors = geometry.colors.clone();
+            mesh.textures = geometry.textures.clone();
+            mesh.triangle_textures = geometry.triangle_textures.clone();
+            let hittables = Arc::make_mut(&mut self.hittables);
+            for (i, h) in hittables.iter_mut().enumerate() {
+                if let Some(material) = &geometry.material {
+                    h.set_material(material.clone());
+                }
+                h.set_color(geometry.hittable_colors[i]);
+                h.set_texture(geometry.hittable_textures[i].clone());
+            }
+        }
+        self.material_override = state.material_override.clone();
+        self.lights = state.lights.clone();
+        self.source = state.source.clone();
     }
 }
 
 impl Clone for SceneObject {
     fn clone(&self) -> Self {
         SceneObject {
-            mesh: self.mesh.clone(),
-            hittables: self.hittables.iter().map(|h| h.clone_box()).collect(),
+            mesh: Arc::clone(&self.mesh),
+            hittables: Arc::clone(&self.hittables),
             lights: self.lights.clone(),
             mat_is_editable: self.mat_is_editable,
+            instanced: self.instanced,
+            transform: self.transform,
+            material_override: self.material_override.clone(),
+            node: self.node,
+            id: self.id,
+            source: self.source.clone(),
         }
     }
 }
\ No newline at end of file
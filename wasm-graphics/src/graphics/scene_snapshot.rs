@@ -0,0 +1,715 @@
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+
+use image::{load_from_memory, ImageFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::{console_log, utils::math::{Mat4, Quat, Transform, Vec3}};
+
+use super::{game::Game, lighting::Light, mesh::{Mesh, PhongProperties}, normal_map::{NormalMap, NormalMapKind, Tangent, TriangleNormalMap}, ray_tracing::{hittable::{Hittable, Sphere, Triangle}, material::{ClearCoat, Dielectric, DiffuseLight, Lambertian, Material, Metal}}, scene_file::SkyDescription, scene_graph::SceneGraph, scene_object::{MaterialOverride, SceneObject}, texture::{Texture, TriangleTexture}};
+
+// Unlike scene files (scene_file.rs), which describe how to build a scene, a snapshot
+// stores the scene exactly as it currently is: every mesh, hittable and light after
+// whatever the user did to it in the editor. Snapshots are JSON, but aren't meant
+// to be written by hand.
+//
+// Version 2 added instances: geometry shared by several objects is only stored with the
+// first of them, the others refer back to it.
+// Version 3 gave every object a transform, stored as translation, rotation and scale.
+// Version 4 added the scene graph: nodes with a parent and a local transform, which objects refer to.
+// Version 5 added vertex normals to smooth shaded meshes and triangles.
+// Version 6 added textures and UVs. Each texture is stored once as a PNG, meshes and
+// triangles refer to it by its index in SceneSnapshot::textures.
+// Version 7 added normal and bump maps, along with their tangents.
+
+const SNAPSHOT_VERSION: u32 = 7;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneSnapshot {
+    pub version: u32,
+    pub camera: CameraSnapshot,
+    pub defocus_angle: f32,
+    pub focus_dist: f32,
+    pub sky: SkyDescription,
+    pub ray_max_depth: usize,
+    pub objects: Vec<SceneObjectSnapshot>,
+    /// Parents always come before their children
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<SceneNodeSnapshot>,
+    /// Every texture the objects use, whether to color them or as a normal or bump map
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub textures: Vec<TextureSnapshot>,
+}
+
+/// Angles are in radians
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraSnapshot {
+    pub pos: [f32; 3],
+    pub theta_y: f32,
+    pub theta_z: f32,
+    pub fov: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneObjectSnapshot {
+    /// Left out if the geometry is shared with an earlier object (see shares_geometry_with)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mesh: Option<MeshSnapshot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hittables: Vec<HittableSnapshot>,
+    pub lights: Vec<LightSnapshot>,
+    pub mat_is_editable: bool,
+    /// Index of an earlier object whose mesh and hittables this one uses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shares_geometry_with: Option<usize>,
+    /// Left out for the identity transform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material_override: Option<MaterialOverrideSnapshot>,
+    /// Index into SceneSnapshot::nodes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneNodeSnapshot {
+    pub name: String,
+    /// Relative to the parent, left out for the identity transform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TransformSnapshot {
+    /// rotation is a quaternion, [w, x, y, z]
+    Parts { translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3] },
+    /// Version 2 only had instance transforms, stored as a row major matrix
+    Matrix([[f32; 4]; 4]),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaterialOverrideSnapshot {
+    pub properties: PhongSnapshot,
+    pub material: MaterialSnapshot,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 3]>,
+}
+
+/// Face normals are recalculated from the vertices on load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshSnapshot {
+    pub vertices: Vec<[f32; 3]>,
+    pub indices: Vec<usize>,
+    pub colors: Vec<[f32; 3]>,
+    /// One per index, empty for flat shading
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vertex_normals: Vec<[f32; 3]>,
+    /// Per vertex, empty for meshes without textures or normal maps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<[f32; 2]>,
+    /// Indices into SceneSnapshot::textures
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub textures: Vec<usize>,
+    /// Per triangle index into textures
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triangle_textures: Vec<Option<usize>>,
+    /// Left out for meshes without textures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture_tint: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub normal_maps: Vec<NormalMapSnapshot>,
+    /// Per triangle index into normal_maps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triangle_normal_maps: Vec<Option<usize>>,
+    /// One per index like vertex_normals, see tangent_arr()
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tangents: Vec<[f32; 4]>,
+    pub properties: PhongSnapshot,
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextureSnapshot {
+    /// A PNG file, base64 encoded
+    pub png: String,
+}
+
+/// texture is an index into SceneSnapshot::textures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NormalMapSnapshot {
+    Normal {
+        texture: usize,
+        scale: f32,
+    },
+    Bump {
+        texture: usize,
+        strength: f32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriangleTextureSnapshot {
+    /// Index into SceneSnapshot::textures
+    pub texture: usize,
+    pub uvs: [[f32; 2]; 3],
+    pub tint: [f32; 3],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriangleNormalMapSnapshot {
+    pub normal_map: NormalMapSnapshot,
+    pub uvs: [[f32; 2]; 3],
+    pub tangents: [[f32; 4]; 3],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhongSnapshot {
+    pub alpha: f32,
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    pub shininess: i32,
+    pub is_light: bool,
+    pub cull_faces: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HittableSnapshot {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        color: [f32; 3],
+        material: MaterialSnapshot,
+    },
+    Triangle {
+        origin: [f32; 3],
+        u: [f32; 3],
+        v: [f32; 3],
+        color: [f32; 3],
+        material: MaterialSnapshot,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normals: Option<[[f32; 3]; 3]>,
+        /// Boxed so untextured triangles and spheres stay small
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        texture: Option<Box<TriangleTextureSnapshot>>,
+        /// Boxed like texture
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normal_map: Option<Box<TriangleNormalMapSnapshot>>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialSnapshot {
+    Lambertian,
+    Metal {
+        fuzz: f32,
+    },
+    Dielectric {
+        index_of_refrac: f32,
+    },
+    DiffuseLight,
+    ClearCoat {
+        base_material: Box<MaterialSnapshot>,
+        index_of_refraction: f32,
+    },
+}
+
+/// Shadow maps aren't saved, they get recalculated the next time lighting is turned on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightSnapshot {
+    pub pos: [f32; 3],
+    pub theta_y: f32,
+    pub theta_z: f32,
+    pub fov: f32,
+    pub color: [f32; 3],
+    pub min_dist: f32,
+    pub max_dist: f32,
+    pub buf_width: usize,
+    pub buf_height: usize,
+}
+
+fn arr(v: Vec3) -> [f32; 3] {
+    return [v.x, v.y, v.z];
+}
+fn vec3(a: [f32; 3]) -> Vec3 {
+    return Vec3::new(a[0], a[1], a[2]);
+}
+/// The direction, then the sign
+fn tangent_arr(t: Tangent) -> [f32; 4] {
+    return [t.dir.x, t.dir.y, t.dir.z, t.sign];
+}
+fn tangent(a: [f32; 4]) -> Tangent {
+    return Tangent { dir: Vec3::new(a[0], a[1], a[2]), sign: a[3] };
+}
+fn get_texture(textures: &[Arc<Texture>], i: usize) -> Result<Arc<Texture>, String> {
+    return textures.get(i).cloned().ok_or_else(|| format!("texture {} doesn't exist", i));
+}
+
+/// The textures met while snapshotting, so each one is stored once
+/// however many meshes, triangles and normal maps use it
+#[derive(Default)]
+pub struct SnapshotTextures {
+    textures: Vec<Arc<Texture>>,
+}
+
+impl SnapshotTextures {
+    /// Where the texture will be in SceneSnapshot::textures
+    pub fn index_of(&mut self, texture: &Arc<Texture>) -> usize {
+        if let Some(i) = self.textures.iter().position(|t| Arc::ptr_eq(t, texture)) {
+            return i;
+        }
+        self.textures.push(texture.clone());
+        return self.textures.len() - 1;
+    }
+}
+
+// SNAPSHOTTING
+impl PhongSnapshot {
+    pub fn new(properties: &PhongProperties) -> PhongSnapshot {
+        return PhongSnapshot {
+            alpha: properties.alpha,
+            ambient: properties.ambient,
+            diffuse: properties.diffuse,
+            specular: properties.specular,
+            shininess: properties.shininess,
+            is_light: properties.is_light,
+            cull_faces: properties.cull_faces,
+        };
+    }
+    pub fn to_phong_properties(&self) -> PhongProperties {
+        return PhongProperties::new(self.alpha, self.ambient, self.diffuse, self.specular, self.shininess, self.is_light, self.cull_faces);
+    }
+}
+
+impl TextureSnapshot {
+    pub fn new(texture: &Texture) -> TextureSnapshot {
+        let mut png = Vec::new();
+        texture.to_image().write_to(&mut Cursor::new(&mut png), ImageFormat::Png).expect("PNGs can always be written to memory");
+        return TextureSnapshot { png: base64::encode(&png) };
+    }
+    pub fn to_texture(&self) -> Result<Texture, String> {
+        let png = base64::decode(&self.png).map_err(|e| format!("invalid base64: {}", e))?;
+        let image = load_from_memory(&png).map_err(|e| format!("couldn't decode image: {}", e))?;
+        return Ok(Texture::from_image(&image));
+    }
+}
+
+impl NormalMapSnapshot {
+    pub fn new(normal_map: &NormalMap, textures: &mut SnapshotTextures) -> NormalMapSnapshot {
+        let texture = textures.index_of(&normal_map.texture);
+        return match normal_map.kind {
+            NormalMapKind::Normal { scale } => NormalMapSnapshot::Normal { texture, scale },
+            NormalMapKind::Bump { strength } => NormalMapSnapshot::Bump { texture, strength },
+        };
+    }
+    pub fn to_normal_map(&self, textures: &[Arc<Texture>]) -> Result<NormalMap, String> {
+        return Ok(match self {
+            NormalMapSnapshot::Normal { texture, scale } => NormalMap { texture: get_texture(textures, *texture)?, kind: NormalMapKind::Normal { scale: *scale } },
+            NormalMapSnapshot::Bump { texture, strength } => NormalMap { texture: get_texture(textures, *texture)?, kind: NormalMapKind::Bump { strength: *strength } },
+        });
+    }
+}
+
+impl MeshSnapshot {
+    pub fn new(mesh: &Mesh, textures: &mut SnapshotTextures) -> MeshSnapshot {
+        return MeshSnapshot {
+            vertices: mesh.vertices.iter().map(|v| arr(*v)).collect(),
+            indices: mesh.indices.clone(),
+            colors: mesh.colors.iter().map(|c| arr(*c)).collect(),
+            vertex_normals: mesh.vertex_normals.iter().map(|n| arr(*n)).collect(),
+            uvs: mesh.uvs.clone(),
+            textures: mesh.textures.iter().map(|t| textures.index_of(t)).collect(),
+            triangle_textures: mesh.triangle_textures.clone(),
+            texture_tint: if mesh.has_textures() { Some(arr(mesh.texture_tint)) } else { None },
+            normal_maps: mesh.normal_maps.iter().map(|m| NormalMapSnapshot::new(m, textures)).collect(),
+            triangle_normal_maps: mesh.triangle_normal_maps.clone(),
+            tangents: mesh.tangents.iter().map(|t| tangent_arr(*t)).collect(),
+            properties: PhongSnapshot::new(&mesh.properties),
+            center: arr(mesh.center),
+            radius: mesh.radius,
+        };
+    }
+    /// textures are the ones in SceneSnapshot::textures
+    pub fn to_mesh(&self, textures: &[Arc<Texture>]) -> Result<Mesh, String> {
+        if self.vertices.is_empty() || !self.indices.len().is_multiple_of(3) {
+            return Err(String::from("mesh needs at least one vertex and a multiple of 3 indices"));
+        }
+        if self.indices.iter().any(|i| *i >= self.vertices.len()) {
+            return Err(String::from("mesh index out of range"));
+        }
+        if self.colors.len() != self.indices.len() / 3 {
+            return Err(format!("mesh has {} triangles but {} colors", self.indices.len() / 3, self.colors.len()));
+        }
+        if !self.vertex_normals.is_empty() && self.vertex_normals.len() != self.indices.len() {
+            return Err(format!("mesh has {} indices but {} vertex normals", self.indices.len(), self.vertex_normals.len()));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != self.vertices.len() {
+            return Err(format!("mesh has {} vertices but {} UVs", self.vertices.len(), self.uvs.len()));
+        }
+        if (!self.textures.is_empty() || !self.normal_maps.is_empty()) && self.uvs.is_empty() {
+            return Err(String::from("mesh has textures or normal maps but no UVs"));
+        }
+        for (name, triangle_maps, map_count) in [("textures", &self.triangle_textures, self.textures.len()), ("normal maps", &self.triangle_normal_maps, self.normal_maps.len())] {
+            if !triangle_maps.is_empty() && triangle_maps.len() != self.indices.len() / 3 {
+                return Err(format!("mesh has {} triangles but {} triangle {}", self.indices.len() / 3, triangle_maps.len(), name));
+            }
+            if triangle_maps.iter().flatten().any(|i| *i >= map_count) {
+                return Err(format!("mesh has {} {}, a triangle uses one past them", map_count, name));
+            }
+        }
+        if !self.normal_maps.is_empty() && self.tangents.len() != self.indices.len() {
+            return Err(format!("mesh has {} indices but {} tangents", self.indices.len(), self.tangents.len()));
+        }
+
+        let vertices = self.vertices.iter().map(|v| vec3(*v)).collect();
+        let colors = self.colors.iter().map(|c| vec3(*c)).collect();
+        let mut mesh = Mesh::new(vertices, self.indices.clone(), colors, self.properties.to_phong_properties());
+        mesh.vertex_normals = self.vertex_normals.iter().map(|n| vec3(*n)).collect();
+        mesh.uvs = self.uvs.clone();
+        mesh.textures = self.textures.iter().map(|i| get_texture(textures, *i)).collect::<Result<_, _>>()?;
+        mesh.triangle_textures = self.triangle_textures.clone();
+        if let Some(tint) = self.texture_tint {
+            mesh.texture_tint = vec3(tint);
+        }
+        mesh.normal_maps = self.normal_maps.iter().map(|m| m.to_normal_map(textures).map(Arc::new)).collect::<Result<_, _>>()?;
+        mesh.triangle_normal_maps = self.triangle_normal_maps.clone();
+        mesh.tangents = self.tangents.iter().map(|t| tangent(*t)).collect();
+        mesh.center = vec3(self.center);
+        mesh.radius = self.radius;
+        return Ok(mesh);
+    }
+}
+
+impl MaterialSnapshot {
+    pub fn to_material(&self) -> Box<dyn Material> {
+        return match self {
+            MaterialSnapshot::Lambertian => Box::new(Lambertian::default()),
+            MaterialSnapshot::Metal { fuzz } => Box::new(Metal::new(*fuzz)),
+            MaterialSnapshot::Dielectric { index_of_refrac } => Box::new(Dielectric::new(*index_of_refrac)),
+            MaterialSnapshot::DiffuseLight => Box::new(DiffuseLight::default()),
+            MaterialSnapshot::ClearCoat { base_material, index_of_refraction } => {
+                Box::new(ClearCoat::new(base_material.to_material(), *index_of_refraction))
+            },
+        };
+    }
+}
+
+impl HittableSnapshot {
+    pub fn sphere(sphere: &Sphere) -> HittableSnapshot {
+        return HittableSnapshot::Sphere {
+            center: arr(sphere.center),
+            radius: sphere.radius,
+            color: arr(sphere.color),
+            material: sphere.material.to_snapshot(),
+        };
+    }
+    pub fn triangle(triangle: &Triangle, textures: &mut SnapshotTextures) -> HittableSnapshot {
+        return HittableSnapshot::Triangle {
+            origin: arr(triangle.origin),
+            u: arr(triangle.u),
+            v: arr(triangle.v),
+            color: arr(triangle.color),
+            material: triangle.material.to_snapshot(),
+            normals: triangle.vertex_normals.as_ref().map(|normals| normals.map(arr)),
+            texture: triangle.texture.as_ref().map(|t| Box::new(TriangleTextureSnapshot {
+                texture: textures.index_of(&t.texture),
+                uvs: t.uvs,
+                tint: arr(t.tint),
+            })),
+            normal_map: triangle.normal_map.as_ref().map(|m| Box::new(TriangleNormalMapSnapshot {
+                normal_map: NormalMapSnapshot::new(&m.normal_map, textures),
+                uvs: m.uvs,
+                tangents: m.tangents.map(tangent_arr),
+            })),
+        };
+    }
+    /// textures are the ones in SceneSnapshot::textures
+    pub fn to_hittable(&self, textures: &[Arc<Texture>]) -> Result<Box<dyn Hittable>, String> {
+        return Ok(match self {
+            HittableSnapshot::Sphere { center, radius, color, material } => {
+                Box::new(Sphere::new(vec3(*center), *radius, vec3(*color), material.to_material()))
+            },
+            HittableSnapshot::Triangle { origin, u, v, color, material, normals, texture, normal_map } => {
+                let mut triangle = Triangle::new_from_directions(vec3(*origin), vec3(*u), vec3(*v), vec3(*color), material.to_material().as_ref());
+                triangle.vertex_normals = normals.map(|normals| Box::new(normals.map(vec3)));
+                if let Some(t) = texture {
+                    triangle.texture = Some(Box::new(TriangleTexture { texture: get_texture(textures, t.texture)?, uvs: t.uvs, tint: vec3(t.tint) }));
+                }
+                if let Some(m) = normal_map {
+                    triangle.normal_map = Some(Box::new(TriangleNormalMap {
+                        normal_map: Arc::new(m.normal_map.to_normal_map(textures)?),
+                        uvs: m.uvs,
+                        tangents: m.tangents.map(tangent),
+                    }));
+                }
+                Box::new(triangle)
+            },
+        });
+    }
+}
+
+impl LightSnapshot {
+    pub fn new(light: &Light) -> LightSnapshot {
+        return LightSnapshot {
+            pos: arr(light.camera.pos),
+            theta_y: light.camera.get_theta_y(),
+            theta_z: light.camera.get_theta_z(),
+            fov: light.camera.get_fov(),
+            color: arr(light.color),
+            min_dist: light.min_dist,
+            max_dist: light.max_dist,
+            buf_width: light.camera.width,
+            buf_height: light.camera.height,
+        };
+    }
+    pub fn to_light(&self) -> Light {
+        let mut light = Light::new_with_angle(
+            vec3(self.pos),
+            self.theta_y,
+            self.theta_z,
+            self.fov,
+            vec3(self.color),
+            self.min_dist,
+            self.buf_width,
+            self.buf_height,
+        );
+        light.max_dist = self.max_dist;
+        return light;
+    }
+}
+
+impl TransformSnapshot {
+    pub fn new(transform: &Transform) -> TransformSnapshot {
+        let rotation = transform.rotation;
+        return TransformSnapshot::Parts {
+            translation: arr(transform.translation),
+            rotation: [rotation.w, rotation.x, rotation.y, rotation.z],
+            scale: arr(transform.scale),
+        };
+    }
+    pub fn to_transform(&self) -> Result<Transform, String> {
+        let transform = match self {
+            TransformSnapshot::Parts { translation, rotation, scale } => {
+                let [w, x, y, z] = *rotation;
+                let mut rotation = Quat::new(w, x, y, z);
+                // only fix up rotations that aren't unit length, so saved ones come back exactly
+                if (rotation.len() - 1.0).abs() > 1e-4 {
+                    rotation = rotation.normalized();
+                }
+                Transform::new(vec3(*translation), rotation, vec3(*scale))
+            },
+            TransformSnapshot::Matrix(m) => Transform::from_mat4(&Mat4::new(*m)).ok_or("transform can't be inverted")?,
+        };
+        if transform.get_min_scale() == 0.0 {
+            return Err(String::from("transform has a scale of 0"));
+        }
+        return Ok(transform);
+    }
+}
+
+impl SceneObjectSnapshot {
+    /// shares_geometry_with is the index of an earlier object with the same mesh and hittables, if any,
+    /// node is the object's node as saved in SceneSnapshot::nodes
+    pub fn new(scene_obj: &SceneObject, shares_geometry_with: Option<usize>, node: Option<usize>, textures: &mut SnapshotTextures) -> SceneObjectSnapshot {
+        let (mesh, hittables) = match shares_geometry_with {
+            Some(_) => (None, Vec::new()),
+            None => (Some(MeshSnapshot::new(&scene_obj.mesh, textures)), scene_obj.hittables.iter().map(|h| h.to_snapshot(textures)).collect()),
+        };
+        return SceneObjectSnapshot {
+            mesh,
+            hittables,
+            lights: scene_obj.lights.iter().map(LightSnapshot::new).collect(),
+            mat_is_editable: scene_obj.mat_is_editable,
+            shares_geometry_with,
+            transform: if scene_obj.transform.is_identity() { None } else { Some(TransformSnapshot::new(&scene_obj.transform)) },
+            material_override: scene_obj.material_override.as_ref().map(|m| MaterialOverrideSnapshot {
+                properties: PhongSnapshot::new(&m.properties),
+                material: m.material.to_snapshot(),
+                color: m.color.map(arr),
+            }),
+            node,
+        };
+    }
+    /// earlier_objects are the objects already restored, for shares_geometry_with to refer to,
+    /// textures are the ones in SceneSnapshot::textures
+    pub fn to_scene_object(&self, earlier_objects: &[SceneObject], textures: &[Arc<Texture>]) -> Result<SceneObject, String> {
+        let lights = self.lights.iter().map(|l| l.to_light()).collect();
+        let mut scene_obj = match (self.shares_geometry_with, &self.mesh) {
+            (Some(i), _) => {
+                let Some(source_obj) = earlier_objects.get(i) else {
+                    return Err(format!("shares geometry with object {}, which doesn't come before it", i));
+                };
+                let mut scene_obj = source_obj.clone();
+                scene_obj.instanced = true;
+                scene_obj.lights = lights;
+                scene_obj.mat_is_editable = self.mat_is_editable;
+                scene_obj
+            },
+            (None, Some(mesh)) => {
+                let hittables = self.hittables.iter().map(|h| h.to_hittable(textures)).collect::<Result<_, _>>()?;
+                SceneObject::new(mesh.to_mesh(textures)?, hittables, lights, self.mat_is_editable)
+            },
+            (None, None) => return Err(String::from("has no mesh")),
+        };
+        // lights were saved where they are in the world, so this doesn't go through set_transform()
+        scene_obj.transform = match &self.transform {
+            Some(transform) => transform.to_transform()?,
+            None => Transform::identity(),
+        };
+        scene_obj.material_override = self.material_override.as_ref().map(|m| MaterialOverride {
+            properties: m.properties.to_phong_properties(),
+            material: m.material.to_material(),
+            color: m.color.map(vec3),
+        });
+        scene_obj.node = self.node;
+        return Ok(scene_obj);
+    }
+}
+
+impl SceneNodeSnapshot {
+    /// The graph's nodes, parents first, and where each node index ended up in that list
+    pub fn from_scene_graph(scene_graph: &SceneGraph) -> (Vec<SceneNodeSnapshot>, HashMap<usize, usize>) {
+        let saved_order = scene_graph.get_nodes_parents_first();
+        let saved_indices: HashMap<usize, usize> = saved_order.iter().enumerate().map(|(saved, &i)| (i, saved)).collect();
+        let nodes = saved_order
+            .iter()
+            .map(|&i| {
+                let node = scene_graph.get(i).unwrap();
+                SceneNodeSnapshot {
+                    name: node.name.clone(),
+                    transform: if node.local_transform.is_identity() { None } else { Some(TransformSnapshot::new(&node.local_transform)) },
+                    parent: node.parent.map(|parent| saved_indices[&parent]),
+                }
+            })
+            .collect();
+        return (nodes, saved_indices);
+    }
+
+    pub fn to_scene_graph(nodes: &[SceneNodeSnapshot]) -> Result<SceneGraph, String> {
+        let mut scene_graph = SceneGraph::new();
+        for (i, node) in nodes.iter().enumerate() {
+            if node.parent.is_some_and(|parent| parent >= i) {
+                return Err(format!("node {}: parent {:?} doesn't come before it", i, node.parent));
+            }
+            let transform = match &node.transform {
+                Some(transform) => transform.to_transform().map_err(|e| format!("node {}: {}", i, e))?,
+                None => Transform::identity(),
+            };
+            scene_graph.add_node(&node.name, transform, node.parent);
+        }
+        return Ok(scene_graph);
+    }
+}
+
+impl Game {
+    pub fn to_scene_snapshot(&self) -> SceneSnapshot {
+        let (nodes, saved_node_indices) = SceneNodeSnapshot::from_scene_graph(&self.scene_graph);
+        let mut textures = SnapshotTextures::default();
+        let objects = self.scene_objects
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .scan(Vec::<(usize, &SceneObject)>::new(), |geometry_owners, (i, scene_obj)| {
+                let shares_geometry_with = geometry_owners
+                    .iter()
+                    .find(|(_, owner)| Arc::ptr_eq(&owner.mesh, &scene_obj.mesh) && Arc::ptr_eq(&owner.hittables, &scene_obj.hittables))
+                    .map(|(owner_index, _)| *owner_index);
+                if shares_geometry_with.is_none() {
+                    geometry_owners.push((i, scene_obj));
+                }
+                let node = scene_obj.node.and_then(|node| saved_node_indices.get(&node).copied());
+                Some(SceneObjectSnapshot::new(scene_obj, shares_geometry_with, node, &mut textures))
+            })
+            .collect();
+        return SceneSnapshot {
+            version: SNAPSHOT_VERSION,
+            camera: CameraSnapshot {
+                pos: arr(self.camera.pos),
+                theta_y: self.camera.get_theta_y(),
+                theta_z: self.camera.get_theta_z(),
+                fov: self.camera.get_fov(),
+            },
+            defocus_angle: self.defocus_angle,
+            focus_dist: self.focus_dist,
+            sky: SkyDescription {
+                max_color: arr(self.max_sky_color),
+                min_color: arr(self.min_sky_color),
+                rt_max_color: arr(self.rt_max_sky_color),
+                rt_min_color: arr(self.rt_min_sky_color),
+            },
+            ray_max_depth: self.ray_max_depth,
+            objects,
+            nodes,
+            textures: textures.textures.iter().map(|t| TextureSnapshot::new(t)).collect(),
+        };
+    }
+
+    /// Replaces the current scene with the snapshot. On error the current scene is left untouched.
+    pub fn load_scene_snapshot(&mut self, snapshot: &SceneSnapshot) -> Result<(), String> {
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(format!("scene was saved by a newer version (format {}, this build reads up to {})", snapshot.version, SNAPSHOT_VERSION));
+        }
+        if snapshot.ray_max_depth == 0 {
+            return Err(String::from("ray_max_depth has to be at least 1"));
+        }
+        let textures = snapshot.textures
+            .iter()
+            .enumerate()
+            .map(|(i, t)| t.to_texture().map(Arc::new).map_err(|e| format!("texture {}: {}", i, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut scene_objects = Vec::with_capacity(snapshot.objects.len());
+        for (i, obj) in snapshot.objects.iter().enumerate() {
+            let scene_obj = obj.to_scene_object(&scene_objects, &textures).map_err(|e| format!("object {}: {}", i, e))?;
+            if scene_obj.node.is_some_and(|node| node >= snapshot.nodes.len()) {
+                return Err(format!("object {}: node {:?} doesn't exist", i, scene_obj.node));
+            }
+            scene_objects.push(scene_obj);
+        }
+        let scene_graph = SceneNodeSnapshot::to_scene_graph(&snapshot.nodes)?;
+
+        self.pre_scene_load();
+
+        *self.scene_objects.write().unwrap() = scene_objects;
+        self.scene_graph = scene_graph;
+
+        self.max_sky_color = vec3(snapshot.sky.max_color);
+        self.min_sky_color = vec3(snapshot.sky.min_color);
+        self.rt_max_sky_color = vec3(snapshot.sky.rt_max_color);
+        self.rt_min_sky_color = vec3(snapshot.sky.rt_min_color);
+
+        self.ray_max_depth = snapshot.ray_max_depth;
+
+        self.camera.pos = vec3(snapshot.camera.pos);
+        self.camera.set_theta_y(snapshot.camera.theta_y);
+        self.camera.set_theta_z(snapshot.camera.theta_z);
+        self.camera.set_fov(snapshot.camera.fov);
+        self.defocus_angle = snapshot.defocus_angle;
+        self.focus_dist = snapshot.focus_dist;
+
+        self.post_scene_load();
+        return Ok(());
+    }
+
+    /// Saves everything in the scene (objects, camera, depth of field, sky) so that
+    /// an editing session can be restored later with deserialize_scene()
+    pub fn serialize_scene(&self) -> Vec<u8> {
+        let snapshot = self.to_scene_snapshot();
+        let bytes = serde_json::to_vec(&snapshot).expect("scene snapshot is always serializable");
+        console_log!("Serialized scene with {} objects ({} bytes)", snapshot.objects.len(), bytes.len());
+        return bytes;
+    }
+
+    /// Restores a scene saved by serialize_scene(). On error the current scene is left untouched.
+    pub fn deserialize_scene(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let snapshot: SceneSnapshot = serde_json::from_slice(bytes).map_err(|e| format!("Invalid scene data: {}", e))?;
+        return self.load_scene_snapshot(&snapshot);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::utils::math::{BarycentricSolver, Vec3};
+
+use super::{normal_map::{NormalMap, Tangent}, texture::{interpolate_uv, TextureFilter, TriangleColor}};
+
+// What the rasterizer needs to shade the pixels of one triangle: its color, flat or from
+// a texture, and its normal, flat or blended from the vertex normals (Phong shading) and
+// then bent by the normal map if it has one.
+// Both come from the pixel's barycentric coordinates, found from its position in camera
+// space, which makes them perspective correct without interpolating anything over 1/z.
+
+#[derive(Debug, Clone, Copy)]
+pub struct TriangleShading<'a> {
+    pub color: TriangleColor<'a>,
+    /// The face normal, in world space
+    pub normal: Vec3,
+    /// In world space, None for flat shading
+    pub vertex_normals: Option<[Vec3; 3]>,
+    pub normal_map: Option<ShadingNormalMap<'a>>,
+    /// Over the triangle's vertices in camera space, None if nothing needs it
+    barycentric: Option<BarycentricSolver>,
+}
+
+impl<'a> TriangleShading<'a> {
+    pub fn flat(color: Vec3, normal: Vec3) -> TriangleShading<'a> {
+        return TriangleShading { color: TriangleColor::Flat(color), normal, vertex_normals: None, normal_map: None, barycentric: None };
+    }
+    /// v1, v2 and v3 are the triangle's vertices in camera space
+    pub fn new(color: TriangleColor<'a>, normal: Vec3, vertex_normals: Option<[Vec3; 3]>, normal_map: Option<ShadingNormalMap<'a>>, v1: Vec3, v2: Vec3, v3: Vec3) -> TriangleShading<'a> {
+        let needs_barycentric = vertex_normals.is_some() || normal_map.is_some() || matches!(color, TriangleColor::Textured { .. });
+        let barycentric = if needs_barycentric { Some(BarycentricSolver::new(v1, v2, v3)) } else { None };
+        return TriangleShading { color, normal, vertex_normals, normal_map, barycentric };
+    }
+
+    /// For back faces that aren't culled, so they're lit from the camera's side
+    pub fn flip_normals(&mut self) {
+        self.normal *= -1.0;
+        if let Some(vertex_normals) = &mut self.vertex_normals {
+            for n in vertex_normals.iter_mut() {
+                *n *= -1.0;
+            }
+        }
+        if let Some(normal_map) = &mut self.normal_map {
+            normal_map.tangents = normal_map.tangents.map(Tangent::flipped);
+        }
+    }
+
+    /// Color and normal at a point on the triangle, in camera space
+    #[inline(always)]
+    pub fn at(&self, camera_space_pos: Vec3) -> (Vec3, Vec3) {
+        let Some(barycentric) = &self.barycentric else {
+            return (self.color.at([1.0, 0.0, 0.0]), self.normal);
+        };
+        let weights = barycentric.solve(camera_space_pos);
+        let normal = match &self.vertex_normals {
+            Some([n1, n2, n3]) => {
+                let blended = *n1 * weights[0] + *n2 * weights[1] + *n3 * weights[2];
+                // vertex normals pointing opposite ways can cancel out
+                if blended.len_squared() > 1e-12 { blended.normalized() } else { self.normal }
+            },
+            None => self.normal,
+        };
+        let normal = match &self.normal_map {
+            Some(normal_map) => normal_map.perturb(normal, weights),
+            None => normal,
+        };
+        return (self.color.at(weights), normal);
+    }
+}
+
+/// A triangle's normal map, with its vertices' UVs and tangents in world space
+#[derive(Debug, Clone, Copy)]
+pub struct ShadingNormalMap<'a> {
+    pub normal_map: &'a NormalMap,
+    pub uvs: [[f32; 2]; 3],
+    pub tangents: [Tangent; 3],
+    pub filter: TextureFilter,
+}
+
+impl ShadingNormalMap<'_> {
+    #[inline(always)]
+    fn perturb(&self, normal: Vec3, barycentric: [f32; 3]) -> Vec3 {
+        let tangent_space_normal = self.normal_map.sample(interpolate_uv(&self.uvs, barycentric), self.filter);
+        return Tangent::interpolate(&self.tangents, barycentric).perturb(normal, tangent_space_normal);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{fmt::Debug, sync::Arc};
+
+use image::{DynamicImage, RgbImage};
+
+use crate::utils::math::Vec3;
+
+use super::game::Game;
+
+// Image textures sampled per pixel by the rasterizer and per hit by the path tracer.
+// Both renderers find the point's barycentric coordinates on its triangle, then blend
+// the triangle's vertex UVs with them (see shading.rs for how the rasterizer finds them).
+//
+// Texels are kept as bytes and read as if already linear, like the per-triangle colors
+// baked from textures always have been.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureFilter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+impl TextureFilter {
+    /// used ONLY for interaction with JS
+    pub fn from_number(n: u32) -> Option<TextureFilter> {
+        return match n {
+            0 => Some(TextureFilter::Nearest),
+            1 => Some(TextureFilter::Bilinear),
+            _ => None,
+        };
+    }
+    pub fn to_number(&self) -> u32 {
+        return match self {
+            TextureFilter::Nearest => 0,
+            TextureFilter::Bilinear => 1,
+        };
+    }
+}
+
+impl Game {
+    pub fn set_texture_filter(&mut self, filter: TextureFilter) {
+        self.texture_filter = filter;
+        // samples taken with the old filter would blend into the new ones
+        self.ray_samples_accumulated = 0;
+    }
+}
+
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    /// Row major, starting at the top left, which is where UV (0, 0) is
+    texels: Vec<[u8; 3]>,
+}
+
+// the texels would flood any log the mesh is printed to
+impl Debug for Texture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Texture({}x{})", self.width, self.height);
+    }
+}
+
+impl Texture {
+    pub fn from_image(image: &DynamicImage) -> Texture {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let texels = rgb.pixels().map(|p| p.0).collect();
+        return Texture { width: width as usize, height: height as usize, texels };
+    }
+    pub fn to_image(&self) -> RgbImage {
+        let bytes = self.texels.iter().flatten().copied().collect();
+        return RgbImage::from_raw(self.width as u32, self.height as u32, bytes).expect("texels always fill the texture");
+    }
+
+    #[inline(always)]
+    fn texel(&self, x: isize, y: isize) -> Vec3 {
+        // UVs outside 0..1 repeat the texture
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        let [r, g, b] = self.texels[y * self.width + x];
+        return Vec3::new(r as f32, g as f32, b as f32) / 255.0;
+    }
+
+    pub fn sample(&self, uv: [f32; 2], filter: TextureFilter) -> Vec3 {
+        let x = uv[0] * self.width as f32;
+        let y = uv[1] * self.height as f32;
+        if !x.is_finite() || !y.is_finite() {
+            return self.texel(0, 0);
+        }
+        return match filter {
+            TextureFilter::Nearest => self.texel(x.floor() as isize, y.floor() as isize),
+            TextureFilter::Bilinear => {
+                // texel centers are at half coordinates
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            },
+        };
+    }
+}
+
+/// Blends the UVs of a triangle's vertices by the point's barycentric coordinates
+#[inline(always)]
+pub fn interpolate_uv(uvs: &[[f32; 2]; 3], barycentric: [f32; 3]) -> [f32; 2] {
+    return [
+        uvs[0][0] * barycentric[0] + uvs[1][0] * barycentric[1] + uvs[2][0] * barycentric[2],
+        uvs[0][1] * barycentric[0] + uvs[1][1] * barycentric[1] + uvs[2][1] * barycentric[2],
+    ];
+}
+
+/// A path tracer triangle's texture, which keeps its own copy of its vertices' UVs
+#[derive(Debug, Clone)]
+pub struct TriangleTexture {
+    pub texture: Arc<Texture>,
+    /// In the order of the vertices given to Triangle::new_from_vertices()
+    pub uvs: [[f32; 2]; 3],
+    /// Multiplies every sample
+    pub tint: Vec3,
+}
+
+impl TriangleTexture {
+    #[inline(always)]
+    pub fn sample(&self, barycentric: [f32; 3], filter: TextureFilter) -> Vec3 {
+        return self.texture.sample(interpolate_uv(&self.uvs, barycentric), filter).mul_elementwise(self.tint);
+    }
+}
+
+/// How the rasterizer colors a triangle's pixels
+#[derive(Debug, Clone, Copy)]
+pub enum TriangleColor<'a> {
+    Flat(Vec3),
+    Textured {
+        texture: &'a Texture,
+        uvs: [[f32; 2]; 3],
+        tint: Vec3,
+        filter: TextureFilter,
+    },
+}
+
+impl TriangleColor<'_> {
+    /// Color at the point with these barycentric coordinates on the triangle
+    #[inline(always)]
+    pub fn at(&self, barycentric: [f32; 3]) -> Vec3 {
+        return match self {
+            TriangleColor::Flat(color) => *color,
+            TriangleColor::Textured { texture, uvs, tint, filter } => {
+                texture.sample(interpolate_uv(uvs, barycentric), *filter).mul_elementwise(*tint)
+            },
+        };
+    }
+}
@@ -0,0 +1,56 @@
+use crate::wasm::wasm::MaterialProperties;
+
+use super::object_id::ObjectId;
+
+/// Receives the UI notifications that Game sends out when its state changes.
+/// In the browser this is the JS bridge (see wasm::wasm::JsUiBridge), headless
+/// runs use HeadlessUiBridge, which drops everything.
+pub trait UiBridge: Send + Sync {
+    /// 0 = Rasterizing, 1 = Editing, 2 = RayTracing
+    fn update_game_status(&self, new_status: u32);
+    fn update_selected_obj_mat_props(&self, selected_object_mat_props: Option<MaterialProperties>);
+    /// Name of the selected group, None when no group is selected
+    fn update_selected_group(&self, group_name: Option<String>);
+    /// Id of the active (last selected) object, None when no object is selected
+    fn update_selected_object_id(&self, id: Option<ObjectId>);
+    /// How many objects are selected, 0 while a group is selected
+    fn update_selected_object_count(&self, count: usize);
+    fn update_follow_camera(&self, follow_cursor: bool);
+    /// 0 = Hidden, 1 = Translate, 2 = Rotate, 3 = Scale
+    fn update_gizmo_mode(&self, mode: u32);
+    /// fov is in degrees
+    fn update_fov(&self, fov: f32);
+    fn update_focal_distance(&self, focal_distance: f32);
+    fn update_dof_strength(&self, defocus_angle: f32);
+    fn update_scene_loading(&self, loading: bool);
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HeadlessUiBridge {
+}
+
+impl UiBridge for HeadlessUiBridge {
+    fn update_game_status(&self, _new_status: u32) {}
+    fn update_selected_obj_mat_props(&self, _selected_object_mat_props: Option<MaterialProperties>) {}
+    fn update_selected_group(&self, _group_name: Option<String>) {}
+    fn update_selected_object_id(&self, _id: Option<ObjectId>) {}
+    fn update_selected_object_count(&self, _count: usize) {}
+    fn update_follow_camera(&self, _follow_cursor: bool) {}
+    fn update_gizmo_mode(&self, _mode: u32) {}
+    fn update_fov(&self, _fov: f32) {}
+    fn update_focal_distance(&self, _focal_distance: f32) {}
+    fn update_dof_strength(&self, _defocus_angle: f32) {}
+    fn update_scene_loading(&self, _loading: bool) {}
+}
+
+/// The bridge Game::new() uses: the JS bridge in the browser, a headless one elsewhere.
+pub fn default_ui_bridge() -> Box<dyn UiBridge> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        return Box::new(crate::wasm::wasm::JsUiBridge::default());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        return Box::new(HeadlessUiBridge::default());
+    }
+}
@@ -5,8 +5,8 @@
 // ./build.sh
 
 mod wasm;
-mod graphics;
-mod utils;
+pub mod graphics;
+pub mod utils;
 
 // re-export the wasm_bindgen_rayon functions
 pub use wasm_bindgen_rayon::init_thread_pool;
\ No newline at end of file
@@ -0,0 +1,70 @@
+use std::{fmt::Arguments, sync::atomic::{AtomicU8, Ordering}};
+
+/// Severity of a log message, ordered from least to most severe.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Log = 0,
+    Warn = 1,
+    Error = 2,
+    Off = 3,
+}
+
+static MIN_LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Log as u8);
+
+/// Messages below this level are dropped. Batch jobs and tests use this to
+/// silence the per-frame logging that the interactive renderer does.
+pub fn set_min_log_level(level: LogLevel) {
+    MIN_LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn get_min_log_level() -> LogLevel {
+    match MIN_LOG_LEVEL.load(Ordering::Relaxed) {
+        0 => LogLevel::Log,
+        1 => LogLevel::Warn,
+        2 => LogLevel::Error,
+        _ => LogLevel::Off,
+    }
+}
+
+/// Backend for the console_log!/console_warn!/console_error! macros.
+/// Goes to the browser console on wasm, and to stdout/stderr everywhere else.
+pub fn write_log(level: LogLevel, args: Arguments) {
+    if level < get_min_log_level() {
+        return;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let msg = args.to_string().into();
+        match level {
+            LogLevel::Log => web_sys::console::log_1(&msg),
+            LogLevel::Warn => web_sys::console::warn_1(&msg),
+            LogLevel::Error => web_sys::console::error_1(&msg),
+            LogLevel::Off => {},
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        match level {
+            LogLevel::Log => println!("{}", args),
+            LogLevel::Warn => eprintln!("WARN: {}", args),
+            LogLevel::Error => eprintln!("ERROR: {}", args),
+            LogLevel::Off => {},
+        }
+    }
+}
+
+// LOGGING MACROS
+#[macro_export]
+macro_rules! console_log {
+    ($($t:tt)*) => ($crate::utils::log::write_log($crate::utils::log::LogLevel::Log, format_args!($($t)*)))
+}
+#[macro_export]
+macro_rules! console_error {
+    ($($t:tt)*) => ($crate::utils::log::write_log($crate::utils::log::LogLevel::Error, format_args!($($t)*)))
+}
+#[macro_export]
+macro_rules! console_warn {
+    ($($t:tt)*) => ($crate::utils::log::write_log($crate::utils::log::LogLevel::Warn, format_args!($($t)*)))
+}
@@ -1,6 +1,4 @@
-use std::{f32::consts::PI, ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign}};
-
-use super::utils::random_float;
+use std::{f32::consts::PI, ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign}};
 
 pub fn degrees_to_radians(degrees: f32) -> f32 {
     return degrees * PI / 180.0;
@@ -35,40 +33,6 @@ impl Vec3 {
         return Vec3::ones();
     }
 
-
-    /// Returns a vector with each component in range [0, 1.0]
-    #[inline(always)]
-    pub fn random() -> Self {
-        return Vec3::new(
-            random_float(),
-            random_float(),
-            random_float()
-        );
-    }
-
-    #[inline(always)]
-    pub fn random_range(min: f32, max: f32) -> Self {
-        return Vec3::random() * (max - min) + Vec3::new(min, min, min);
-    }
-    #[inline(always)]
-    pub fn random_on_unit_sphere() -> Self {
-        loop {
-            let v = Vec3::random_range(-1.0, 1.0);
-            let len_squared = v.len_squared();
-            if 1e-30 < len_squared && len_squared < 1.0 {
-                return v / len_squared.sqrt();
-            }
-        }
-    }
-    #[inline(always)]
-    pub fn random_on_hemisphere(normal: Vec3) -> Self {
-        let mut v = Vec3::random_on_unit_sphere();
-        if v.dot(normal) < 0.0 {
-            v = -v;
-        }
-        return v;
-    }
-
     #[inline(always)]
     pub fn dot_product(a: Self, b: Self) -> f32 {
         return a.x * b.x + a.y * b.y + a.z * b.z;
@@ -339,4 +303,414 @@ impl DivAssign<f32> for Vec3 {
         self.y /= scalar;
         self.z /= scalar;
     }
-}
\ No newline at end of file
+}
+
+/// Component by axis index, 0 = x, 1 = y, 2 = z
+impl Index<usize> for Vec3 {
+    type Output = f32;
+    #[inline(always)]
+    fn index(&self, axis: usize) -> &f32 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", axis),
+        }
+    }
+}
+impl IndexMut<usize> for Vec3 {
+    #[inline(always)]
+    fn index_mut(&mut self, axis: usize) -> &mut f32 {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3 index out of range: {}", axis),
+        }
+    }
+}
+
+// Mat4 struct, row major, transforms column vectors (p' = M * p)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+impl Mat4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Mat4 { m }
+    }
+    pub fn identity() -> Self {
+        return Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+    }
+    pub fn translation(offset: Vec3) -> Self {
+        let mut mat = Mat4::identity();
+        mat.m[0][3] = offset.x;
+        mat.m[1][3] = offset.y;
+        mat.m[2][3] = offset.z;
+        return mat;
+    }
+    pub fn scale(scale: Vec3) -> Self {
+        let mut mat = Mat4::identity();
+        mat.m[0][0] = scale.x;
+        mat.m[1][1] = scale.y;
+        mat.m[2][2] = scale.z;
+        return mat;
+    }
+    /// Same rotation as Vec3::rotate_z
+    pub fn rotation_z(theta_z: f32) -> Self {
+        let (sin, cos) = theta_z.sin_cos();
+        let mut mat = Mat4::identity();
+        mat.m[0][0] = cos;
+        mat.m[0][1] = -sin;
+        mat.m[1][0] = sin;
+        mat.m[1][1] = cos;
+        return mat;
+    }
+    /// Same rotation as Vec3::rotate_y
+    pub fn rotation_y(theta_y: f32) -> Self {
+        let (sin, cos) = theta_y.sin_cos();
+        let mut mat = Mat4::identity();
+        mat.m[0][0] = cos;
+        mat.m[0][2] = -sin;
+        mat.m[2][0] = sin;
+        mat.m[2][2] = cos;
+        return mat;
+    }
+
+    #[inline(always)]
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        return Vec3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        );
+    }
+    /// Ignores the translation
+    #[inline(always)]
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        return Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        );
+    }
+
+    pub fn transposed(&self) -> Mat4 {
+        let mut mat = *self;
+        for row in 0..4 {
+            for col in 0..4 {
+                mat.m[row][col] = self.m[col][row];
+            }
+        }
+        return mat;
+    }
+
+    pub fn get_translation(&self) -> Vec3 {
+        return Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3]);
+    }
+    /// Largest factor any length is scaled by (ignoring shear), useful for scaling radii
+    pub fn get_max_scale(&self) -> f32 {
+        let x = self.transform_vector(Vec3::new(1.0, 0.0, 0.0)).len();
+        let y = self.transform_vector(Vec3::new(0.0, 1.0, 0.0)).len();
+        let z = self.transform_vector(Vec3::new(0.0, 0.0, 1.0)).len();
+        return x.max(y).max(z);
+    }
+
+    /// True if the transform's axes aren't perpendicular anymore, which a Transform can't hold
+    pub fn is_sheared(&self) -> bool {
+        let axes = [0, 1, 2].map(|axis| Vec3::new(self.m[0][axis], self.m[1][axis], self.m[2][axis]));
+        return [(0, 1), (0, 2), (1, 2)]
+            .iter()
+            .any(|&(a, b)| axes[a].dot(axes[b]).abs() > 1e-4 * axes[a].len() * axes[b].len());
+    }
+
+    /// Inverse of an affine transform (the bottom row is assumed to be 0, 0, 0, 1).
+    /// None if the transform squashes space flat.
+    pub fn affine_inverse(&self) -> Option<Mat4> {
+        let m = &self.m;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let mut inv = Mat4::identity();
+        inv.m[0][0] = (m[1][1] * m[2][2] - m[1][2] * m[2][1]) * inv_det;
+        inv.m[0][1] = (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det;
+        inv.m[0][2] = (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det;
+        inv.m[1][0] = (m[1][2] * m[2][0] - m[1][0] * m[2][2]) * inv_det;
+        inv.m[1][1] = (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det;
+        inv.m[1][2] = (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det;
+        inv.m[2][0] = (m[1][0] * m[2][1] - m[1][1] * m[2][0]) * inv_det;
+        inv.m[2][1] = (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det;
+        inv.m[2][2] = (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det;
+
+        let translation = inv.transform_vector(self.get_translation());
+        inv.m[0][3] = -translation.x;
+        inv.m[1][3] = -translation.y;
+        inv.m[2][3] = -translation.z;
+        return Some(inv);
+    }
+
+    /// Transforms normals the same way this transforms surfaces (the inverse transpose),
+    /// results still need normalizing
+    pub fn normal_matrix(&self) -> Mat4 {
+        let mut normal_mat = self.affine_inverse().unwrap_or_default().transposed();
+        normal_mat.m[3] = [0.0, 0.0, 0.0, 1.0];
+        return normal_mat;
+    }
+}
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut mat = Mat4::new([[0.0; 4]; 4]);
+        for row in 0..4 {
+            for col in 0..4 {
+                mat.m[row][col] = (0..4).map(|i| self.m[row][i] * other.m[i][col]).sum();
+            }
+        }
+        return mat;
+    }
+}
+
+// Quat struct, unit quaternions (w + xi + yj + zk) for rotations
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+impl Quat {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        return Quat { w, x, y, z };
+    }
+    pub fn identity() -> Self {
+        return Quat::new(1.0, 0.0, 0.0, 0.0);
+    }
+    /// Right handed rotation around axis, which doesn't need to be normalized
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let axis_len = axis.len();
+        if axis_len < 1e-12 {
+            return Quat::identity();
+        }
+        let (sin, cos) = (0.5 * angle).sin_cos();
+        let v = axis * (sin / axis_len);
+        return Quat::new(cos, v.x, v.y, v.z);
+    }
+    pub fn rotation_x(theta_x: f32) -> Self {
+        return Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), theta_x);
+    }
+    /// Same rotation as Vec3::rotate_y
+    pub fn rotation_y(theta_y: f32) -> Self {
+        // Vec3::rotate_y turns x towards z, which is the left handed direction around y
+        return Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), -theta_y);
+    }
+    /// Same rotation as Vec3::rotate_z
+    pub fn rotation_z(theta_z: f32) -> Self {
+        return Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), theta_z);
+    }
+    /// The rotation part of a matrix without scale or shear
+    pub fn from_rotation_matrix(mat: &Mat4) -> Self {
+        let m = &mat.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let quat = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quat::new(0.25 * s, (m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s, (m[1][0] - m[0][1]) / s)
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Quat::new((m[2][1] - m[1][2]) / s, 0.25 * s, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s)
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Quat::new((m[0][2] - m[2][0]) / s, (m[0][1] + m[1][0]) / s, 0.25 * s, (m[1][2] + m[2][1]) / s)
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Quat::new((m[1][0] - m[0][1]) / s, (m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, 0.25 * s)
+        };
+        return quat.normalized();
+    }
+
+    pub fn len(self) -> f32 {
+        return (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+    }
+    /// Composing many rotations slowly drifts away from unit length, this brings it back
+    pub fn normalized(self) -> Self {
+        let len = self.len();
+        if len < 1e-12 {
+            return Quat::identity();
+        }
+        return Quat::new(self.w / len, self.x / len, self.y / len, self.z / len);
+    }
+    /// The opposite rotation (for unit quaternions)
+    pub fn conjugate(self) -> Self {
+        return Quat::new(self.w, -self.x, -self.y, -self.z);
+    }
+
+    #[inline(always)]
+    pub fn rotate_vector(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * u.cross(v);
+        return v + self.w * t + u.cross(t);
+    }
+    pub fn to_mat4(self) -> Mat4 {
+        let Quat { w, x, y, z } = self;
+        return Mat4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+    }
+}
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::identity()
+    }
+}
+/// a * b rotates by b first, then by a
+impl Mul for Quat {
+    type Output = Quat;
+    fn mul(self, other: Quat) -> Quat {
+        return Quat::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        );
+    }
+}
+
+/// Places an object in the world: scaled (per axis), then rotated, then translated.
+/// Kept as separate parts so that edits never have to multiply matrices together.
+/// Putting one transform inside another is done with Mat4s (see to_mat4() and from_mat4()),
+/// a non-uniform scale on the outside can shear what's inside, which only a matrix can hold.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+impl Transform {
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        return Transform { translation, rotation, scale };
+    }
+    pub fn identity() -> Self {
+        return Transform::new(Vec3::zero(), Quat::identity(), Vec3::ones());
+    }
+    /// Splits an affine matrix into its parts. A sheared matrix gets the rotation closest to its
+    /// x axis (then its y axis) and the lengths of its axes as the scale, the shear is lost.
+    /// None if the matrix squashes space flat.
+    pub fn from_mat4(mat: &Mat4) -> Option<Self> {
+        mat.affine_inverse()?;
+        let mut columns = [Vec3::zero(); 3];
+        let mut scale = Vec3::zero();
+        for axis in 0..3 {
+            columns[axis] = Vec3::new(mat.m[0][axis], mat.m[1][axis], mat.m[2][axis]);
+            scale[axis] = columns[axis].len();
+        }
+        // a mirrored matrix still needs a proper rotation, so the mirroring goes into the scale
+        if columns[0].cross(columns[1]).dot(columns[2]) < 0.0 {
+            scale.x = -scale.x;
+        }
+        // the axes of a sheared matrix aren't perpendicular, but a rotation's have to be
+        let x_axis = columns[0] / scale.x;
+        let y_axis = (columns[1] - x_axis * x_axis.dot(columns[1])).normalized();
+        let rotation_columns = [x_axis, y_axis, x_axis.cross(y_axis)];
+        let mut rotation_mat = Mat4::identity();
+        for (axis, column) in rotation_columns.into_iter().enumerate() {
+            rotation_mat.m[0][axis] = column.x;
+            rotation_mat.m[1][axis] = column.y;
+            rotation_mat.m[2][axis] = column.z;
+        }
+        return Some(Transform::new(mat.get_translation(), Quat::from_rotation_matrix(&rotation_mat), scale));
+    }
+
+    pub fn is_identity(&self) -> bool {
+        return *self == Transform::identity();
+    }
+    pub fn to_mat4(&self) -> Mat4 {
+        return Mat4::translation(self.translation) * self.rotation.to_mat4() * Mat4::scale(self.scale);
+    }
+    #[inline(always)]
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        return self.translation + self.rotation.rotate_vector(p.mul_elementwise(self.scale));
+    }
+    /// Largest factor any length is scaled by, useful for scaling radii
+    pub fn get_max_scale(&self) -> f32 {
+        return self.scale.x.abs().max(self.scale.y.abs()).max(self.scale.z.abs());
+    }
+    /// Smallest factor any length is scaled by, 0 means the transform can't be undone
+    pub fn get_min_scale(&self) -> f32 {
+        return self.scale.x.abs().min(self.scale.y.abs()).min(self.scale.z.abs());
+    }
+    /// True if every axis is scaled by the same amount (mirroring aside)
+    pub fn has_uniform_scale(&self) -> bool {
+        return self.get_max_scale() - self.get_min_scale() <= 1e-4 * self.get_max_scale();
+    }
+
+    /// Rotates the whole transform around a point in world space
+    pub fn rotate_around(&mut self, center_of_rotation: Vec3, rotation: Quat) {
+        self.translation = center_of_rotation + rotation.rotate_vector(self.translation - center_of_rotation);
+        self.rotation = (rotation * self.rotation).normalized();
+    }
+    /// Scales the whole transform uniformly around a point in world space
+    pub fn scale_around(&mut self, center_of_scale: Vec3, scale_factor: f32) {
+        self.translation = center_of_scale + (self.translation - center_of_scale) * scale_factor;
+        self.scale *= scale_factor;
+    }
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+/// Finds barycentric coordinates of points on a triangle, with what only depends on the
+/// triangle worked out once. Points off the triangle's plane are projected onto it.
+#[derive(Copy, Clone, Debug)]
+pub struct BarycentricSolver {
+    origin: Vec3,
+    edge1: Vec3,
+    edge2: Vec3,
+    d11: f32,
+    d12: f32,
+    d22: f32,
+    inv_denom: f32,
+}
+impl BarycentricSolver {
+    pub fn new(v1: Vec3, v2: Vec3, v3: Vec3) -> BarycentricSolver {
+        let edge1 = v2 - v1;
+        let edge2 = v3 - v1;
+        let d11 = edge1.dot(edge1);
+        let d12 = edge1.dot(edge2);
+        let d22 = edge2.dot(edge2);
+        let denom = d11 * d22 - d12 * d12;
+        let inv_denom = if denom.abs() > f32::EPSILON { 1.0 / denom } else { 0.0 };
+        return BarycentricSolver { origin: v1, edge1, edge2, d11, d12, d22, inv_denom };
+    }
+
+    /// Weights of v1, v2 and v3, clamped so that points just past an edge count as on it
+    #[inline(always)]
+    pub fn solve(&self, p: Vec3) -> [f32; 3] {
+        let to_p = p - self.origin;
+        let d1 = to_p.dot(self.edge1);
+        let d2 = to_p.dot(self.edge2);
+        let w2 = ((self.d22 * d1 - self.d12 * d2) * self.inv_denom).clamp(0.0, 1.0);
+        let w3 = ((self.d11 * d2 - self.d12 * d1) * self.inv_denom).clamp(0.0, 1.0 - w2);
+        return [1.0 - w2 - w3, w2, w3];
+    }
+}
@@ -1,2 +1,4 @@
 pub mod math;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod rng;
+pub mod log;
\ No newline at end of file
@@ -0,0 +1,118 @@
+use super::math::Vec3;
+
+/// Small, fast, seedable RNG (PCG32) for the path tracer.
+/// Every sample of every pixel gets its own generator derived from (seed, x, y, sample_index),
+/// so a render only depends on the seed, not on which thread traced which row or in what order.
+#[derive(Debug, Clone)]
+pub struct SampleRng {
+    state: u64,
+    inc: u64,
+}
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+/// splitmix64 finalizer, used to turn the seed + pixel coordinates into well mixed bits
+#[inline(always)]
+fn mix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return z ^ (z >> 31);
+}
+
+impl SampleRng {
+    pub fn new(seed: u64) -> SampleRng {
+        let mut rng = SampleRng {
+            state: 0,
+            inc: (mix64(seed ^ 0xda3e39cb94b95bdb) << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(mix64(seed));
+        rng.next_u32();
+        return rng;
+    }
+
+    /// The generator for one sample of one pixel
+    #[inline(always)]
+    pub fn for_pixel_sample(seed: u64, x: usize, y: usize, sample_index: usize) -> SampleRng {
+        let mut h = mix64(seed);
+        h = mix64(h ^ x as u64);
+        h = mix64(h ^ y as u64);
+        h = mix64(h ^ sample_index as u64);
+        return SampleRng::new(h);
+    }
+
+    #[inline(always)]
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        return xorshifted.rotate_right(rot);
+    }
+
+    /// Uniform in [0, 1)
+    #[inline(always)]
+    pub fn random_float(&mut self) -> f32 {
+        // top 24 bits, so every value is exactly representable
+        return (self.next_u32() >> 8) as f32 * (1.0 / (1 << 24) as f32);
+    }
+    #[inline(always)]
+    pub fn random_range(&mut self, min: f32, max: f32) -> f32 {
+        return self.random_float() * (max - min) + min;
+    }
+    /// Samples uniformly from the range [min, max]
+    #[inline(always)]
+    pub fn random_int(&mut self, min: i32, max: i32) -> i32 {
+        let range = (max as i64 - min as i64 + 1) as u64;
+        let offset = (self.next_u32() as u64 * range) >> 32;
+        return (min as i64 + offset as i64) as i32;
+    }
+
+    /// Samples points in a square in the range [-0.5, 0.5], scaled by side_length
+    #[inline(always)]
+    pub fn sample_square(&mut self, side_length: f32) -> (f32, f32) {
+        let x = self.random_float();
+        let y = self.random_float();
+        return ((x - 0.5) * side_length, (y - 0.5) * side_length);
+    }
+    /// Returns a random x,y coordinate in the circle, with even distribution
+    #[inline(always)]
+    pub fn sample_circle(&mut self, radius: f32) -> (f32, f32) {
+        loop {
+            let x = self.random_range(-1.0, 1.0);
+            let y = self.random_range(-1.0, 1.0);
+            if x*x + y*y <= 1.0 {
+                return (radius * x, radius * y);
+            }
+        }
+    }
+
+    /// Returns a vector with each component in range [min, max)
+    #[inline(always)]
+    pub fn random_vec3_range(&mut self, min: f32, max: f32) -> Vec3 {
+        return Vec3::new(
+            self.random_range(min, max),
+            self.random_range(min, max),
+            self.random_range(min, max),
+        );
+    }
+    #[inline(always)]
+    pub fn random_on_unit_sphere(&mut self) -> Vec3 {
+        loop {
+            let v = self.random_vec3_range(-1.0, 1.0);
+            let len_squared = v.len_squared();
+            if 1e-30 < len_squared && len_squared < 1.0 {
+                return v / len_squared.sqrt();
+            }
+        }
+    }
+    #[inline(always)]
+    pub fn random_on_hemisphere(&mut self, normal: Vec3) -> Vec3 {
+        let mut v = self.random_on_unit_sphere();
+        if v.dot(normal) < 0.0 {
+            v = -v;
+        }
+        return v;
+    }
+}
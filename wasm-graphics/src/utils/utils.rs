@@ -1,10 +1,11 @@
-use rand::{distr::{uniform::SampleRange, Uniform}, rngs::ThreadRng, Rng};
-
 use crate::graphics::mesh::Mesh;
 
 use super::math::Vec3;
 
-// WEB-SYS / JS BASED UTILITIES
+// TIME UTILITIES
+/// Milliseconds since some fixed point, only meaningful for measuring durations.
+/// Uses performance.now() in the browser and a monotonic clock natively.
+#[cfg(target_arch = "wasm32")]
 pub fn get_time() -> f64 {
     return web_sys::window()
         .expect("No global window exists")
@@ -12,42 +13,11 @@ pub fn get_time() -> f64 {
         .expect("Window doesn't have Performance")
         .now()
 }
-
-// RAND UTILITIES
-#[inline(always)]
-pub fn random_float() -> f32 {
-    // let mut rng = rand::rng();
-    // return rng.random();
-    return rand::random::<f32>();
-}
-#[inline(always)]
-pub fn random_range(min: f32, max: f32) -> f32 {
-    return random_float() * (max - min) + min;
-}
-/// Samples uniformly from the range [low, high]
-pub fn random_int(min: i32, max: i32) -> i32 {
-    let distr = Uniform::new_inclusive(min, max).expect("rand::distr::Uniform::new_inclusive failed");
-    let mut rng = rand::rng();
-    return rng.sample(distr);
-}
-/// Samples points in a square in the range [-0.5, 0.5].
-#[inline(always)]
-pub fn sample_square(side_length: f32) -> (f32, f32) {
-    let x = random_float();
-    let y = random_float();
-    return ((x - 0.5) * side_length, (y - 0.5) * side_length);
-}
-
-/// Returns a random x,y coordinate in the unit circle, with even distribution
-#[inline(always)]
-pub fn sample_circle(radius: f32) -> (f32 ,f32) {
-    loop {
-        let x = random_range(-1.0, 1.0);
-        let y = random_range(-1.0, 1.0);
-        if x*x + y*y <= 1.0 {
-            return (radius * x, radius * y);
-        }
-    }
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_time() -> f64 {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    let start = START.get_or_init(std::time::Instant::now);
+    return start.elapsed().as_secs_f64() * 1000.0;
 }
 
 // OTHER UTILITIES
@@ -1,10 +1,9 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use gltf::json::extensions::scene;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::console;
 use web_sys::js_sys::Promise;
 use web_sys::js_sys::Uint8ClampedArray;
 use web_sys::Event;
@@ -14,33 +13,28 @@ use web_sys::KeyboardEvent;
 use web_sys::MouseEvent;
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, Window};
 
-use crate::graphics::game::Game;
+use crate::console_error;
+use crate::console_log;
+use crate::graphics::game::{Game, GameStatus};
+use crate::graphics::gizmo::GizmoMode;
+use crate::graphics::grid::{GridSettings, ObjectSnap};
+use crate::graphics::debug_overlay::DebugOverlays;
+use crate::graphics::frame_stats::FrameStats;
+use crate::graphics::ray_tracing::bvh_heatmap::{BVHHeatmap, HeatmapMetric};
+use crate::graphics::texture::TextureFilter;
+use crate::graphics::gltf_parser::decode_glb_bytes;
+use crate::graphics::gltf_parser::extract_node_tree_from_gltf;
+use crate::graphics::object_id::ObjectId;
+use crate::graphics::scene_file::builtin_scene;
+use crate::graphics::scene_file::SceneAssets;
+use crate::graphics::scene_file::SceneDescription;
+use crate::graphics::ui_bridge::UiBridge;
 use crate::utils::math::radians_to_degrees;
-use crate::utils::math::Vec3;
+use crate::utils::math::{Quat, Transform, Vec3};
 use crate::utils::utils::color_to_u32;
 use crate::utils::utils::color_to_u8;
 
 
-// WASM UTIL EXPORTS
-#[macro_export]
-macro_rules! console_log {
-    // Note that this is using the `log` function imported above during
-    // `bare_bones`
-    ($($t:tt)*) => (web_sys::console::log_1(&format_args!($($t)*).to_string().into()))
-}
-#[macro_export]
-macro_rules! console_error {
-    // Note that this is using the `log` function imported above during
-    // `bare_bones`
-    ($($t:tt)*) => (web_sys::console::error_1(&format_args!($($t)*).to_string().into()))
-}
-#[macro_export]
-macro_rules! console_warn {
-    // Note that this is using the `log` function imported above during
-    // `bare_bones`
-    ($($t:tt)*) => (web_sys::console::warn_1(&format_args!($($t)*).to_string().into()))
-}
-
 // EXPOSING JS FUNCTIONS TO RUST
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
@@ -75,6 +69,167 @@ impl MaterialProperties {
     }
 }
 
+/// Absolute transform of an object: scaled along its own axes, then rotated
+/// by the quaternion (w, x, y, z), then translated
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ObjectTransform {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub qw: f32,
+    pub qx: f32,
+    pub qy: f32,
+    pub qz: f32,
+    pub sx: f32,
+    pub sy: f32,
+    pub sz: f32,
+}
+
+#[wasm_bindgen]
+impl ObjectTransform {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(x: f32, y: f32, z: f32, qw: f32, qx: f32, qy: f32, qz: f32, sx: f32, sy: f32, sz: f32) -> Self {
+        ObjectTransform { x, y, z, qw, qx, qy, qz, sx, sy, sz }
+    }
+}
+
+impl ObjectTransform {
+    fn from_transform(transform: &Transform) -> Self {
+        let Transform { translation, rotation, scale } = *transform;
+        ObjectTransform::new(translation.x, translation.y, translation.z, rotation.w, rotation.x, rotation.y, rotation.z, scale.x, scale.y, scale.z)
+    }
+    fn to_transform(&self) -> Transform {
+        Transform::new(
+            Vec3::new(self.x, self.y, self.z),
+            Quat::new(self.qw, self.qx, self.qy, self.qz).normalized(),
+            Vec3::new(self.sx, self.sy, self.sz),
+        )
+    }
+}
+
+/// Edit mode grid, axes and snapping. object_snap: 0 = Off, 1 = Surface, 2 = Vertex
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct GridOptions {
+    pub show_grid: bool,
+    pub show_axes: bool,
+    pub spacing: f32,
+    pub snap_to_grid: bool,
+    pub snap_angles: bool,
+    pub angle_step: f32,
+    pub object_snap: u32,
+}
+
+#[wasm_bindgen]
+impl GridOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(show_grid: bool, show_axes: bool, spacing: f32, snap_to_grid: bool, snap_angles: bool, angle_step: f32, object_snap: u32) -> Self {
+        GridOptions { show_grid, show_axes, spacing, snap_to_grid, snap_angles, angle_step, object_snap }
+    }
+}
+
+impl GridOptions {
+    fn from_grid_settings(settings: &GridSettings) -> Self {
+        let GridSettings { show_grid, show_axes, spacing, snap_to_grid, snap_angles, angle_step, object_snap } = *settings;
+        GridOptions::new(show_grid, show_axes, spacing, snap_to_grid, snap_angles, angle_step, object_snap.to_number())
+    }
+    fn to_grid_settings(&self) -> Option<GridSettings> {
+        Some(GridSettings {
+            show_grid: self.show_grid,
+            show_axes: self.show_axes,
+            spacing: self.spacing,
+            snap_to_grid: self.snap_to_grid,
+            snap_angles: self.snap_angles,
+            angle_step: self.angle_step,
+            object_snap: ObjectSnap::from_number(self.object_snap)?,
+        })
+    }
+}
+
+/// Debug views drawn over the rasterized frame. bvh_max_depth of None draws every level below bvh_min_depth.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct DebugOverlayOptions {
+    pub wireframe: bool,
+    pub normals: bool,
+    pub bounding_spheres: bool,
+    pub bvh_boxes: bool,
+    pub bvh_min_depth: u32,
+    pub bvh_max_depth: Option<u32>,
+    pub only_selected: bool,
+}
+
+#[wasm_bindgen]
+impl DebugOverlayOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(wireframe: bool, normals: bool, bounding_spheres: bool, bvh_boxes: bool, bvh_min_depth: u32, bvh_max_depth: Option<u32>, only_selected: bool) -> Self {
+        DebugOverlayOptions { wireframe, normals, bounding_spheres, bvh_boxes, bvh_min_depth, bvh_max_depth, only_selected }
+    }
+}
+
+impl DebugOverlayOptions {
+    fn from_debug_overlays(overlays: &DebugOverlays) -> Self {
+        let DebugOverlays { wireframe, normals, bounding_spheres, bvh_boxes, bvh_min_depth, bvh_max_depth, only_selected } = *overlays;
+        DebugOverlayOptions::new(wireframe, normals, bounding_spheres, bvh_boxes, bvh_min_depth as u32, bvh_max_depth.map(|depth| depth as u32), only_selected)
+    }
+    fn to_debug_overlays(&self) -> DebugOverlays {
+        DebugOverlays {
+            wireframe: self.wireframe,
+            normals: self.normals,
+            bounding_spheres: self.bounding_spheres,
+            bvh_boxes: self.bvh_boxes,
+            bvh_min_depth: self.bvh_min_depth as usize,
+            bvh_max_depth: self.bvh_max_depth.map(|depth| depth as usize),
+            only_selected: self.only_selected,
+        }
+    }
+}
+
+/// Where the time went in the last whole frame, times in ms. See FrameStats.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct FrameStatsInfo {
+    pub frame_time_ms: f64,
+    pub frames_per_second: f64,
+    pub clear_ms: f64,
+    pub transform_ms: f64,
+    pub rasterize_opaque_ms: f64,
+    pub rasterize_transparent_ms: f64,
+    pub dof_ms: f64,
+    pub triangles_submitted: u32,
+    pub triangles_culled: u32,
+    pub triangles_drawn: u32,
+    pub shadow_map_rebuild_ms: f64,
+    pub bvh_build_ms: f64,
+    pub ray_tracing_ms: f64,
+    pub rays_per_second: f64,
+    pub samples_accumulated: u32,
+}
+
+impl FrameStatsInfo {
+    fn from_frame_stats(stats: &FrameStats) -> Self {
+        FrameStatsInfo {
+            frame_time_ms: stats.frame_time_ms,
+            frames_per_second: stats.frames_per_second(),
+            clear_ms: stats.clear_ms,
+            transform_ms: stats.transform_ms,
+            rasterize_opaque_ms: stats.rasterize_opaque_ms,
+            rasterize_transparent_ms: stats.rasterize_transparent_ms,
+            dof_ms: stats.dof_ms,
+            triangles_submitted: stats.triangles_submitted as u32,
+            triangles_culled: stats.triangles_culled as u32,
+            triangles_drawn: stats.triangles_drawn as u32,
+            shadow_map_rebuild_ms: stats.shadow_map_rebuild_ms,
+            bvh_build_ms: stats.bvh_build_ms,
+            ray_tracing_ms: stats.ray_tracing_ms,
+            rays_per_second: stats.rays_per_second,
+            samples_accumulated: stats.samples_accumulated as u32,
+        }
+    }
+}
+
 #[wasm_bindgen]
 extern "C" {
     // This declares the JS function that Rust can call.
@@ -91,6 +246,9 @@ extern "C" {
     #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateFollowCamera)]
     pub fn js_update_follow_camera(follow_cursor: bool);
 
+    #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateGizmoMode)]
+    pub fn js_update_gizmo_mode(mode: u32);
+
     #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateFov)]
     pub fn js_update_fov(fov: f32);
 
@@ -110,6 +268,56 @@ extern "C" {
     #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateSceneLoading)]
     pub fn js_update_scene_loading(loading: bool);
 
+    #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateSelectedGroup)]
+    pub fn js_update_selected_group(group_name: Option<String>);
+
+    #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateSelectedObjectId)]
+    pub fn js_update_selected_object_id(id: Option<u64>);
+
+    #[wasm_bindgen(js_namespace = ["wasmToJsBridge"], js_name = updateSelectedObjectCount)]
+    pub fn js_update_selected_object_count(count: usize);
+
+}
+
+/// Forwards Game's UI notifications to the global wasmToJsBridge object.
+#[derive(Debug, Clone, Default)]
+pub struct JsUiBridge {
+}
+
+impl UiBridge for JsUiBridge {
+    fn update_game_status(&self, new_status: u32) {
+        js_update_game_status(new_status);
+    }
+    fn update_selected_obj_mat_props(&self, selected_object_mat_props: Option<MaterialProperties>) {
+        js_update_selected_obj_mat_props(selected_object_mat_props);
+    }
+    fn update_selected_group(&self, group_name: Option<String>) {
+        js_update_selected_group(group_name);
+    }
+    fn update_selected_object_id(&self, id: Option<ObjectId>) {
+        js_update_selected_object_id(id.map(|id| id.to_bits()));
+    }
+    fn update_selected_object_count(&self, count: usize) {
+        js_update_selected_object_count(count);
+    }
+    fn update_follow_camera(&self, follow_cursor: bool) {
+        js_update_follow_camera(follow_cursor);
+    }
+    fn update_gizmo_mode(&self, mode: u32) {
+        js_update_gizmo_mode(mode);
+    }
+    fn update_fov(&self, fov: f32) {
+        js_update_fov(fov);
+    }
+    fn update_focal_distance(&self, focal_distance: f32) {
+        js_update_focal_distance(focal_distance);
+    }
+    fn update_dof_strength(&self, defocus_angle: f32) {
+        js_update_dof_strength(defocus_angle);
+    }
+    fn update_scene_loading(&self, loading: bool) {
+        js_update_scene_loading(loading);
+    }
 }
 
 // EXPOSING RUST FUNCTIONS TO JS
@@ -176,6 +384,24 @@ pub fn scale_selected_obj(scale_factor: f32) {
     });
 }
 #[wasm_bindgen]
+pub fn rotate_selected_obj_around_axis(axis_x: f32, axis_y: f32, axis_z: f32, degrees: f32) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().rotate_selected_obj_around_axis(Vec3::new(axis_x, axis_y, axis_z), degrees);
+    });
+}
+#[wasm_bindgen]
+pub fn get_selected_obj_transform() -> Option<ObjectTransform> {
+    return GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow().get_selected_obj_transform().map(|t| ObjectTransform::from_transform(&t))
+    });
+}
+#[wasm_bindgen]
+pub fn set_selected_obj_transform(transform: ObjectTransform) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().set_selected_obj_transform(transform.to_transform());
+    });
+}
+#[wasm_bindgen]
 pub fn add_sphere(radius: f32) {
     GAME_INSTANCE.with(|game_instance| {
         game_instance.borrow_mut().add_sphere(radius);
@@ -188,6 +414,255 @@ pub fn add_box(x: f32, y: f32, z: f32) {
     });
 }
 #[wasm_bindgen]
+pub fn instance_selected_object() {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().instance_selected_obj();
+    });
+}
+/// Copies the selection, moved by (x, y, z), and selects the copies
+#[wasm_bindgen]
+pub fn duplicate_selection(x: f32, y: f32, z: f32) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().duplicate_selection(Vec3::new(x, y, z));
+    });
+}
+/// Duplicates next to the selection, to the right as seen from the camera
+#[wasm_bindgen]
+pub fn duplicate_selection_beside() {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().duplicate_selection_beside();
+    });
+}
+/// 0 = Hidden, 1 = Translate, 2 = Rotate, 3 = Scale. Returns false for anything else.
+#[wasm_bindgen]
+pub fn set_gizmo_mode(mode: u32) -> bool {
+    let Some(mode) = GizmoMode::from_number(mode) else {
+        console_error!("wasm.rs: set_gizmo_mode called with unknown mode {}", mode);
+        return false;
+    };
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().set_gizmo_mode(mode);
+    });
+    return true;
+}
+#[wasm_bindgen]
+pub fn get_gizmo_mode() -> u32 {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().gizmo.mode.to_number();
+    })
+}
+/// Returns false, changing nothing, for an unknown object_snap
+#[wasm_bindgen]
+pub fn set_grid_options(options: GridOptions) -> bool {
+    let Some(settings) = options.to_grid_settings() else {
+        console_error!("wasm.rs: set_grid_options called with unknown object snap {}", options.object_snap);
+        return false;
+    };
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().set_grid_settings(settings);
+    });
+    return true;
+}
+#[wasm_bindgen]
+pub fn get_grid_options() -> GridOptions {
+    GAME_INSTANCE.with(|game_instance| {
+        return GridOptions::from_grid_settings(&game_instance.borrow().grid);
+    })
+}
+#[wasm_bindgen]
+pub fn set_debug_overlay_options(options: DebugOverlayOptions) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().set_debug_overlays(options.to_debug_overlays());
+    });
+}
+#[wasm_bindgen]
+pub fn get_debug_overlay_options() -> DebugOverlayOptions {
+    GAME_INSTANCE.with(|game_instance| {
+        return DebugOverlayOptions::from_debug_overlays(&game_instance.borrow().debug_overlays);
+    })
+}
+#[wasm_bindgen]
+pub fn get_frame_stats() -> FrameStatsInfo {
+    GAME_INSTANCE.with(|game_instance| {
+        return FrameStatsInfo::from_frame_stats(&game_instance.borrow().frame_stats);
+    })
+}
+/// metric 0 = nodes visited, 1 = hittables tested, None turns the heatmap off. A max_count of
+/// None scales the colors to the frame. Returns false, changing nothing, for an unknown metric.
+#[wasm_bindgen]
+pub fn set_bvh_heatmap(metric: Option<u32>, max_count: Option<u32>) -> bool {
+    let heatmap = match metric {
+        Some(number) => {
+            let Some(metric) = HeatmapMetric::from_number(number) else {
+                console_error!("wasm.rs: set_bvh_heatmap called with unknown metric {}", number);
+                return false;
+            };
+            Some(BVHHeatmap { metric, max_count: max_count.map(|count| count as usize) })
+        },
+        None => None,
+    };
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().set_bvh_heatmap(heatmap);
+    });
+    return true;
+}
+#[wasm_bindgen]
+pub fn get_bvh_heatmap_metric() -> Option<u32> {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().bvh_heatmap.map(|heatmap| heatmap.metric.to_number());
+    })
+}
+/// Averages from the last heatmap traced, as text
+#[wasm_bindgen]
+pub fn get_bvh_heatmap_summary() -> Option<String> {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().bvh_heatmap_summary.as_ref().map(|summary| summary.to_string());
+    })
+}
+/// filter 0 = nearest, 1 = bilinear. Returns false, changing nothing, for an unknown filter.
+#[wasm_bindgen]
+pub fn set_texture_filter(filter: u32) -> bool {
+    let Some(filter) = TextureFilter::from_number(filter) else {
+        console_error!("wasm.rs: set_texture_filter called with unknown filter {}", filter);
+        return false;
+    };
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().set_texture_filter(filter);
+    });
+    return true;
+}
+#[wasm_bindgen]
+pub fn get_texture_filter() -> u32 {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().texture_filter.to_number();
+    })
+}
+/// The copy survives loading another scene
+#[wasm_bindgen]
+pub fn copy_selection() {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().copy_selection();
+    });
+}
+#[wasm_bindgen]
+pub fn paste() {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().paste();
+    });
+}
+#[wasm_bindgen]
+pub fn can_paste() -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        return !game_instance.borrow().clipboard.is_empty();
+    })
+}
+/// count copies of the selection, the i-th moved by i * (x, y, z)
+#[wasm_bindgen]
+pub fn array_selection_linear(count: usize, x: f32, y: f32, z: f32) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().array_selection_linear(count, Vec3::new(x, y, z));
+    });
+}
+/// count copies of the selection around an axis through the selection's middle plus the pivot offset.
+/// total_degrees of 360 (or more) spreads the copies and the original evenly around the circle.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn array_selection_circular(count: usize, axis_x: f32, axis_y: f32, axis_z: f32, pivot_x: f32, pivot_y: f32, pivot_z: f32, total_degrees: f32) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().array_selection_circular(count, Vec3::new(axis_x, axis_y, axis_z), Vec3::new(pivot_x, pivot_y, pivot_z), total_degrees);
+    });
+}
+/// Widens the selection to the group the selected object (or group) is in.
+/// Moving, rotating, scaling and deleting then apply to the whole group.
+#[wasm_bindgen]
+pub fn select_parent_group() {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().select_parent_group();
+    });
+}
+/// Reverts the last edit, returns false if there was nothing to undo
+#[wasm_bindgen]
+pub fn undo() -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow_mut().undo();
+    })
+}
+/// Reapplies the last undone edit, returns false if there was nothing to redo
+#[wasm_bindgen]
+pub fn redo() -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow_mut().redo();
+    })
+}
+#[wasm_bindgen]
+pub fn can_undo() -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().edit_history.can_undo();
+    })
+}
+#[wasm_bindgen]
+pub fn can_redo() -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().edit_history.can_redo();
+    })
+}
+/// Object ids are stable across other objects being added, removed or reordered,
+/// so JS can hold on to them. Deleted objects' ids are never reused.
+#[wasm_bindgen]
+pub fn get_object_ids() -> Vec<u64> {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().get_object_ids().into_iter().map(|id| id.to_bits()).collect();
+    })
+}
+/// The active object, the last one selected
+#[wasm_bindgen]
+pub fn get_selected_object_id() -> Option<u64> {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().get_active_object().map(|id| id.to_bits());
+    })
+}
+#[wasm_bindgen]
+pub fn get_selected_object_ids() -> Vec<u64> {
+    GAME_INSTANCE.with(|game_instance| {
+        return game_instance.borrow().selected_objects.iter().map(|id| id.to_bits()).collect();
+    })
+}
+/// Returns false if the object doesn't exist anymore
+#[wasm_bindgen]
+pub fn select_object(id: u64) -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        let mut game = game_instance.borrow_mut();
+        let id = ObjectId::from_bits(id);
+        if !game.contains_object(id) {
+            console_error!("wasm.rs: select_object called with deleted object {}", id);
+            return false;
+        }
+        game.select_object(id);
+        return true;
+    })
+}
+/// Adds the object to the selection or takes it out. Returns false if the object doesn't exist anymore
+#[wasm_bindgen]
+pub fn toggle_object_selection(id: u64) -> bool {
+    GAME_INSTANCE.with(|game_instance| {
+        let mut game = game_instance.borrow_mut();
+        let id = ObjectId::from_bits(id);
+        if !game.contains_object(id) {
+            console_error!("wasm.rs: toggle_object_selection called with deleted object {}", id);
+            return false;
+        }
+        game.toggle_object_selection(id);
+        return true;
+    })
+}
+/// Corners are in canvas pixels
+#[wasm_bindgen]
+pub fn box_select(x0: usize, y0: usize, x1: usize, y1: usize, add: bool) {
+    GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow_mut().box_select(x0, y0, x1, y1, add);
+    });
+}
+#[wasm_bindgen]
 pub fn add_custom_object(glb_bytes: Option<Vec<u8>>) {
     if let Some(bytes) = glb_bytes {
         console_log!("wasm.rs: add_custom_object");
@@ -218,124 +693,212 @@ pub fn set_defocus_angle(defocus_angle: f32) {
 }
 
 // loading scenes
-#[wasm_bindgen]
-pub fn load_scene_random_spheres() {
+fn load_builtin_scene(name: &str, assets: SceneAssets) {
+    console_log!("wasm.rs: loading built-in scene {}", name);
     GAME_INSTANCE.with(|game_instance| {
-        game_instance.borrow_mut().create_rt_test_scene_spheres();
+        if let Err(e) = game_instance.borrow_mut().load_builtin_scene(name, &assets) {
+            console_error!("wasm.rs: loading scene {} failed: {}", name, e);
+        }
     });
 }
+/// Builds the asset map for a built-in scene from the bytes JS fetched, in the order of its asset_paths()
+fn builtin_scene_assets(name: &str, asset_bytes: Vec<Option<Vec<u8>>>) -> Option<SceneAssets> {
+    let paths = builtin_scene(name)?.asset_paths();
+    let mut assets = SceneAssets::new();
+    for (path, bytes) in paths.into_iter().zip(asset_bytes.into_iter()) {
+        match bytes {
+            Some(bytes) => { assets.insert(path, bytes); },
+            None => {
+                console_error!("wasm.rs: loading scene {} failed, bytes for {} are None", name, path);
+                return None;
+            }
+        }
+    }
+    return Some(assets);
+}
+fn load_builtin_scene_with_assets(name: &str, asset_bytes: Vec<Option<Vec<u8>>>) {
+    if let Some(assets) = builtin_scene_assets(name, asset_bytes) {
+        load_builtin_scene(name, assets);
+    }
+}
+
+#[wasm_bindgen]
+pub fn load_scene_random_spheres() {
+    load_builtin_scene("spheres", SceneAssets::new());
+}
 #[wasm_bindgen]
 pub fn load_scene_fantasy_book(glb_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = glb_bytes {
-        console_log!("wasm.rs: load_scene_fantasy_book");
-        GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().load_scene_fantasy_book(&bytes);
-        });
-        return;
-    } else {
-        console_error!("wasm.rs: load_scene_fantasy_book failed, bytes are None");
-    }
+    load_builtin_scene_with_assets("fantasy_book", vec![glb_bytes]);
 }
 #[wasm_bindgen]
 pub fn load_scene_magic_bridge(glb_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = glb_bytes {
-        console_log!("wasm.rs: load_scene_magic_bridge");
-        GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().load_scene_magic_bridge(&bytes);
-        });
-        return;
-    } else {
-        console_error!("wasm.rs: load_scene_magic_bridge failed, bytes are None");
-    }
+    load_builtin_scene_with_assets("magic_bridge", vec![glb_bytes]);
 }
 #[wasm_bindgen]
 pub fn load_scene_cornell_box() {
-    GAME_INSTANCE.with(|game_instance| {
-        game_instance.borrow_mut().create_rt_test_scene_cornell();
-    });
+    load_builtin_scene("cornell", SceneAssets::new());
 }
 #[wasm_bindgen]
 pub fn load_scene_cornell_box_extra(stl_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = stl_bytes {
-        console_log!("wasm.rs: load_scene_cornell_box_extra");
-        GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().create_rt_test_scene_cornell_plus_plus(&bytes);
-        });
-    } else {
-        console_error!("wasm.rs: load_scene_cornell_box_extra failed, bytes are None");
-    }
+    load_builtin_scene_with_assets("cornell_plus_plus", vec![stl_bytes]);
 }
 #[wasm_bindgen]
 pub fn load_scene_simple_light() {
-    GAME_INSTANCE.with(|game_instance| {
-        game_instance.borrow_mut().create_rt_test_scene_simple_light();
-    });
+    load_builtin_scene("simple_light", SceneAssets::new());
 }
 #[wasm_bindgen]
 pub fn load_scene_gandalf_bust(stl_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = stl_bytes {
-        console_log!("wasm.rs: load_scene_gandalf_bust");
-        GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().load_scene_gandalf_bust(&bytes);
-        });
-    } else {
-        console_error!("wasm.rs: load_scene_gandalf_bust failed, bytes are None");
-    }
+    load_builtin_scene_with_assets("gandalf_bust", vec![stl_bytes]);
 }
 #[wasm_bindgen]
 pub fn load_scene_roza_bust(glb_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = glb_bytes {
-        console_log!("wasm.rs: load_scene_roza_bust");
-        GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().load_scene_roza_bust(&bytes);
-        });
-    } else {
-        console_error!("wasm.rs: load_scene_roza_bust failed, bytes are None");
-    }
+    load_builtin_scene_with_assets("roza_bust", vec![glb_bytes]);
 }
 #[wasm_bindgen]
 pub fn load_scene_dragon(stl_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = stl_bytes {
-        console_log!("wasm.rs: load_scene_dragon");
-        GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().load_scene_dragon(&bytes);
-        });
-    } else {
-        console_error!("wasm.rs: load_scene_dragon failed, bytes are None");
-    }
+    load_builtin_scene_with_assets("dragon", vec![stl_bytes]);
 }
 #[wasm_bindgen]
 pub fn load_scene_mirror_box(skull_stl_bytes: Option<Vec<u8>>, sculpture_stl_bytes: Option<Vec<u8>>) {
-    if let Some(skull_bytes) = skull_stl_bytes {
-        if let Some(sculpture_bytes) = sculpture_stl_bytes {
-            console_log!("wasm.rs: load_scene_mirror_box");
-            GAME_INSTANCE.with(|game_instance| {
-                game_instance.borrow_mut().load_scene_mirror_box(&skull_bytes, &sculpture_bytes);
-            });
-        } else {
-            console_error!("wasm.rs: load_scene_mirror_box failed, sculpture bytes are None");
+    load_builtin_scene_with_assets("mirror_box", vec![skull_stl_bytes, sculpture_stl_bytes]);
+}
+#[wasm_bindgen]
+pub fn load_scene_suzanne_monkey(stl_bytes: Option<Vec<u8>>) {
+    load_builtin_scene_with_assets("suzanne_monkey", vec![stl_bytes]);
+}
+
+// data-driven scenes
+// JS adds the bytes of every path in get_scene_asset_paths() with add_scene_asset(),
+// then calls load_scene_from_json()
+#[wasm_bindgen]
+pub fn get_scene_asset_paths(scene_json: &str) -> Vec<String> {
+    match SceneDescription::from_json(scene_json) {
+        Ok(scene) => scene.asset_paths(),
+        Err(e) => {
+            console_error!("wasm.rs: get_scene_asset_paths failed: {}", e);
+            Vec::new()
         }
-    } else {
-        console_error!("wasm.rs: load_scene_mirror_box failed, skull bytes are None");
     }
 }
 #[wasm_bindgen]
-pub fn load_scene_suzanne_monkey(stl_bytes: Option<Vec<u8>>) {
-    if let Some(bytes) = stl_bytes {
-        console_log!("wasm.rs: load_scene_suzanne_monkey");
+pub fn add_scene_asset(path: String, bytes: Vec<u8>) {
+    SCENE_ASSETS.with(|assets| {
+        assets.borrow_mut().insert(path, bytes);
+    });
+}
+#[wasm_bindgen]
+pub fn clear_scene_assets() {
+    SCENE_ASSETS.with(|assets| {
+        assets.borrow_mut().clear();
+    });
+}
+#[wasm_bindgen]
+pub fn load_scene_from_json(scene_json: &str) -> bool {
+    let scene = match SceneDescription::from_json(scene_json) {
+        Ok(scene) => scene,
+        Err(e) => {
+            console_error!("wasm.rs: load_scene_from_json failed: {}", e);
+            return false;
+        }
+    };
+    return SCENE_ASSETS.with(|assets| {
         GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().load_scene_suzanne_monkey(&bytes);
+            match game_instance.borrow_mut().load_scene_description(&scene, &assets.borrow()) {
+                Ok(()) => true,
+                Err(e) => {
+                    console_error!("wasm.rs: load_scene_from_json failed: {}", e);
+                    false
+                }
+            }
         })
-    } else {
-        console_error!("wasm.rs: load_scene_suzanne_monkey failed, bytes are None");
-    }
+    });
+}
+#[wasm_bindgen]
+pub fn get_builtin_scene_json(name: &str) -> Option<String> {
+    return builtin_scene(name).map(|scene| scene.to_json());
+}
+/// The scene as it is now, edits included, for load_scene_from_json()
+#[wasm_bindgen]
+pub fn get_scene_json() -> String {
+    return GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow().to_scene_description().to_json()
+    });
+}
+
+// saving/restoring the edited scene
+#[wasm_bindgen]
+pub fn serialize_scene() -> Vec<u8> {
+    return GAME_INSTANCE.with(|game_instance| {
+        game_instance.borrow().serialize_scene()
+    });
+}
+#[wasm_bindgen]
+pub fn deserialize_scene(bytes: &[u8]) -> bool {
+    return GAME_INSTANCE.with(|game_instance| {
+        match game_instance.borrow_mut().deserialize_scene(bytes) {
+            Ok(()) => true,
+            Err(e) => {
+                console_error!("wasm.rs: deserialize_scene failed: {}", e);
+                false
+            }
+        }
+    });
 }
 
 
+// exporting
+#[wasm_bindgen]
+pub fn export_scene_glb() -> Option<Vec<u8>> {
+    return GAME_INSTANCE.with(|game_instance| {
+        match game_instance.borrow().export_scene_glb() {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                console_error!("wasm.rs: export_scene_glb failed: {}", e);
+                None
+            }
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn load_glb_model(glb_bytes: &[u8]) -> bool {
+    match decode_glb_bytes(glb_bytes) {
+        Ok((gltf, buffers)) => {
+
+            let tree = match extract_node_tree_from_gltf(&gltf, &buffers) {
+                Ok(tree) => tree,
+                Err(e) => {
+                    console_error!("GLTF parse error on extract_node_tree_from_gltf(): {}", e);
+                    return false;
+                }
+            };
+
+            GAME_INSTANCE.with(|game_instance| {
+                let mut g = game_instance.borrow_mut();
+                let group = g.add_gltf_node_tree(tree, "Model", Transform::identity());
+                // center the model
+                if let Some((center, radius)) = g.get_group_bounds(group) {
+                    let scale_factor = if radius > 50.0 { 50.0 / radius } else { 1.0 };
+                    g.edit_node_transform(group, |transform, _| {
+                        transform.scale_around(center, scale_factor);
+                        transform.translation -= center;
+                    });
+                }
+            });
+            true
+        },
+        Err(e) => {
+            console_error!("GLB error on decode_glb_bytes(): {}", e);
+            false
+        }
+    }
+}
+
 
 // MAIN GAME INSTANCE
 thread_local! {
     pub static GAME_INSTANCE: RefCell<Game> = RefCell::new(Game::new());
+    static SCENE_ASSETS: RefCell<SceneAssets> = RefCell::new(SceneAssets::new());
 }
 
 // MAIN GAME LOOP
@@ -374,23 +937,82 @@ pub fn init_and_begin_game_loop() {
     // Add event listeners
     add_event_listener(&window, "keydown", move |event: Event| {
         let event = event.dyn_ref::<KeyboardEvent>().expect("Failed to cast keydown event to KeyboardEvent");
+        if (event.ctrl_key() || event.meta_key()) && event.key() == "d" {
+            event.prevent_default(); // Ctrl+D duplicates instead of bookmarking the page
+        }
         GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().keys_currently_pressed.insert(event.key());
-            game_instance.borrow_mut().keys_pressed_last_frame.insert(event.key());
+            game_instance.borrow_mut().key_down(event.key());
         });
     });
 
     add_event_listener(&window, "keyup", move |event: Event| {
         let event = event.dyn_ref::<KeyboardEvent>().expect("Failed to cast keyup event to KeyboardEvent");
         GAME_INSTANCE.with(|game_instance| {
-            game_instance.borrow_mut().keys_currently_pressed.remove(&event.key());
+            game_instance.borrow_mut().key_up(&event.key());
         });
     });
 
+    // dragging on the canvas while the pointer isn't locked, in edit mode: drags a gizmo
+    // handle if one is under the mouse, else drags out a box selection
+    let box_select_start: Rc<Cell<Option<(usize, usize)>>> = Rc::new(Cell::new(None));
+    let mouse_dragged = Rc::new(Cell::new(false)); // so the click ending a drag doesn't lock the pointer
+
+    let canvas_clone = canvas.clone();
+    let doc_clone = document.clone();
+    let box_select_start_clone = box_select_start.clone();
+    add_event_listener(&canvas, "mousedown",  move |event: Event| {
+        let event = event.dyn_ref::<MouseEvent>().expect("Failed to cast mousedown to MouseEvent");
+        if event.button() == 0 {
+            GAME_INSTANCE.with(|game_instance| {
+                game_instance.borrow_mut().mouse_down = true;
+            });
+        }
+        if event.button() == 0 && doc_clone.pointer_lock_element().is_none() {
+            let (x, y) = canvas_mouse_pos(&canvas_clone, event);
+            GAME_INSTANCE.with(|game_instance| {
+                let mut game = game_instance.borrow_mut();
+                if game.status == GameStatus::RasterizingNoLighting && !game.gizmo_mouse_down(x as f32, y as f32) {
+                    box_select_start_clone.set(Some((x, y)));
+                }
+            });
+        }
+    });
+
+    let canvas_clone = canvas.clone();
+    let box_select_start_clone = box_select_start.clone();
+    let mouse_dragged_clone = mouse_dragged.clone();
+    add_event_listener(&window, "mouseup",  move |event: Event| {
+        let event = event.dyn_ref::<MouseEvent>().expect("Failed to cast mouseup to MouseEvent");
+        GAME_INSTANCE.with(|game_instance| {
+            let mut game = game_instance.borrow_mut();
+            if event.button() == 0 {
+                game.mouse_down = false;
+            }
+            if game.gizmo.is_dragging() {
+                game.gizmo_mouse_up();
+                mouse_dragged_clone.set(true);
+            }
+        });
+        if let Some((x0, y0)) = box_select_start_clone.take() {
+            let (x1, y1) = canvas_mouse_pos(&canvas_clone, event);
+            GAME_INSTANCE.with(|game_instance| {
+                let mut game = game_instance.borrow_mut();
+                game.selection_box = None;
+                if x0.abs_diff(x1) > BOX_SELECT_MIN_DRAG || y0.abs_diff(y1) > BOX_SELECT_MIN_DRAG {
+                    game.box_select(x0, y0, x1, y1, event.shift_key());
+                    mouse_dragged_clone.set(true);
+                }
+            });
+        }
+    });
+
     let canvas_clone = canvas.clone();
     let doc_clone = document.clone();
     add_event_listener(&canvas, "click",  move |event: Event| {
         let event = event.dyn_ref::<MouseEvent>().expect("Failed to cast click to MouseEvent");
+        if mouse_dragged.replace(false) {
+            return;
+        }
         if event.button() == 0 {
             if doc_clone.pointer_lock_element().is_none() {
                 canvas_clone.request_pointer_lock();
@@ -402,8 +1024,20 @@ pub fn init_and_begin_game_loop() {
         }
     });
 
+    let canvas_clone = canvas.clone();
     add_event_listener(&canvas, "mousemove",  move |event: Event| {
         let event = event.dyn_ref::<MouseEvent>().expect("Failed to cast mousemove to MouseEvent");
+        if document.pointer_lock_element().is_none() {
+            let (x1, y1) = canvas_mouse_pos(&canvas_clone, event);
+            GAME_INSTANCE.with(|game_instance| {
+                let mut game = game_instance.borrow_mut();
+                if let Some((x0, y0)) = box_select_start.get() {
+                    game.selection_box = Some((x0, y0, x1, y1));
+                } else if game.status == GameStatus::RasterizingNoLighting {
+                    game.gizmo_mouse_move(x1 as f32, y1 as f32);
+                }
+            });
+        }
         if document.pointer_lock_element().is_some() {
             GAME_INSTANCE.with(|game_instance| {
                 game_instance.borrow_mut().mouse_move.x = event.movement_x() as f32;
@@ -449,6 +1083,18 @@ pub fn init_and_begin_game_loop() {
 
 }
 
+/// Drags shorter than this many pixels are clicks, not box selections
+const BOX_SELECT_MIN_DRAG: usize = 4;
+
+/// Mouse position in canvas pixels, the canvas can be displayed at a different size
+fn canvas_mouse_pos(canvas: &HtmlCanvasElement, event: &MouseEvent) -> (usize, usize) {
+    let x_scale = canvas.width() as f64 / canvas.client_width().max(1) as f64;
+    let y_scale = canvas.height() as f64 / canvas.client_height().max(1) as f64;
+    let x = (event.offset_x().max(0) as f64 * x_scale) as usize;
+    let y = (event.offset_y().max(0) as f64 * y_scale) as usize;
+    return (x, y);
+}
+
 pub fn init_panic_hook() {
     console_error_panic_hook::set_once();
 }
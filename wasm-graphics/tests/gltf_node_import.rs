@@ -0,0 +1,59 @@
+// GLB node trees are placed by the product of the file's node matrices. Here a child rotated a
+// quarter turn sits under a parent scaled differently along each axis, so the child has to be
+// stretched along the parent's axes, not its own.
+
+use std::borrow::Cow;
+
+use gltf::binary::Glb;
+use serde_json::{json, Value};
+use wasm_graphics::{graphics::{game::Game, gltf_exporter::export_scene_objects_to_glb, gltf_parser::extract_node_tree_from_raw_glb_bytes, scene_object::SceneObject}, utils::{log::{set_min_log_level, LogLevel}, math::{Transform, Vec3}}};
+
+/// Column major, in glTF's axes
+const PARENT_MATRIX: [f32; 16] = [2.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0, 0.0, 1.0];
+/// A quarter turn around glTF's up axis, then moved along x
+const CHILD_MATRIX: [f32; 16] = [0.0, 0.0, -1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+
+/// A GLB with one box mesh, drawn by a child node under a parent node
+fn two_level_glb() -> Vec<u8> {
+    let cube = SceneObject::new_box_from_corners(Vec3::new(-1.0, -2.0, -3.0), Vec3::new(1.0, 2.0, 3.0), Vec3::new(0.7, 0.7, 0.7), SceneObject::new_diffuse_mat());
+    let exported = export_scene_objects_to_glb(&[cube]).unwrap();
+    let glb = Glb::from_slice(&exported).unwrap();
+    let mut json: Value = serde_json::from_slice(&glb.json).unwrap();
+    json["nodes"] = json!([
+        { "name": "child", "mesh": 0, "matrix": CHILD_MATRIX },
+        { "name": "parent", "children": [0], "matrix": PARENT_MATRIX },
+    ]);
+    json["scenes"] = json!([{ "nodes": [1] }]);
+    json["scene"] = json!(0);
+    let glb = Glb {
+        header: glb.header,
+        json: Cow::Owned(serde_json::to_vec(&json).unwrap()),
+        bin: glb.bin,
+    };
+    return glb.to_vec().unwrap();
+}
+
+#[test]
+fn child_under_non_uniform_parent_is_placed_by_the_matrix_product() {
+    set_min_log_level(LogLevel::Warn);
+    let glb = two_level_glb();
+    let tree = extract_node_tree_from_raw_glb_bytes(&glb).unwrap();
+    let expected = tree.roots[0].local_matrix * tree.roots[0].children[0].local_matrix;
+
+    let mut game = Game::new();
+    game.add_gltf_node_tree(tree, "model", Transform::identity());
+    let scene_objects = game.scene_objects.read().unwrap();
+    assert_eq!(scene_objects.len(), 1);
+    let transform = scene_objects[0].transform;
+    for p in [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+        let (actual, expected) = (transform.transform_point(p), expected.transform_point(p));
+        assert!((actual - expected).len() < 1e-4, "{:?} goes to {:?}, expected {:?}", p, actual, expected);
+    }
+
+    // the scene graph agrees, so moving the model later doesn't change the object's shape
+    let node = scene_objects[0].node.unwrap();
+    let graph_transform = game.scene_graph.get_world_transform(node);
+    for p in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+        assert!((graph_transform.transform_point(p) - transform.transform_point(p)).len() < 1e-4);
+    }
+}
@@ -0,0 +1,88 @@
+// Keys go in through Game::key_down() and Game::key_up() the way the browser reports them,
+// then a frame is run. Keys held for a shortcut or a Shift-click don't move the camera, and
+// no key is left held once every key has been let go.
+
+use wasm_graphics::{graphics::game::Game, utils::log::{set_min_log_level, LogLevel}};
+
+fn new_game_with_sphere() -> Game {
+    set_min_log_level(LogLevel::Warn);
+    let mut game = Game::new();
+    game.set_resolution(64, 64);
+    game.enter_edit_mode();
+    game.add_sphere(1.0);
+    return game;
+}
+
+#[test]
+fn redo_with_ctrl_shift_z_leaves_the_camera_alone() {
+    let mut game = new_game_with_sphere();
+    let camera_pos = game.camera.pos;
+
+    game.key_down(String::from("Control"));
+    game.key_down(String::from("z"));
+    game.game_loop();
+    game.key_up("z");
+    assert_eq!(game.get_object_ids().len(), 0);
+
+    game.key_down(String::from("Shift"));
+    game.key_down(String::from("Z"));
+    game.game_loop();
+    assert_eq!(game.get_object_ids().len(), 1);
+    assert_eq!(game.camera.pos, camera_pos);
+
+    // Shift let go first, so the browser reports the Z as "z"
+    game.key_up("Shift");
+    game.key_up("z");
+    game.key_up("Control");
+    assert!(game.keys_currently_pressed.is_empty());
+    game.game_loop();
+    assert_eq!(game.camera.pos, camera_pos);
+}
+
+#[test]
+fn movement_keys_still_move_the_camera() {
+    let mut game = new_game_with_sphere();
+    let camera_pos = game.camera.pos;
+    game.key_down(String::from("w"));
+    game.game_loop();
+    game.key_up("w");
+    assert_ne!(game.camera.pos, camera_pos);
+}
+
+#[test]
+fn shift_click_selects_without_moving_the_camera() {
+    let mut game = new_game_with_sphere();
+    game.deselect_object();
+    game.game_loop(); // fills the id buffer
+    let camera_pos = game.camera.pos;
+
+    game.key_down(String::from("Shift"));
+    game.mouse_down = true;
+    game.game_loop();
+    game.mouse_down = false;
+    game.mouse_clicked_last_frame = true;
+    game.game_loop();
+    game.key_up("Shift");
+    assert_eq!(game.selected_objects, game.get_object_ids());
+    assert_eq!(game.camera.pos, camera_pos);
+}
+
+#[test]
+fn cmd_d_duplicates_without_strafing_or_leaving_d_held() {
+    let mut game = new_game_with_sphere();
+    let sphere = game.get_object_ids()[0];
+    game.select_object(sphere);
+    let camera_pos = game.camera.pos;
+
+    game.key_down(String::from("Meta"));
+    game.key_down(String::from("d"));
+    game.game_loop();
+    assert_eq!(game.get_object_ids().len(), 2);
+    assert_eq!(game.camera.pos, camera_pos);
+
+    // the d's keyup never comes on macs
+    game.key_up("Meta");
+    assert!(game.keys_currently_pressed.is_empty());
+    game.game_loop();
+    assert_eq!(game.camera.pos, camera_pos);
+}
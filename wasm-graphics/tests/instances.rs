@@ -0,0 +1,68 @@
+// Instances share their original's geometry. Only objects made as instances keep their color
+// and material edits to themselves, other objects are edited in their own geometry, even while
+// the edit history or the clipboard hold on to copies of it.
+
+use wasm_graphics::{graphics::{edit_history::EditCommand, game::Game}, utils::{log::{set_min_log_level, LogLevel}, math::Vec3}};
+
+fn new_game_with_sphere() -> Game {
+    set_min_log_level(LogLevel::Warn);
+    let mut game = Game::new();
+    game.set_resolution(64, 64);
+    game.enter_edit_mode();
+    game.add_sphere(1.0);
+    let sphere = game.get_object_ids()[0];
+    game.select_object(sphere);
+    return game;
+}
+
+#[test]
+fn edited_objects_dont_become_instances() {
+    let game = new_game_with_sphere();
+    let mut scene_objects = game.scene_objects.write().unwrap();
+    let sphere = &mut scene_objects[0];
+    assert!(!sphere.is_instance());
+
+    // adding the sphere was recorded in the edit history, which keeps a copy of it around
+    sphere.set_color(Vec3::new(0.9, 0.1, 0.1));
+    sphere.set_material_properties(2, 0.2, Vec3::new(0.9, 0.1, 0.1));
+    assert!(!sphere.is_instance());
+    assert!(sphere.material_override.is_none());
+    assert_eq!(sphere.get_material_number(), 2);
+    assert_eq!(sphere.mesh.colors[0], Vec3::new(0.9, 0.1, 0.1));
+}
+
+#[test]
+fn instance_edits_stay_with_the_instance() {
+    let mut game = new_game_with_sphere();
+    game.instance_selected_obj();
+    let mut scene_objects = game.scene_objects.write().unwrap();
+    assert!(!scene_objects[0].is_instance());
+    assert!(scene_objects[1].is_instance());
+
+    scene_objects[1].set_material_properties(3, 1.5, Vec3::new(0.2, 0.2, 0.9));
+    assert!(scene_objects[1].material_override.is_some());
+    assert_eq!(scene_objects[0].get_material_number(), 1);
+    assert_eq!(scene_objects[1].get_material_number(), 3);
+
+    // the original's edits go into its geometry, which then stops being shared
+    scene_objects[0].set_color(Vec3::new(0.1, 0.9, 0.1));
+    assert!(scene_objects[0].material_override.is_none());
+    assert_eq!(scene_objects[0].get_triangle_color(0), Vec3::new(0.1, 0.9, 0.1));
+    assert_eq!(scene_objects[1].get_triangle_color(0), Vec3::new(0.2, 0.2, 0.9));
+}
+
+#[test]
+fn undoing_a_material_edit_restores_the_colors() {
+    let mut game = new_game_with_sphere();
+    let sphere = game.get_object_ids()[0];
+    let original_color = game.scene_objects.read().unwrap()[0].get_triangle_color(0);
+    let before = Box::new(game.scene_objects.read().unwrap()[0].get_material_state());
+    game.scene_objects.write().unwrap()[0].set_color(Vec3::new(0.9, 0.1, 0.1));
+    let after = Box::new(game.scene_objects.read().unwrap()[0].get_material_state());
+    game.record_edit(EditCommand::Material { id: sphere, before, after });
+
+    assert!(game.undo());
+    assert_eq!(game.scene_objects.read().unwrap()[0].get_triangle_color(0), original_color);
+    assert!(game.redo());
+    assert_eq!(game.scene_objects.read().unwrap()[0].get_triangle_color(0), Vec3::new(0.9, 0.1, 0.1));
+}
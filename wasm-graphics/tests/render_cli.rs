@@ -0,0 +1,37 @@
+// Runs the render binary the way it's run from the command line, for the arguments
+// that get checked before anything is rendered.
+
+use std::{path::PathBuf, process::{Command, Output}};
+
+fn out_path(name: &str) -> PathBuf {
+    return std::env::temp_dir().join(format!("wasm_graphics_render_cli_{}_{}.png", name, std::process::id()));
+}
+
+fn render(args: &[&str], out: &PathBuf) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_render"))
+        .args(["--scene", "simple_light", "--width", "16", "--height", "16", "--spp", "1", "--quiet", "--out"])
+        .arg(out)
+        .args(args)
+        .output()
+        .expect("render binary didn't run");
+}
+
+#[test]
+fn shallow_max_depths_render() {
+    for max_depth in ["1", "2"] {
+        let out = out_path(&format!("max_depth_{}", max_depth));
+        let output = render(&["--max-depth", max_depth], &out);
+        assert!(output.status.success(), "--max-depth {} failed: {}", max_depth, String::from_utf8_lossy(&output.stderr));
+        assert!(out.exists());
+        std::fs::remove_file(&out).unwrap();
+    }
+}
+
+#[test]
+fn max_depth_of_0_is_rejected() {
+    let out = out_path("max_depth_0");
+    let output = render(&["--max-depth", "0"], &out);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--max-depth must be at least 1"));
+    assert!(!out.exists());
+}
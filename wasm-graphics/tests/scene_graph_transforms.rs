@@ -0,0 +1,114 @@
+// World transforms in the scene graph are products of matrices. A rotated child under a
+// non-uniformly scaled parent has to be stretched along the parent's axes, not its own.
+// Sphere lights can't be stretched at all, their light is sampled from a sphere.
+
+use std::f32::consts::PI;
+
+use wasm_graphics::{graphics::{game::Game, scene_graph::SceneGraph, scene_object::SceneObject}, utils::{log::{set_min_log_level, LogLevel}, math::{Mat4, Quat, Transform, Vec3}}};
+
+const POINTS: [Vec3; 4] = [
+    Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+    Vec3 { x: 1.0, y: 0.0, z: 0.0 },
+    Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+    Vec3 { x: 0.3, y: -0.7, z: 2.0 },
+];
+
+fn assert_places_points_like(transform: &Transform, matrix: &Mat4) {
+    for p in POINTS {
+        let (actual, expected) = (transform.transform_point(p), matrix.transform_point(p));
+        assert!((actual - expected).len() < 1e-4, "{:?} goes to {:?}, expected {:?}", p, actual, expected);
+    }
+}
+
+fn non_uniform_parent() -> Transform {
+    return Transform::new(Vec3::new(1.0, 2.0, 3.0), Quat::rotation_z(0.3), Vec3::new(2.0, 1.0, 0.5));
+}
+
+fn rotated_child() -> Transform {
+    return Transform::new(Vec3::new(1.0, -1.0, 0.0), Quat::rotation_z(PI / 2.0), Vec3::new(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn rotated_child_under_non_uniform_parent() {
+    let mut scene_graph = SceneGraph::new();
+    let parent = scene_graph.add_node("parent", non_uniform_parent(), None);
+    let child = scene_graph.add_node("child", rotated_child(), Some(parent));
+
+    let expected = non_uniform_parent().to_mat4() * rotated_child().to_mat4();
+    assert_places_points_like(&scene_graph.get_world_transform(child), &expected);
+    let (_, subtree_transform) = scene_graph.get_subtree_world_transforms(parent)[1];
+    assert_places_points_like(&subtree_transform, &expected);
+
+    // setting the world transform it already has gives back the same local transform
+    let world_transform = scene_graph.get_world_transform(child);
+    scene_graph.set_world_transform(child, world_transform);
+    assert_places_points_like(&scene_graph.get(child).unwrap().local_transform, &rotated_child().to_mat4());
+}
+
+#[test]
+fn reparenting_under_non_uniform_parent_keeps_world_placement() {
+    let mut scene_graph = SceneGraph::new();
+    // rotated the same way as the node, so the node's axes line up with the parent's
+    let parent_transform = Transform::new(Vec3::new(1.0, 2.0, 3.0), Quat::rotation_z(PI / 2.0), Vec3::new(2.0, 1.0, 0.5));
+    let parent = scene_graph.add_node("parent", parent_transform, None);
+    let node = scene_graph.add_node("node", rotated_child(), None);
+
+    assert!(!scene_graph.would_shear(node, Some(parent)));
+    assert!(scene_graph.set_parent(node, Some(parent)));
+    assert_places_points_like(&scene_graph.get_world_transform(node), &rotated_child().to_mat4());
+    assert!(scene_graph.set_parent(node, None));
+    assert_places_points_like(&scene_graph.get(node).unwrap().local_transform, &rotated_child().to_mat4());
+
+    // the node's axes would be at an angle to the parent's scale, no Transform can keep it in place
+    let sheared_parent = scene_graph.add_node("sheared parent", non_uniform_parent(), None);
+    assert!(scene_graph.would_shear(node, Some(sheared_parent)));
+}
+
+#[test]
+fn objects_in_a_non_uniformly_scaled_group() {
+    set_min_log_level(LogLevel::Warn);
+    let mut game = Game::new();
+    game.set_resolution(64, 64);
+    game.enter_edit_mode();
+    game.add_box(1.0, 2.0, 3.0);
+    let object = game.get_object_ids()[0];
+    game.select_object(object);
+    game.set_selected_obj_transform(rotated_child());
+    game.deselect_object();
+
+    let group = game.add_group("group", Transform::identity(), None);
+    game.set_object_parent(object, Some(group));
+    game.edit_node_transform(group, |transform, _| *transform = non_uniform_parent());
+
+    let index = game.get_object_index(object).unwrap();
+    let transform = game.scene_objects.read().unwrap()[index].transform;
+    assert_places_points_like(&transform, &(non_uniform_parent().to_mat4() * rotated_child().to_mat4()));
+
+    // turning the object inside the group would shear it, so the group refuses it
+    game.select_object(object);
+    game.rotate_selected_obj(0.0, 0.0, 30.0);
+    let transform_after = game.scene_objects.read().unwrap()[index].transform;
+    assert_eq!(transform, transform_after);
+}
+
+#[test]
+fn sphere_lights_only_scale_uniformly() {
+    set_min_log_level(LogLevel::Error);
+    let mut light = SceneObject::new_sphere_omni_light(Vec3::new(1.0, 0.0, 2.0), 0.5, Vec3::new(1.0, 1.0, 1.0), 2, 16);
+    light.scale_axes_by(Vec3::new(2.0, 1.0, 1.0));
+    assert!(light.transform.is_identity());
+    light.scale_by(2.0);
+    assert_eq!(light.transform.scale, Vec3::new(2.0, 2.0, 2.0));
+    assert!(!light.has_inexact_world_hittables());
+
+    // the sampled sphere is the one that gets hit
+    let mut game = Game::new();
+    game.add_scene_object(light);
+    game.extract_rt_lights_from_scene_objects();
+    assert!((game.get_rt_lights()[0].get_area() - 4.0 * PI).abs() < 1e-4);
+
+    // set directly (like a snapshot does), the light is only sampled roughly
+    let mut squashed = SceneObject::new_sphere_omni_light(Vec3::zero(), 0.5, Vec3::new(1.0, 1.0, 1.0), 2, 16);
+    squashed.transform.scale = Vec3::new(1.0, 1.0, 0.5);
+    assert!(squashed.has_inexact_world_hittables());
+}
@@ -0,0 +1,149 @@
+// Round trips through the scene file format: a built-in scene is loaded and edited the way the
+// editor does it, saved with Game::to_scene_description(), and loaded again. The edits have to
+// survive, so the reloaded scene renders the same and saves to the same description.
+
+use wasm_graphics::{graphics::{game::Game, object_id::ObjectId, scene_file::{SceneAssets, SceneDescription}}, utils::{log::{set_min_log_level, LogLevel}, math::{Quat, Transform, Vec3}}};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 64;
+
+fn new_game() -> Game {
+    set_min_log_level(LogLevel::Warn);
+    let mut game = Game::new();
+    game.set_resolution(WIDTH, HEIGHT);
+    return game;
+}
+
+/// What the editor's material panel does to an object
+fn set_material(game: &mut Game, id: ObjectId, color: Vec3, material_type: u32, extra_prop: f32) {
+    let index = game.get_object_index(id).unwrap();
+    let scene_obj = &mut game.scene_objects.write().unwrap()[index];
+    scene_obj.set_color(color);
+    scene_obj.set_material_properties(material_type, extra_prop, color);
+}
+
+/// Moves, recolors, instances, deletes, adds and groups objects of the Cornell box
+fn load_edited_cornell() -> Game {
+    let mut game = new_game();
+    game.load_builtin_scene("cornell", &SceneAssets::new()).expect("built-in scene failed to load");
+    game.enter_edit_mode();
+    let ids = game.get_object_ids();
+    let (left_wall, back_wall, tall_box, short_box) = (ids[0], ids[5], ids[6], ids[7]);
+
+    game.select_object(tall_box);
+    game.translate_selected_obj(4.0, -2.0, 0.0);
+    game.rotate_selected_obj(0.0, 0.0, 10.0);
+    set_material(&mut game, tall_box, Vec3::new(0.8, 0.2, 0.1), 2, 0.3);
+
+    game.select_object(short_box);
+    game.scale_selected_obj(0.8);
+    game.instance_selected_obj();
+    let instance = *game.get_object_ids().last().unwrap();
+    game.select_object(instance);
+    game.set_selected_obj_transform(Transform::new(Vec3::new(40.0, 30.0, 10.0), Quat::rotation_z(0.5), Vec3::new(0.5, 0.5, 0.5)));
+    set_material(&mut game, instance, Vec3::new(0.2, 0.3, 0.9), 3, 1.5);
+
+    game.select_object(left_wall);
+    set_material(&mut game, left_wall, Vec3::new(0.9, 0.9, 0.2), 1, 0.0);
+
+    game.select_object(back_wall);
+    game.delete_selected_object();
+
+    game.add_sphere(6.0);
+    let sphere = *game.get_object_ids().last().unwrap();
+    game.select_object(sphere);
+    game.set_selected_obj_transform(Transform::new(Vec3::new(20.0, 15.0, 40.0), Quat::identity(), Vec3::new(1.0, 1.0, 1.0)));
+    set_material(&mut game, sphere, Vec3::new(1.0, 0.8, 0.6), 4, 2.0);
+
+    let group = game.add_group("Boxes", Transform::identity(), None);
+    game.set_object_parent(tall_box, Some(group));
+    game.set_object_parent(short_box, Some(group));
+    game.edit_node_transform(group, |transform, center| transform.rotate_around(center, Quat::rotation_z(-0.2)));
+
+    game.deselect_object();
+    return game;
+}
+
+fn reload(game: &Game) -> (Game, SceneDescription) {
+    let json = game.to_scene_description().to_json();
+    let description = SceneDescription::from_json(&json).expect("saved scene doesn't parse");
+    let mut reloaded = new_game();
+    reloaded.load_scene_description(&description, &SceneAssets::new()).expect("saved scene failed to load");
+    return (reloaded, description);
+}
+
+fn render_raster(game: &mut Game, lit: bool) -> Vec<u8> {
+    if lit {
+        game.exit_edit_mode();
+    } else {
+        game.enter_edit_mode();
+    }
+    game.game_loop();
+    return game.pixel_buf.get_gamma_corrected_buf_as_u8();
+}
+
+fn max_channel_difference(a: &[u8], b: &[u8]) -> u8 {
+    return a.iter().zip(b.iter()).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0);
+}
+
+#[test]
+fn edits_survive_save_and_load() {
+    let game = load_edited_cornell();
+    let (reloaded, _) = reload(&game);
+
+    let objects = game.scene_objects.read().unwrap();
+    let reloaded_objects = reloaded.scene_objects.read().unwrap();
+    assert_eq!(objects.len(), reloaded_objects.len());
+    assert_eq!(game.scene_graph.node_count(), reloaded.scene_graph.node_count());
+
+    // instances come back right after their original, so match objects up by where they are
+    for obj in objects.iter() {
+        let reloaded_obj = reloaded_objects
+            .iter()
+            .find(|other| other.transform == obj.transform && other.get_center() == obj.get_center())
+            .unwrap_or_else(|| panic!("no object placed at {:?} after reloading", obj.transform));
+        assert_eq!(obj.get_material_number(), reloaded_obj.get_material_number());
+        assert_eq!(obj.get_triangle_color(0), reloaded_obj.get_triangle_color(0));
+        assert_eq!(obj.is_instance(), reloaded_obj.is_instance());
+        assert_eq!(obj.lights.len(), reloaded_obj.lights.len());
+        assert_eq!(obj.node.is_some(), reloaded_obj.node.is_some());
+    }
+}
+
+#[test]
+fn reloaded_scene_renders_the_same() {
+    let mut game = load_edited_cornell();
+    let (mut reloaded, _) = reload(&game);
+    for lit in [false, true] {
+        let expected = render_raster(&mut game, lit);
+        let actual = render_raster(&mut reloaded, lit);
+        let difference = max_channel_difference(&expected, &actual);
+        assert!(difference <= 2, "lit: {}, pixels differ by up to {}", lit, difference);
+    }
+}
+
+#[test]
+fn saving_again_gives_the_same_description() {
+    let game = load_edited_cornell();
+    let (reloaded, description) = reload(&game);
+    let saved_again = reloaded.to_scene_description();
+    assert_eq!(serde_json::to_value(&description.objects).unwrap(), serde_json::to_value(&saved_again.objects).unwrap());
+    assert_eq!(serde_json::to_value(&description.nodes).unwrap(), serde_json::to_value(&saved_again.nodes).unwrap());
+}
+
+#[test]
+fn ray_max_depth_below_1_is_rejected() {
+    let mut game = load_edited_cornell();
+    let object_count = game.get_object_ids().len();
+    let mut description = game.to_scene_description();
+    description.ray_max_depth = 0;
+    assert!(game.load_scene_description(&description, &SceneAssets::new()).is_err());
+    assert_eq!(game.get_object_ids().len(), object_count);
+
+    // shallow depths leave no bounces before Russian roulette
+    for max_depth in [1, 2] {
+        description.ray_max_depth = max_depth;
+        game.load_scene_description(&description, &SceneAssets::new()).unwrap();
+        game.render_ray_tracing_pass();
+    }
+}
@@ -0,0 +1,103 @@
+// Snapshots keep textured meshes as they are: their UVs, textures and normal or bump maps
+// come back after serialize_scene() and deserialize_scene(), in the mesh and in the triangles
+// the path tracer uses.
+
+use std::sync::Arc;
+
+use image::{DynamicImage, RgbImage};
+use wasm_graphics::{graphics::{game::Game, mesh::Mesh, normal_map::{NormalMap, NormalMapKind}, scene_object::SceneObject, texture::{Texture, TextureFilter}}, utils::{log::{set_min_log_level, LogLevel}, math::{Quat, Transform, Vec3}}};
+
+const UV_POINTS: [[f32; 2]; 3] = [[0.1, 0.2], [0.6, 0.3], [0.9, 0.8]];
+
+fn checkerboard() -> Arc<Texture> {
+    let image = RgbImage::from_fn(4, 4, |x, y| if (x + y) % 2 == 0 { image::Rgb([250, 40, 10]) } else { image::Rgb([20, 90, 200]) });
+    return Arc::new(Texture::from_image(&DynamicImage::ImageRgb8(image)));
+}
+
+fn ramp() -> Arc<Texture> {
+    let image = RgbImage::from_fn(8, 8, |x, _| image::Rgb([(x * 30) as u8; 3]));
+    return Arc::new(Texture::from_image(&DynamicImage::ImageRgb8(image)));
+}
+
+/// A quad with a texture on one of its triangles and a bump map on both
+fn textured_quad() -> Mesh {
+    let (properties, _) = SceneObject::new_diffuse_mat();
+    let vertices = vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 0.0), Vec3::new(0.0, 2.0, 0.0)];
+    let colors = vec![Vec3::new(0.5, 0.5, 0.5), Vec3::new(0.3, 0.6, 0.9)];
+    let mut mesh = Mesh::new(vertices, vec![0, 1, 2, 0, 2, 3], colors, properties);
+    mesh.uvs = vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+    mesh.textures = vec![checkerboard()];
+    mesh.triangle_textures = vec![Some(0), None];
+    mesh.texture_tint = Vec3::new(0.9, 0.8, 0.7);
+    mesh.set_normal_map(Arc::new(NormalMap { texture: ramp(), kind: NormalMapKind::Bump { strength: 3.0 } })).unwrap();
+    return mesh;
+}
+
+fn new_game_with(scene_obj: SceneObject) -> Game {
+    set_min_log_level(LogLevel::Warn);
+    let mut game = Game::new();
+    game.set_resolution(64, 64);
+    game.add_scene_object(scene_obj);
+    return game;
+}
+
+#[test]
+fn textured_mesh_survives_save_and_load() {
+    let (_, material) = SceneObject::new_diffuse_mat();
+    let game = new_game_with(SceneObject::new_from_mesh(textured_quad(), material, true));
+    // the checkerboard and the ramp, however many triangles use them
+    assert_eq!(game.to_scene_snapshot().textures.len(), 2);
+
+    let mut reloaded = Game::new();
+    reloaded.deserialize_scene(&game.serialize_scene()).unwrap();
+
+    let objects = game.scene_objects.read().unwrap();
+    let reloaded_objects = reloaded.scene_objects.read().unwrap();
+    let (mesh, reloaded_mesh) = (&objects[0].mesh, &reloaded_objects[0].mesh);
+    assert_eq!(mesh.uvs, reloaded_mesh.uvs);
+    assert_eq!(mesh.triangle_textures, reloaded_mesh.triangle_textures);
+    assert_eq!(mesh.texture_tint, reloaded_mesh.texture_tint);
+    assert_eq!(mesh.triangle_normal_maps, reloaded_mesh.triangle_normal_maps);
+    assert_eq!(mesh.tangents, reloaded_mesh.tangents);
+    assert_eq!(reloaded_mesh.normal_maps[0].kind, NormalMapKind::Bump { strength: 3.0 });
+    for uv in UV_POINTS {
+        assert_eq!(mesh.textures[0].sample(uv, TextureFilter::Nearest), reloaded_mesh.textures[0].sample(uv, TextureFilter::Nearest));
+        assert_eq!(mesh.normal_maps[0].sample(uv, TextureFilter::Bilinear), reloaded_mesh.normal_maps[0].sample(uv, TextureFilter::Bilinear));
+    }
+
+    // the path tracer's triangles keep their textures too
+    for (hittable, reloaded_hittable) in objects[0].hittables.iter().zip(reloaded_objects[0].hittables.iter()) {
+        assert_eq!(hittable.get_texture().map(|t| t.uvs), reloaded_hittable.get_texture().map(|t| t.uvs));
+        assert_eq!(hittable.get_texture().map(|t| t.tint), reloaded_hittable.get_texture().map(|t| t.tint));
+        if let (Some(texture), Some(reloaded_texture)) = (hittable.get_texture(), reloaded_hittable.get_texture()) {
+            assert_eq!(texture.sample([0.2, 0.3, 0.5], TextureFilter::Nearest), reloaded_texture.sample([0.2, 0.3, 0.5], TextureFilter::Nearest));
+        }
+    }
+}
+
+#[test]
+fn world_hittables_keep_their_textures() {
+    let mut scene_obj = SceneObject::new_from_mesh_with_omni_light(textured_quad(), Vec3::new(1.0, 1.0, 1.0), None, 16, true);
+    scene_obj.set_transform(Transform::new(Vec3::new(5.0, 0.0, 1.0), Quat::rotation_z(0.4), Vec3::new(2.0, 1.0, 1.0)));
+    let world_hittables = scene_obj.get_world_hittables();
+    assert!(world_hittables[0].get_texture().is_some());
+    assert!(world_hittables[1].get_texture().is_none());
+
+    // lights are the world hittables of emissive objects
+    let mut game = new_game_with(scene_obj);
+    game.extract_rt_lights_from_scene_objects();
+    assert!(game.get_rt_lights()[0].get_texture().is_some());
+}
+
+#[test]
+fn ray_max_depth_below_1_is_rejected() {
+    let (_, material) = SceneObject::new_diffuse_mat();
+    let mut game = new_game_with(SceneObject::new_from_mesh(textured_quad(), material, true));
+    let mut snapshot = game.to_scene_snapshot();
+    snapshot.ray_max_depth = 0;
+    snapshot.objects.clear();
+    assert!(game.load_scene_snapshot(&snapshot).is_err());
+    // the scene is left as it was
+    assert_eq!(game.scene_objects.read().unwrap().len(), 1);
+    assert_ne!(game.ray_max_depth, 0);
+}
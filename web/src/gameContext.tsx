@@ -11,6 +11,10 @@ interface IGameContext {
     focalDistance: number;
     dofStrength: number;
     sceneLoading: boolean;
+    selectedGroup: string | null | undefined;
+    selectedObjectId: bigint | null | undefined;
+    selectedObjectCount: number;
+    gizmoMode: number;
     // Add other shared states here, e.g., selectedObjectProperties, rayTraceProgress
 
     // Actions callable from React components (which might then call WASM)
@@ -40,6 +44,10 @@ export const GameProvider: React.FC<GameProviderProps> = ({ children }) => {
     const [focalDistance, setFocalDistance] = useState<number>(10.0);
     const [dofStrength, setDofStrength] = useState<number>(0.0);
     const [sceneLoading, setSceneLoading] = useState<boolean>(false);
+    const [selectedGroup, setSelectedGroup] = useState<string | null | undefined>(null);
+    const [selectedObjectId, setSelectedObjectId] = useState<bigint | null | undefined>(null);
+    const [selectedObjectCount, setSelectedObjectCount] = useState<number>(0);
+    const [gizmoMode, setGizmoMode] = useState<number>(1);
 
     // Setup the WASM to JS bridge implementations
     useEffect(() => {
@@ -90,6 +98,22 @@ export const GameProvider: React.FC<GameProviderProps> = ({ children }) => {
                 console.log("GameProvider: Bridge updating scene loading status", loading);
                 setSceneLoading(loading);
             },
+            updateSelectedGroup: (groupName) => {
+                console.log("GameProvider: Bridge updating selected group", groupName);
+                setSelectedGroup(groupName);
+            },
+            updateSelectedObjectId: (objectId) => {
+                console.log("GameProvider: Bridge updating selected object id", objectId);
+                setSelectedObjectId(objectId);
+            },
+            updateSelectedObjectCount: (count) => {
+                console.log("GameProvider: Bridge updating selected object count", count);
+                setSelectedObjectCount(count);
+            },
+            updateGizmoMode: (mode) => {
+                console.log("GameProvider: Bridge updating gizmo mode", mode);
+                setGizmoMode(mode);
+            },
             // Implement other bridge functions here to update context state
         };
 
@@ -110,6 +134,10 @@ export const GameProvider: React.FC<GameProviderProps> = ({ children }) => {
         focalDistance,
         dofStrength,
         sceneLoading,
+        selectedGroup,
+        selectedObjectId,
+        selectedObjectCount,
+        gizmoMode,
     };
 
     return <GameContext.Provider value={value}>{children}</GameContext.Provider>;
@@ -1,5 +1,6 @@
 import React from 'react';
 import MainCanvas from './MainCanvas';
+import FrameStatsOverlay from './FrameStatsOverlay';
 import SceneControlPanel from './SceneControlPanel/SceneControlPanel';
 import { GameProvider } from '@/gameContext';
 import { ThemeProvider } from "@/components/theme-provider"
@@ -17,7 +18,10 @@ const App = () => {
 
                 {/* Main Canvas Area */}
                 <div className="flex-grow flex justify-center h-full p-4 bg-muted/20"> {/* Added padding and a slightly different bg for canvas area */}
-                    <MainCanvas />
+                    <div className="relative flex justify-center h-full max-w-full">
+                        <MainCanvas />
+                        <FrameStatsOverlay />
+                    </div>
                 </div>
             </div>
         </ThemeProvider>
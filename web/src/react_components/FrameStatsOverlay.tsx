@@ -0,0 +1,60 @@
+import * as wasm from "@wasm/wasm_graphics";
+
+import React, { useEffect, useState } from 'react';
+import { Button } from "@/components/ui/button";
+
+// How often the numbers are read back from wasm, every frame would be unreadable anyway
+const REFRESH_INTERVAL_MS = 500;
+
+const formatMs = (ms: number) => `${ms.toFixed(1)}ms`;
+
+// Per stage frame times, triangle counts and ray tracing throughput, over the top left of the canvas
+const FrameStatsOverlay = () => {
+    const [shown, setShown] = useState(false);
+    const [stats, setStats] = useState<wasm.FrameStatsInfo | undefined>(undefined);
+
+    useEffect(() => {
+        if (!shown) {
+            return;
+        }
+        const interval = setInterval(() => setStats(wasm.get_frame_stats()), REFRESH_INTERVAL_MS);
+        return () => clearInterval(interval);
+    }, [shown]);
+
+    const lines: string[] = [];
+    if (shown && stats) {
+        lines.push(`frame ${formatMs(stats.frame_time_ms)} (${stats.frames_per_second.toFixed(0)} fps)`);
+        if (stats.ray_tracing_ms > 0) {
+            lines.push(`ray tracing ${formatMs(stats.ray_tracing_ms)}`);
+            lines.push(`${(stats.rays_per_second / 1000).toFixed(0)}k rays/s, ${stats.samples_accumulated} samples`);
+        } else {
+            lines.push(`clear ${formatMs(stats.clear_ms)}, transform ${formatMs(stats.transform_ms)}`);
+            lines.push(`opaque ${formatMs(stats.rasterize_opaque_ms)}, transparent ${formatMs(stats.rasterize_transparent_ms)}`);
+            if (stats.dof_ms > 0) {
+                lines.push(`dof ${formatMs(stats.dof_ms)}`);
+            }
+            lines.push(`triangles ${stats.triangles_drawn} drawn / ${stats.triangles_submitted} (${stats.triangles_culled} culled)`);
+        }
+        if (stats.shadow_map_rebuild_ms > 0) {
+            lines.push(`shadow maps ${formatMs(stats.shadow_map_rebuild_ms)}`);
+        }
+        if (stats.bvh_build_ms > 0) {
+            lines.push(`bvh ${formatMs(stats.bvh_build_ms)}`);
+        }
+    }
+
+    return (
+        <div className="absolute top-2 left-2 flex flex-col items-start space-y-1">
+            <Button variant="outline" size="sm" className="h-6 text-xs opacity-75" onClick={() => setShown(!shown)}>
+                {shown ? "Hide stats" : "Stats"}
+            </Button>
+            {lines.length > 0 && (
+                <pre className="text-xs font-mono bg-black/60 text-white rounded p-1 pointer-events-none">
+                    {lines.join("\n")}
+                </pre>
+            )}
+        </div>
+    );
+};
+
+export default FrameStatsOverlay;
@@ -0,0 +1,138 @@
+import * as wasm from "@wasm/wasm_graphics";
+
+import React, { useState } from 'react';
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import { Switch } from "@/components/ui/switch";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select";
+
+type DebugOverlayOptions = {
+    wireframe: boolean;
+    normals: boolean;
+    boundingSpheres: boolean;
+    bvhBoxes: boolean;
+    bvhMinDepth: number;
+    bvhMaxDepth: number | undefined; // undefined = no limit
+    onlySelected: boolean;
+};
+
+// The options live in wasm, read them back so they survive this panel being closed
+const readDebugOverlayOptions = (): DebugOverlayOptions => {
+    const options = wasm.get_debug_overlay_options();
+    return {
+        wireframe: options.wireframe,
+        normals: options.normals,
+        boundingSpheres: options.bounding_spheres,
+        bvhBoxes: options.bvh_boxes,
+        bvhMinDepth: options.bvh_min_depth,
+        bvhMaxDepth: options.bvh_max_depth,
+        onlySelected: options.only_selected,
+    };
+};
+
+// A label with a switch on the right
+const SwitchRow: React.FC<{
+    id: string;
+    label: string;
+    checked: boolean;
+    onCheckedChange: (checked: boolean) => void;
+}> = ({ id, label, checked, onCheckedChange }) => (
+    <div className="flex items-center justify-between py-1">
+        <Label htmlFor={id} className="text-xs text-muted-foreground">{label}</Label>
+        <Switch id={id} checked={checked} onCheckedChange={onCheckedChange} />
+    </div>
+);
+
+interface DebugOverlayPanelProps {
+}
+
+const DebugOverlayPanel: React.FC<DebugOverlayPanelProps> = () => {
+    const [options, setOptions] = useState<DebugOverlayOptions>(readDebugOverlayOptions);
+    // "shaded" or the heatmap metric number, 0 = nodes visited, 1 = hittables tested
+    const [heatmap, setHeatmap] = useState<string>(() => wasm.get_bvh_heatmap_metric()?.toString() ?? "shaded");
+    const [heatmapSummary] = useState<string | undefined>(() => wasm.get_bvh_heatmap_summary());
+
+    const updateHeatmap = (value: string) => {
+        if (wasm.set_bvh_heatmap(value === "shaded" ? undefined : parseInt(value), undefined)) {
+            setHeatmap(value);
+        }
+    };
+
+    const updateOptions = (changes: Partial<DebugOverlayOptions>) => {
+        const newOptions = { ...options, ...changes };
+        wasm.set_debug_overlay_options(new wasm.DebugOverlayOptions(
+            newOptions.wireframe,
+            newOptions.normals,
+            newOptions.boundingSpheres,
+            newOptions.bvhBoxes,
+            newOptions.bvhMinDepth,
+            newOptions.bvhMaxDepth,
+            newOptions.onlySelected,
+        ));
+        setOptions(newOptions);
+    };
+
+    return (
+        <div className="space-y-2">
+            <SwitchRow id="debug-wireframe" label="Wireframe" checked={options.wireframe} onCheckedChange={(checked) => updateOptions({ wireframe: checked })} />
+            <SwitchRow id="debug-normals" label="Face normals" checked={options.normals} onCheckedChange={(checked) => updateOptions({ normals: checked })} />
+            <SwitchRow id="debug-bounding-spheres" label="Bounding spheres" checked={options.boundingSpheres} onCheckedChange={(checked) => updateOptions({ boundingSpheres: checked })} />
+            <SwitchRow id="debug-bvh-boxes" label="BVH boxes" checked={options.bvhBoxes} onCheckedChange={(checked) => updateOptions({ bvhBoxes: checked })} />
+            <div className="flex items-center justify-between py-1">
+                <Label htmlFor="debug-bvh-min-depth" className="text-xs text-muted-foreground">BVH depths</Label>
+                <div className="flex items-center space-x-1">
+                    <Input
+                        id="debug-bvh-min-depth"
+                        type="number"
+                        value={options.bvhMinDepth}
+                        onChange={(e) => updateOptions({ bvhMinDepth: Math.max(0, parseInt(e.target.value) || 0) })}
+                        step="1"
+                        min="0"
+                        className="w-14 h-6 text-xs"
+                        title="Shallowest depth drawn, the root is 0"
+                    />
+                    <span className="text-xs text-muted-foreground">to</span>
+                    <Input
+                        id="debug-bvh-max-depth"
+                        type="number"
+                        value={options.bvhMaxDepth ?? ''}
+                        placeholder="any"
+                        onChange={(e) => {
+                            const depth = parseInt(e.target.value);
+                            updateOptions({ bvhMaxDepth: isNaN(depth) ? undefined : Math.max(0, depth) });
+                        }}
+                        step="1"
+                        min="0"
+                        className="w-14 h-6 text-xs"
+                        title="Deepest depth drawn, empty for no limit"
+                    />
+                </div>
+            </div>
+            <SwitchRow id="debug-only-selected" label="Selected objects only" checked={options.onlySelected} onCheckedChange={(checked) => updateOptions({ onlySelected: checked })} />
+            <div className="flex items-center justify-between py-1">
+                <Label htmlFor="debug-bvh-heatmap" className="text-xs text-muted-foreground">Ray trace as</Label>
+                <Select value={heatmap} onValueChange={updateHeatmap}>
+                    <SelectTrigger id="debug-bvh-heatmap" className="w-32 h-6 text-xs">
+                        <SelectValue />
+                    </SelectTrigger>
+                    <SelectContent>
+                        <SelectItem value="shaded">Shaded</SelectItem>
+                        <SelectItem value="0">BVH nodes heatmap</SelectItem>
+                        <SelectItem value="1">Hit tests heatmap</SelectItem>
+                    </SelectContent>
+                </Select>
+            </div>
+            {heatmapSummary && (
+                <p className="text-xs text-muted-foreground">Last heatmap: {heatmapSummary}</p>
+            )}
+        </div>
+    );
+};
+
+export default DebugOverlayPanel;
@@ -0,0 +1,122 @@
+import * as wasm from "@wasm/wasm_graphics"
+
+import React, { useState } from 'react';
+import {
+    AccordionContent,
+    AccordionItem,
+    AccordionTrigger,
+} from "@/components/ui/accordion";
+import { Button } from "@/components/ui/button";
+import { Label } from "@/components/ui/label";
+import { Input } from "@/components/ui/input";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select";
+
+type Vec3Input = { x: number; y: number; z: number };
+
+// Three number inputs in a row, for an offset, axis or pivot
+const Vec3InputRow: React.FC<{
+    id: string;
+    label: string;
+    value: Vec3Input;
+    onChange: (value: Vec3Input) => void;
+}> = ({ id, label, value, onChange }) => (
+    <div className="flex items-center justify-between py-1">
+        <Label htmlFor={`${id}-x`} className="text-xs text-muted-foreground">{label}</Label>
+        <div className="flex space-x-1">
+            {(['x', 'y', 'z'] as const).map((axis) => (
+                <Input
+                    key={axis}
+                    id={`${id}-${axis}`}
+                    type="number"
+                    value={value[axis]}
+                    onChange={(e) => onChange({ ...value, [axis]: parseFloat(e.target.value) || 0 })}
+                    step="0.1"
+                    className="w-16 h-6 text-xs"
+                    title={axis.toUpperCase()}
+                />
+            ))}
+        </div>
+    </div>
+);
+
+interface ArrayControlsProps {
+}
+
+const ArrayControls: React.FC<ArrayControlsProps> = ({}) => {
+    const [mode, setMode] = useState<'linear' | 'circular'>('linear');
+    const [count, setCount] = useState<number>(3);
+    const [offset, setOffset] = useState<Vec3Input>({ x: 2, y: 0, z: 0 });
+    const [axis, setAxis] = useState<Vec3Input>({ x: 0, y: 0, z: 1 });
+    const [pivot, setPivot] = useState<Vec3Input>({ x: 5, y: 0, z: 0 });
+    const [totalDegrees, setTotalDegrees] = useState<number>(360);
+
+    const handleCreateArray = () => {
+        if (mode === 'linear') {
+            wasm.array_selection_linear(count, offset.x, offset.y, offset.z);
+        } else {
+            wasm.array_selection_circular(count, axis.x, axis.y, axis.z, pivot.x, pivot.y, pivot.z, totalDegrees);
+        }
+    };
+
+    return (
+        <AccordionItem value="array-controls">
+            <AccordionTrigger>Array</AccordionTrigger>
+            <AccordionContent className="space-y-2 pt-2">
+                <div className="flex items-center justify-between py-1">
+                    <Label htmlFor="array-mode" className="text-xs text-muted-foreground">Layout</Label>
+                    <Select value={mode} onValueChange={(value) => setMode(value as 'linear' | 'circular')}>
+                        <SelectTrigger id="array-mode" className="w-32 h-6 text-xs">
+                            <SelectValue />
+                        </SelectTrigger>
+                        <SelectContent>
+                            <SelectItem value="linear">In a row</SelectItem>
+                            <SelectItem value="circular">Around a circle</SelectItem>
+                        </SelectContent>
+                    </Select>
+                </div>
+                <div className="flex items-center justify-between py-1">
+                    <Label htmlFor="array-count" className="text-xs text-muted-foreground">Copies</Label>
+                    <Input
+                        id="array-count"
+                        type="number"
+                        value={count}
+                        onChange={(e) => setCount(Math.max(1, parseInt(e.target.value) || 1))}
+                        step="1"
+                        min="1"
+                        className="w-20 h-6 text-xs"
+                    />
+                </div>
+                {mode === 'linear' ? (
+                    <Vec3InputRow id="array-offset" label="Offset" value={offset} onChange={setOffset} />
+                ) : (
+                    <>
+                        <Vec3InputRow id="array-axis" label="Axis" value={axis} onChange={setAxis} />
+                        <Vec3InputRow id="array-pivot" label="Pivot offset" value={pivot} onChange={setPivot} />
+                        <div className="flex items-center justify-between py-1">
+                            <Label htmlFor="array-degrees" className="text-xs text-muted-foreground">Spread (°)</Label>
+                            <Input
+                                id="array-degrees"
+                                type="number"
+                                value={totalDegrees}
+                                onChange={(e) => setTotalDegrees(parseFloat(e.target.value) || 360)}
+                                step="15"
+                                className="w-20 h-6 text-xs"
+                            />
+                        </div>
+                    </>
+                )}
+                <Button variant="secondary" className="w-full" onClick={handleCreateArray}>
+                    Create Array
+                </Button>
+            </AccordionContent>
+        </AccordionItem>
+    );
+};
+
+export default ArrayControls;
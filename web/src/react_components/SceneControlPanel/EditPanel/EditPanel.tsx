@@ -3,9 +3,10 @@ import * as wasm from "@wasm/wasm_graphics"
 import React from 'react';
 import { Accordion } from "@/components/ui/accordion";
 import { Button } from "@/components/ui/button";
-import { Trash2, CheckCircle2 } from 'lucide-react';
+import { Trash2, CheckCircle2, Copy, Group, CopyPlus, ClipboardCopy, ClipboardPaste } from 'lucide-react';
 import TransformControls from './TransformControls';
 import MaterialEditorControls from './MaterialEditorControls';
+import ArrayControls from './ArrayControls';
 import { useGameContext } from "@/gameContext";
 
 interface EditPanelProps {
@@ -16,18 +17,66 @@ const EditPanel: React.FC<EditPanelProps> = ({}) => {
     const {
         selectedObjMatProps,
         gameStatus,
-        followCamera
+        followCamera,
+        selectedGroup,
+        selectedObjectCount,
     } = useGameContext();
 
     const handleDeleteClick = () => {
-        if (window.confirm("Are you sure you want to delete this object?")) {
+        const message = selectedGroup
+            ? `Are you sure you want to delete the group "${selectedGroup}" and everything in it?`
+            : selectedObjectCount > 1
+                ? `Are you sure you want to delete these ${selectedObjectCount} objects?`
+                : "Are you sure you want to delete this object?";
+        if (window.confirm(message)) {
             wasm.delete_selected_object();
         }
     };
 
+    const handleSelectGroupClick = () => {
+        wasm.select_parent_group();
+    };
+
+    const handleInstanceClick = () => {
+        wasm.instance_selected_object();
+    };
+
+    const handleDuplicateClick = () => {
+        wasm.duplicate_selection_beside();
+    };
+
     return (
         <div className="space-y-3">
+            {selectedGroup && (
+                <p className="text-sm text-muted-foreground">
+                    Group: <span className="font-medium text-foreground">{selectedGroup}</span>
+                </p>
+            )}
+            {selectedObjectCount > 1 && (
+                <p className="text-sm text-muted-foreground">
+                    <span className="font-medium text-foreground">{selectedObjectCount}</span> objects selected (shift-click or drag to change)
+                </p>
+            )}
             <div className="flex w-full gap-2">
+                <Button
+                    variant="secondary"
+                    className="flex-1 min-w-0"
+                    onClick={handleSelectGroupClick}
+                    title="Select the group this is part of, to move or delete it as a whole"
+                >
+                    <Group className="mr-1 h-4 w-4" />
+                    Select Group
+                </Button>
+                <Button
+                    variant="secondary"
+                    className="flex-1 min-w-0"
+                    onClick={handleInstanceClick}
+                    disabled={!!selectedGroup}
+                    title="Add a copy that shares this object's geometry"
+                >
+                    <Copy className="mr-1 h-4 w-4" />
+                    Instance
+                </Button>
                 <Button
                     variant="destructive"
                     className="
@@ -41,6 +90,35 @@ const EditPanel: React.FC<EditPanelProps> = ({}) => {
                     Delete
                 </Button>
             </div>
+            <div className="flex w-full gap-2">
+                <Button
+                    variant="secondary"
+                    className="flex-1 min-w-0"
+                    onClick={handleDuplicateClick}
+                    title="Add a copy next to the selection (Ctrl+D)"
+                >
+                    <CopyPlus className="mr-1 h-4 w-4" />
+                    Duplicate
+                </Button>
+                <Button
+                    variant="secondary"
+                    className="flex-1 min-w-0"
+                    onClick={() => wasm.copy_selection()}
+                    title="Copy the selection, it can be pasted into another scene too (Ctrl+C)"
+                >
+                    <ClipboardCopy className="mr-1 h-4 w-4" />
+                    Copy
+                </Button>
+                <Button
+                    variant="secondary"
+                    className="flex-1 min-w-0"
+                    onClick={() => wasm.paste()}
+                    title="Paste where you're looking (Ctrl+V)"
+                >
+                    <ClipboardPaste className="mr-1 h-4 w-4" />
+                    Paste
+                </Button>
+            </div>
             
             <Accordion 
                 type="multiple" 
@@ -49,6 +127,7 @@ const EditPanel: React.FC<EditPanelProps> = ({}) => {
             >
                 <TransformControls/> 
                 <MaterialEditorControls/>
+                <ArrayControls/>
             </Accordion>
         </div>
     );
@@ -55,6 +55,7 @@ const TransformControls: React.FC<TransformControlsProps> = ({  }) => {
         selectedObjMatProps,
         gameStatus,
         followCamera,
+        gizmoMode,
     } = useGameContext();
 
     // Local state for increments
@@ -100,6 +101,14 @@ const TransformControls: React.FC<TransformControlsProps> = ({  }) => {
         wasm.scale_selected_obj(scale);
     };
 
+    // 0 = Hidden, 1 = Translate, 2 = Rotate, 3 = Scale, the same as keys 0-3
+    const gizmoModes = [
+        { mode: 1, label: "Move" },
+        { mode: 2, label: "Rotate" },
+        { mode: 3, label: "Scale" },
+        { mode: 0, label: "Off" },
+    ];
+
     const handleToggleFollowCursor = (checked: boolean) => {
         console.log("JS: Follow cursor toggled:", checked);
         wasm.set_follow_camera(checked);
@@ -109,6 +118,23 @@ const TransformControls: React.FC<TransformControlsProps> = ({  }) => {
         <AccordionItem value="transformAndSnap">
             <AccordionTrigger>Transform</AccordionTrigger>
             <AccordionContent className="space-y-4 pt-2">
+                {/* Gizmo mode */}
+                <div className="space-y-2">
+                    <Label className="text-sm font-medium">Gizmo</Label>
+                    <div className="flex w-full gap-1" title="Drag the handles on the selection with the mouse unlocked (keys 1-3, 0 hides them)">
+                        {gizmoModes.map(({ mode, label }) => (
+                            <Button
+                                key={mode}
+                                variant={gizmoMode === mode ? "default" : "outline"}
+                                className="flex-1 min-w-0 h-7 text-xs"
+                                onClick={() => wasm.set_gizmo_mode(mode)}
+                            >
+                                {label}
+                            </Button>
+                        ))}
+                    </div>
+                </div>
+
                 {/* Position Controls */}
                 <div className="space-y-2">
                     <Label className="text-sm font-medium">Position</Label>
@@ -0,0 +1,122 @@
+import * as wasm from "@wasm/wasm_graphics";
+
+import React, { useState } from 'react';
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import { Switch } from "@/components/ui/switch";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select";
+
+type GridOptions = {
+    showGrid: boolean;
+    showAxes: boolean;
+    spacing: number;
+    snapToGrid: boolean;
+    snapAngles: boolean;
+    angleStep: number;
+    objectSnap: number; // 0 = Off, 1 = Surface, 2 = Vertex
+};
+
+// The options live in wasm, read them back so they survive this panel being closed
+const readGridOptions = (): GridOptions => {
+    const options = wasm.get_grid_options();
+    return {
+        showGrid: options.show_grid,
+        showAxes: options.show_axes,
+        spacing: options.spacing,
+        snapToGrid: options.snap_to_grid,
+        snapAngles: options.snap_angles,
+        angleStep: options.angle_step,
+        objectSnap: options.object_snap,
+    };
+};
+
+// A label with a switch on the right
+const SwitchRow: React.FC<{
+    id: string;
+    label: string;
+    checked: boolean;
+    onCheckedChange: (checked: boolean) => void;
+}> = ({ id, label, checked, onCheckedChange }) => (
+    <div className="flex items-center justify-between py-1">
+        <Label htmlFor={id} className="text-xs text-muted-foreground">{label}</Label>
+        <Switch id={id} checked={checked} onCheckedChange={onCheckedChange} />
+    </div>
+);
+
+interface GridPanelProps {
+}
+
+const GridPanel: React.FC<GridPanelProps> = () => {
+    const [options, setOptions] = useState<GridOptions>(readGridOptions);
+
+    const updateOptions = (changes: Partial<GridOptions>) => {
+        const newOptions = { ...options, ...changes };
+        if (!(newOptions.spacing > 0) || !(newOptions.angleStep > 0)) {
+            return; // wasm would refuse them anyway
+        }
+        wasm.set_grid_options(new wasm.GridOptions(
+            newOptions.showGrid,
+            newOptions.showAxes,
+            newOptions.spacing,
+            newOptions.snapToGrid,
+            newOptions.snapAngles,
+            newOptions.angleStep,
+            newOptions.objectSnap,
+        ));
+        setOptions(newOptions);
+    };
+
+    return (
+        <div className="space-y-2">
+            <SwitchRow id="grid-show-grid" label="Ground grid" checked={options.showGrid} onCheckedChange={(checked) => updateOptions({ showGrid: checked })} />
+            <SwitchRow id="grid-show-axes" label="World axes" checked={options.showAxes} onCheckedChange={(checked) => updateOptions({ showAxes: checked })} />
+            <div className="flex items-center justify-between py-1">
+                <Label htmlFor="grid-spacing" className="text-xs text-muted-foreground">Grid spacing</Label>
+                <Input
+                    id="grid-spacing"
+                    type="number"
+                    value={options.spacing}
+                    onChange={(e) => updateOptions({ spacing: parseFloat(e.target.value) || 0 })}
+                    step="0.25"
+                    min="0.01"
+                    className="w-20 h-6 text-xs"
+                />
+            </div>
+            <SwitchRow id="grid-snap-to-grid" label="Snap moves to grid" checked={options.snapToGrid} onCheckedChange={(checked) => updateOptions({ snapToGrid: checked })} />
+            <SwitchRow id="grid-snap-angles" label="Snap rotations" checked={options.snapAngles} onCheckedChange={(checked) => updateOptions({ snapAngles: checked })} />
+            <div className="flex items-center justify-between py-1">
+                <Label htmlFor="grid-angle-step" className="text-xs text-muted-foreground">Angle step (°)</Label>
+                <Input
+                    id="grid-angle-step"
+                    type="number"
+                    value={options.angleStep}
+                    onChange={(e) => updateOptions({ angleStep: parseFloat(e.target.value) || 0 })}
+                    step="5"
+                    min="1"
+                    className="w-20 h-6 text-xs"
+                />
+            </div>
+            <div className="flex items-center justify-between py-1">
+                <Label htmlFor="grid-object-snap" className="text-xs text-muted-foreground">Follow camera onto</Label>
+                <Select value={options.objectSnap.toString()} onValueChange={(value) => updateOptions({ objectSnap: parseInt(value) })}>
+                    <SelectTrigger id="grid-object-snap" className="w-32 h-6 text-xs">
+                        <SelectValue />
+                    </SelectTrigger>
+                    <SelectContent>
+                        <SelectItem value="0">Where you look</SelectItem>
+                        <SelectItem value="1">Surfaces</SelectItem>
+                        <SelectItem value="2">Vertices</SelectItem>
+                    </SelectContent>
+                </Select>
+            </div>
+        </div>
+    );
+};
+
+export default GridPanel;
@@ -29,6 +29,8 @@ import { ModeToggle } from "@/components/mode-toggle";
 
 import EditPanel from './EditPanel/EditPanel';
 import AddObjectPanel from './AddObjectPanel';
+import GridPanel from './GridPanel';
+import DebugOverlayPanel from './DebugOverlayPanel';
 import InstructionsDialog from "./InstructionsDialog";
 import { useGameContext } from "@/gameContext";
 import { wasmToJsBridge } from "@/wasmToJSBridge";
@@ -142,11 +144,14 @@ const SceneControlPanel: React.FC = () => {
         focalDistance,
         dofStrength,
         sceneLoading,
+        selectedGroup,
     } = useGameContext();
 
     // state to control which accordion items are open
     const [openAccordionItems, setOpenAccordionItems] = useState<string[]>([]);
     const [instructionsOpen, setInstructionsOpen] = useState(false);
+    // 0 = nearest, 1 = bilinear, kept in wasm so it survives scene loads
+    const [textureFilter, setTextureFilter] = useState<string>(() => wasm.get_texture_filter().toString());
 
     // --- handlers ---
     const handleLoadDefaultScene = (sceneValue: string) => {
@@ -230,16 +235,22 @@ const SceneControlPanel: React.FC = () => {
         wasm.set_defocus_angle(radians);
     };
 
+    const handleTextureFilterChange = (value: string) => {
+        if (wasm.set_texture_filter(parseInt(value))) {
+            setTextureFilter(value);
+        }
+    };
+
     const inEditMode = gameStatus === 'Editing';
     const inRayTracingMode = gameStatus === 'RayTracing';
 
     const showAddObjectTrigger = gameStatus === 'Editing';
-    const canEditSelectedObject = gameStatus === 'Editing' && selectedObjMatProps;
+    const canEditSelectedObject = gameStatus === 'Editing' && (selectedObjMatProps || selectedGroup);
 
     useEffect(() => {
         // If an object is deselected (selectedObjMatProps becomes null)
         // while in edit mode, ensure the edit panel is closed.
-        if (inEditMode && !selectedObjMatProps) {
+        if (inEditMode && !selectedObjMatProps && !selectedGroup) {
             setOpenAccordionItems(prevItems =>
                 prevItems.filter(item => item !== 'edit-selected-object-panel')
             );
@@ -253,7 +264,7 @@ const SceneControlPanel: React.FC = () => {
         // This effect primarily handles closing the edit panel on deselection
         // or when exiting edit mode. Opening panels is handled by user interaction
         // or explicitly in mode change handlers (like handleEnterEditMode).
-    }, [selectedObjMatProps, inEditMode]); // Depend on inEditMode directly
+    }, [selectedObjMatProps, selectedGroup, inEditMode]); // Depend on inEditMode directly
 
     useEffect(() => {
         // Show on first load. For "first visit only", use localStorage here.
@@ -405,6 +416,20 @@ const SceneControlPanel: React.FC = () => {
                     {!inEditMode ? "Scene editing unavailable when using real-time lighting." : ""}
                 </p>
 
+                {/* Texture Filtering */}
+                <div className="flex items-center justify-between space-x-2 p-2">
+                    <Label htmlFor="texture-filter-select" className="text-sm font-medium">Texture Filtering</Label>
+                    <Select value={textureFilter} onValueChange={handleTextureFilterChange}>
+                        <SelectTrigger id="texture-filter-select" className="w-32">
+                            <SelectValue />
+                        </SelectTrigger>
+                        <SelectContent>
+                            <SelectItem value="0">Nearest</SelectItem>
+                            <SelectItem value="1">Bilinear</SelectItem>
+                        </SelectContent>
+                    </Select>
+                </div>
+
                 <Accordion
                     type="multiple"
                     className="w-full"
@@ -428,6 +453,32 @@ const SceneControlPanel: React.FC = () => {
                         </AccordionContent>
                     </AccordionItem>
 
+                    {/* Grid & Snapping Panel Accordion Item */}
+                    <AccordionItem value="grid-panel">
+                        <AccordionTrigger disabled={!showAddObjectTrigger}>
+                            Grid & Snapping
+                        </AccordionTrigger>
+                        <AccordionContent>
+                            {showAddObjectTrigger ? (
+                                <GridPanel/>
+                            ) : (
+                                <p className="text-sm text-muted-foreground p-4 text-center">
+                                    The grid is only available in Edit Mode.
+                                </p>
+                            )}
+                        </AccordionContent>
+                    </AccordionItem>
+
+                    {/* Debug Views Panel Accordion Item */}
+                    <AccordionItem value="debug-overlay-panel">
+                        <AccordionTrigger>
+                            Debug Views
+                        </AccordionTrigger>
+                        <AccordionContent>
+                            <DebugOverlayPanel/>
+                        </AccordionContent>
+                    </AccordionItem>
+
                     {/* Edit Selected Object Panel Accordion Item */}
                     <AccordionItem value="edit-selected-object-panel">
                         <AccordionTrigger 
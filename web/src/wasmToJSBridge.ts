@@ -16,6 +16,22 @@ export interface IWasmToJsBridge {
     updateFocalDistance: (focalDistance: number) => void;
     updateDofStrength: (dofStrength: number) => void;
     updateSceneLoading: (loading: boolean) => void;
+    /**
+     * @param groupName name of the selected group, null/undefined when no group is selected
+     */
+    updateSelectedGroup: (groupName: string | null | undefined) => void;
+    /**
+     * @param objectId stable id of the active (last selected) object (see wasm.get_object_ids), null/undefined when no object is selected
+     */
+    updateSelectedObjectId: (objectId: bigint | null | undefined) => void;
+    /**
+     * @param count how many objects are selected, 0 while a group is selected
+     */
+    updateSelectedObjectCount: (count: number) => void;
+    /**
+     * @param mode 0 = Hidden, 1 = Translate, 2 = Rotate, 3 = Scale
+     */
+    updateGizmoMode: (mode: number) => void;
     // getGlbBytes: (url: string) => Promise<Uint8Array>;
 }
 
@@ -45,6 +61,18 @@ class WasmToJsBridge implements IWasmToJsBridge {
     public updateSceneLoading: (loading: boolean) => void = (loading) => {
         console.warn("WasmToJsBridge.updateSceneLoading called before React context initialized it.", loading);
     };
+    public updateSelectedGroup: (groupName: string | null | undefined) => void = (groupName) => {
+        console.warn("WasmToJsBridge.updateSelectedGroup called before React context initialized it.", groupName);
+    };
+    public updateSelectedObjectId: (objectId: bigint | null | undefined) => void = (objectId) => {
+        console.warn("WasmToJsBridge.updateSelectedObjectId called before React context initialized it.", objectId);
+    };
+    public updateSelectedObjectCount: (count: number) => void = (count) => {
+        console.warn("WasmToJsBridge.updateSelectedObjectCount called before React context initialized it.", count);
+    };
+    public updateGizmoMode: (mode: number) => void = (mode) => {
+        console.warn("WasmToJsBridge.updateGizmoMode called before React context initialized it.", mode);
+    };
 
     // Implement other methods with default warnings
 }